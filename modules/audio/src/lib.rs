@@ -62,6 +62,12 @@ pub fn clip_state(handle: AudioClipHandle) -> ResourceState {
     ctx().clip_state(handle)
 }
 
+/// Gets the UUID of the clip which is created from file, it's stable across runs.
+#[inline]
+pub fn clip_uuid(handle: AudioClipHandle) -> Option<Uuid> {
+    ctx().clip_uuid(handle)
+}
+
 /// Deletes a `AudioClip` resource from `AudioSystem`.
 #[inline]
 pub fn delete_clip(handle: AudioClipHandle) {
@@ -80,6 +80,12 @@ impl AudioSystem {
         self.clips.read().unwrap().state(handle)
     }
 
+    /// Gets the UUID of the clip which is created from file.
+    #[inline]
+    pub fn clip_uuid(&self, handle: AudioClipHandle) -> Option<Uuid> {
+        self.clips.read().unwrap().uuid(handle)
+    }
+
     /// Deletes a `AudioClip` resource from `AudioSystem`.
     #[inline]
     pub fn delete_clip(&self, handle: AudioClipHandle) {
@@ -28,6 +28,7 @@ pub use self::inside::{discard, setup};
 pub use self::system::WorldDefaultResources;
 
 use crayon::res::utils::prelude::ResourceState;
+use crayon::uuid::Uuid;
 use std::sync::Arc;
 
 use self::assets::prelude::{Prefab, PrefabHandle};
@@ -69,6 +70,12 @@ pub fn prefab_state(handle: PrefabHandle) -> ResourceState {
     ctx().prefab_state(handle)
 }
 
+/// Gets the UUID of the prefab which is created from file, it's stable across runs.
+#[inline]
+pub fn prefab_uuid(handle: PrefabHandle) -> Option<Uuid> {
+    ctx().prefab_uuid(handle)
+}
+
 /// Delete a prefab object from this world.
 #[inline]
 pub fn delete_prefab(handle: PrefabHandle) {
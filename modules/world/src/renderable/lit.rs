@@ -2,6 +2,8 @@ use crayon::math::prelude::Color;
 
 use spatial::prelude::Transform;
 
+use super::shadow::ShadowBias;
+
 /// In order to calculate the shading of a 3D object, we needs to knowns the intensity,
 /// direction and color of the light that falls on it. These properties are provided by
 /// Lit components in the scene.
@@ -11,6 +13,9 @@ pub struct Lit {
     pub enable: bool,
     /// Is this light casting shadow.
     pub shadow_caster: bool,
+    /// The biases used when rendering the shadow of this light, the defaults of light
+    /// source are used if it's `None`.
+    pub shadow_bias: Option<ShadowBias>,
     /// Color of the light.
     pub color: Color<f32>,
     /// Brightness of the light source, in lumens.
@@ -41,6 +46,7 @@ impl Default for Lit {
         Lit {
             enable: true,
             shadow_caster: false,
+            shadow_bias: None,
             color: Color::white(),
             intensity: 1.0,
             source: LitSource::Dir,
@@ -48,3 +54,12 @@ impl Default for Lit {
         }
     }
 }
+
+impl Lit {
+    /// Gets the biases used when rendering the shadow of this light.
+    #[inline]
+    pub fn shadow_bias(&self) -> ShadowBias {
+        self.shadow_bias
+            .unwrap_or_else(|| ShadowBias::new(self.source))
+    }
+}
@@ -1,6 +1,7 @@
 mod camera;
 mod lit;
 mod mesh_renderer;
+mod shadow;
 mod simple;
 
 pub mod headless;
@@ -9,6 +10,7 @@ pub mod prelude {
     pub use super::camera::Camera;
     pub use super::lit::{Lit, LitSource};
     pub use super::mesh_renderer::MeshRenderer;
+    pub use super::shadow::ShadowBias;
     pub use super::simple::{SimpleMaterial, SimpleRenderer};
     pub use super::{Renderable, Renderer};
 }
@@ -0,0 +1,90 @@
+//! Depth biases that used when rendering shadow maps.
+
+use crayon::video::assets::shader::{Comparison, RenderState};
+
+use super::lit::LitSource;
+
+/// The biases applied when rendering and sampling shadow maps, which combats the shadow
+/// acne and peter-panning artifacts.
+///
+/// A shadow map only has limited resolution, so a texel of it covers a range of depths
+/// on surfaces that are not facing the light. Comparing those surfaces against the map
+/// without any bias makes them shadow themselves in stripes, which is called shadow acne.
+/// On the other side, pushing the casters too far away detaches the shadows from their
+/// casters, which is called peter-panning. There is no value that works for every scene,
+/// the defaults of `ShadowBias::dir` and `ShadowBias::point` are a good starting point to
+/// tune from.
+///
+/// * The `slope_scale` and `constant` biases are applied through polygon offset when
+/// rendering the casters into shadow map. The slope-scaled one grows with the slope of
+/// polygons in depth, which fixes the acne on grazing surfaces cheaply, but it also makes
+/// the peter-panning of thin objects much more noticeable.
+/// * The `normal_offset` moves the sampling position along the surface normal instead of
+/// changing depths. It's measured in the texels of shadow map, and needs the cooperation
+/// of the receiving shaders. It removes the acne without detaching the shadows, but it
+/// shifts the shadows slightly which might be visible at the contact points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowBias {
+    /// The factor scales the maximum depth slope of polygons.
+    pub slope_scale: f32,
+    /// The constant depth offset, in the units of the minimum resolvable depth
+    /// difference of depth buffer.
+    pub constant: f32,
+    /// The offset along normals at the receivers, in the texels of shadow map. Zero
+    /// disables it.
+    pub normal_offset: f32,
+}
+
+impl ShadowBias {
+    /// The biases tuned for directional lights. The orthographic projection distributes
+    /// depths evenly, so small biases are enough.
+    pub fn dir() -> Self {
+        ShadowBias {
+            slope_scale: 1.5,
+            constant: 2.0,
+            normal_offset: 1.0,
+        }
+    }
+
+    /// The biases tuned for point lights. The perspective projection of cube faces loses
+    /// depth precision quickly with distance, and the texels get larger than the ones of
+    /// directional lights, so the biases are larger.
+    pub fn point() -> Self {
+        ShadowBias {
+            slope_scale: 2.0,
+            constant: 8.0,
+            normal_offset: 1.5,
+        }
+    }
+
+    /// Creates the default biases of light source.
+    pub fn new(source: LitSource) -> Self {
+        match source {
+            LitSource::Dir => Self::dir(),
+            LitSource::Point { .. } => Self::point(),
+        }
+    }
+
+    /// Sets up the render states of shadow casters, which writes depth with the polygon
+    /// offset of `slope_scale` and `constant`.
+    pub fn apply(&self, state: &mut RenderState) {
+        state.depth_write = true;
+        state.depth_test = Comparison::LessOrEqual;
+        state.depth_write_offset = Some((self.slope_scale, self.constant));
+    }
+
+    /// Gets the distance in world space that the sampling position of receivers should
+    /// be moved along its normal, with the world space size of a shadow map texel at the
+    /// receiver. Shaders usually scale it with `1 - dot(N, L)` further, so surfaces
+    /// facing the light are not affected.
+    #[inline]
+    pub fn normal_offset_distance(&self, texel_size: f32) -> f32 {
+        self.normal_offset * texel_size
+    }
+}
+
+impl Default for ShadowBias {
+    fn default() -> Self {
+        Self::dir()
+    }
+}
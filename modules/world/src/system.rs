@@ -2,6 +2,7 @@ use std::sync::{Arc, RwLock};
 
 use crayon::application::prelude::*;
 use crayon::res::utils::prelude::*;
+use crayon::uuid::Uuid;
 use crayon::video::assets::prelude::*;
 use failure::Error;
 
@@ -88,6 +89,12 @@ impl WorldSystem {
         self.prefabs.read().unwrap().state(handle)
     }
 
+    /// Gets the UUID of the prefab which is created from file.
+    #[inline]
+    pub fn prefab_uuid(&self, handle: PrefabHandle) -> Option<Uuid> {
+        self.prefabs.read().unwrap().uuid(handle)
+    }
+
     /// Delete a prefab object from this world.
     #[inline]
     pub fn delete_prefab(&self, handle: PrefabHandle) {
@@ -107,11 +107,7 @@ impl EngineSystem {
     }
 
     pub fn run_oneshot(&self) -> Result<()> {
-        super::foreach(|v| v.on_pre_update())?;
-        super::foreach(|v| v.on_update())?;
-        super::foreach(|v| v.on_render())?;
-        super::foreach_rev(|v| v.on_post_update())?;
-        Ok(())
+        advance()
     }
 
     pub fn run<L, T, T2>(&self, latch: L, closure: T) -> Result<()>
@@ -138,11 +134,7 @@ impl EngineSystem {
 
                 super::sys::run_forever(
                     move || {
-                        super::foreach(|v| v.on_pre_update())?;
-                        super::foreach(|v| v.on_update())?;
-                        super::foreach(|v| v.on_render())?;
-                        super::foreach_rev(|v| v.on_post_update())?;
-
+                        advance()?;
                         Ok(state.alive.load(Ordering::Relaxed))
                     },
                     move || {
@@ -160,3 +152,26 @@ impl EngineSystem {
         Ok(())
     }
 }
+
+/// Runs the lifecycle of one frame.
+fn advance() -> Result<()> {
+    profile_scope!("Frame");
+
+    {
+        profile_scope!("PreUpdate");
+        super::foreach(|v| v.on_pre_update())?;
+    }
+
+    {
+        profile_scope!("Update");
+        super::foreach(|v| v.on_update())?;
+    }
+
+    {
+        profile_scope!("Render");
+        super::foreach(|v| v.on_render())?;
+    }
+
+    profile_scope!("PostUpdate");
+    super::foreach_rev(|v| v.on_post_update())
+}
@@ -153,6 +153,14 @@ pub fn discard() {
     ctx().shutdown()
 }
 
+/// Breaks the main loop cleanly after the current frame, the systems are discarded once
+/// the loop has been left. It wakes up the loop if it's waiting for events.
+#[inline]
+pub fn exit() {
+    ctx().shutdown();
+    crate::window::request_redraw();
+}
+
 pub(crate) unsafe fn late_discard() {
     drop(Box::from_raw(CTX as *mut EngineSystem));
     CTX = std::ptr::null();
@@ -176,6 +184,13 @@ pub fn headless() -> bool {
     ctx().headless()
 }
 
+/// Runs the lifecycle of one frame immediately. This should never be called inside the
+/// callbacks of `LifecycleListener`.
+#[inline]
+pub(crate) fn run_oneshot() -> Result<()> {
+    ctx().run_oneshot()
+}
+
 #[inline]
 pub fn attach<T>(lis: T) -> LifecycleListenerHandle
 where
@@ -211,6 +226,45 @@ pub fn set_max_fps(mut fps: u32) {
     time_ctx().set_max_fps(fps);
 }
 
+/// Set the target frames per second of the software frame limiter, which sleeps the
+/// rest of frame budget with a precise timer after `swap_buffers`. It's disabled with
+/// `None`.
+///
+/// Unlike `set_max_fps`, it paces the presentation instead of the begining of frames,
+/// which is useful to cap the GPU usage or measure the input latency consistently when
+/// the vsync is off.
+#[allow(unused_assignments, unused_mut)]
+#[inline]
+pub fn set_target_fps(mut fps: Option<u32>) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        warn!("The target FPS could not be controlled in web environment.");
+        fps = None;
+    }
+
+    time_ctx().set_target_fps(fps);
+}
+
+/// Sets whether the application is event-driven. The main loop waits for events instead of
+/// spinning in this mode, and runs a frame only on input or if `request_redraw` has been
+/// called, which saves the power of applications that do not animate continuously.
+///
+/// # Platform-specific
+///
+/// The browser drives the frames with `requestAnimationFrame`, so it has no effect on the
+/// web.
+#[inline]
+pub fn set_event_driven(event_driven: bool) {
+    crate::window::set_wait_events(event_driven);
+}
+
+/// Requests a new frame in event-driven mode, it could be called from any thread. The
+/// request made during a frame is served right after it.
+#[inline]
+pub fn request_redraw() {
+    crate::window::request_redraw();
+}
+
 /// Set maximum frames per second when the application does not have input
 /// focus.
 #[inline]
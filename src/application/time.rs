@@ -3,7 +3,7 @@
 use std;
 use std::collections::VecDeque;
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::application::{LifecycleListener, LifecycleListenerHandle};
 use crate::utils::time::Timestamp;
@@ -19,6 +19,7 @@ struct TimeStateShared {
     min_fps: RwLock<u32>,
     max_fps: RwLock<u32>,
     max_inactive_fps: RwLock<u32>,
+    target_fps: RwLock<Option<u32>>,
     smoothing_step: RwLock<usize>,
     timestep: RwLock<Duration>,
 }
@@ -31,6 +32,7 @@ struct TimeState {
     timestep: Duration,
     previous_timesteps: VecDeque<Duration>,
     last_frame_timepoint: Timestamp,
+    last_present_timepoint: Instant,
     shared: Arc<TimeStateShared>,
 }
 
@@ -87,6 +89,50 @@ impl LifecycleListener for TimeState {
         *self.shared.timestep.write().unwrap() = self.timestep;
         Ok(())
     }
+
+    fn on_post_update(&mut self) -> crate::errors::Result<()> {
+        // The time system is attached before the window system, so this is called after
+        // `swap_buffers`.
+        let target_fps = *self.shared.target_fps.read().unwrap();
+        match target_fps {
+            Some(fps) if fps > 0 => {
+                let budget = Duration::from_nanos(1_000_000_000 / u64::from(fps));
+                let deadline = self.last_present_timepoint + budget;
+                wait_until(deadline);
+
+                // Schedules the next frame from the deadline instead of now, so the errors
+                // of waking up don't accumulate. Unless we are falling behind too much.
+                let now = Instant::now();
+                self.last_present_timepoint = if now > deadline + budget {
+                    now
+                } else {
+                    deadline
+                };
+            }
+            _ => self.last_present_timepoint = Instant::now(),
+        }
+
+        Ok(())
+    }
+}
+
+/// Sleeps until the `deadline` precisely. The OS scheduler usually wakes us up a few
+/// milliseconds late, so we sleep until being close to it and spin for the rest.
+fn wait_until(deadline: Instant) {
+    let margin = Duration::from_millis(2);
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return;
+        }
+
+        let rest = deadline - now;
+        if rest > margin {
+            std::thread::sleep(rest - margin);
+        } else {
+            std::thread::yield_now();
+        }
+    }
 }
 
 impl Drop for TimeSystem {
@@ -101,6 +147,7 @@ impl TimeSystem {
             min_fps: RwLock::new(setup.min_fps),
             max_fps: RwLock::new(setup.max_fps),
             max_inactive_fps: RwLock::new(setup.max_inactive_fps),
+            target_fps: RwLock::new(None),
             smoothing_step: RwLock::new(setup.time_smooth_step as usize),
             timestep: RwLock::new(Duration::new(0, 0)),
         });
@@ -113,6 +160,7 @@ impl TimeSystem {
             previous_timesteps: VecDeque::new(),
             timestep: Duration::new(0, 0),
             last_frame_timepoint: Timestamp::now(),
+            last_present_timepoint: Instant::now(),
             shared: shared.clone(),
         };
 
@@ -144,6 +192,13 @@ impl TimeSystem {
         *self.shared.max_inactive_fps.write().unwrap() = fps;
     }
 
+    /// Set the target frames per second of the software frame limiter, which sleeps the
+    /// rest of frame budget precisely after `swap_buffers`. It's disabled with `None`.
+    #[inline]
+    pub fn set_target_fps(&self, fps: Option<u32>) {
+        *self.shared.target_fps.write().unwrap() = fps;
+    }
+
     /// Set how many frames to average for timestep smoothing.
     #[inline]
     pub fn set_time_smoothing_step(&self, step: u32) {
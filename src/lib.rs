@@ -47,7 +47,7 @@ extern crate log;
 #[macro_use]
 extern crate cgmath;
 #[macro_use]
-extern crate serde;
+pub extern crate serde;
 extern crate byteorder;
 extern crate serde_json;
 
@@ -151,24 +151,22 @@ impl<S: BaseFloat> Projection<S> {
     /// Gets the orthographic projection matrix in left hand coordinates.
     pub fn ortho_matrix(w: S, h: S, n: S, f: S) -> Matrix4<S> {
         let half: S = cast(0.5).unwrap();
+        let (hw, hh) = (w * half, h * half);
+        Self::ortho_off_center_matrix(-hw, hw, -hh, hh, n, f)
+    }
+
+    /// Gets the orthographic projection matrix of a off-center view volume in left hand
+    /// coordinates. E.G. it could be used to map UI elements in pixels, which are placed
+    /// at different depths between near and far clip planes, into the viewport.
+    pub fn ortho_off_center_matrix(l: S, r: S, b: S, t: S, n: S, f: S) -> Matrix4<S> {
         let two: S = cast(2.0).unwrap();
         let zero = S::zero();
         let one = S::one();
 
-        let (hw, hh) = (w * half, h * half);
-
-        let (l0, r0) = (-hw, hw);
-        let (b0, t0) = (-hh, hh);
-
-        let c0 = [two / (r0 - l0), zero, zero, zero];
-        let c1 = [zero, two / (t0 - b0), zero, zero];
+        let c0 = [two / (r - l), zero, zero, zero];
+        let c1 = [zero, two / (t - b), zero, zero];
         let c2 = [zero, zero, two / (f - n), zero];
-        let c3 = [
-            (r0 + l0) / (l0 - r0),
-            (t0 + b0) / (b0 - t0),
-            (f + n) / (n - f),
-            one,
-        ];
+        let c3 = [(r + l) / (l - r), (t + b) / (b - t), (f + n) / (n - f), one];
         Matrix4::from_cols(c0.into(), c1.into(), c2.into(), c3.into())
     }
 
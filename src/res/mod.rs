@@ -55,6 +55,7 @@ pub mod prelude {
 }
 
 mod system;
+mod workers;
 
 use std::sync::Arc;
 
@@ -74,6 +75,13 @@ pub struct ResourceParams {
     pub shortcuts: ShortcutResolver,
     pub schemas: SchemaResolver,
     pub dirs: Vec<String>,
+    /// The number of threads dedicated to loading and decoding resources, which keeps the
+    /// loadings from starving the jobs of gameplay in the global scheduler. The loadings are
+    /// spawned into the global scheduler if it's zero.
+    ///
+    /// It's the number of logical cores minus one (at least one) by default, and zero on the
+    /// web.
+    pub workers: u32,
 }
 
 impl Default for ResourceParams {
@@ -82,8 +90,15 @@ impl Default for ResourceParams {
             shortcuts: ShortcutResolver::new(),
             schemas: SchemaResolver::new(),
             dirs: Vec::new(),
+            workers: 0,
         };
 
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let cores = std::thread::available_parallelism().map_or(1, |v| v.get());
+            params.workers = cores.max(2) as u32 - 1;
+        }
+
         #[cfg(not(target_arch = "wasm32"))]
         params.schemas.add("file", self::vfs::dir::Dir::new());
         #[cfg(target_arch = "wasm32")]
@@ -160,6 +175,16 @@ pub fn exists(uuid: Uuid) -> bool {
     ctx().exists(uuid)
 }
 
+/// Spawns an asynchronous job on the loading threads, e.g. decoding resources from the
+/// bytes in memory.
+#[inline]
+pub fn spawn<F>(func: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    ctx().spawn(func)
+}
+
 /// Loads file asynchronously with response callback.
 #[inline]
 pub fn load_with_callback<T>(uuid: Uuid, func: T) -> Result<(), failure::Error>
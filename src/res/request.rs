@@ -4,6 +4,8 @@ use std::sync::{Arc, Mutex};
 
 use crate::sched::prelude::{LatchProbe, LockLatch};
 
+use super::workers::Workers;
+
 pub type Response = Result<Box<[u8]>, failure::Error>;
 
 /// A asynchronous loading request. You sould checks the completion status with
@@ -82,14 +84,16 @@ pub struct RequestQueue {
     last_frame_tasks: FrameTasks,
     tasks: FrameTasks,
     idxes: Mutex<Vec<usize>>,
+    workers: Arc<Workers>,
 }
 
 impl RequestQueue {
-    pub fn new() -> Self {
+    pub(crate) fn new(workers: Arc<Workers>) -> Self {
         RequestQueue {
             last_frame_tasks: Mutex::new(Vec::new()),
             tasks: Mutex::new(Vec::new()),
             idxes: Mutex::new(Vec::new()),
+            workers,
         }
     }
 
@@ -131,7 +135,7 @@ impl RequestQueue {
         for i in idxes.drain(..) {
             let (request, mut func) = tasks.remove(i);
             let v: Option<Response> = request.into();
-            crate::sched::spawn(move || func(v.unwrap()));
+            self.workers.spawn(move || func(v.unwrap()));
         }
     }
 }
@@ -10,6 +10,7 @@ use super::request::{Request, RequestQueue, Response};
 use super::shortcut::ShortcutResolver;
 use super::url::Url;
 use super::vfs::SchemaResolver;
+use super::workers::Workers;
 use super::ResourceParams;
 
 pub struct ResourceSystem {
@@ -17,6 +18,7 @@ pub struct ResourceSystem {
     schemas: SchemaResolver,
     manifest: RwLock<ManfiestResolver>,
     requests: Arc<RequestQueue>,
+    workers: Arc<Workers>,
     lifecycle: LifecycleListenerHandle,
 }
 
@@ -41,12 +43,14 @@ impl ResourceSystem {
     pub fn new(params: ResourceParams) -> Result<Self, failure::Error> {
         debug_assert!(crate::application::valid(), "");
 
-        let requests = Arc::new(RequestQueue::new());
+        let workers = Arc::new(Workers::new(params.workers)?);
+        let requests = Arc::new(RequestQueue::new(workers.clone()));
         let sys = ResourceSystem {
             shortcut: params.shortcuts,
             schemas: params.schemas,
             manifest: RwLock::new(ManfiestResolver::new()),
             requests: requests.clone(),
+            workers,
             lifecycle: crate::application::attach(Lifecycle { requests }),
         };
 
@@ -88,6 +92,15 @@ impl ResourceSystem {
         self.manifest.read().unwrap().contains(uuid)
     }
 
+    /// Spawns an asynchronous job on the loading threads.
+    #[inline]
+    pub fn spawn<F>(&self, func: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.workers.spawn(func);
+    }
+
     /// Loads file asynchronously with response callback.
     #[inline]
     pub fn load_with_callback<T>(&self, uuid: Uuid, func: T) -> Result<(), failure::Error>
@@ -121,7 +134,7 @@ impl ResourceSystem {
         self.requests.add(req, func);
 
         let vfs = self.schemas.locate(url.schema())?;
-        crate::sched::spawn(move || vfs.request(&url, state));
+        self.workers.spawn(move || vfs.request(&url, state));
 
         Ok(())
     }
@@ -157,7 +170,7 @@ impl ResourceSystem {
         let state = Request::latch();
         let req = Request::new(state.clone());
 
-        crate::sched::spawn(move || vfs.request(&url, state));
+        self.workers.spawn(move || vfs.request(&url, state));
         Ok(req)
     }
 
@@ -40,6 +40,16 @@ pub trait ResourceLoader: Send + Sync {
 
     fn load(&self, _: Self::Handle, _: &[u8]) -> Result<Self::Intermediate, Error>;
     fn create(&self, _: Self::Handle, _: Self::Intermediate) -> Result<Self::Resource, Error>;
+
+    /// Creates a batch of resources at once. The default implementation creates them one
+    /// by one with `create`.
+    fn create_batch(
+        &self,
+        items: Vec<(Self::Handle, Self::Intermediate)>,
+    ) -> Result<Vec<Self::Resource>, Error> {
+        items.into_iter().map(|(h, v)| self.create(h, v)).collect()
+    }
+
     fn delete(&self, _: Self::Handle, _: Self::Resource);
 }
 
@@ -129,6 +139,55 @@ where
         }
     }
 
+    /// Create a batch of resources with provided value instances at once, which saves the
+    /// overheads of creating them one by one.
+    ///
+    /// The associated `Handle`s are returned in order, none of them will be created if
+    /// there is any error.
+    pub fn create_batch(&mut self, params: Vec<Loader::Intermediate>) -> Result<Vec<H>, Error> {
+        let handles: Vec<_> = params.iter().map(|_| self.alloc(None)).collect();
+        let items = handles.iter().cloned().zip(params).collect();
+
+        match self.loader.create_batch(items) {
+            Ok(values) => {
+                for (&handle, value) in handles.iter().zip(values) {
+                    self.items.get_mut(handle).unwrap().resource = Some(value);
+                }
+
+                Ok(handles)
+            }
+            Err(error) => {
+                for handle in handles {
+                    self.delete(handle);
+                }
+
+                Err(error)
+            }
+        }
+    }
+
+    /// Create a resource asynchronously, whose intermediate value is produced by `func` on
+    /// the loading threads, e.g. decoding from the bytes in memory.
+    pub fn create_async<F>(&mut self, func: F) -> Result<H, Error>
+    where
+        F: FnOnce(H) -> Result<Loader::Intermediate, Error> + Send + 'static,
+    {
+        let handle = self.alloc(None);
+
+        let rx = Arc::new(Mutex::new(ResourceAsyncState::NotReady));
+        let tx = rx.clone();
+
+        crate::res::spawn(move || {
+            *tx.lock().unwrap() = match func(handle) {
+                Ok(item) => ResourceAsyncState::Ok(item),
+                Err(err) => ResourceAsyncState::Err(err),
+            };
+        });
+
+        self.requests.insert(handle, rx);
+        Ok(handle)
+    }
+
     /// Create a resource from file asynchronously.
     #[inline]
     pub fn create_from<T: AsRef<str>>(&mut self, url: T) -> Result<H, Error> {
@@ -224,12 +283,29 @@ where
             .unwrap_or(ResourceState::NotReady)
     }
 
+    /// Gets the UUID of the resource which is created from file, it's `None` if the
+    /// resource is created at runtime.
+    ///
+    /// Unlike the handles which are allocated in the order of creations, the UUIDs are
+    /// stable across runs. So it should be used to identify resources in persistent data
+    /// like save or scene files, and recreates the handles with `create_from_uuid` on load.
+    #[inline]
+    pub fn uuid(&self, handle: H) -> Option<Uuid> {
+        self.items.get(handle).and_then(|e| e.uuid)
+    }
+
     /// Checks if the handle is still avaiable in this pool.
     #[inline]
     pub fn contains(&self, handle: H) -> bool {
         self.items.contains(handle)
     }
 
+    /// Gets the loader of resources.
+    #[inline]
+    pub fn loader(&self) -> &Loader {
+        &self.loader
+    }
+
     /// Return immutable reference to internal value with name `Handle`.
     #[inline]
     pub fn resource(&self, handle: H) -> Option<&Loader::Resource> {
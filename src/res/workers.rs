@@ -0,0 +1,81 @@
+//! A dedicated pool of threads that runs the asynchronous loadings, so they don't compete
+//! with the jobs of gameplay in the global scheduler.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+#[derive(Default)]
+pub struct Workers {
+    tx: Mutex<Option<Sender<Job>>>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Workers {
+    /// Spawns `num` threads for loadings. The jobs are spawned into the global scheduler
+    /// if `num` is zero.
+    pub fn new(num: u32) -> Result<Self, failure::Error> {
+        if num == 0 {
+            return Ok(Workers::default());
+        }
+
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+
+        let mut handles = Vec::new();
+        for i in 0..num {
+            let rx = rx.clone();
+            let handle = thread::Builder::new()
+                .name(format!("Loader#{}", i))
+                .spawn(move || Self::main_loop(&rx))?;
+
+            handles.push(handle);
+        }
+
+        Ok(Workers {
+            tx: Mutex::new(Some(tx)),
+            handles,
+        })
+    }
+
+    /// Spawns an asynchronous loading job.
+    pub fn spawn<F>(&self, func: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if let Some(ref tx) = *self.tx.lock().unwrap() {
+            if tx.send(Box::new(func)).is_err() {
+                error!("[Workers] The loading threads have been terminated.");
+            }
+        } else {
+            crate::sched::spawn(func);
+        }
+    }
+
+    fn main_loop(rx: &Mutex<Receiver<Job>>) {
+        loop {
+            let job = match rx.lock().unwrap().recv() {
+                Ok(job) => job,
+                Err(_) => return,
+            };
+
+            if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                error!("[Workers] A loading job panicked.");
+            }
+        }
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        // Closes the channel, the threads exit after finishing the jobs in queue.
+        self.tx.lock().unwrap().take();
+
+        for v in self.handles.drain(..) {
+            let _ = v.join();
+        }
+    }
+}
@@ -17,6 +17,30 @@ impl DataBuffer {
         DataBuffer(Vec::with_capacity(capacity))
     }
 
+    /// Returns the number of bytes in this buffer.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if there is no bytes in this buffer.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the number of bytes this buffer can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    /// Shrinks the capacity of this buffer with a lower bound.
+    #[inline]
+    pub fn shrink_to(&mut self, capacity: usize) {
+        self.0.shrink_to(capacity);
+    }
+
     pub fn clear(&mut self) {
         unsafe {
             self.0.set_len(0);
@@ -2,6 +2,8 @@
 
 #[macro_use]
 pub mod handle;
+#[macro_use]
+pub mod profiler;
 pub mod data_buf;
 pub mod double_buf;
 pub mod handle_pool;
@@ -9,6 +11,7 @@ pub mod hash;
 pub mod hash_value;
 pub mod object_pool;
 pub mod time;
+pub mod triple_buf;
 
 pub mod prelude {
     pub use super::data_buf::{DataBuffer, DataBufferPtr};
@@ -19,4 +22,5 @@ pub mod prelude {
     pub use super::hash_value::HashValue;
     pub use super::object_pool::ObjectPool;
     pub use super::time::Timestamp;
+    pub use super::triple_buf::TripleBuf;
 }
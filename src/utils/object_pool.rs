@@ -76,11 +76,9 @@ impl<H: HandleLike, T: Sized> ObjectPool<H, T> {
     #[inline]
     pub fn free(&mut self, handle: H) -> Option<T> {
         if self.handles.free(handle) {
-            unsafe {
-                let mut v = ::std::mem::uninitialized();
-                ::std::mem::swap(&mut v, &mut self.entries[handle.index() as usize]);
-                Some(v)
-            }
+            // The slot is left as it is, and overwritten without dropping by the next
+            // `create` that reuses its index.
+            unsafe { Some(::std::ptr::read(&self.entries[handle.index() as usize])) }
         } else {
             None
         }
@@ -95,12 +93,11 @@ impl<H: HandleLike, T: Sized> ObjectPool<H, T> {
     {
         let entries = &mut self.entries;
         self.handles.retain(|handle| unsafe {
-            let mut v = entries.get_unchecked_mut(handle.index() as usize);
+            let v = entries.get_unchecked_mut(handle.index() as usize);
             if predicate(handle, v) {
                 true
             } else {
-                #[allow(clippy::invalid_ref)]
-                std::mem::swap(&mut v, &mut std::mem::uninitialized());
+                ::std::ptr::drop_in_place(v);
                 false
             }
         });
@@ -0,0 +1,165 @@
+//! Scoped markers around the phases of frame, which could be forwarded into external
+//! profilers like Tracy or Perfetto.
+//!
+//! The markers are only emitted if crayon is built with the `profiler` feature, they are
+//! compiled out entirely otherwise. Bridges the markers into the profiler of choice by
+//! installing a `ProfilerSink`:
+//!
+//! ```rust,ignore
+//! struct Tracy;
+//!
+//! impl ProfilerSink for Tracy {
+//!     fn begin(&self, name: &'static str) { /* starts a zone of `name` */ }
+//!     fn end(&self, name: &'static str) { /* ends the zone of `name` */ }
+//! }
+//!
+//! crayon::utils::profiler::set_sink(Box::new(Tracy));
+//! ```
+//!
+//! The markers of a thread are always nested, and the phases of video dispatching might
+//! be emitted on the render thread if the threaded rendering is enabled. The sink is read
+//! without any locking, so it should be installed before `application::setup`, and only
+//! be removed after the engine has been discarded.
+//!
+//! The measurements of GPU timers (see `video::begin_gpu_timer`) are forwarded into the
+//! sink too, once they have been completed by GPU a few frames later.
+
+use std::time::Duration;
+
+use self::ins::{sink, SINK};
+
+/// The receiver of profiling markers. It's called from the main thread and the render
+/// thread, so it should be thread-safe.
+pub trait ProfilerSink: Send + Sync {
+    /// Called when entering the scope of `name`.
+    fn begin(&self, name: &'static str);
+    /// Called when leaving the scope of `name`.
+    fn end(&self, name: &'static str);
+    /// Called when the GPU timer `name` has been completed.
+    fn gpu_timer(&self, _name: &str, _elapsed: Duration) {}
+}
+
+/// Installs the sink that receives the markers, replacing the previous one.
+pub fn set_sink(sink: Box<dyn ProfilerSink>) {
+    unsafe {
+        clear_sink();
+        SINK = Box::into_raw(Box::new(sink));
+    }
+}
+
+/// Removes the installed sink.
+pub fn clear_sink() {
+    unsafe {
+        if !SINK.is_null() {
+            drop(Box::from_raw(SINK as *mut Box<dyn ProfilerSink>));
+            SINK = std::ptr::null();
+        }
+    }
+}
+
+/// Forwards the measurement of GPU timer `name` into the sink.
+#[inline]
+pub fn gpu_timer(name: &str, elapsed: Duration) {
+    if let Some(sink) = sink() {
+        sink.gpu_timer(name, elapsed);
+    }
+}
+
+/// A scoped marker, which ends the scope when being dropped.
+pub struct Scope(&'static str);
+
+impl Scope {
+    #[inline]
+    pub fn new(name: &'static str) -> Self {
+        if let Some(sink) = sink() {
+            sink.begin(name);
+        }
+
+        Scope(name)
+    }
+}
+
+impl Drop for Scope {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(sink) = sink() {
+            sink.end(self.0);
+        }
+    }
+}
+
+mod ins {
+    use super::ProfilerSink;
+
+    pub static mut SINK: *const Box<dyn ProfilerSink> = std::ptr::null();
+
+    #[inline]
+    pub fn sink() -> Option<&'static dyn ProfilerSink> {
+        unsafe {
+            if SINK.is_null() {
+                None
+            } else {
+                Some((*SINK).as_ref())
+            }
+        }
+    }
+}
+
+/// Emits a marker that covers the rest of the enclosing block.
+#[cfg(feature = "profiler")]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::utils::profiler::Scope::new($name);
+    };
+}
+
+/// Emits a marker that covers the rest of the enclosing block.
+#[cfg(not(feature = "profiler"))]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {};
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl ProfilerSink for Recorder {
+        fn begin(&self, name: &'static str) {
+            self.0.lock().unwrap().push(format!("+{}", name));
+        }
+
+        fn end(&self, name: &'static str) {
+            self.0.lock().unwrap().push(format!("-{}", name));
+        }
+
+        fn gpu_timer(&self, name: &str, elapsed: Duration) {
+            let ms = elapsed.as_millis();
+            self.0.lock().unwrap().push(format!("{}={}ms", name, ms));
+        }
+    }
+
+    #[test]
+    fn nested() {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        set_sink(Box::new(Recorder(records.clone())));
+
+        {
+            let _a = Scope::new("A");
+            let _b = Scope::new("B");
+        }
+
+        gpu_timer("Shadow", Duration::from_millis(2));
+
+        clear_sink();
+        let _c = Scope::new("C");
+        gpu_timer("Shadow", Duration::from_millis(2));
+
+        let expected = vec!["+A", "+B", "-B", "-A", "Shadow=2ms"];
+        assert_eq!(*records.lock().unwrap(), expected);
+    }
+}
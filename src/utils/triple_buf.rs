@@ -0,0 +1,113 @@
+use std::sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A triple buffer, which has a middle buffer between the write and back buffers. The
+/// producer publishes the newest complete buffer into the middle one without waiting for
+/// the consumer, and the consumer always picks up the freshest one.
+///
+/// It could also be used as a regular double buffer with `swap`, which leaves the middle
+/// buffer untouched.
+pub struct TripleBuf<T> {
+    wbuf: RwLock<T>,
+    mbuf: Mutex<(T, bool)>,
+    rbuf: RwLock<T>,
+}
+
+impl<T: Default> Default for TripleBuf<T> {
+    fn default() -> Self {
+        TripleBuf {
+            wbuf: RwLock::new(Default::default()),
+            mbuf: Mutex::new((Default::default(), false)),
+            rbuf: RwLock::new(Default::default()),
+        }
+    }
+}
+
+impl<T> TripleBuf<T> {
+    #[inline]
+    pub fn new(w: T, m: T, r: T) -> Self {
+        TripleBuf {
+            wbuf: RwLock::new(w),
+            mbuf: Mutex::new((m, false)),
+            rbuf: RwLock::new(r),
+        }
+    }
+
+    #[inline]
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.wbuf.write().unwrap()
+    }
+
+    #[inline]
+    pub fn write_back_buf(&self) -> RwLockWriteGuard<'_, T> {
+        self.rbuf.write().unwrap()
+    }
+
+    #[inline]
+    pub fn read_back_buf(&self) -> RwLockReadGuard<'_, T> {
+        self.rbuf.read().unwrap()
+    }
+
+    /// Swaps the write and back buffers directly.
+    #[inline]
+    pub fn swap(&self) {
+        let mut wbuf = self.wbuf.write().unwrap();
+        let mut rbuf = self.rbuf.write().unwrap();
+        ::std::mem::swap::<T>(&mut wbuf, &mut rbuf);
+    }
+
+    /// Publishes the write buffer into the middle one. If the replaced middle buffer has
+    /// never been consumed, it's swapped into the write buffer and `merge(stale, fresh)` is
+    /// called, which gives a chance to salvage it before dropping.
+    pub fn publish_with<F>(&self, merge: F)
+    where
+        F: FnOnce(&mut T, &mut T),
+    {
+        let mut wbuf = self.wbuf.write().unwrap();
+        let mut mbuf = self.mbuf.lock().unwrap();
+        ::std::mem::swap::<T>(&mut wbuf, &mut mbuf.0);
+
+        if mbuf.1 {
+            merge(&mut wbuf, &mut mbuf.0);
+        }
+
+        mbuf.1 = true;
+    }
+
+    /// Swaps the freshest published buffer into the back buffer. Returns false if nothing
+    /// has been published since the last consumption.
+    pub fn consume(&self) -> bool {
+        let mut mbuf = self.mbuf.lock().unwrap();
+        if !mbuf.1 {
+            return false;
+        }
+
+        let mut rbuf = self.rbuf.write().unwrap();
+        ::std::mem::swap::<T>(&mut rbuf, &mut mbuf.0);
+        mbuf.1 = false;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn publish() {
+        let buf = TripleBuf::<Vec<u32>>::default();
+        assert!(!buf.consume());
+
+        buf.write().push(1);
+        buf.publish_with(|_, _| unreachable!());
+        buf.write().clear();
+        buf.write().push(2);
+        buf.publish_with(|stale, fresh| {
+            stale.extend(fresh.drain(..));
+            ::std::mem::swap(stale, fresh);
+        });
+
+        assert!(buf.consume());
+        assert_eq!(*buf.read_back_buf(), vec![1, 2]);
+        assert!(!buf.consume());
+    }
+}
@@ -0,0 +1,38 @@
+use super::mesh::{InstanceBufferHandle, MeshHandle};
+use super::shader::ShaderHandle;
+use super::storage_buffer::ShaderStorageBufferHandle;
+use super::surface::SurfaceHandle;
+use super::texture::{RenderTextureHandle, TextureHandle};
+use super::uniform_buffer::UniformBufferHandle;
+
+/// The handle of any kind of video resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VideoHandle {
+    Surface(SurfaceHandle),
+    Shader(ShaderHandle),
+    Mesh(MeshHandle),
+    Texture(TextureHandle),
+    RenderTexture(RenderTextureHandle),
+    InstanceBuffer(InstanceBufferHandle),
+    ShaderStorageBuffer(ShaderStorageBufferHandle),
+    UniformBuffer(UniformBufferHandle),
+}
+
+macro_rules! impl_video_handle {
+    ($name:ident, $variant:ident) => {
+        impl From<$name> for VideoHandle {
+            fn from(handle: $name) -> Self {
+                VideoHandle::$variant(handle)
+            }
+        }
+    };
+}
+
+impl_video_handle!(SurfaceHandle, Surface);
+impl_video_handle!(ShaderHandle, Shader);
+impl_video_handle!(MeshHandle, Mesh);
+impl_video_handle!(TextureHandle, Texture);
+impl_video_handle!(RenderTextureHandle, RenderTexture);
+impl_video_handle!(InstanceBufferHandle, InstanceBuffer);
+impl_video_handle!(ShaderStorageBufferHandle, ShaderStorageBuffer);
+impl_video_handle!(UniformBufferHandle, UniformBuffer);
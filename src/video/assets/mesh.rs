@@ -21,12 +21,33 @@ pub struct MeshParams {
     pub primitive: MeshPrimitive,
     /// The number of vertices in this mesh.
     pub num_verts: usize,
-    /// The number of indices in this mesh.
+    /// The number of indices in this mesh. The vertices are assembled in order without
+    /// index buffer if it's zero.
     pub num_idxes: usize,
     /// The start indices of sub-meshes.
     pub sub_mesh_offsets: SmallVec<[usize; 8]>,
+    /// The names and material slots of sub-meshes. It's either empty, or has one entry for
+    /// every offset in `sub_mesh_offsets`.
+    pub sub_meshes: Vec<SubMesh>,
     /// Trivial bounding box of vertices.
     pub aabb: Aabb3<f32>,
+    /// Reorders the indexed triangles with `mesh_process::optimize_vertex_cache` when the
+    /// mesh is created, which improves the hit rate of post-transform vertex cache. It's
+    /// worth it for large static meshes.
+    ///
+    /// Notes that this is not serialized with the mesh assets.
+    #[serde(skip)]
+    pub optimize_vertex_cache: bool,
+}
+
+/// The description of a sub-mesh, which is usually a primitive of authored model that has
+/// its own material.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SubMesh {
+    /// The name of this sub-mesh.
+    pub name: String,
+    /// The index of material slot that this sub-mesh should be drawn with.
+    pub material: usize,
 }
 
 /// Continuous data of vertices and its indices.
@@ -49,31 +70,154 @@ impl Default for MeshParams {
             num_idxes: 0,
             aabb: Aabb3::zero(),
             sub_mesh_offsets: SmallVec::new(),
+            sub_meshes: Vec::new(),
+            optimize_vertex_cache: false,
         }
     }
 }
 
 impl MeshParams {
     pub fn validate(&self, data: Option<&MeshData>) -> Result<()> {
+        if self.is_indexed() && self.num_verts > self.index_format.max_vertices() {
+            return Err(Error::MeshInvalid(format!(
+                "{} vertices could not be addressed with {:?}, which supports {} vertices \
+                 at most. Use IndexFormat::U32 instead.",
+                self.num_verts,
+                self.index_format,
+                self.index_format.max_vertices()
+            )));
+        }
+
         if let Some(v) = data {
+            let stride = self.layout.stride() as usize;
+            if stride > 0 && v.vptr.len() % stride != 0 {
+                return Err(Error::MeshInvalid(format!(
+                    "vertex data length {} is not a multiple of the vertex stride {}.",
+                    v.vptr.len(),
+                    stride
+                )));
+            }
+
             if v.vptr.len() > self.vertex_buffer_len() {
-                return Err(Error::OutOfBounds);
+                return Err(Error::MeshInvalid(format!(
+                    "vertex data length {} exceeds {} vertices * {} bytes stride.",
+                    v.vptr.len(),
+                    self.num_verts,
+                    stride
+                )));
+            }
+
+            if v.iptr.len() % self.index_format.stride() != 0 {
+                return Err(Error::MeshInvalid(format!(
+                    "index data length {} is not a multiple of the index stride {}.",
+                    v.iptr.len(),
+                    self.index_format.stride()
+                )));
             }
 
             if v.iptr.len() > self.index_buffer_len() {
-                return Err(Error::OutOfBounds);
+                return Err(Error::MeshInvalid(format!(
+                    "index data length {} exceeds {} indices * {} bytes stride.",
+                    v.iptr.len(),
+                    self.num_idxes,
+                    self.index_format.stride()
+                )));
+            }
+
+            // Immutable meshes could never be filled later, so partial data is an error.
+            if self.hint == MeshHint::Immutable
+                && (v.vptr.len() != self.vertex_buffer_len()
+                    || v.iptr.len() != self.index_buffer_len())
+            {
+                return Err(Error::MeshInvalid(format!(
+                    "immutable mesh expects {} bytes of vertices and {} bytes of indices, \
+                     but got {} and {}.",
+                    self.vertex_buffer_len(),
+                    self.index_buffer_len(),
+                    v.vptr.len(),
+                    v.iptr.len()
+                )));
+            }
+
+            // Scanning indices is linear in the size of mesh, so we only do it in debug builds.
+            if cfg!(debug_assertions) {
+                self.validate_indices(&v.iptr)?;
             }
         }
 
         for v in &self.sub_mesh_offsets {
-            if *v >= self.num_idxes {
+            if *v >= self.num_elements() {
                 return Err(Error::OutOfBounds);
             }
         }
 
+        if !self.sub_meshes.is_empty() && self.sub_meshes.len() != self.sub_mesh_offsets.len() {
+            return Err(Error::MeshInvalid(format!(
+                "{} sub-meshes are described while there are {} sub-mesh offsets.",
+                self.sub_meshes.len(),
+                self.sub_mesh_offsets.len()
+            )));
+        }
+
         Ok(())
     }
 
+    fn validate_indices(&self, iptr: &[u8]) -> Result<()> {
+        let stride = self.index_format.stride();
+        for (i, bytes) in iptr.chunks(stride).enumerate() {
+            let v = match self.index_format {
+                IndexFormat::U16 => usize::from(u16::from_ne_bytes([bytes[0], bytes[1]])),
+                IndexFormat::U32 => {
+                    u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+                }
+            };
+
+            if v >= self.num_verts {
+                return Err(Error::MeshInvalid(format!(
+                    "index {} at position {} is out of range of {} vertices.",
+                    v, i, self.num_verts
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the primitives are assembled with index buffer. Meshes without
+    /// indices are drawn with `glDrawArrays` instead.
+    #[inline]
+    pub fn is_indexed(&self) -> bool {
+        self.num_idxes > 0
+    }
+
+    /// Gets the number of elements that assemble primitives, which are indices for indexed
+    /// meshes and vertices otherwise. The offsets of sub-meshes are measured in elements.
+    #[inline]
+    pub fn num_elements(&self) -> usize {
+        if self.is_indexed() {
+            self.num_idxes
+        } else {
+            self.num_verts
+        }
+    }
+
+    /// Finds the sub-mesh by `name`, and returns the index to draw it.
+    pub fn sub_mesh(&self, name: &str) -> Option<MeshIndex> {
+        self.sub_meshes
+            .iter()
+            .position(|v| v.name == name)
+            .map(MeshIndex::SubMesh)
+    }
+
+    /// Gets the indices of sub-meshes that should be drawn with material slot `material`.
+    pub fn sub_meshes_of_material(&self, material: usize) -> impl Iterator<Item = MeshIndex> + '_ {
+        self.sub_meshes
+            .iter()
+            .enumerate()
+            .filter(move |(_, v)| v.material == material)
+            .map(|(i, _)| MeshIndex::SubMesh(i))
+    }
+
     #[inline]
     pub fn vertex_buffer_len(&self) -> usize {
         self.num_verts * self.layout.stride() as usize
@@ -85,8 +229,54 @@ impl MeshParams {
     }
 }
 
+impl_handle!(InstanceBufferHandle);
+
+/// The setup parameters of per-instance vertex buffer object. The attributes in `layout`
+/// advance once per instance instead of once per vertex.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct InstanceBufferParams {
+    /// Usage hints.
+    pub hint: MeshHint,
+    /// How a single instance structure looks like.
+    pub layout: VertexLayout,
+    /// The number of instances in this buffer.
+    pub num_instances: usize,
+}
+
+impl Default for InstanceBufferParams {
+    fn default() -> Self {
+        InstanceBufferParams {
+            hint: MeshHint::Stream,
+            layout: VertexLayout::default(),
+            num_instances: 0,
+        }
+    }
+}
+
+impl InstanceBufferParams {
+    pub fn validate(&self, data: Option<&[u8]>) -> Result<()> {
+        if let Some(v) = data {
+            if v.len() > self.buffer_len() {
+                return Err(Error::MeshInvalid(format!(
+                    "instance data length {} exceeds {} instances * {} bytes stride.",
+                    v.len(),
+                    self.num_instances,
+                    self.layout.stride()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn buffer_len(&self) -> usize {
+        self.num_instances * self.layout.stride() as usize
+    }
+}
+
 /// Mesh index.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum MeshIndex {
     SubMesh(usize),
     Ptr(usize, usize),
@@ -160,6 +350,14 @@ impl IndexFormat {
         }
     }
 
+    /// Gets the maximum number of vertices that could be addressed with this format.
+    pub fn max_vertices(self) -> usize {
+        match self {
+            IndexFormat::U16 => usize::from(u16::max_value()) + 1,
+            IndexFormat::U32 => u32::max_value() as usize,
+        }
+    }
+
     pub fn encode<T>(values: &[T]) -> &[u8]
     where
         T: Copy,
@@ -349,6 +547,80 @@ mod test {
         assert_eq!(layout.element(Attribute::Normal), None);
     }
 
+    #[test]
+    fn validate() {
+        let layout = VertexLayout::build()
+            .with(Attribute::Position, VertexFormat::Float, 2, false)
+            .finish();
+
+        let mut params = MeshParams::default();
+        params.layout = layout;
+        params.num_verts = 3;
+        params.num_idxes = 3;
+
+        let data = |verts: usize, idxes: &[u16]| MeshData {
+            vptr: vec![0; verts * 8].into_boxed_slice(),
+            iptr: IndexFormat::encode(idxes).to_vec().into_boxed_slice(),
+        };
+
+        assert!(params.validate(Some(&data(3, &[0, 1, 2]))).is_ok());
+        assert!(params.validate(Some(&data(2, &[0, 1, 2]))).is_err());
+        assert!(params.validate(Some(&data(4, &[0, 1, 2]))).is_err());
+        assert!(params.validate(Some(&data(3, &[0, 1]))).is_err());
+
+        let mut v = data(3, &[0, 1, 2]);
+        v.vptr = vec![0; 23].into_boxed_slice();
+        assert!(params.validate(Some(&v)).is_err());
+
+        if cfg!(debug_assertions) {
+            assert!(params.validate(Some(&data(3, &[0, 1, 3]))).is_err());
+        }
+
+        params.hint = MeshHint::Dynamic;
+        assert!(params.validate(Some(&data(2, &[0, 1]))).is_ok());
+    }
+
+    #[test]
+    fn index_format() {
+        let mut params = MeshParams::default();
+        params.num_verts = 70000;
+        params.num_idxes = 3;
+        assert!(params.validate(None).is_err());
+
+        params.index_format = IndexFormat::U32;
+        let idxes = [0u32, 65536, 69999];
+        let data = MeshData {
+            vptr: vec![0; params.vertex_buffer_len()].into_boxed_slice(),
+            iptr: IndexFormat::encode(&idxes).to_vec().into_boxed_slice(),
+        };
+
+        assert!(params.validate(Some(&data)).is_ok());
+    }
+
+    #[test]
+    fn sub_meshes() {
+        let mut params = MeshParams::default();
+        params.num_verts = 6;
+        params.sub_mesh_offsets.push(0);
+        params.sub_mesh_offsets.push(3);
+
+        let sub_mesh = |name: &str, material| SubMesh {
+            name: name.into(),
+            material,
+        };
+
+        params.sub_meshes.push(sub_mesh("body", 0));
+        assert!(params.validate(None).is_err());
+
+        params.sub_meshes.push(sub_mesh("glass", 1));
+        assert!(params.validate(None).is_ok());
+        assert_eq!(params.sub_mesh("glass"), Some(MeshIndex::SubMesh(1)));
+        assert_eq!(params.sub_mesh("wheel"), None);
+
+        let v: Vec<_> = params.sub_meshes_of_material(0).collect();
+        assert_eq!(v, vec![MeshIndex::SubMesh(0)]);
+    }
+
     #[test]
     fn rewrite() {
         let layout = VertexLayout::build()
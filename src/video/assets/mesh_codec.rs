@@ -0,0 +1,395 @@
+//! Decoders of compressed vertex and index data, which are used by `MeshLoader` to
+//! decompress mesh assets on the worker threads.
+
+use crate::errors::*;
+
+use super::mesh::{IndexFormat, MeshData, MeshParams, MeshPrimitive};
+
+/// The codecs of compressed vertex and index data.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshCodec {
+    /// The vertex codec and triangle index codec of [meshoptimizer](https://github.com/zeux/meshoptimizer).
+    /// It requires the stride of vertex to be a multiple of 4, and the primitive to be
+    /// `MeshPrimitive::Triangles`.
+    Meshopt,
+}
+
+impl MeshCodec {
+    /// Returns all the codecs that could be decoded.
+    #[inline]
+    pub fn supported() -> &'static [MeshCodec] {
+        &[MeshCodec::Meshopt]
+    }
+
+    /// Decompresses the vertex and index data of mesh with `params`.
+    pub fn decode(self, params: &MeshParams, data: &MeshData) -> Result<MeshData> {
+        match self {
+            MeshCodec::Meshopt => {
+                if params.primitive != MeshPrimitive::Triangles {
+                    bail!(
+                        "[MeshCodec] {:?} could only decode triangles, but got {:?}.",
+                        self,
+                        params.primitive
+                    );
+                }
+
+                let stride = params.layout.stride() as usize;
+                let verts = meshopt::decode_vertex_buffer(&data.vptr, params.num_verts, stride)?;
+                let idxes = meshopt::decode_index_buffer(
+                    &data.iptr,
+                    params.num_idxes,
+                    params.index_format,
+                )?;
+
+                Ok(MeshData {
+                    vptr: verts.into_boxed_slice(),
+                    iptr: idxes.into_boxed_slice(),
+                })
+            }
+        }
+    }
+}
+
+mod meshopt {
+    use crate::errors::*;
+
+    use super::IndexFormat;
+
+    const VERTEX_HEADER: u8 = 0xa0;
+    const INDEX_HEADER: u8 = 0xe0;
+
+    const VERTEX_BLOCK_SIZE_BYTES: usize = 8192;
+    const VERTEX_BLOCK_MAX_SIZE: usize = 256;
+    const BYTE_GROUP_SIZE: usize = 16;
+    const BYTE_GROUP_DECODE_LIMIT: usize = 24;
+    const TAIL_MAX_SIZE: usize = 32;
+
+    pub fn decode_vertex_buffer(bytes: &[u8], count: usize, size: usize) -> Result<Vec<u8>> {
+        if size == 0 || size > 256 || size % 4 != 0 {
+            bail!("[Meshopt] vertex size {} is not supported.", size);
+        }
+
+        let tail = size.max(TAIL_MAX_SIZE);
+        if bytes.len() < 1 + tail {
+            bail!("[Meshopt] vertex data is truncated.");
+        }
+
+        if bytes[0] & 0xf0 != VERTEX_HEADER || bytes[0] & 0x0f > 0 {
+            bail!("[Meshopt] unknown vertex codec header {:#x}.", bytes[0]);
+        }
+
+        // The baseline of the first vertex is stored at the end of data.
+        let mut last = [0u8; 256];
+        last[..size].copy_from_slice(&bytes[bytes.len() - size..]);
+
+        let block =
+            ((VERTEX_BLOCK_SIZE_BYTES / size) & !(BYTE_GROUP_SIZE - 1)).min(VERTEX_BLOCK_MAX_SIZE);
+
+        let mut buf = [0u8; VERTEX_BLOCK_MAX_SIZE];
+        let mut verts = vec![0u8; count * size];
+        let mut cursor = 1;
+        let mut offset = 0;
+
+        while offset < count {
+            let n = block.min(count - offset);
+            let aligned = (n + BYTE_GROUP_SIZE - 1) & !(BYTE_GROUP_SIZE - 1);
+
+            // Every byte of vertices is encoded as a delta to the previous vertex in separate
+            // streams.
+            for k in 0..size {
+                cursor = decode_bytes(bytes, cursor, &mut buf[..aligned])?;
+
+                let mut p = last[k];
+                for i in 0..n {
+                    let v = unzigzag8(buf[i]).wrapping_add(p);
+                    verts[(offset + i) * size + k] = v;
+                    p = v;
+                }
+
+                last[k] = p;
+            }
+
+            offset += n;
+        }
+
+        if cursor != bytes.len() - tail {
+            bail!("[Meshopt] vertex data is malformed.");
+        }
+
+        Ok(verts)
+    }
+
+    fn decode_bytes(bytes: &[u8], cursor: usize, buf: &mut [u8]) -> Result<usize> {
+        let groups = buf.len() / BYTE_GROUP_SIZE;
+        let header = cursor;
+        let mut cursor = cursor + (groups + 3) / 4;
+
+        for (i, v) in buf.chunks_mut(BYTE_GROUP_SIZE).enumerate() {
+            if cursor > bytes.len() || bytes.len() - cursor < BYTE_GROUP_DECODE_LIMIT {
+                bail!("[Meshopt] vertex data is truncated.");
+            }
+
+            let bits = (bytes[header + i / 4] >> ((i % 4) * 2)) & 3;
+            cursor = decode_bytes_group(bytes, cursor, v, bits);
+        }
+
+        Ok(cursor)
+    }
+
+    fn decode_bytes_group(bytes: &[u8], cursor: usize, buf: &mut [u8], bits_log2: u8) -> usize {
+        match bits_log2 {
+            0 => {
+                for v in buf.iter_mut() {
+                    *v = 0;
+                }
+
+                cursor
+            }
+            1 | 2 => {
+                // Packed 2-bits or 4-bits values, and the values that do not fit are stored
+                // in the following bytes.
+                let bits = 1 << bits_log2;
+                let mask = (1u8 << bits) - 1;
+                let mut var = cursor + bits * 2;
+
+                for (i, v) in buf.iter_mut().enumerate() {
+                    let byte = bytes[cursor + i * bits / 8];
+                    let enc = (byte >> (8 - bits - (i * bits) % 8)) & mask;
+                    if enc == mask {
+                        *v = bytes[var];
+                        var += 1;
+                    } else {
+                        *v = enc;
+                    }
+                }
+
+                var
+            }
+            _ => {
+                buf.copy_from_slice(&bytes[cursor..cursor + BYTE_GROUP_SIZE]);
+                cursor + BYTE_GROUP_SIZE
+            }
+        }
+    }
+
+    #[inline]
+    fn unzigzag8(v: u8) -> u8 {
+        0u8.wrapping_sub(v & 1) ^ (v >> 1)
+    }
+
+    pub fn decode_index_buffer(bytes: &[u8], count: usize, format: IndexFormat) -> Result<Vec<u8>> {
+        if count % 3 != 0 {
+            bail!("[Meshopt] index count {} is not a multiple of 3.", count);
+        }
+
+        if bytes.len() < 1 + count / 3 + 16 {
+            bail!("[Meshopt] index data is truncated.");
+        }
+
+        if bytes[0] & 0xf0 != INDEX_HEADER || bytes[0] & 0x0f > 1 {
+            bail!("[Meshopt] unknown index codec header {:#x}.", bytes[0]);
+        }
+
+        let mut edges = [(u32::max_value(), u32::max_value()); 16];
+        let mut edges_offset = 0usize;
+        let mut verts = [u32::max_value(); 16];
+        let mut verts_offset = 0usize;
+
+        let mut next = 0u32;
+        let mut last = 0u32;
+        let fec_max = if bytes[0] & 0x0f >= 1 { 13 } else { 15 };
+
+        let mut code = 1;
+        let mut data = 1 + count / 3;
+        let data_safe_end = bytes.len() - 16;
+        let code_aux_table = &bytes[data_safe_end..];
+
+        let mut idxes = Vec::with_capacity(count * format.stride());
+
+        for _ in 0..count / 3 {
+            if data > data_safe_end {
+                bail!("[Meshopt] index data is truncated.");
+            }
+
+            let code_tri = bytes[code];
+            code += 1;
+
+            let (a, b, c);
+            if code_tri < 0xf0 {
+                // The triangle shares an edge with the recent triangles.
+                let fe = (code_tri >> 4) as usize;
+                let edge = edges[edges_offset.wrapping_sub(1 + fe) & 15];
+                a = edge.0;
+                b = edge.1;
+
+                let fec = (code_tri & 15) as usize;
+                if fec < fec_max {
+                    let fec0 = fec == 0;
+                    c = if fec0 {
+                        next
+                    } else {
+                        verts[verts_offset.wrapping_sub(1 + fec) & 15]
+                    };
+
+                    if fec0 {
+                        next += 1;
+                    }
+
+                    push_vertex(&mut verts, &mut verts_offset, c, fec0);
+                } else {
+                    // Decodes 13, 14 into -1, 1, and 15 into a delta-encoded index.
+                    c = match fec {
+                        13 => last.wrapping_sub(1),
+                        14 => last.wrapping_add(1),
+                        _ => decode_index(bytes, &mut data, last),
+                    };
+
+                    last = c;
+                    push_vertex(&mut verts, &mut verts_offset, c, true);
+                }
+
+                push_edge(&mut edges, &mut edges_offset, c, b);
+                push_edge(&mut edges, &mut edges_offset, a, c);
+            } else {
+                let (fea, feb, fec) = if code_tri < 0xfe {
+                    let code_aux = code_aux_table[(code_tri & 15) as usize];
+                    (0, (code_aux >> 4) as usize, (code_aux & 15) as usize)
+                } else {
+                    let code_aux = bytes[data];
+                    data += 1;
+
+                    if code_aux == 0 {
+                        next = 0;
+                    }
+
+                    let fea = if code_tri == 0xfe { 0 } else { 15 };
+                    (fea, (code_aux >> 4) as usize, (code_aux & 15) as usize)
+                };
+
+                let mut read = |fe: usize| {
+                    if fe == 0 {
+                        next += 1;
+                        next - 1
+                    } else {
+                        verts[verts_offset.wrapping_sub(fe) & 15]
+                    }
+                };
+
+                let mut v = [read(fea), read(feb), read(fec)];
+                for (i, &fe) in [fea, feb, fec].iter().enumerate() {
+                    if fe == 15 && code_tri >= 0xfe {
+                        v[i] = decode_index(bytes, &mut data, last);
+                        last = v[i];
+                    }
+                }
+
+                a = v[0];
+                b = v[1];
+                c = v[2];
+
+                // Only the new vertices are pushed into the fifo.
+                let fixed = code_tri >= 0xfe;
+                let new_b = feb == 0 || (fixed && feb == 15);
+                let new_c = fec == 0 || (fixed && fec == 15);
+                push_vertex(&mut verts, &mut verts_offset, a, true);
+                push_vertex(&mut verts, &mut verts_offset, b, new_b);
+                push_vertex(&mut verts, &mut verts_offset, c, new_c);
+
+                push_edge(&mut edges, &mut edges_offset, b, a);
+                push_edge(&mut edges, &mut edges_offset, c, b);
+                push_edge(&mut edges, &mut edges_offset, a, c);
+            }
+
+            for &v in &[a, b, c] {
+                match format {
+                    IndexFormat::U16 => {
+                        if v > u32::from(u16::max_value()) {
+                            bail!("[Meshopt] index {} overflows IndexFormat::U16.", v);
+                        }
+
+                        idxes.extend_from_slice(&(v as u16).to_ne_bytes());
+                    }
+                    IndexFormat::U32 => idxes.extend_from_slice(&v.to_ne_bytes()),
+                }
+            }
+        }
+
+        if data != data_safe_end {
+            bail!("[Meshopt] index data is malformed.");
+        }
+
+        Ok(idxes)
+    }
+
+    #[inline]
+    fn push_edge(fifo: &mut [(u32, u32); 16], offset: &mut usize, a: u32, b: u32) {
+        fifo[*offset] = (a, b);
+        *offset = (*offset + 1) & 15;
+    }
+
+    #[inline]
+    fn push_vertex(fifo: &mut [u32; 16], offset: &mut usize, v: u32, cond: bool) {
+        fifo[*offset] = v;
+        *offset = (*offset + cond as usize) & 15;
+    }
+
+    fn decode_index(bytes: &[u8], data: &mut usize, last: u32) -> u32 {
+        let mut v = 0;
+        for i in 0..5 {
+            let group = bytes[*data];
+            *data += 1;
+
+            v |= u32::from(group & 127) << (7 * i);
+            if group < 128 {
+                break;
+            }
+        }
+
+        last.wrapping_add((v >> 1) ^ 0u32.wrapping_sub(v & 1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vertex_buffer() {
+        let mut bytes = vec![0xa0];
+        // Byte 0, 2-bits deltas.
+        bytes.extend_from_slice(&[0x01, 0x80, 0, 0, 0]);
+        // Byte 1, zero deltas.
+        bytes.extend_from_slice(&[0x00]);
+        // Byte 2, 4-bits deltas.
+        bytes.extend_from_slice(&[0x02, 0x10, 0, 0, 0, 0, 0, 0, 0]);
+        // Byte 3, raw deltas.
+        bytes.extend_from_slice(&[0x03, 0x08]);
+        bytes.extend_from_slice(&[0; 15]);
+        // Tail with the baseline vertex.
+        bytes.extend_from_slice(&[0; 32]);
+
+        let verts = meshopt::decode_vertex_buffer(&bytes, 1, 4).unwrap();
+        assert_eq!(verts, vec![1, 0, 255, 4]);
+
+        assert!(meshopt::decode_vertex_buffer(&bytes[1..], 1, 4).is_err());
+        assert!(meshopt::decode_vertex_buffer(&bytes[..bytes.len() - 1], 1, 4).is_err());
+        assert!(meshopt::decode_vertex_buffer(&bytes, 1, 3).is_err());
+    }
+
+    #[test]
+    fn index_buffer() {
+        // A triangle with new vertices, and another one that shares an edge with it.
+        let mut bytes = vec![0xe1, 0xfe, 0x10, 0x00];
+        bytes.extend_from_slice(&[0; 16]);
+
+        let idxes = meshopt::decode_index_buffer(&bytes, 6, IndexFormat::U16).unwrap();
+        assert_eq!(idxes, IndexFormat::encode::<u16>(&[0, 1, 2, 2, 1, 3]));
+
+        let idxes = meshopt::decode_index_buffer(&bytes, 6, IndexFormat::U32).unwrap();
+        assert_eq!(idxes, IndexFormat::encode::<u32>(&[0, 1, 2, 2, 1, 3]));
+
+        assert!(meshopt::decode_index_buffer(&bytes, 5, IndexFormat::U16).is_err());
+        assert!(meshopt::decode_index_buffer(&bytes, 3, IndexFormat::U16).is_err());
+        assert!(meshopt::decode_index_buffer(&bytes[1..], 6, IndexFormat::U16).is_err());
+    }
+}
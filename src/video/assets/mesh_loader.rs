@@ -1,41 +1,91 @@
 use bincode;
 use std::io::Cursor;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use crate::errors::*;
 use crate::res::utils::prelude::ResourceLoader;
-use crate::utils::double_buf::DoubleBuf;
+use crate::utils::triple_buf::TripleBuf;
 
 use super::super::backends::frame::{Command, Frame};
 use super::mesh::*;
+use super::mesh_codec::MeshCodec;
+use super::mesh_process;
 
 pub const MAGIC: [u8; 8] = [b'V', b'M', b'S', b'H', b' ', 0, 0, 1];
+/// The magic number of meshes whose vertex and index data are compressed with `MeshCodec`,
+/// which is serialized right after the `MeshParams`.
+pub const MAGIC_COMPRESSED: [u8; 8] = [b'V', b'M', b'S', b'H', b' ', 0, 0, 2];
+
+/// The container formats of encoded meshes that could be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshFormat {
+    /// The mesh resources compiled by crayon-cli, whose vertex and index data might be
+    /// compressed with `MeshCodec`.
+    Crayon,
+}
+
+impl MeshFormat {
+    /// Decodes the mesh from `bytes`.
+    pub fn decode(self, bytes: &[u8]) -> Result<(MeshParams, MeshData)> {
+        match self {
+            MeshFormat::Crayon => {
+                let compressed = bytes.len() >= 8 && bytes[0..8] == MAGIC_COMPRESSED[..];
+                if !compressed && (bytes.len() < 8 || bytes[0..8] != MAGIC[..]) {
+                    bail!("[MeshLoader] MAGIC number not match.");
+                }
+
+                let mut file = Cursor::new(&bytes[8..]);
+                let params: MeshParams = bincode::deserialize_from(&mut file)?;
+
+                let data = if compressed {
+                    let codec: MeshCodec = bincode::deserialize_from(&mut file)?;
+                    let data: MeshData = bincode::deserialize_from(&mut file)?;
+                    info!("[MeshLoader] decodes mesh with {:?}.", codec);
+                    codec.decode(&params, &data)?
+                } else {
+                    bincode::deserialize_from(&mut file)?
+                };
+
+                Ok((params, data))
+            }
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct MeshLoader {
-    frames: Arc<DoubleBuf<Frame>>,
+    frames: Arc<TripleBuf<Frame>>,
+    fix_winding: Arc<AtomicBool>,
 }
 
 impl MeshLoader {
-    pub(crate) fn new(frames: Arc<DoubleBuf<Frame>>) -> Self {
-        MeshLoader { frames }
+    pub(crate) fn new(frames: Arc<TripleBuf<Frame>>) -> Self {
+        MeshLoader {
+            frames,
+            fix_winding: Arc::new(AtomicBool::new(false)),
+        }
     }
-}
 
-impl ResourceLoader for MeshLoader {
-    type Handle = MeshHandle;
-    type Intermediate = (MeshParams, Option<MeshData>);
-    type Resource = MeshParams;
+    pub(crate) fn decode(
+        &self,
+        handle: MeshHandle,
+        bytes: &[u8],
+        format: MeshFormat,
+    ) -> Result<(MeshParams, Option<MeshData>)> {
+        let (params, mut data) = format.decode(bytes)?;
+        params.validate(Some(&data))?;
 
-    fn load(&self, handle: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate> {
-        if bytes[0..8] != MAGIC[..] {
-            bail!("[MeshLoader] MAGIC number not match.");
+        if self.fix_winding.load(Ordering::Relaxed)
+            && params.primitive == MeshPrimitive::Triangles
+            && params.is_indexed()
+        {
+            match mesh_process::fix_winding(&params, &mut data) {
+                Ok(num) => info!("[MeshLoader] flips {} triangles of {:?}.", num, handle),
+                Err(err) => warn!("[MeshLoader] fails to fix winding of {:?}. {}", handle, err),
+            }
         }
 
-        let mut file = Cursor::new(&bytes[8..]);
-        let params: MeshParams = bincode::deserialize_from(&mut file)?;
-        let data = bincode::deserialize_from(&mut file)?;
-
         info!(
             "[MeshLoader] load {:?}. (Verts: {}, Indxes: {})",
             handle, params.num_verts, params.num_idxes
@@ -44,12 +94,63 @@ impl ResourceLoader for MeshLoader {
         Ok((params, Some(data)))
     }
 
+    /// Sets whether the winding order of imported triangles should be made consistent
+    /// with `mesh_process::fix_winding`. It's disabled by default.
+    pub(crate) fn set_fix_winding(&self, enabled: bool) {
+        self.fix_winding.store(enabled, Ordering::Relaxed);
+    }
+}
+
+impl ResourceLoader for MeshLoader {
+    type Handle = MeshHandle;
+    type Intermediate = (MeshParams, Option<MeshData>);
+    type Resource = MeshParams;
+
+    fn load(&self, handle: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate> {
+        self.decode(handle, bytes, MeshFormat::Crayon)
+    }
+
     fn create(&self, handle: Self::Handle, item: Self::Intermediate) -> Result<Self::Resource> {
         info!("[MeshLoader] create {:?}.", handle);
-        item.0.validate(item.1.as_ref())?;
-        let cmd = Command::CreateMesh(Box::new((handle, item.0.clone(), item.1)));
+        let (params, mut data) = item;
+        params.validate(data.as_ref())?;
+
+        if params.optimize_vertex_cache {
+            if let Some(ref mut data) = data {
+                mesh_process::optimize_vertex_cache(&params, data)?;
+            }
+        }
+
+        let cmd = Command::CreateMesh(Box::new((handle, params.clone(), data)));
+        self.frames.write().cmds.push(cmd);
+        Ok(params)
+    }
+
+    fn create_batch(
+        &self,
+        items: Vec<(Self::Handle, Self::Intermediate)>,
+    ) -> Result<Vec<Self::Resource>> {
+        info!("[MeshLoader] create {} meshes.", items.len());
+
+        let mut resources = Vec::with_capacity(items.len());
+        let mut creations = Vec::with_capacity(items.len());
+
+        for (handle, (params, mut data)) in items {
+            params.validate(data.as_ref())?;
+
+            if params.optimize_vertex_cache {
+                if let Some(ref mut data) = data {
+                    mesh_process::optimize_vertex_cache(&params, data)?;
+                }
+            }
+
+            resources.push(params.clone());
+            creations.push((handle, params, data));
+        }
+
+        let cmd = Command::CreateMeshes(creations);
         self.frames.write().cmds.push(cmd);
-        Ok(item.0)
+        Ok(resources)
     }
 
     fn delete(&self, handle: Self::Handle, _: Self::Resource) {
@@ -0,0 +1,422 @@
+//! Optional post-processing steps of mesh data, which could be applied by `MeshLoader` on
+//! the worker threads while importing mesh assets.
+
+use std::collections::VecDeque;
+
+use crate::errors::*;
+use crate::utils::hash::FastHashMap;
+use crate::video::assets::shader::Attribute;
+
+use super::mesh::{IndexFormat, MeshData, MeshParams, MeshPrimitive, VertexFormat};
+
+/// Makes the winding order of triangles consistent, so back-face culling works on meshes
+/// whose triangles are partially inside-out. Returns the number of flipped triangles.
+///
+/// The triangles sharing an edge are flood-filled from a seed triangle, and the neighbours
+/// that traverse the shared edge in the same direction are flipped. The vertices are welded
+/// by position, so the seams with split normals or texcoords don't break the adjacency. The
+/// majority of each connected piece keeps its winding order.
+pub fn fix_winding(params: &MeshParams, data: &mut MeshData) -> Result<usize> {
+    if params.primitive != MeshPrimitive::Triangles || !params.is_indexed() {
+        bail!("[MeshProcess] could only fix the winding of indexed triangles.");
+    }
+
+    let mut idxes = checked_indices(params, &data.iptr)?;
+    let welded = weld_positions(params, &data.vptr)?;
+    let num_tris = idxes.len() / 3;
+
+    // Maps the undirected edges into the triangles sharing them, along with the direction
+    // they are traversed in.
+    let mut edges: FastHashMap<(u32, u32), Vec<(usize, bool)>> = FastHashMap::default();
+    for t in 0..num_tris {
+        for e in 0..3 {
+            let a = welded[idxes[t * 3 + e] as usize];
+            let b = welded[idxes[t * 3 + (e + 1) % 3] as usize];
+            if a != b {
+                let key = (a.min(b), a.max(b));
+                edges.entry(key).or_insert_with(Vec::new).push((t, a < b));
+            }
+        }
+    }
+
+    let mut visited = vec![false; num_tris];
+    let mut flipped = vec![false; num_tris];
+    let mut piece = Vec::new();
+    let mut queue = VecDeque::new();
+
+    for seed in 0..num_tris {
+        if visited[seed] {
+            continue;
+        }
+
+        visited[seed] = true;
+        queue.push_back(seed);
+        piece.clear();
+
+        while let Some(t) = queue.pop_front() {
+            piece.push(t);
+
+            for e in 0..3 {
+                let a = welded[idxes[t * 3 + e] as usize];
+                let b = welded[idxes[t * 3 + (e + 1) % 3] as usize];
+                if a == b {
+                    continue;
+                }
+
+                // The consistent neighbour traverses the shared edge in the opposite
+                // direction.
+                let dir = (a < b) != flipped[t];
+                for &(n, v) in &edges[&(a.min(b), a.max(b))] {
+                    if !visited[n] {
+                        visited[n] = true;
+                        flipped[n] = v == dir;
+                        queue.push_back(n);
+                    }
+                }
+            }
+        }
+
+        let count = piece.iter().filter(|&&t| flipped[t]).count();
+        if count * 2 > piece.len() {
+            for &t in &piece {
+                flipped[t] = !flipped[t];
+            }
+        }
+    }
+
+    let mut num = 0;
+    for t in 0..num_tris {
+        if flipped[t] {
+            idxes.swap(t * 3 + 1, t * 3 + 2);
+            num += 1;
+        }
+    }
+
+    if num > 0 {
+        data.iptr = write_indices(params.index_format, &idxes);
+    }
+
+    Ok(num)
+}
+
+/// Reorders the triangles to improve the hit rate of post-transform vertex cache, with the
+/// [algorithm](https://tomforsyth1000.github.io/papers/fast_vert_cache_opt.html) of Tom
+/// Forsyth. The triangles of each sub-mesh are reordered separately, and their vertices and
+/// winding orders are kept as they are.
+pub fn optimize_vertex_cache(params: &MeshParams, data: &mut MeshData) -> Result<()> {
+    if params.primitive != MeshPrimitive::Triangles || !params.is_indexed() {
+        bail!("[MeshProcess] could only optimize the indexed triangles.");
+    }
+
+    let idxes = checked_indices(params, &data.iptr)?;
+    let mut offsets: Vec<usize> = params.sub_mesh_offsets.iter().cloned().collect();
+    offsets.push(0);
+    offsets.push(idxes.len());
+    offsets.sort();
+    offsets.dedup();
+
+    let mut optimized = Vec::with_capacity(idxes.len());
+    for range in offsets.windows(2) {
+        if range[0] % 3 != 0 || range[1] % 3 != 0 {
+            bail!("[MeshProcess] the sub-mesh offsets should be multiples of 3.");
+        }
+
+        let tris = &idxes[range[0]..range[1]];
+        optimized.extend(forsyth(tris, params.num_verts));
+    }
+
+    data.iptr = write_indices(params.index_format, &optimized);
+    Ok(())
+}
+
+const CACHE_SIZE: usize = 32;
+
+fn vertex_score(position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+
+    // The vertices of last triangle get a fixed score, since it doesn't matter in which
+    // order they are used. The others decay with their positions in cache.
+    let score = match position {
+        Some(v) if v < 3 => 0.75,
+        Some(v) => (1.0 - (v - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(1.5),
+        None => 0.0,
+    };
+
+    // Boosts the vertices with few triangles left, so the lone ones are not left behind.
+    score + 2.0 * (remaining as f32).powf(-0.5)
+}
+
+fn forsyth(idxes: &[u32], num_verts: usize) -> Vec<u32> {
+    let num_tris = idxes.len() / 3;
+
+    let mut tris: Vec<Vec<usize>> = vec![Vec::new(); num_verts];
+    for (i, &v) in idxes.iter().enumerate() {
+        tris[v as usize].push(i / 3);
+    }
+
+    let mut positions = vec![None; num_verts];
+    let mut vscores: Vec<f32> = tris.iter().map(|v| vertex_score(None, v.len())).collect();
+    let tscore = |vscores: &[f32], t: usize| -> f32 {
+        idxes[t * 3..t * 3 + 3]
+            .iter()
+            .map(|&v| vscores[v as usize])
+            .sum()
+    };
+
+    let mut added = vec![false; num_tris];
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut output = Vec::with_capacity(idxes.len());
+    let mut cursor = 0;
+
+    let mut best = None;
+    let mut score = ::std::f32::MIN;
+    for t in 0..num_tris {
+        let s = tscore(&vscores, t);
+        if s > score {
+            best = Some(t);
+            score = s;
+        }
+    }
+
+    while let Some(t) = best {
+        let tri = &idxes[t * 3..t * 3 + 3];
+        added[t] = true;
+        output.extend_from_slice(tri);
+
+        let mut next = Vec::with_capacity(CACHE_SIZE + 3);
+        for &v in tri {
+            tris[v as usize].retain(|&x| x != t);
+            if !next.contains(&v) {
+                next.push(v);
+            }
+        }
+
+        for &v in &cache {
+            if !next.contains(&v) {
+                next.push(v);
+            }
+        }
+
+        // Updates the scores of vertices which have been moved or evicted from the cache.
+        for (i, &v) in next.iter().enumerate() {
+            let v = v as usize;
+            positions[v] = if i < CACHE_SIZE { Some(i) } else { None };
+            vscores[v] = vertex_score(positions[v], tris[v].len());
+        }
+
+        next.truncate(CACHE_SIZE);
+        cache = next;
+
+        // Picks the next triangle from the ones referencing the cached vertices, or falls
+        // back to the first one left if none of them is referenced.
+        best = None;
+        score = ::std::f32::MIN;
+        for &v in &cache {
+            for &n in &tris[v as usize] {
+                let s = tscore(&vscores, n);
+                if s > score {
+                    best = Some(n);
+                    score = s;
+                }
+            }
+        }
+
+        if best.is_none() {
+            while cursor < num_tris && added[cursor] {
+                cursor += 1;
+            }
+
+            if cursor < num_tris {
+                best = Some(cursor);
+            }
+        }
+    }
+
+    output
+}
+
+/// Maps the vertices into the first ones which have the same position.
+fn weld_positions(params: &MeshParams, vptr: &[u8]) -> Result<Vec<u32>> {
+    let offset = match params.layout.offset(Attribute::Position) {
+        Some(v) => v as usize,
+        None => bail!("[MeshProcess] the vertex layout does not have positions."),
+    };
+
+    let element = params.layout.element(Attribute::Position).unwrap();
+    let size = match element.format {
+        VertexFormat::Byte | VertexFormat::UByte => 1,
+        VertexFormat::Short | VertexFormat::UShort => 2,
+        VertexFormat::Float => 4,
+    } * element.size as usize;
+
+    let stride = params.layout.stride() as usize;
+    if vptr.len() < params.num_verts * stride {
+        bail!("[MeshProcess] the vertex data is truncated.");
+    }
+
+    let mut positions = FastHashMap::default();
+    let welded = (0..params.num_verts)
+        .map(|i| {
+            let bytes = &vptr[i * stride + offset..i * stride + offset + size];
+            *positions.entry(bytes).or_insert(i as u32)
+        })
+        .collect();
+
+    Ok(welded)
+}
+
+/// Reads the indices of `iptr`, and makes sure that they address the vertices of `params`.
+fn checked_indices(params: &MeshParams, iptr: &[u8]) -> Result<Vec<u32>> {
+    if iptr.len() % params.index_format.stride() != 0 {
+        bail!("[MeshProcess] the index data is truncated.");
+    }
+
+    let idxes = read_indices(params.index_format, iptr);
+    if idxes.iter().any(|&v| v as usize >= params.num_verts) {
+        bail!("[MeshProcess] the indices are out of the vertex range.");
+    }
+
+    Ok(idxes)
+}
+
+fn read_indices(format: IndexFormat, iptr: &[u8]) -> Vec<u32> {
+    match format {
+        IndexFormat::U16 => iptr
+            .chunks(2)
+            .map(|v| u32::from(u16::from_ne_bytes([v[0], v[1]])))
+            .collect(),
+        IndexFormat::U32 => iptr
+            .chunks(4)
+            .map(|v| u32::from_ne_bytes([v[0], v[1], v[2], v[3]]))
+            .collect(),
+    }
+}
+
+fn write_indices(format: IndexFormat, idxes: &[u32]) -> Box<[u8]> {
+    let mut bytes = Vec::with_capacity(idxes.len() * format.stride());
+    for &v in idxes {
+        match format {
+            IndexFormat::U16 => bytes.extend_from_slice(&(v as u16).to_ne_bytes()),
+            IndexFormat::U32 => bytes.extend_from_slice(&v.to_ne_bytes()),
+        }
+    }
+
+    bytes.into_boxed_slice()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::video::assets::mesh::VertexLayout;
+
+    #[test]
+    fn winding() {
+        // A quad of two triangles, whose second one is inside-out. The vertices 4 and 5
+        // are the duplicates of 0 and 2 with split attributes, and the last triangle is
+        // consistent with the first one.
+        let verts: [[f32; 2]; 7] = [
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 1.0],
+            [0.0, 0.0],
+            [1.0, 1.0],
+            [0.5, -1.0],
+        ];
+
+        let idxes: [u16; 9] = [0, 1, 2, 4, 3, 5, 1, 0, 6];
+        let mut params = MeshParams::default();
+        params.layout = VertexLayout::build()
+            .with(Attribute::Position, VertexFormat::Float, 2, false)
+            .finish();
+        params.num_verts = 7;
+        params.num_idxes = 9;
+
+        let mut data = MeshData {
+            vptr: verts
+                .iter()
+                .flat_map(|v| v.iter().flat_map(|f| f.to_ne_bytes().to_vec()))
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+            iptr: IndexFormat::encode(&idxes).to_vec().into_boxed_slice(),
+        };
+
+        assert_eq!(fix_winding(&params, &mut data).unwrap(), 1);
+        let idxes = read_indices(IndexFormat::U16, &data.iptr);
+        assert_eq!(idxes, vec![0, 1, 2, 4, 5, 3, 1, 0, 6]);
+
+        // The malformed index data should be rejected instead of panicking.
+        let iptr = data.iptr.clone();
+        data.iptr = iptr[..iptr.len() - 1].to_vec().into_boxed_slice();
+        assert!(fix_winding(&params, &mut data).is_err());
+
+        let idxes: [u16; 3] = [0, 1, 7];
+        data.iptr = IndexFormat::encode(&idxes).to_vec().into_boxed_slice();
+        assert!(fix_winding(&params, &mut data).is_err());
+    }
+
+    fn misses(idxes: &[u32]) -> usize {
+        let mut fifo = VecDeque::new();
+        let mut misses = 0;
+        for &v in idxes {
+            if !fifo.contains(&v) {
+                misses += 1;
+                fifo.push_back(v);
+                if fifo.len() > 16 {
+                    fifo.pop_front();
+                }
+            }
+        }
+
+        misses
+    }
+
+    #[test]
+    fn vertex_cache() {
+        // A grid of 32x32 quads, whose triangles are scattered.
+        let n = 32;
+        let mut idxes = Vec::new();
+        for y in 0..n {
+            for x in 0..n {
+                let v = y * (n + 1) + x;
+                idxes.extend_from_slice(&[v, v + 1, v + n + 2]);
+                idxes.extend_from_slice(&[v, v + n + 2, v + n + 1]);
+            }
+        }
+
+        let num_tris = idxes.len() / 3;
+        let mut scattered = Vec::new();
+        for i in 0..num_tris {
+            let t = (i * 97) % num_tris;
+            scattered.extend_from_slice(&idxes[t * 3..t * 3 + 3]);
+        }
+
+        let mut params = MeshParams::default();
+        params.index_format = IndexFormat::U32;
+        params.num_verts = ((n + 1) * (n + 1)) as usize;
+        params.num_idxes = scattered.len();
+        params.sub_mesh_offsets.push(0);
+        params.sub_mesh_offsets.push(num_tris / 2 * 3);
+
+        let mut data = MeshData {
+            vptr: Vec::new().into_boxed_slice(),
+            iptr: write_indices(IndexFormat::U32, &scattered),
+        };
+
+        optimize_vertex_cache(&params, &mut data).unwrap();
+        let optimized = read_indices(IndexFormat::U32, &data.iptr);
+        assert!(misses(&optimized) * 2 < misses(&scattered));
+
+        // Each sub-mesh should still have the same triangles with the same winding order.
+        let half = num_tris / 2 * 3;
+        for (a, b) in [(0, half), (half, scattered.len())].iter().cloned() {
+            let mut lhs: Vec<&[u32]> = scattered[a..b].chunks(3).collect();
+            let mut rhs: Vec<&[u32]> = optimized[a..b].chunks(3).collect();
+            lhs.sort();
+            rhs.sort();
+            assert_eq!(lhs, rhs);
+        }
+    }
+}
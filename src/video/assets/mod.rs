@@ -1,27 +1,46 @@
+pub mod handle;
 pub mod shader;
 pub mod surface;
 pub mod texture;
 pub mod texture_loader;
 #[macro_use]
 pub mod mesh;
+pub mod mesh_codec;
 pub mod mesh_loader;
+pub mod mesh_process;
+pub mod storage_buffer;
+pub mod uniform_buffer;
 
 pub mod prelude {
-    pub use super::surface::{SurfaceHandle, SurfaceParams, SurfaceScissor, SurfaceViewport};
+    pub use super::handle::VideoHandle;
+
+    pub use super::surface::{
+        AaMode, OutputAdjustment, SurfaceHandle, SurfaceIntegerClear, SurfaceParams,
+        SurfaceSampler, SurfaceScissor, SurfaceViewport,
+    };
 
     pub use super::shader::{
-        Attribute, AttributeLayout, AttributeLayoutBuilder, BlendFactor, BlendValue, Comparison,
-        CullFace, Equation, FrontFaceOrder, RenderState, ShaderHandle, ShaderParams,
+        Attribute, AttributeLayout, AttributeLayoutBuilder, AttributeLocations, BlendFactor,
+        BlendValue, Comparison, CullFace, Equation, FrontFaceOrder, ImageAccess, ImageBinding,
+        LogicOp, PolygonMode, ProvokingVertex, RenderState, ShaderHandle, ShaderParams,
         UniformVariable, UniformVariableLayout, UniformVariableLayoutBuilder, UniformVariableType,
     };
 
     pub use super::texture::{
-        RenderTextureFormat, RenderTextureHandle, RenderTextureParams, TextureData, TextureFilter,
-        TextureFormat, TextureHandle, TextureHint, TextureParams, TextureWrap,
+        CubeTextureData, FormatUsage, Image, RenderTextureFormat, RenderTextureHandle,
+        RenderTextureParams, RenderTextureUsage, TextureData, TextureFilter, TextureFormat,
+        TextureHandle, TextureHint, TextureKind, TextureParams, TextureUsage, TextureWrap,
     };
 
     pub use super::mesh::{
-        IndexFormat, MeshData, MeshHandle, MeshHint, MeshIndex, MeshParams, MeshPrimitive,
-        VertexFormat, VertexLayout,
+        IndexFormat, InstanceBufferHandle, InstanceBufferParams, MeshData, MeshHandle, MeshHint,
+        MeshIndex, MeshParams, MeshPrimitive, SubMesh, VertexFormat, VertexLayout,
     };
+
+    pub use super::storage_buffer::{ShaderStorageBufferHandle, ShaderStorageBufferParams};
+    pub use super::uniform_buffer::{UniformBufferHandle, UniformBufferParams};
+
+    pub use super::mesh_codec::MeshCodec;
+    pub use super::mesh_loader::MeshFormat;
+    pub use super::texture_loader::ImageFormat;
 }
@@ -3,10 +3,12 @@
 use std::collections::hash_map::Values;
 use std::str::FromStr;
 
-use crate::math::prelude::{Matrix2, Matrix3, Matrix4, Vector2, Vector3, Vector4};
+use crate::math::prelude::{Color, Matrix2, Matrix3, Matrix4, Vector2, Vector3, Vector4};
 use crate::utils::prelude::{FastHashMap, HashValue};
 use crate::video::assets::mesh::VertexLayout;
-use crate::video::assets::texture::{RenderTextureHandle, TextureHandle};
+use crate::video::assets::storage_buffer::ShaderStorageBufferHandle;
+use crate::video::assets::texture::{RenderTextureFormat, RenderTextureHandle, TextureHandle};
+use crate::video::assets::uniform_buffer::UniformBufferHandle;
 use crate::video::errors::{Error, Result};
 use crate::video::{MAX_UNIFORM_VARIABLES, MAX_VERTEX_ATTRIBUTES};
 
@@ -14,9 +16,12 @@ impl_handle!(ShaderHandle);
 
 /// A `ShaderParams` encapusulate all the informations we need to configurate
 /// OpenGL before real drawing, like shaders, render states, etc.
-#[derive(Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ShaderParams {
     pub attributes: AttributeLayout,
+    /// The explicit locations of attributes, which are binded before linking. The locations
+    /// of the others are assigned by the linker.
+    pub attribute_locations: AttributeLocations,
     pub uniforms: UniformVariableLayout,
     pub state: RenderState,
 }
@@ -42,6 +47,23 @@ impl ShaderParams {
             ));
         }
 
+        for (name, location) in self.attribute_locations.iter() {
+            for (other, v) in self.attribute_locations.iter() {
+                if other != name && v == location {
+                    return Err(Error::ShaderInvalid(format!(
+                        "Attribute {:?} and {:?} are binded to the same location {}.",
+                        name, other, location
+                    )));
+                }
+            }
+        }
+
+        if self.state.logic_op.is_some() && self.state.color_blend.is_some() {
+            return Err(Error::ShaderInvalid(
+                "Logical operation is mutually exclusive with color blending.".into(),
+            ));
+        }
+
         Ok(())
     }
 }
@@ -106,7 +128,7 @@ impl FromStr for Attribute {
 }
 
 // AttributeLayout defines an layout of attributes into program.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub struct AttributeLayout {
     len: u8,
     elements: [(Attribute, u8, bool); MAX_VERTEX_ATTRIBUTES],
@@ -210,8 +232,82 @@ impl AttributeLayoutBuilder {
     }
 }
 
+/// The explicit locations of attributes, which is useful for interop with externally-authored
+/// shaders that use `layout(location = N)`, and avoids silent mismatches when the ordering
+/// of attributes differs.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct AttributeLocations {
+    locations: [Option<u8>; MAX_VERTEX_ATTRIBUTES],
+}
+
+impl AttributeLocations {
+    /// Creates a mapping that binds every attribute to its ordinal in `Attribute`, e.g.
+    /// `Position` to 0, `Normal` to 1, and `Texcoord0` to 8.
+    pub fn ordinal() -> Self {
+        let mut locations = AttributeLocations::default();
+        for (i, v) in locations.locations.iter_mut().enumerate() {
+            *v = Some(i as u8);
+        }
+
+        locations
+    }
+
+    /// Binds `attribute` to `location`.
+    #[inline]
+    pub fn with(mut self, attribute: Attribute, location: u8) -> Self {
+        self.set(attribute, location);
+        self
+    }
+
+    /// Binds `attribute` to `location`.
+    #[inline]
+    pub fn set(&mut self, attribute: Attribute, location: u8) {
+        self.locations[attribute as usize] = Some(location);
+    }
+
+    /// Removes the explicit location of `attribute`.
+    #[inline]
+    pub fn remove(&mut self, attribute: Attribute) {
+        self.locations[attribute as usize] = None;
+    }
+
+    /// Gets the explicit location of `attribute`.
+    #[inline]
+    pub fn get(&self, attribute: Attribute) -> Option<u8> {
+        self.locations[attribute as usize]
+    }
+
+    /// Returns true if there is no explicit location.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.locations.iter().all(|v| v.is_none())
+    }
+
+    /// Gets the iterator over the attributes with explicit locations.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (Attribute, u8)> + 'a {
+        ATTRIBUTES
+            .iter()
+            .filter_map(move |&v| self.get(v).map(|location| (v, location)))
+    }
+}
+
+const ATTRIBUTES: [Attribute; MAX_VERTEX_ATTRIBUTES] = [
+    Attribute::Position,
+    Attribute::Normal,
+    Attribute::Tangent,
+    Attribute::Bitangent,
+    Attribute::Color0,
+    Attribute::Color1,
+    Attribute::Indices,
+    Attribute::Weight,
+    Attribute::Texcoord0,
+    Attribute::Texcoord1,
+    Attribute::Texcoord2,
+    Attribute::Texcoord3,
+];
+
 /// Specify whether front- or back-facing polygons can be culled.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum CullFace {
     Nothing,
     Front,
@@ -219,14 +315,14 @@ pub enum CullFace {
 }
 
 /// Define front- and back-facing polygons.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum FrontFaceOrder {
     Clockwise,
     CounterClockwise,
 }
 
 /// A pixel-wise comparison function.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Comparison {
     Never,
     Less,
@@ -240,7 +336,7 @@ pub enum Comparison {
 
 /// Specifies how incoming RGBA values (source) and the RGBA in framebuffer (destination)
 /// are combined.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Equation {
     /// Adds source and destination. Source and destination are multiplied
     /// by blending parameters before addition.
@@ -254,16 +350,20 @@ pub enum Equation {
 }
 
 /// Blend values.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BlendValue {
     SourceColor,
     SourceAlpha,
     DestinationColor,
     DestinationAlpha,
+    /// The constant color specified with `RenderState::blend_color`.
+    ConstantColor,
+    /// The alpha component of `RenderState::blend_color`.
+    ConstantAlpha,
 }
 
 /// Blend factors.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BlendFactor {
     Zero,
     One,
@@ -271,8 +371,51 @@ pub enum BlendFactor {
     OneMinusValue(BlendValue),
 }
 
+/// Logical pixel operations applied between the incoming RGBA color (source) and the
+/// RGBA color in framebuffer (destination).
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LogicOp {
+    Clear,
+    Set,
+    Copy,
+    CopyInverted,
+    Noop,
+    Invert,
+    And,
+    Nand,
+    Or,
+    Nor,
+    Xor,
+    Equiv,
+    AndReverse,
+    AndInverted,
+    OrReverse,
+    OrInverted,
+}
+
+/// The vertex of primitive whose outputs are used for the `flat` qualified varyings.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ProvokingVertex {
+    /// The first vertex of primitive.
+    First,
+    /// The last vertex of primitive, which is the default convention of OpenGL.
+    Last,
+}
+
+/// The rasterization mode of polygons.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PolygonMode {
+    /// The interiors of polygons are filled.
+    Fill,
+    /// The edges of polygons are drawn as lines, which is useful to inspect the geometry
+    /// in wireframe.
+    Line,
+    /// The vertices of polygons are drawn as points.
+    Point,
+}
+
 /// A struct that encapsulate all the necessary render states.
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 pub struct RenderState {
     pub cull_face: CullFace,
     pub front_face_order: FrontFaceOrder,
@@ -280,7 +423,26 @@ pub struct RenderState {
     pub depth_write: bool,
     pub depth_write_offset: Option<(f32, f32)>,
     pub color_blend: Option<(Equation, BlendFactor, BlendFactor)>,
+    /// The constant color used by `BlendValue::ConstantColor` and `BlendValue::ConstantAlpha`.
+    pub blend_color: Color<f32>,
+    /// The logical operation applied to color instead of blending. It's mutually exclusive
+    /// with `color_blend`, and only supported on desktop OpenGL.
+    pub logic_op: Option<LogicOp>,
     pub color_write: (bool, bool, bool, bool),
+    /// The temporary coverage value `(value, invert)` which is ANDed with the fragment
+    /// coverage. It only takes effect when rendering into multisampled surfaces.
+    pub sample_coverage: Option<(f32, bool)>,
+    /// The mask of samples that could be updated by fragments, all the samples are enabled
+    /// by default. It only takes effect when rendering into multisampled surfaces, and
+    /// requires OpenGL 3.2 or OpenGL ES 3.1.
+    pub sample_mask: u32,
+    /// The vertex that provides the values of `flat` varyings, which matters for the flat
+    /// shading with per-triangle attributes. `ProvokingVertex::First` requires OpenGL 3.2,
+    /// the convention is always fixed to the last vertex in OpenGL ES and WebGL.
+    pub provoking_vertex: ProvokingVertex,
+    /// The rasterization mode of polygons, it's applied to both front and back faces.
+    /// `PolygonMode::Line` and `PolygonMode::Point` are only supported on desktop OpenGL.
+    pub polygon_mode: PolygonMode,
 }
 
 impl Default for RenderState {
@@ -292,13 +454,19 @@ impl Default for RenderState {
             depth_write: false,             // no depth write,
             depth_write_offset: None,
             color_blend: None,
+            blend_color: Color::transparent(),
+            logic_op: None,
             color_write: (true, true, true, true),
+            sample_coverage: None,
+            sample_mask: !0,
+            provoking_vertex: ProvokingVertex::Last,
+            polygon_mode: PolygonMode::Fill,
         }
     }
 }
 
 /// Uniform variable type.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
 pub enum UniformVariableType {
     Texture,
     RenderTexture,
@@ -310,14 +478,61 @@ pub enum UniformVariableType {
     Matrix2f,
     Matrix3f,
     Matrix4f,
+    /// Image that could be loaded from and stored into with `imageLoad`/`imageStore`.
+    Image,
+    /// Shader storage block, which is declared with its block name instead of the name of
+    /// instance.
+    StorageBuffer,
+    /// Uniform block, which is declared with its block name instead of the name of
+    /// instance.
+    UniformBuffer,
+}
+
+impl UniformVariableType {
+    /// Returns true if the variable of type `v` could be assigned to the uniform of this
+    /// type. The textures and render textures are interchangeable, since both of them are
+    /// sampled with samplers in shaders.
+    pub fn accepts(self, v: UniformVariableType) -> bool {
+        match (self, v) {
+            (UniformVariableType::Texture, UniformVariableType::RenderTexture)
+            | (UniformVariableType::RenderTexture, UniformVariableType::Texture) => true,
+            _ => self == v,
+        }
+    }
+}
+
+/// The kinds of accesses to images in shaders.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ImageAccess {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// The level of render texture binded as image, which could be accessed with
+/// `imageLoad`/`imageStore` in shaders.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ImageBinding {
+    pub texture: RenderTextureHandle,
+    pub access: ImageAccess,
+    /// The format that the texels are interpreted as in shader, it should have the same
+    /// size with the format of texture. Only `RGBA8`, `RGBA16F`, `R32UI` and `R32I` could
+    /// be used.
+    pub format: RenderTextureFormat,
+    /// The mipmap level to bind.
+    pub level: u8,
 }
 
 /// Uniform variable for video program object. Each matrix based `UniformVariable`
 /// is assumed to be supplied in row major order with a optional transpose.
-#[derive(Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub enum UniformVariable {
     Texture(TextureHandle),
     RenderTexture(RenderTextureHandle),
+    /// Texture that binds to a explicit texture unit.
+    TextureUnit(u8, TextureHandle),
+    /// Render texture that binds to a explicit texture unit.
+    RenderTextureUnit(u8, RenderTextureHandle),
     I32(i32),
     F32(f32),
     Vector2f([f32; 2]),
@@ -326,6 +541,12 @@ pub enum UniformVariable {
     Matrix2f([[f32; 2]; 2], bool),
     Matrix3f([[f32; 3]; 3], bool),
     Matrix4f([[f32; 4]; 4], bool),
+    /// Render texture that binds to a image unit for load/store.
+    Image(u8, ImageBinding),
+    /// Shader storage buffer that binds to a indexed binding point.
+    StorageBuffer(u8, ShaderStorageBufferHandle),
+    /// Uniform buffer that binds to a indexed binding point.
+    UniformBuffer(u8, UniformBufferHandle),
 }
 
 impl UniformVariable {
@@ -333,6 +554,11 @@ impl UniformVariable {
         match *self {
             UniformVariable::RenderTexture(_) => UniformVariableType::RenderTexture,
             UniformVariable::Texture(_) => UniformVariableType::Texture,
+            UniformVariable::RenderTextureUnit(_, _) => UniformVariableType::RenderTexture,
+            UniformVariable::TextureUnit(_, _) => UniformVariableType::Texture,
+            UniformVariable::Image(_, _) => UniformVariableType::Image,
+            UniformVariable::StorageBuffer(_, _) => UniformVariableType::StorageBuffer,
+            UniformVariable::UniformBuffer(_, _) => UniformVariableType::UniformBuffer,
             UniformVariable::I32(_) => UniformVariableType::I32,
             UniformVariable::F32(_) => UniformVariableType::F32,
             UniformVariable::Vector2f(_) => UniformVariableType::Vector2f,
@@ -442,7 +668,7 @@ impl Into<UniformVariable> for [f32; 4] {
 }
 
 // UniformVariableLayout defines an layout of uniforms in program.
-#[derive(Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct UniformVariableLayout {
     variables: FastHashMap<HashValue<str>, (String, UniformVariableType)>,
 }
@@ -503,3 +729,22 @@ impl UniformVariableLayoutBuilder {
         self.0
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn logic_op() {
+        let mut params = ShaderParams::default();
+        params.state.logic_op = Some(LogicOp::Xor);
+        assert!(params.validate("vs", "fs").is_ok());
+
+        let blend = (Equation::Add, BlendFactor::One, BlendFactor::Zero);
+        params.state.color_blend = Some(blend);
+        assert!(params.validate("vs", "fs").is_err());
+
+        params.state.logic_op = None;
+        assert!(params.validate("vs", "fs").is_ok());
+    }
+}
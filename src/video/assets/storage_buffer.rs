@@ -0,0 +1,45 @@
+//! Shader storage buffer objects, which could be read and written by shaders with
+//! variable-size structured data.
+
+use crate::video::assets::mesh::MeshHint;
+use crate::video::errors::{Error, Result};
+
+impl_handle!(ShaderStorageBufferHandle);
+
+/// The setup parameters of shader storage buffer object.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ShaderStorageBufferParams {
+    /// Usage hints.
+    pub hint: MeshHint,
+    /// The size in bytes of this buffer.
+    pub size: usize,
+}
+
+impl Default for ShaderStorageBufferParams {
+    fn default() -> Self {
+        ShaderStorageBufferParams {
+            hint: MeshHint::Dynamic,
+            size: 0,
+        }
+    }
+}
+
+impl ShaderStorageBufferParams {
+    pub fn validate(&self, data: Option<&[u8]>) -> Result<()> {
+        if self.size == 0 {
+            return Err(Error::BufferInvalid("buffer size is zero.".into()));
+        }
+
+        if let Some(v) = data {
+            if v.len() > self.size {
+                return Err(Error::BufferInvalid(format!(
+                    "data length {} exceeds buffer size {}.",
+                    v.len(),
+                    self.size
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -3,21 +3,28 @@
 
 use crate::math::prelude::{Color, Vector2};
 
-use crate::video::assets::texture::RenderTextureHandle;
+use crate::video::assets::shader::CullFace;
+use crate::video::assets::texture::{RenderTextureHandle, TextureFilter, TextureWrap};
 use crate::video::errors::{Error, Result};
-use crate::video::MAX_FRAMEBUFFER_ATTACHMENTS;
+use crate::video::{MAX_FRAMEBUFFER_ATTACHMENTS, MAX_VIEWPORTS};
 
 /// The setup data of `Surface` which wraps common rendering operations to a render-target.
 /// Likes clearing, MSAA resolves, etc.. The `RenderTarget` is the window framebuffer as
 /// default, but you can specify `RenderTarget` with `SurfaceParams::set_attachments`
 /// manually also.
-#[derive(Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct SurfaceParams {
     pub(crate) colors: [Option<RenderTextureHandle>; MAX_FRAMEBUFFER_ATTACHMENTS],
     pub(crate) depth_stencil: Option<RenderTextureHandle>,
     pub(crate) clear_color: Option<Color<f32>>,
     pub(crate) clear_depth: Option<f32>,
     pub(crate) clear_stencil: Option<i32>,
+    pub(crate) clear_integer: Option<SurfaceIntegerClear>,
+    pub(crate) viewports: [Option<SurfaceViewport>; MAX_VIEWPORTS],
+    pub(crate) layered: bool,
+    pub(crate) srgb_write: bool,
+    pub(crate) cull_override: Option<CullFace>,
+    pub(crate) sampler_override: Option<SurfaceSampler>,
 }
 
 impl Default for SurfaceParams {
@@ -28,6 +35,12 @@ impl Default for SurfaceParams {
             clear_color: Some(Color::black()),
             clear_depth: Some(1.0),
             clear_stencil: None,
+            clear_integer: None,
+            viewports: [None; MAX_VIEWPORTS],
+            layered: false,
+            srgb_write: false,
+            cull_override: None,
+            sampler_override: None,
         }
     }
 }
@@ -35,11 +48,20 @@ impl Default for SurfaceParams {
 impl_handle!(SurfaceHandle);
 
 impl SurfaceParams {
+    /// Returns true if `handle` is one of the attachments.
+    pub(crate) fn is_attached(&self, handle: RenderTextureHandle) -> bool {
+        self.colors
+            .iter()
+            .chain(Some(&self.depth_stencil))
+            .any(|v| *v == Some(handle))
+    }
+
     /// Sets the attachments of internal frame-buffer. It consists of multiple color attachments
     /// and a optional `Depth/DepthStencil` buffer attachment.
     ///
     /// If none attachment is assigned, the default framebuffer generated by the system will be
-    /// used.
+    /// used. The dimensions of attachments are checked against each other when creating the
+    /// surface.
     pub fn set_attachments<T1>(
         &mut self,
         colors: &[RenderTextureHandle],
@@ -64,7 +86,83 @@ impl SurfaceParams {
         Ok(())
     }
 
-    /// Sets the clear flags for this surface.A
+    /// Sets the viewports for layered rendering, the geometry shader could route primitives
+    /// to a specified viewport with `gl_ViewportIndex`. The first viewport will be used as
+    /// default one.
+    ///
+    /// Notes that this requires `GL_ARB_viewport_array`, the surface creation fails if its
+    /// not supported.
+    pub fn set_viewports(&mut self, viewports: &[SurfaceViewport]) -> Result<()> {
+        if viewports.len() > MAX_VIEWPORTS {
+            return Err(Error::SurfaceInvalid("Too many viewports.".into()));
+        }
+
+        for (i, v) in self.viewports.iter_mut().enumerate() {
+            *v = viewports.get(i).cloned();
+        }
+
+        Ok(())
+    }
+
+    /// Attaches the whole render textures instead of a single layer, so the geometry shader
+    /// could route primitives to layers with `gl_Layer`. This makes it possible to render
+    /// all the faces of cubemap in one pass.
+    ///
+    /// Notes that render buffers could not be attached as layered attachments.
+    #[inline]
+    pub fn set_layered(&mut self, layered: bool) {
+        self.layered = layered;
+    }
+
+    /// Sets whether the linear colors written by fragments should be encoded into sRGB,
+    /// when rendering into attachments with sRGB encoding. This is toggled around the draws
+    /// of this surface only, so the gamma could be controlled at the boundaries of passes
+    /// explicitly. It's disabled by default.
+    ///
+    /// Notes that this requires OpenGL 3.0 or `GL_EXT_sRGB_write_control`, the surface
+    /// creation fails if its not supported.
+    #[inline]
+    pub fn set_srgb_write(&mut self, enable: bool) {
+        self.srgb_write = enable;
+    }
+
+    /// Overrides the cull face of render states for all the draws of this surface, e.g.
+    /// culling the front faces in depth-only passes of shadow maps, which reduces the
+    /// shadow acne without editing every material.
+    ///
+    /// It also configures the picking passes that render object ids into integer render
+    /// textures: leaves it `None` to pick with the same culling as the visible pass, so the
+    /// faces that can't be seen are never picked, or overrides with `CullFace::Nothing` to
+    /// pick through thin double-sided geometry.
+    #[inline]
+    pub fn set_cull_override<T>(&mut self, face: T)
+    where
+        T: Into<Option<CullFace>>,
+    {
+        self.cull_override = face.into();
+    }
+
+    /// Overrides the sampling parameters of all the textures sampled by the draws of this
+    /// surface with a sampler object, e.g. sampling the nearest texels in the UI or pixel-art
+    /// passes, while the same textures are still filtered in other passes. The textures
+    /// themselves are left untouched.
+    ///
+    /// Notes that the overridden textures are sampled at their base levels without depth
+    /// comparison and anisotropy. This requires OpenGL 3.3, OpenGL ES 3.0 or
+    /// `GL_ARB_sampler_objects`, the surface creation fails if its not supported.
+    #[inline]
+    pub fn set_sampler_override<T>(&mut self, sampler: T)
+    where
+        T: Into<Option<SurfaceSampler>>,
+    {
+        self.sampler_override = sampler.into();
+    }
+
+    /// Sets the clear flags for this surface.
+    ///
+    /// The attachments are cleared entirely when the surface is binded the first time in
+    /// a frame. The viewports, scissor box and color/depth write masks of render states
+    /// have no effects on this clear operation.
     #[inline]
     pub fn set_clear<C, D, S>(&mut self, color: C, depth: D, stentil: S)
     where
@@ -76,12 +174,76 @@ impl SurfaceParams {
         self.clear_depth = depth.into();
         self.clear_stencil = stentil.into();
     }
+
+    /// Sets the clear value of integer color attachment, e.g. the "no object" sentinel of
+    /// picking buffers. It's used instead of the clear color when the first color attachment
+    /// has an integer format (checkout `RenderTextureFormat::is_integer`).
+    #[inline]
+    pub fn set_clear_integer<T>(&mut self, value: T)
+    where
+        T: Into<Option<SurfaceIntegerClear>>,
+    {
+        self.clear_integer = value.into();
+    }
+}
+
+/// The sampling parameters that override the ones of textures in a surface.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SurfaceSampler {
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+}
+
+impl SurfaceSampler {
+    /// Samples the nearest texels, which keeps the UI and pixel-art crisp.
+    pub fn nearest() -> Self {
+        SurfaceSampler {
+            filter: TextureFilter::Nearest,
+            wrap: TextureWrap::Clamp,
+        }
+    }
+}
+
+/// The non-normalized clear value of integer color attachments. The value will be converted
+/// if its signedness mismatches the format of attachment.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceIntegerClear {
+    U32([u32; 4]),
+    I32([i32; 4]),
+}
+
+impl SurfaceIntegerClear {
+    pub(crate) fn to_u32(self) -> [u32; 4] {
+        match self {
+            SurfaceIntegerClear::U32(v) => v,
+            SurfaceIntegerClear::I32(v) => [v[0] as u32, v[1] as u32, v[2] as u32, v[3] as u32],
+        }
+    }
+
+    pub(crate) fn to_i32(self) -> [i32; 4] {
+        match self {
+            SurfaceIntegerClear::U32(v) => [v[0] as i32, v[1] as i32, v[2] as i32, v[3] as i32],
+            SurfaceIntegerClear::I32(v) => v,
+        }
+    }
+}
+
+impl From<[u32; 4]> for SurfaceIntegerClear {
+    fn from(v: [u32; 4]) -> Self {
+        SurfaceIntegerClear::U32(v)
+    }
+}
+
+impl From<[i32; 4]> for SurfaceIntegerClear {
+    fn from(v: [i32; 4]) -> Self {
+        SurfaceIntegerClear::I32(v)
+    }
 }
 
 /// Defines a rectangle, called the scissor box, in window coordinates. The test is
 /// initially disabled. While the test is enabled, only pixels that lie within the
 /// scissor box can be modified by drawing commands.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SurfaceScissor {
     Enable {
         /// Specify the lower left corner of the scissor box, in pixels. Initially (0, 0).
@@ -93,11 +255,46 @@ pub enum SurfaceScissor {
     Disable,
 }
 
+impl SurfaceScissor {
+    /// Returns the scissor box that only contains the pixels inside both `self` and `rhs`,
+    /// the disabled test is treated as an unbounded box. The box is empty if they do not
+    /// overlap at all, which discards every pixel.
+    pub fn intersect(self, rhs: SurfaceScissor) -> SurfaceScissor {
+        match (self, rhs) {
+            (SurfaceScissor::Disable, v) | (v, SurfaceScissor::Disable) => v,
+            (
+                SurfaceScissor::Enable {
+                    position: p0,
+                    size: s0,
+                },
+                SurfaceScissor::Enable {
+                    position: p1,
+                    size: s1,
+                },
+            ) => {
+                let min = Vector2::new(p0.x.max(p1.x), p0.y.max(p1.y));
+                let max = Vector2::new(
+                    (p0.x + s0.x as i32).min(p1.x + s1.x as i32),
+                    (p0.y + s0.y as i32).min(p1.y + s1.y as i32),
+                );
+
+                SurfaceScissor::Enable {
+                    position: min,
+                    size: Vector2::new(
+                        (max.x - min.x).max(0) as u32,
+                        (max.y - min.y).max(0) as u32,
+                    ),
+                }
+            }
+        }
+    }
+}
+
 /// Sets the viewport of surface. This specifies the affine transformation of (x, y),
 /// in window coordinates to normalized window coordinates.
 ///
 /// NDC(normalized device coordinates) to normalized window coordinates.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SurfaceViewport {
     /// Specify the lower left corner of the viewport rectangle, in pixels. Initially (0, 0).
     pub position: Vector2<i32>,
@@ -105,3 +302,83 @@ pub struct SurfaceViewport {
     /// a window, width and height are set to the dimensions of that window.
     pub size: Vector2<u32>,
 }
+
+/// The antialiasing mode of surfaces that render into the window framebuffer.
+///
+/// When it is not `None`, those surfaces are redirected into an internal offscreen
+/// framebuffer, which will be resolved into the window framebuffer at the end of
+/// every frame.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AaMode {
+    /// Renders into the window framebuffer directly.
+    None,
+    /// Renders into multisampled attachments with specified samples, and resolves them
+    /// with `glBlitFramebuffer`.
+    Msaa(u8),
+    /// Renders into single-sampled attachments, and resolves them with a fast approximate
+    /// antialiasing post pass.
+    Fxaa,
+}
+
+impl Default for AaMode {
+    fn default() -> Self {
+        AaMode::None
+    }
+}
+
+/// The adjustment of colors that presented to the window, which is usually exposed in the
+/// display settings for calibration and accessibility.
+///
+/// It's applied by a full-screen pass at the end of frame, so the surfaces that render into
+/// the window framebuffer are redirected into an offscreen framebuffer like antialiasing if
+/// it's not the identity.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct OutputAdjustment {
+    /// The colors are raised to the power of `1 / gamma`, values larger than 1.0 brighten
+    /// the dark tones.
+    pub gamma: f32,
+    /// The offset that added to the colors after gamma correction.
+    pub brightness: f32,
+    /// The scale of the colors around the middle gray after gamma correction.
+    pub contrast: f32,
+}
+
+impl Default for OutputAdjustment {
+    fn default() -> Self {
+        OutputAdjustment {
+            gamma: 1.0,
+            brightness: 0.0,
+            contrast: 1.0,
+        }
+    }
+}
+
+impl OutputAdjustment {
+    /// Returns true if it leaves the colors untouched.
+    #[inline]
+    pub fn is_identity(&self) -> bool {
+        *self == OutputAdjustment::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intersect_scissors() {
+        let scissor = |x, y, w, h| SurfaceScissor::Enable {
+            position: Vector2::new(x, y),
+            size: Vector2::new(w, h),
+        };
+
+        let a = scissor(0, 0, 100, 100);
+        assert_eq!(a.intersect(SurfaceScissor::Disable), a);
+        assert_eq!(SurfaceScissor::Disable.intersect(a), a);
+        assert_eq!(
+            a.intersect(scissor(50, -10, 100, 40)),
+            scissor(50, 0, 50, 30)
+        );
+        assert_eq!(a.intersect(scissor(200, 0, 10, 10)), scissor(200, 0, 0, 10));
+    }
+}
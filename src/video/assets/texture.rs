@@ -1,6 +1,7 @@
-//! Immutable or dynamic 2D texture. A texture is a container of one or more images. It
-//! can be the source of a texture access from a Shader.
+//! Immutable or dynamic 2D texture and cube map. A texture is a container of one or more
+//! images. It can be the source of a texture access from a Shader.
 use crate::math::prelude::Vector2;
+use crate::video::assets::shader::Comparison;
 use crate::video::errors::{Error, Result};
 
 impl_handle!(TextureHandle);
@@ -10,30 +11,113 @@ impl_handle!(TextureHandle);
 pub struct TextureParams {
     /// Hint abouts the intended update strategy of the data.
     pub hint: TextureHint,
-    /// Sets the wrap parameter for texture.
-    pub wrap: TextureWrap,
-    /// Specify how the texture is used whenever the pixel being sampled.
-    pub filter: TextureFilter,
+    /// Sets the wrap parameter for the horizontal texture coordinate.
+    pub wrap_s: TextureWrap,
+    /// Sets the wrap parameter for the vertical texture coordinate.
+    pub wrap_t: TextureWrap,
+    /// Specify how the texture is sampled when it's minified.
+    pub min_filter: TextureFilter,
+    /// Specify how the texture is sampled when it's magnified.
+    pub mag_filter: TextureFilter,
+    /// Specify how the mipmap levels are selected and blended when the texture is minified.
+    /// Only the base level is sampled if it's `None`, and it's ignored if the texture has
+    /// only one level.
+    pub mip_filter: Option<TextureFilter>,
+    /// Generates the full chain of mipmap levels from the base level after uploading, and
+    /// regenerates them whenever the texture is updated. It's ignored with compressed
+    /// formats, and on devices that could not generate mipmaps of this texture (e.g. the
+    /// non-power-of-two ones in OpenGL ES 2.0).
+    pub mipmap: bool,
     /// Sets the format of data.
     pub format: TextureFormat,
-    /// Sets the dimensions of texture.
+    /// Sets the dimensions of texture. It's the dimensions of every face for cube maps.
     pub dimensions: Vector2<u32>,
+    /// The kind of texture, which is set by `create_cube_texture` for cube maps.
+    ///
+    /// Notes that this is not serialized with the texture assets.
+    #[serde(skip)]
+    pub kind: TextureKind,
+    /// Restricts the sampling to the inclusive range of mipmap levels. All the levels will be
+    /// sampled if it's `None`.
+    ///
+    /// Notes that this is not serialized with the texture assets.
+    #[serde(skip)]
+    pub mip_range: Option<(u32, u32)>,
 }
 
 impl Default for TextureParams {
     fn default() -> Self {
         TextureParams {
             format: TextureFormat::RGBA8,
-            wrap: TextureWrap::Clamp,
-            filter: TextureFilter::Linear,
+            wrap_s: TextureWrap::Clamp,
+            wrap_t: TextureWrap::Clamp,
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            mip_filter: Some(TextureFilter::Linear),
             hint: TextureHint::Immutable,
+            mipmap: false,
             dimensions: Vector2::new(0, 0),
+            kind: TextureKind::Texture2D,
+            mip_range: None,
         }
     }
 }
 
 impl TextureParams {
+    /// Sets the wrap parameter for both texture coordinates.
+    #[inline]
+    pub fn set_wrap(&mut self, wrap: TextureWrap) {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+    }
+
+    /// Samples the texture with `filter` whenever it's minified or magnified, and blends
+    /// the mipmap levels with `filter` too.
+    #[inline]
+    pub fn set_filter(&mut self, filter: TextureFilter) {
+        self.min_filter = filter;
+        self.mag_filter = filter;
+        self.mip_filter = Some(filter);
+    }
+
+    /// Restricts the sampling to mipmap levels from `base` to `max` (inclusive), which maps
+    /// to `GL_TEXTURE_BASE_LEVEL` and `GL_TEXTURE_MAX_LEVEL`.
+    #[inline]
+    pub fn set_mip_range(&mut self, base: u32, max: u32) {
+        self.mip_range = Some((base, max));
+    }
+
+    /// Returns the inclusive range of mipmap levels that should be sampled, with `levels`
+    /// levels of data uploaded. The full chain is sampled if the mipmaps are generated.
+    pub fn mip_levels(&self, levels: u32) -> (u32, u32) {
+        let levels = if self.mipmap {
+            self.full_mip_levels()
+        } else {
+            levels
+        };
+
+        let last = levels.max(1) - 1;
+        match self.mip_range {
+            Some((base, max)) => (base.min(last), max.min(last)),
+            None => (0, last),
+        }
+    }
+
+    /// Returns the number of levels in the full chain of mipmaps down to 1x1.
+    #[inline]
+    pub fn full_mip_levels(&self) -> u32 {
+        let size = self.dimensions.x.max(self.dimensions.y).max(1);
+        32 - size.leading_zeros()
+    }
+
     pub fn validate(&self, data: Option<&TextureData>) -> Result<()> {
+        if self.mipmap && self.format.compressed() {
+            return Err(Error::TextureInvalid(format!(
+                "the mipmaps of compressed format {:?} could not be generated.",
+                self.format
+            )));
+        }
+
         if let Some(buf) = data {
             let len = self.format.size(self.dimensions);
             if !buf.bytes.is_empty() && buf.bytes[0].len() > len as usize {
@@ -41,10 +125,75 @@ impl TextureParams {
             }
         }
 
+        if let Some((base, max)) = self.mip_range {
+            let levels = match data {
+                _ if self.mipmap => self.full_mip_levels() as usize,
+                Some(v) => v.bytes.len().max(1),
+                None => 1,
+            };
+
+            if base > max || max as usize >= levels {
+                return Err(Error::OutOfBounds);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates the parameters and data of cube map. The faces should be squares, and all
+    /// of them should have the same dimensions and number of mipmap levels.
+    pub fn validate_cube(&self, data: Option<&CubeTextureData>) -> Result<()> {
+        if self.dimensions.x != self.dimensions.y {
+            return Err(Error::TextureInvalid(format!(
+                "the faces of cube map should be squares instead of {:?}.",
+                self.dimensions
+            )));
+        }
+
+        if let Some(data) = data {
+            let first = &data.faces[0].bytes;
+            for (i, face) in data.faces.iter().enumerate() {
+                let same = face.bytes.len() == first.len()
+                    && face
+                        .bytes
+                        .iter()
+                        .zip(first.iter())
+                        .all(|(lhs, rhs)| lhs.len() == rhs.len());
+
+                if !same {
+                    return Err(Error::TextureInvalid(format!(
+                        "the face {} of cube map has different dimensions or format.",
+                        i
+                    )));
+                }
+
+                self.validate(Some(face))?;
+            }
+        } else {
+            self.validate(None)?;
+        }
+
         Ok(())
     }
 }
 
+/// The kinds of texture.
+#[repr(u8)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TextureKind {
+    /// The two-dimensional texture, which is sampled with `sampler2D` in shaders.
+    Texture2D,
+    /// The cube map made of six square faces, which is sampled with `samplerCube` in
+    /// shaders.
+    Cube,
+}
+
+impl Default for TextureKind {
+    fn default() -> Self {
+        TextureKind::Texture2D
+    }
+}
+
 /// Continuous texture data of different mipmap levels.
 ///
 /// Notes that mipmaps are stored in order from largest size to smallest size
@@ -53,17 +202,64 @@ pub struct TextureData {
     pub bytes: Vec<Box<[u8]>>,
 }
 
+/// The data of six faces of cube map, which are stored in the order of `+X`, `-X`, `+Y`,
+/// `-Y`, `+Z` and `-Z`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CubeTextureData {
+    pub faces: [TextureData; 6],
+}
+
+/// The image which is read back from framebuffer. The pixels are stored as RGBA8 in
+/// rows from bottom to top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub dimensions: Vector2<u32>,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Returns true if both images have the same dimensions, and the difference of every
+    /// component is not greater than `tolerance`.
+    pub fn approx_eq(&self, rhs: &Image, tolerance: u8) -> bool {
+        if self.dimensions != rhs.dimensions || self.pixels.len() != rhs.pixels.len() {
+            return false;
+        }
+
+        self.pixels
+            .iter()
+            .zip(rhs.pixels.iter())
+            .all(|(&lhs, &rhs)| (i16::from(lhs) - i16::from(rhs)).abs() <= i16::from(tolerance))
+    }
+}
+
 /// A `RenderTexture` object is basicly texture object with special format. It can
 /// be used as a render target. If the `sampler` field is true, it can also be ther
 /// source of a texture access from a __shader__.
 ///
-#[derive(Debug, Copy, Clone)]
+/// The sampleable render textures could be used anywhere a `TextureHandle` is accepted
+/// for sampling, e.g. binding it to a uniform that declared as `UniformVariableType::Texture`
+/// in later passes.
+///
+/// Notes that reading a render texture while it's also an attachment of the current
+/// surface forms a feedback loop, the results are undefined in OpenGL and it's an error
+/// in WebGL. Renders into another render texture (e.g. ping-pong between two of them)
+/// if you need to read the contents that being rendered.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct RenderTextureParams {
     pub format: RenderTextureFormat,
     pub wrap: TextureWrap,
     pub filter: TextureFilter,
     pub dimensions: Vector2<u32>,
     pub sampler: bool,
+    /// Enables the depth comparison mode with specified function, which makes it possible
+    /// to sample a depth render texture with `sampler2DShadow` (hardware PCF). It only
+    /// takes effect with depth formats.
+    pub compare: Option<Comparison>,
+    /// Sizes the render texture relative to the window framebuffer in pixels, e.g. `Some(0.5)`
+    /// makes a half-resolution target. The `dimensions` is ignored, and the render texture is
+    /// reallocated with its contents discarded whenever the window resizes. All the
+    /// attachments of a surface should use the same scale to keep it complete.
+    pub screen_scale: Option<f32>,
 }
 
 impl Default for RenderTextureParams {
@@ -74,6 +270,22 @@ impl Default for RenderTextureParams {
             filter: TextureFilter::Linear,
             dimensions: Vector2::new(0, 0),
             sampler: true,
+            compare: None,
+            screen_scale: None,
+        }
+    }
+}
+
+impl RenderTextureParams {
+    /// Gets the dimensions of render texture when the window framebuffer is `screen` in
+    /// pixels.
+    pub fn dimensions_of(&self, screen: Vector2<u32>) -> Vector2<u32> {
+        match self.screen_scale {
+            Some(scale) => Vector2::new(
+                ((screen.x as f32 * scale).round() as u32).max(1),
+                ((screen.y as f32 * scale).round() as u32).max(1),
+            ),
+            None => self.dimensions,
         }
     }
 }
@@ -98,7 +310,7 @@ pub enum TextureHint {
 
 /// Specify how the texture is used whenever the pixel being sampled.
 #[repr(u8)]
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum TextureFilter {
     /// Returns the value of the texture element that is nearest (in Manhattan distance)
     /// to the center of the pixel being textured.
@@ -110,7 +322,7 @@ pub enum TextureFilter {
 
 /// Sets the wrap parameter for texture.
 #[repr(u8)]
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum TextureWrap {
     /// Samples at coord x + 1 map to coord x.
     Repeat,
@@ -120,6 +332,9 @@ pub enum TextureWrap {
     Clamp,
     /// Same as Mirror, but only for one repetition.
     MirrorClamp,
+    /// Samples outside of [0, 1] return the border color, which is transparent black. It
+    /// falls back to `Clamp` in WebGL.
+    Border,
 }
 
 /// List of all the possible formats of renderable texture which could be use as
@@ -129,11 +344,16 @@ pub enum TextureWrap {
 /// floating point, multiplies by the signed scale factor, adds the signed bias, and
 /// clamps to the range [0,1].
 #[repr(u8)]
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum RenderTextureFormat {
     RGB8,
     RGBA4,
     RGBA8,
+    RGBA16F,
+    /// A single unsigned integer channel, which is usually used as ID buffer.
+    R32UI,
+    /// A single signed integer channel.
+    R32I,
     Depth16,
     Depth24,
     Depth32,
@@ -145,6 +365,54 @@ impl RenderTextureFormat {
         self == RenderTextureFormat::RGB8
             || self == RenderTextureFormat::RGBA4
             || self == RenderTextureFormat::RGBA8
+            || self == RenderTextureFormat::RGBA16F
+            || self.is_integer()
+    }
+
+    /// Returns true if this is a non-normalized integer color format, which should be
+    /// cleared with integer values and sampled with integer samplers.
+    pub fn is_integer(self) -> bool {
+        self == RenderTextureFormat::R32UI || self == RenderTextureFormat::R32I
+    }
+
+    /// Checks if the render texture of this format could be blitted into the one of `dst`
+    /// with `filter`. The rules follow the `glBlitFramebuffer` of OpenGL ES 3.0:
+    ///
+    /// | Source                              | Destination                         | Filter  |
+    /// |-------------------------------------|-------------------------------------|---------|
+    /// | `RGB8`, `RGBA4`, `RGBA8`, `RGBA16F` | `RGB8`, `RGBA4`, `RGBA8`, `RGBA16F` | Any     |
+    /// | `R32UI`                             | `R32UI`                             | Nearest |
+    /// | `R32I`                              | `R32I`                              | Nearest |
+    /// | `Depth16`, `Depth24`, `Depth32`     | The same format                     | Nearest |
+    /// | `Depth24Stencil8`                   | `Depth24Stencil8`                   | Nearest |
+    ///
+    /// The normalized and floating-point color formats are converted into each other,
+    /// the channels missing in destination are dropped, and the ones missing in source are
+    /// filled with 0 (or 1 for alpha). Everything else is rejected.
+    pub fn validate_blit(self, dst: RenderTextureFormat, filter: TextureFilter) -> Result<()> {
+        let compatible = if self.is_integer() || dst.is_integer() {
+            self == dst
+        } else if self.is_color() {
+            dst.is_color()
+        } else {
+            self == dst
+        };
+
+        if !compatible {
+            return Err(Error::BlitInvalid(format!(
+                "{:?} could not be blitted into {:?}.",
+                self, dst
+            )));
+        }
+
+        if filter == TextureFilter::Linear && (self.is_integer() || !self.is_color()) {
+            return Err(Error::BlitInvalid(format!(
+                "{:?} could only be blitted with nearest filtering.",
+                self
+            )));
+        }
+
+        Ok(())
     }
 
     /// Returns the size in bytes of texture with `dimensions`.
@@ -154,12 +422,66 @@ impl RenderTextureFormat {
             RenderTextureFormat::RGBA4 | RenderTextureFormat::Depth16 => 2 * square,
             RenderTextureFormat::RGB8 | RenderTextureFormat::Depth24 => 3 * square,
             RenderTextureFormat::RGBA8
+            | RenderTextureFormat::R32UI
+            | RenderTextureFormat::R32I
             | RenderTextureFormat::Depth32
             | RenderTextureFormat::Depth24Stencil8 => 4 * square,
+            RenderTextureFormat::RGBA16F => 8 * square,
         }
     }
 }
 
+impl RenderTextureFormat {
+    /// Picks the best format for `usage` that could be rendered to on this device, the
+    /// formats that are always available are used as fallbacks.
+    pub fn best_for(usage: RenderTextureUsage) -> Self {
+        let candidates: &[RenderTextureFormat] = match usage {
+            RenderTextureUsage::Color => &[RenderTextureFormat::RGBA8],
+            RenderTextureUsage::Hdr => &[RenderTextureFormat::RGBA16F, RenderTextureFormat::RGBA8],
+            RenderTextureUsage::Depth => &[
+                RenderTextureFormat::Depth24,
+                RenderTextureFormat::Depth32,
+                RenderTextureFormat::Depth16,
+            ],
+            RenderTextureUsage::DepthStencil => &[RenderTextureFormat::Depth24Stencil8],
+        };
+
+        let (&fallback, candidates) = candidates.split_last().unwrap();
+        candidates
+            .iter()
+            .cloned()
+            .find(|&v| crate::video::is_format_supported(v, FormatUsage::Render))
+            .unwrap_or(fallback)
+    }
+}
+
+/// The intended usages of render textures, which is used to pick formats with
+/// `RenderTextureFormat::best_for`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RenderTextureUsage {
+    /// Color buffers with 8 bits per channel.
+    Color,
+    /// Color buffers of high dynamic range. It prefers floating-point formats if the
+    /// device could render into them, e.g. with `EXT_color_buffer_float`.
+    Hdr,
+    /// Depth buffers, it prefers the precision of 24 bits.
+    Depth,
+    /// Packed depth and stencil buffers.
+    DepthStencil,
+}
+
+/// The usages of `RenderTextureFormat` that could be queried with `video::is_format_supported`.
+#[repr(u8)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FormatUsage {
+    /// The format could be used to create textures.
+    Texture,
+    /// The format is renderable when attached to a surface.
+    Render,
+    /// Textures with this format could be sampled with linear filtering.
+    Filter,
+}
+
 /// List of all the possible formats of input data when uploading to texture.
 #[repr(u8)]
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
@@ -195,7 +517,50 @@ pub enum TextureFormat {
     S3tcDxt5RGBA8BPP,
 }
 
+/// The intended usages of textures, which is used to pick formats with
+/// `TextureFormat::best_for`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TextureUsage {
+    /// Opaque colors, e.g. albedo maps.
+    Color,
+    /// Colors with alpha channel.
+    ColorAlpha,
+    /// Colors of high dynamic range, e.g. environment maps.
+    Hdr,
+}
+
 impl TextureFormat {
+    /// Picks the best format for `usage` that is supported on this device. The compressed
+    /// formats are preferred since they save memory and bandwidth, and the uncompressed
+    /// ones are used as fallbacks.
+    ///
+    /// This helps to choose which variant of assets should be loaded, since the data must
+    /// be encoded in the picked format already.
+    pub fn best_for(usage: TextureUsage) -> Self {
+        let candidates: &[TextureFormat] = match usage {
+            TextureUsage::Color => &[
+                TextureFormat::S3tcDxt1RGB4BPP,
+                TextureFormat::Etc2RGB4BPP,
+                TextureFormat::PvrtcRGB4BPP,
+                TextureFormat::RGB8,
+            ],
+            TextureUsage::ColorAlpha => &[
+                TextureFormat::S3tcDxt5RGBA8BPP,
+                TextureFormat::Etc2RGBA8BPP,
+                TextureFormat::PvrtcRGBA4BPP,
+                TextureFormat::RGBA8,
+            ],
+            TextureUsage::Hdr => &[TextureFormat::RGBA16F, TextureFormat::RGBA8],
+        };
+
+        let (&fallback, candidates) = candidates.split_last().unwrap();
+        candidates
+            .iter()
+            .cloned()
+            .find(|&v| crate::video::is_texture_format_supported(v))
+            .unwrap_or(fallback)
+    }
+
     /// Returns the number of components of this client format.
     pub fn components(self) -> u8 {
         match self {
@@ -249,6 +614,17 @@ impl TextureFormat {
         }
     }
 
+    /// Returns the largest alignment (1, 2, 4 or 8) that rows of tightly packed pixels with
+    /// `width` satisfy, which could be used as `UNPACK_ALIGNMENT` when uploading.
+    pub fn unpack_alignment(self, width: u32) -> u32 {
+        let row = self.size(Vector2::new(width, 1));
+        [8, 4, 2]
+            .iter()
+            .cloned()
+            .find(|&v| row % v == 0)
+            .unwrap_or(1)
+    }
+
     pub fn compressed(self) -> bool {
         match self {
             TextureFormat::Etc2RGB4BPP
@@ -263,3 +639,91 @@ impl TextureFormat {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn blit() {
+        let nearest = TextureFilter::Nearest;
+        let linear = TextureFilter::Linear;
+
+        let rgba8 = RenderTextureFormat::RGBA8;
+        assert!(rgba8
+            .validate_blit(RenderTextureFormat::RGBA16F, linear)
+            .is_ok());
+        assert!(rgba8
+            .validate_blit(RenderTextureFormat::R32UI, nearest)
+            .is_err());
+        assert!(rgba8
+            .validate_blit(RenderTextureFormat::Depth24, nearest)
+            .is_err());
+
+        let r32ui = RenderTextureFormat::R32UI;
+        assert!(r32ui.validate_blit(r32ui, nearest).is_ok());
+        assert!(r32ui.validate_blit(r32ui, linear).is_err());
+        assert!(r32ui
+            .validate_blit(RenderTextureFormat::R32I, nearest)
+            .is_err());
+
+        let depth = RenderTextureFormat::Depth24;
+        assert!(depth.validate_blit(depth, nearest).is_ok());
+        assert!(depth.validate_blit(depth, linear).is_err());
+        assert!(depth
+            .validate_blit(RenderTextureFormat::Depth16, nearest)
+            .is_err());
+    }
+
+    #[test]
+    fn cube() {
+        let mut params = TextureParams::default();
+        params.dimensions = Vector2::new(2, 2);
+
+        let face = |len| TextureData {
+            bytes: vec![vec![0; len].into_boxed_slice()],
+        };
+
+        let mut data = CubeTextureData {
+            faces: [face(16), face(16), face(16), face(16), face(16), face(16)],
+        };
+
+        assert!(params.validate_cube(Some(&data)).is_ok());
+        assert!(params.validate_cube(None).is_ok());
+
+        data.faces[3] = face(4);
+        assert!(params.validate_cube(Some(&data)).is_err());
+
+        params.dimensions = Vector2::new(2, 1);
+        assert!(params.validate_cube(None).is_err());
+    }
+
+    #[test]
+    fn mipmap() {
+        let mut params = TextureParams::default();
+        params.dimensions = Vector2::new(256, 100);
+        assert_eq!(params.mip_levels(1), (0, 0));
+
+        params.mipmap = true;
+        assert_eq!(params.full_mip_levels(), 9);
+        assert_eq!(params.mip_levels(1), (0, 8));
+
+        params.set_mip_range(2, 8);
+        assert!(params.validate(None).is_ok());
+
+        params.format = TextureFormat::Etc2RGB4BPP;
+        assert!(params.validate(None).is_err());
+    }
+
+    #[test]
+    fn screen_scale() {
+        let mut params = RenderTextureParams::default();
+        params.dimensions = Vector2::new(64, 64);
+        let screen = Vector2::new(801, 600);
+        assert_eq!(params.dimensions_of(screen), Vector2::new(64, 64));
+
+        params.screen_scale = Some(0.5);
+        assert_eq!(params.dimensions_of(screen), Vector2::new(401, 300));
+        assert_eq!(params.dimensions_of(Vector2::new(0, 0)), Vector2::new(1, 1));
+    }
+}
@@ -3,56 +3,203 @@ use std::io::Cursor;
 use std::sync::Arc;
 
 use crate::errors::*;
+use crate::math::prelude::Vector2;
 use crate::res::utils::prelude::ResourceLoader;
-use crate::utils::double_buf::DoubleBuf;
+use crate::utils::triple_buf::TripleBuf;
 
 use super::super::backends::frame::{Command, Frame};
 use super::texture::*;
 
-pub const MAGIC: [u8; 8] = [b'V', b'T', b'E', b'X', b' ', 0, 0, 1];
+pub const MAGIC: [u8; 8] = [b'V', b'T', b'E', b'X', b' ', 0, 0, 2];
 
-#[derive(Clone)]
-pub struct TextureLoader {
-    frames: Arc<DoubleBuf<Frame>>,
+/// The container formats of encoded images that could be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// The texture resources compiled by crayon-cli.
+    Crayon,
+    /// The uncompressed 24- or 32-bit Windows bitmaps, which are decoded into `RGBA8`.
+    Bmp,
 }
 
-impl TextureLoader {
-    pub(crate) fn new(frames: Arc<DoubleBuf<Frame>>) -> Self {
-        TextureLoader { frames }
+impl ImageFormat {
+    /// Decodes the texture from `bytes`.
+    pub fn decode(self, bytes: &[u8]) -> Result<(TextureParams, TextureData)> {
+        match self {
+            ImageFormat::Crayon => {
+                if bytes.len() < 8 || bytes[0..8] != MAGIC[..] {
+                    bail!("[TextureLoader] MAGIC number not match.");
+                }
+
+                let mut file = Cursor::new(&bytes[8..]);
+                let params: TextureParams = bincode::deserialize_from(&mut file)?;
+                let data = bincode::deserialize_from(&mut file)?;
+                Ok((params, data))
+            }
+            ImageFormat::Bmp => decode_bmp(bytes),
+        }
     }
 }
 
-impl ResourceLoader for TextureLoader {
-    type Handle = TextureHandle;
-    type Intermediate = (TextureParams, Option<TextureData>);
-    type Resource = TextureParams;
+/// Decodes the bitmap into rows from bottom to top, which is the order of OpenGL textures.
+fn decode_bmp(bytes: &[u8]) -> Result<(TextureParams, TextureData)> {
+    let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+    let u32_at =
+        |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
 
-    fn load(&self, handle: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate> {
-        if bytes[0..8] != MAGIC[..] {
-            bail!("[TextureLoader] MAGIC number not match.");
+    if bytes.len() < 54 || &bytes[0..2] != b"BM" {
+        bail!("[TextureLoader] BMP signature not match.");
+    }
+
+    let offset = u32_at(10) as usize;
+    let width = u32_at(18) as i32;
+    let height = u32_at(22) as i32;
+    let bpp = u16_at(28);
+    let compression = u32_at(30);
+
+    // The BI_BITFIELDS bitmaps written by most tools have the same layout as BI_RGB ones.
+    if (bpp != 24 && bpp != 32) || (compression != 0 && compression != 3) {
+        bail!(
+            "[TextureLoader] BMP with {} bits per pixel and compression {} is not supported.",
+            bpp,
+            compression
+        );
+    }
+
+    if width <= 0 || height == 0 {
+        bail!(
+            "[TextureLoader] invalid BMP dimensions {}x{}.",
+            width,
+            height
+        );
+    }
+
+    let (w, h) = (width as usize, height.abs() as usize);
+    let channels = bpp as usize / 8;
+    let stride = (w * channels + 3) & !3;
+    if bytes.len() < offset + stride * h {
+        bail!("[TextureLoader] BMP pixels are truncated.");
+    }
+
+    let mut pixels = Vec::with_capacity(w * h * 4);
+    for y in 0..h {
+        // The rows are stored from top to bottom if the height is negative.
+        let row = if height > 0 { y } else { h - 1 - y };
+        let row = &bytes[offset + row * stride..offset + row * stride + w * channels];
+
+        for v in row.chunks(channels) {
+            let a = if channels == 4 { v[3] } else { 255 };
+            pixels.extend_from_slice(&[v[2], v[1], v[0], a]);
         }
+    }
+
+    let mut params = TextureParams::default();
+    params.dimensions = Vector2::new(w as u32, h as u32);
+
+    let data = TextureData {
+        bytes: vec![pixels.into_boxed_slice()],
+    };
 
-        let mut file = Cursor::new(&bytes[8..]);
-        let params: TextureParams = bincode::deserialize_from(&mut file)?;
-        let data = bincode::deserialize_from(&mut file)?;
+    Ok((params, data))
+}
+
+/// The source of texture, which is either the initial data or a native texture object
+/// created externally.
+#[derive(Debug, Clone)]
+pub enum TextureSource {
+    Data(Option<TextureData>),
+    Cube(Option<CubeTextureData>),
+    Native(u32),
+}
+
+#[derive(Clone)]
+pub struct TextureLoader {
+    frames: Arc<TripleBuf<Frame>>,
+}
+
+impl TextureLoader {
+    pub(crate) fn new(frames: Arc<TripleBuf<Frame>>) -> Self {
+        TextureLoader { frames }
+    }
+
+    pub(crate) fn decode(
+        &self,
+        handle: TextureHandle,
+        bytes: &[u8],
+        format: ImageFormat,
+    ) -> Result<(TextureParams, TextureSource)> {
+        let (params, data) = format.decode(bytes)?;
 
         info!(
             "[TextureLoader] load {:?} ({}x{} - {:?}).",
             handle, params.dimensions.x, params.dimensions.y, params.format
         );
 
-        Ok((params, Some(data)))
+        Ok((params, TextureSource::Data(Some(data))))
+    }
+}
+
+impl ResourceLoader for TextureLoader {
+    type Handle = TextureHandle;
+    type Intermediate = (TextureParams, TextureSource);
+    type Resource = TextureParams;
+
+    fn load(&self, handle: Self::Handle, bytes: &[u8]) -> Result<Self::Intermediate> {
+        self.decode(handle, bytes, ImageFormat::Crayon)
     }
 
     fn create(&self, handle: Self::Handle, item: Self::Intermediate) -> Result<Self::Resource> {
         info!("[TextureLoader] create {:?}.", handle);
 
-        item.0.validate(item.1.as_ref())?;
+        let (params, source) = item;
+        let cmd = match source {
+            TextureSource::Data(data) => {
+                params.validate(data.as_ref())?;
+                Command::CreateTexture(Box::new((handle, params, data)))
+            }
+            TextureSource::Cube(data) => {
+                params.validate_cube(data.as_ref())?;
+                Command::CreateCubeTexture(Box::new((handle, params, data)))
+            }
+            TextureSource::Native(id) => Command::ImportTexture(handle, params, id),
+        };
 
-        let cmd = Command::CreateTexture(Box::new((handle, item.0, item.1)));
         self.frames.write().cmds.push(cmd);
+        Ok(params)
+    }
+
+    fn create_batch(
+        &self,
+        items: Vec<(Self::Handle, Self::Intermediate)>,
+    ) -> Result<Vec<Self::Resource>> {
+        info!("[TextureLoader] create {} textures.", items.len());
+
+        let mut resources = Vec::with_capacity(items.len());
+        let mut creations = Vec::with_capacity(items.len());
+        let mut singles = Vec::new();
+
+        for (handle, (params, source)) in items {
+            match source {
+                TextureSource::Data(data) => {
+                    params.validate(data.as_ref())?;
+                    creations.push((handle, params, data));
+                }
+                TextureSource::Cube(data) => {
+                    params.validate_cube(data.as_ref())?;
+                    let cmd = Command::CreateCubeTexture(Box::new((handle, params, data)));
+                    singles.push(cmd);
+                }
+                TextureSource::Native(id) => {
+                    singles.push(Command::ImportTexture(handle, params, id));
+                }
+            }
+
+            resources.push(params);
+        }
 
-        Ok(item.0)
+        let mut frame = self.frames.write();
+        frame.cmds.push(Command::CreateTextures(creations));
+        frame.cmds.extend(singles);
+        Ok(resources)
     }
 
     fn delete(&self, handle: Self::Handle, _: Self::Resource) {
@@ -62,3 +209,32 @@ impl ResourceLoader for TextureLoader {
         self.frames.write().cmds.push(cmd);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bmp() {
+        // A 2x2 24-bit bitmap, whose rows are padded to 8 bytes.
+        let mut bytes = vec![0; 54];
+        bytes[0..2].copy_from_slice(b"BM");
+        bytes[10] = 54;
+        bytes[14] = 40;
+        bytes[18] = 2;
+        bytes[22] = 2;
+        bytes[26] = 1;
+        bytes[28] = 24;
+        bytes.extend_from_slice(&[0, 0, 255, 0, 255, 0, 0, 0]);
+        bytes.extend_from_slice(&[255, 0, 0, 255, 255, 255, 0, 0]);
+
+        let (params, data) = ImageFormat::Bmp.decode(&bytes).unwrap();
+        assert_eq!(params.dimensions, Vector2::new(2, 2));
+        assert_eq!(
+            &data.bytes[0][..],
+            &[255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255][..]
+        );
+
+        assert!(ImageFormat::Bmp.decode(&bytes[0..60]).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+//! Uniform buffer objects, which hold the constants that are shared by many draws, e.g.
+//! the matrices of camera.
+
+use crate::video::assets::mesh::MeshHint;
+use crate::video::errors::{Error, Result};
+
+impl_handle!(UniformBufferHandle);
+
+/// The setup parameters of uniform buffer object.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct UniformBufferParams {
+    /// Usage hints.
+    pub hint: MeshHint,
+    /// The size in bytes of this buffer, which should be large enough to hold the uniform
+    /// blocks it binded to with the `std140` layout.
+    pub size: usize,
+}
+
+impl Default for UniformBufferParams {
+    fn default() -> Self {
+        UniformBufferParams {
+            hint: MeshHint::Dynamic,
+            size: 0,
+        }
+    }
+}
+
+impl UniformBufferParams {
+    pub fn validate(&self, data: Option<&[u8]>) -> Result<()> {
+        if self.size == 0 {
+            return Err(Error::BufferInvalid("buffer size is zero.".into()));
+        }
+
+        if let Some(v) = data {
+            if v.len() > self.size {
+                return Err(Error::BufferInvalid(format!(
+                    "data length {} exceeds buffer size {}.",
+                    v.len(),
+                    self.size
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
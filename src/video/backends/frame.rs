@@ -1,19 +1,57 @@
+use std::mem;
+use std::ops::{BitOr, BitOrAssign};
+use std::time::{Duration, Instant};
+
 use crate::errors::*;
 use crate::math::prelude::{Aabb2, Vector2};
 use crate::utils::prelude::{DataBuffer, DataBufferPtr, HashValue};
 
 use super::super::assets::prelude::*;
+use super::upload::UploadQueue;
 use super::Visitor;
 
 type VarsPtr = DataBufferPtr<[(HashValue<str>, UniformVariable)]>;
 type BytesPtr = DataBufferPtr<[u8]>;
+type InstanceBufferCreation = (
+    InstanceBufferHandle,
+    InstanceBufferParams,
+    Option<Box<[u8]>>,
+);
+type ShaderStorageBufferCreation = (
+    ShaderStorageBufferHandle,
+    ShaderStorageBufferParams,
+    Option<Box<[u8]>>,
+);
+type UniformBufferCreation = (UniformBufferHandle, UniformBufferParams, Option<Box<[u8]>>);
+
+/// The states of a draw besides its uniform variables.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct DrawCommand {
+    pub shader: ShaderHandle,
+    pub mesh: MeshHandle,
+    pub mesh_index: MeshIndex,
+    pub scissor: Option<SurfaceScissor>,
+    pub viewport: Option<SurfaceViewport>,
+    pub instances: Option<InstanceBufferHandle>,
+    /// The number of instances of instanced draw.
+    pub num_instances: Option<u32>,
+    /// The data of per-object uniform block.
+    pub per_object: Option<BytesPtr>,
+}
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Command {
     Bind(SurfaceHandle),
-    Draw(ShaderHandle, MeshHandle, MeshIndex, VarsPtr),
+    Draw(DrawCommand, VarsPtr),
     UpdateScissor(SurfaceScissor),
     UpdateViewport(SurfaceViewport),
+    UpdateAntialiasing(AaMode),
+    Finish,
+    FlushGpu,
+    MemoryBarrier(MemoryBarrier),
+    Blit(RenderTextureHandle, RenderTextureHandle, TextureFilter),
+    InvalidateStateCache,
+    SetFallbackTexture(Option<TextureHandle>),
 
     CreateSurface(Box<(SurfaceHandle, SurfaceParams)>),
     DeleteSurface(SurfaceHandle),
@@ -22,7 +60,8 @@ pub enum Command {
     DeleteShader(ShaderHandle),
 
     CreateTexture(Box<(TextureHandle, TextureParams, Option<TextureData>)>),
-    UpdateTexture(TextureHandle, Aabb2<u32>, BytesPtr),
+    ImportTexture(TextureHandle, TextureParams, u32),
+    UpdateTexture(TextureHandle, Aabb2<u32>, u32, BytesPtr),
     DeleteTexture(TextureHandle),
 
     CreateRenderTexture(Box<(RenderTextureHandle, RenderTextureParams)>),
@@ -32,12 +71,222 @@ pub enum Command {
     UpdateVertexBuffer(MeshHandle, usize, BytesPtr),
     UpdateIndexBuffer(MeshHandle, usize, BytesPtr),
     DeleteMesh(MeshHandle),
+
+    CreateInstanceBuffer(Box<InstanceBufferCreation>),
+    UpdateInstanceBuffer(InstanceBufferHandle, usize, BytesPtr),
+    DeleteInstanceBuffer(InstanceBufferHandle),
+
+    CreateShaderStorageBuffer(Box<ShaderStorageBufferCreation>),
+    UpdateShaderStorageBuffer(ShaderStorageBufferHandle, usize, BytesPtr),
+    ReadShaderStorageBuffer(ShaderStorageBufferHandle),
+    DeleteShaderStorageBuffer(ShaderStorageBufferHandle),
+
+    CreateTextures(Vec<(TextureHandle, TextureParams, Option<TextureData>)>),
+    CreateMeshes(Vec<(MeshHandle, MeshParams, Option<MeshData>)>),
+    ResizeRenderTexture(RenderTextureHandle, Vector2<u32>),
+    UpdateOutputAdjustment(OutputAdjustment),
+    UpdateMaxFrameLatency(u32),
+    ReadRenderTexture(RenderTextureHandle, Aabb2<u32>),
+    CreateCubeTexture(Box<(TextureHandle, TextureParams, Option<CubeTextureData>)>),
+    CreateUniformBuffer(Box<UniformBufferCreation>),
+    UpdateUniformBuffer(UniformBufferHandle, usize, BytesPtr),
+    DeleteUniformBuffer(UniformBufferHandle),
+    BeginGpuTimer(String),
+    EndGpuTimer,
+}
+
+/// The statistics of the dispatching of a frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// The number of framebuffer binds, the consecutive surfaces sharing the same
+    /// framebuffer are bound only once.
+    pub framebuffer_binds: u32,
+    /// The number of clear operations of surfaces.
+    pub clears: u32,
+    /// The number of clear operations of surfaces that have been skipped, since their
+    /// attachments have been cleared with the same values already.
+    pub skipped_clears: u32,
+    /// The number of commands that have been deferred to subsequent frames, since the
+    /// upload budget of this frame has been exhausted.
+    pub deferred_uploads: u32,
+    /// The number of commands that have been submitted into the frame.
+    pub commands: u32,
+    /// The number of draw calls that have been submitted into the frame.
+    pub draws: u32,
+    /// The number of bytes used in the data buffer of frame, e.g. uniform variables and
+    /// the updates of resources.
+    pub buffer_bytes: usize,
+    /// The capacity in bytes of the data buffer of frame, which is reallocated once the
+    /// `buffer_bytes` exceeds it.
+    pub buffer_capacity: usize,
+    /// The largest number of commands that have been submitted into the frame since its
+    /// buffers were shrunk last time.
+    pub peak_commands: u32,
+    /// The largest number of bytes that have been used in the data buffer of frame since
+    /// it was shrunk last time.
+    pub peak_buffer_bytes: usize,
+}
+
+/// The policy of shrinking the buffers of frames. The buffers are reused across frames
+/// without releasing their memory, so a few huge frames would pin the peak memory forever
+/// without shrinking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameShrinkPolicy {
+    /// The number of commands that are always retained.
+    pub cmds: usize,
+    /// The number of bytes of data buffer that are always retained.
+    pub bufs: usize,
+    /// The number of consecutive frames that buffers have been using less than half of
+    /// its capacity before being shrunk. Buffers are never shrunk if it's zero.
+    pub frames: u32,
+}
+
+impl Default for FrameShrinkPolicy {
+    fn default() -> Self {
+        FrameShrinkPolicy {
+            cmds: 1024,
+            bufs: 64 * 1024,
+            frames: 300,
+        }
+    }
+}
+
+/// The kinds of memory accesses that should be synchronized with the incoherent writes
+/// of shaders (e.g. image stores and shader storage buffers) before them. They could be
+/// combined with `|`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MemoryBarrier(u32);
+
+impl MemoryBarrier {
+    /// Vertex data sourced from buffers.
+    pub const VERTEX_ATTRIB_ARRAY: MemoryBarrier = MemoryBarrier(1 << 0);
+    /// Vertex indices sourced from buffers.
+    pub const ELEMENT_ARRAY: MemoryBarrier = MemoryBarrier(1 << 1);
+    /// Uniform variables sourced from buffers.
+    pub const UNIFORM: MemoryBarrier = MemoryBarrier(1 << 2);
+    /// Texture fetches from shaders.
+    pub const TEXTURE_FETCH: MemoryBarrier = MemoryBarrier(1 << 3);
+    /// Image loads, stores and atomics from shaders.
+    pub const SHADER_IMAGE_ACCESS: MemoryBarrier = MemoryBarrier(1 << 4);
+    /// Indirect commands sourced from buffers.
+    pub const COMMAND: MemoryBarrier = MemoryBarrier(1 << 5);
+    /// Reads and writes of pixel buffers.
+    pub const PIXEL_BUFFER: MemoryBarrier = MemoryBarrier(1 << 6);
+    /// Updates and reads back of textures.
+    pub const TEXTURE_UPDATE: MemoryBarrier = MemoryBarrier(1 << 7);
+    /// Updates and reads back of buffers.
+    pub const BUFFER_UPDATE: MemoryBarrier = MemoryBarrier(1 << 8);
+    /// Reads and writes through framebuffer attachments.
+    pub const FRAMEBUFFER: MemoryBarrier = MemoryBarrier(1 << 9);
+    /// Accesses of shader storage buffers from shaders.
+    pub const SHADER_STORAGE: MemoryBarrier = MemoryBarrier(1 << 10);
+    /// All kinds of the memory accesses.
+    pub const ALL: MemoryBarrier = MemoryBarrier(!0);
+
+    /// Returns a empty set of barriers.
+    #[inline]
+    pub fn empty() -> Self {
+        MemoryBarrier(0)
+    }
+
+    /// Returns true if there is no barrier in this set.
+    #[inline]
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if all the barriers in `other` are contained in this set.
+    #[inline]
+    pub fn contains(self, other: MemoryBarrier) -> bool {
+        (self.0 & other.0) == other.0
+    }
+}
+
+impl BitOr for MemoryBarrier {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        MemoryBarrier(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for MemoryBarrier {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// The budget of incremental dispatching, which limits the amount of work that
+/// would be submitted to the backend context per call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DispatchBudget {
+    /// The maximum number of commands could be executed.
+    pub commands: Option<usize>,
+    /// The maximum duration of time could be consumed.
+    pub duration: Option<Duration>,
+}
+
+impl DispatchBudget {
+    /// Creates a budget which dispatches all the commands in one go.
+    pub fn unlimited() -> Self {
+        Default::default()
+    }
+
+    #[inline]
+    fn exhausted(&self, commands: usize, ts: Instant) -> bool {
+        if let Some(v) = self.commands {
+            if commands >= v {
+                return true;
+            }
+        }
+
+        if let Some(v) = self.duration {
+            if ts.elapsed() >= v {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// A serializable log of the commands submitted into a frame, which could be replayed
+/// with `VideoSystem::replay` to reproduce it.
+///
+/// The handles in log are used as they are, so the resources referenced by the commands
+/// should be created in the same log, and it should be replayed into a video system that
+/// has not created any resource yet.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FrameLog {
+    cmds: Vec<Command>,
+    bufs: DataBuffer,
+}
+
+impl FrameLog {
+    /// Returns the number of commands in this log.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cmds.len()
+    }
+
+    /// Returns true if there is no command in this log.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.cmds.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 pub(crate) struct Frame {
     pub cmds: Vec<Command>,
     pub bufs: DataBuffer,
+    progress: Option<(u32, u32)>,
+    oversized: u32,
+    peak: (usize, usize),
+    usage: FrameStats,
+    usage_peak: (usize, usize),
 }
 
 unsafe impl Send for Frame {}
@@ -49,105 +298,700 @@ impl Frame {
         Frame {
             cmds: Vec::with_capacity(16),
             bufs: DataBuffer::with_capacity(capacity),
+            progress: None,
+            oversized: 0,
+            peak: (0, 0),
+            usage: FrameStats::default(),
+            usage_peak: (0, 0),
         }
     }
 
-    #[inline]
-    pub fn clear(&mut self) {
+    /// Clears the frame, and shrinks the buffers if they have been over-sized for the
+    /// number of frames specified in `policy`.
+    pub fn clear(&mut self, policy: FrameShrinkPolicy) {
+        let oversized = policy.frames > 0
+            && (self.cmds.capacity() > policy.cmds.max(self.cmds.len() * 2)
+                || self.bufs.capacity() > policy.bufs.max(self.bufs.len() * 2));
+
+        if oversized {
+            self.oversized += 1;
+            self.peak.0 = self.peak.0.max(self.cmds.len());
+            self.peak.1 = self.peak.1.max(self.bufs.len());
+        } else {
+            self.oversized = 0;
+            self.peak = (0, 0);
+        }
+
         self.cmds.clear();
         self.bufs.clear();
+        self.progress = None;
+
+        if self.oversized >= policy.frames && oversized {
+            self.cmds.shrink_to(policy.cmds.max(self.peak.0));
+            self.bufs.shrink_to(policy.bufs.max(self.peak.1));
+            self.oversized = 0;
+            self.peak = (0, 0);
+            self.usage_peak = (0, 0);
+        }
+    }
+
+    /// Records the commands that have not been dispatched yet.
+    pub fn record(&self) -> FrameLog {
+        FrameLog {
+            cmds: self.cmds.clone(),
+            bufs: self.bufs.clone(),
+        }
+    }
+
+    /// Appends the commands of `log` into this frame.
+    pub fn replay(&mut self, log: &FrameLog) {
+        for cmd in &log.cmds {
+            self.append(&log.bufs, cmd);
+        }
+    }
+
+    /// Drops the presentation commands of this stale frame which would never be shown, and
+    /// moves the rest of them (e.g. the creations of resources) before the commands of
+    /// `fresh`, so they will not be lost. The GPU timers and the reads of render textures
+    /// are dropped too, since they measure or read back the dropped draws.
+    pub fn merge_stale(&mut self, fresh: &mut Frame) {
+        self.cmds.retain(|v| match *v {
+            Command::Bind(_)
+            | Command::Draw(_, _)
+            | Command::UpdateScissor(_)
+            | Command::UpdateViewport(_)
+            | Command::Finish
+            | Command::FlushGpu
+            | Command::MemoryBarrier(_)
+            | Command::Blit(_, _, _)
+            | Command::ReadRenderTexture(_, _)
+            | Command::BeginGpuTimer(_)
+            | Command::EndGpuTimer => false,
+            _ => true,
+        });
+
+        for cmd in &fresh.cmds {
+            self.append(&fresh.bufs, cmd);
+        }
+
+        mem::swap(self, fresh);
+    }
+
+    /// Appends `cmd`, whose data is stored in `bufs`.
+    pub(super) fn append(&mut self, bufs: &DataBuffer, cmd: &Command) {
+        let cmd = match *cmd {
+            Command::Draw(mut dc, ptr) => {
+                if let Some(v) = dc.per_object {
+                    dc.per_object = Some(self.bufs.extend_from_slice(bufs.as_slice(v)));
+                }
+
+                let vars = bufs.as_slice(ptr);
+                Command::Draw(dc, self.bufs.extend_from_slice(vars))
+            }
+            Command::UpdateTexture(handle, area, row_length, ptr) => {
+                let bytes = bufs.as_slice(ptr);
+                let ptr = self.bufs.extend_from_slice(bytes);
+                Command::UpdateTexture(handle, area, row_length, ptr)
+            }
+            Command::UpdateVertexBuffer(handle, offset, ptr) => {
+                let bytes = bufs.as_slice(ptr);
+                Command::UpdateVertexBuffer(handle, offset, self.bufs.extend_from_slice(bytes))
+            }
+            Command::UpdateIndexBuffer(handle, offset, ptr) => {
+                let bytes = bufs.as_slice(ptr);
+                Command::UpdateIndexBuffer(handle, offset, self.bufs.extend_from_slice(bytes))
+            }
+            Command::UpdateInstanceBuffer(handle, offset, ptr) => {
+                let bytes = bufs.as_slice(ptr);
+                let ptr = self.bufs.extend_from_slice(bytes);
+                Command::UpdateInstanceBuffer(handle, offset, ptr)
+            }
+            Command::UpdateShaderStorageBuffer(handle, offset, ptr) => {
+                let bytes = bufs.as_slice(ptr);
+                let ptr = self.bufs.extend_from_slice(bytes);
+                Command::UpdateShaderStorageBuffer(handle, offset, ptr)
+            }
+            Command::UpdateUniformBuffer(handle, offset, ptr) => {
+                let bytes = bufs.as_slice(ptr);
+                let ptr = self.bufs.extend_from_slice(bytes);
+                Command::UpdateUniformBuffer(handle, offset, ptr)
+            }
+            ref v => v.clone(),
+        };
+
+        self.cmds.push(cmd);
+    }
+
+    /// Merges the statistics of backend with the usage of commands and buffers of this
+    /// frame, which is captured at the begining of dispatch.
+    pub fn stats(&self, stats: FrameStats) -> FrameStats {
+        FrameStats {
+            commands: self.usage.commands,
+            draws: self.usage.draws,
+            buffer_bytes: self.usage.buffer_bytes,
+            buffer_capacity: self.usage.buffer_capacity,
+            peak_commands: self.usage.peak_commands,
+            peak_buffer_bytes: self.usage.peak_buffer_bytes,
+            ..stats
+        }
+    }
+
+    /// Returns true if this frame has been partially dispatched.
+    #[inline]
+    pub fn is_dispatching(&self) -> bool {
+        self.progress.is_some()
     }
 
     /// Dispatch frame tasks and draw calls to the backend context.
     pub fn dispatch(
         &mut self,
         visitor: &mut Visitor,
+        uploads: &mut UploadQueue,
         dimensions: Vector2<u32>,
     ) -> Result<(u32, u32)> {
+        let budget = DispatchBudget::unlimited();
+        Ok(self
+            .dispatch_with(visitor, uploads, dimensions, budget)?
+            .unwrap())
+    }
+
+    /// Dispatch frame tasks and draw calls to the backend context incrementally, it
+    /// stops once the `budget` is exhausted and continues at next call. At least one
+    /// command is dispatched per call. Returns true if all the commands have been
+    /// dispatched.
+    pub fn dispatch_budget(
+        &mut self,
+        visitor: &mut dyn Visitor,
+        uploads: &mut UploadQueue,
+        dimensions: Vector2<u32>,
+        budget: DispatchBudget,
+    ) -> Result<bool> {
+        Ok(self
+            .dispatch_with(visitor, uploads, dimensions, budget)?
+            .is_some())
+    }
+
+    fn dispatch_with(
+        &mut self,
+        visitor: &mut dyn Visitor,
+        uploads: &mut UploadQueue,
+        dimensions: Vector2<u32>,
+        budget: DispatchBudget,
+    ) -> Result<Option<(u32, u32)>> {
         unsafe {
-            visitor.advance()?;
+            let (mut dc, mut tris) = match self.progress {
+                Some(v) => v,
+                None => {
+                    let draws = self.cmds.iter().filter(|v| match **v {
+                        Command::Draw(_, _) => true,
+                        _ => false,
+                    });
 
-            let (mut dc, mut tris) = (0, 0);
-            for v in self.cmds.drain(..) {
-                match v {
-                    Command::Bind(surface) => {
-                        visitor.bind(surface, dimensions)?;
-                    }
+                    self.usage_peak.0 = self.usage_peak.0.max(self.cmds.len());
+                    self.usage_peak.1 = self.usage_peak.1.max(self.bufs.len());
+                    self.usage = FrameStats {
+                        commands: self.cmds.len() as u32,
+                        draws: draws.count() as u32,
+                        buffer_bytes: self.bufs.len(),
+                        buffer_capacity: self.bufs.capacity(),
+                        peak_commands: self.usage_peak.0 as u32,
+                        peak_buffer_bytes: self.usage_peak.1,
+                        ..FrameStats::default()
+                    };
 
-                    Command::Draw(shader, mesh, mesh_index, ptr) => {
-                        let vars = self.bufs.as_slice(ptr);
-                        dc += 1;
-                        tris += visitor.draw(shader, mesh, mesh_index, vars)?;
-                    }
+                    visitor.advance()?;
+                    uploads.advance(visitor, dimensions)?;
+                    (0, 0)
+                }
+            };
 
-                    Command::UpdateScissor(scissor) => {
-                        visitor.update_surface_scissor(scissor)?;
-                    }
+            let ts = Instant::now();
+            let mut cmds = mem::replace(&mut self.cmds, Vec::new());
 
-                    Command::UpdateViewport(view) => {
-                        visitor.update_surface_viewport(view)?;
-                    }
+            let rest: Vec<Command> = {
+                let mut iter = cmds.drain(..);
+                let mut executed = 0;
 
-                    Command::CreateSurface(v) => {
-                        visitor.create_surface(v.0, v.1)?;
+                loop {
+                    // At least one command is executed per call, so the dispatching always
+                    // makes progress even with an exhausted budget.
+                    if executed > 0 && budget.exhausted(executed, ts) {
+                        break iter.collect();
                     }
 
-                    Command::DeleteSurface(handle) => {
-                        visitor.delete_surface(handle)?;
+                    match iter.next() {
+                        Some(cmd) => {
+                            let (a, b) = uploads.execute(&self.bufs, visitor, dimensions, cmd)?;
+                            dc += a;
+                            tris += b;
+                            executed += 1;
+                        }
+                        None => break Vec::new(),
                     }
+                }
+            };
 
-                    Command::CreateShader(v) => {
-                        visitor.create_shader(v.0, v.1, &v.2, &v.3)?;
-                    }
+            cmds.extend(rest);
+            self.cmds = cmds;
 
-                    Command::DeleteShader(handle) => {
-                        visitor.delete_shader(handle)?;
-                    }
+            if self.cmds.is_empty() {
+                visitor.flush()?;
+                self.progress = None;
+                Ok(Some((dc, tris)))
+            } else {
+                self.progress = Some((dc, tris));
+                Ok(None)
+            }
+        }
+    }
 
-                    Command::CreateTexture(v) => {
-                        visitor.create_texture(v.0, v.1, v.2)?;
-                    }
+    pub(super) unsafe fn execute(
+        bufs: &DataBuffer,
+        visitor: &mut dyn Visitor,
+        dimensions: Vector2<u32>,
+        cmd: Command,
+    ) -> Result<(u32, u32)> {
+        match cmd {
+            Command::Bind(surface) => {
+                visitor.bind(surface, dimensions)?;
+            }
 
-                    Command::UpdateTexture(handle, area, ptr) => {
-                        let data = self.bufs.as_slice(ptr);
-                        visitor.update_texture(handle, area, data)?;
-                    }
+            Command::Draw(dc, ptr) => {
+                let vars = bufs.as_slice(ptr);
+                let per_object = dc.per_object.map(|v| bufs.as_slice(v));
+                return Ok((1, visitor.draw(dc, vars, per_object)?));
+            }
 
-                    Command::DeleteTexture(handle) => {
-                        visitor.delete_texture(handle)?;
-                    }
+            Command::UpdateScissor(scissor) => {
+                visitor.update_surface_scissor(scissor)?;
+            }
 
-                    Command::CreateRenderTexture(v) => {
-                        visitor.create_render_texture(v.0, v.1)?;
-                    }
+            Command::UpdateViewport(view) => {
+                visitor.update_surface_viewport(view)?;
+            }
 
-                    Command::DeleteRenderTexture(handle) => {
-                        visitor.delete_render_texture(handle)?;
-                    }
+            Command::UpdateAntialiasing(mode) => {
+                visitor.set_antialiasing(mode)?;
+            }
 
-                    Command::CreateMesh(v) => {
-                        visitor.create_mesh(v.0, v.1, v.2)?;
-                    }
+            Command::UpdateOutputAdjustment(adjustment) => {
+                visitor.set_output_adjustment(adjustment)?;
+            }
 
-                    Command::UpdateVertexBuffer(handle, offset, ptr) => {
-                        let data = self.bufs.as_slice(ptr);
-                        visitor.update_vertex_buffer(handle, offset, data)?;
-                    }
+            Command::UpdateMaxFrameLatency(frames) => {
+                visitor.set_max_frame_latency(frames)?;
+            }
 
-                    Command::UpdateIndexBuffer(handle, offset, ptr) => {
-                        let data = self.bufs.as_slice(ptr);
-                        visitor.update_index_buffer(handle, offset, data)?;
-                    }
+            Command::ReadRenderTexture(handle, area) => {
+                visitor.read_render_texture(handle, area)?;
+            }
 
-                    Command::DeleteMesh(handle) => {
-                        visitor.delete_mesh(handle)?;
-                    }
+            Command::Finish => {
+                visitor.finish()?;
+            }
+
+            Command::FlushGpu => {
+                visitor.flush_gpu()?;
+            }
+
+            Command::MemoryBarrier(barriers) => {
+                visitor.memory_barrier(barriers)?;
+            }
+
+            Command::Blit(src, dst, filter) => {
+                visitor.blit(src, dst, filter)?;
+            }
+
+            Command::InvalidateStateCache => {
+                visitor.invalidate_state_cache()?;
+            }
+
+            Command::SetFallbackTexture(handle) => {
+                visitor.set_fallback_texture(handle)?;
+            }
+
+            Command::CreateSurface(v) => {
+                visitor.create_surface(v.0, v.1)?;
+            }
+
+            Command::DeleteSurface(handle) => {
+                visitor.delete_surface(handle)?;
+            }
+
+            Command::CreateShader(v) => {
+                visitor.create_shader(v.0, v.1, &v.2, &v.3)?;
+            }
+
+            Command::DeleteShader(handle) => {
+                visitor.delete_shader(handle)?;
+            }
+
+            Command::CreateTexture(v) => {
+                visitor.create_texture(v.0, v.1, v.2)?;
+            }
+
+            Command::CreateCubeTexture(v) => {
+                visitor.create_cube_texture(v.0, v.1, v.2)?;
+            }
+
+            Command::ImportTexture(handle, params, id) => {
+                visitor.import_texture(handle, params, id)?;
+            }
+
+            Command::CreateTextures(v) => {
+                for (handle, params, data) in v {
+                    visitor.create_texture(handle, params, data)?;
+                }
+            }
+
+            Command::UpdateTexture(handle, area, row_length, ptr) => {
+                let data = bufs.as_slice(ptr);
+                visitor.update_texture(handle, area, row_length, data)?;
+            }
+
+            Command::DeleteTexture(handle) => {
+                visitor.delete_texture(handle)?;
+            }
+
+            Command::CreateRenderTexture(v) => {
+                visitor.create_render_texture(v.0, v.1)?;
+            }
+
+            Command::DeleteRenderTexture(handle) => {
+                visitor.delete_render_texture(handle)?;
+            }
+
+            Command::ResizeRenderTexture(handle, dimensions) => {
+                visitor.resize_render_texture(handle, dimensions)?;
+            }
+
+            Command::CreateMesh(v) => {
+                visitor.create_mesh(v.0, v.1, v.2)?;
+            }
+
+            Command::CreateMeshes(v) => {
+                for (handle, params, data) in v {
+                    visitor.create_mesh(handle, params, data)?;
                 }
             }
 
-            visitor.flush()?;
-            self.cmds.clear();
-            Ok((dc, tris))
+            Command::UpdateVertexBuffer(handle, offset, ptr) => {
+                let data = bufs.as_slice(ptr);
+                visitor.update_vertex_buffer(handle, offset, data)?;
+            }
+
+            Command::UpdateIndexBuffer(handle, offset, ptr) => {
+                let data = bufs.as_slice(ptr);
+                visitor.update_index_buffer(handle, offset, data)?;
+            }
+
+            Command::DeleteMesh(handle) => {
+                visitor.delete_mesh(handle)?;
+            }
+
+            Command::CreateInstanceBuffer(v) => {
+                visitor.create_instance_buffer(v.0, v.1, v.2.as_ref().map(|v| v.as_ref()))?;
+            }
+
+            Command::UpdateInstanceBuffer(handle, offset, ptr) => {
+                let data = bufs.as_slice(ptr);
+                visitor.update_instance_buffer(handle, offset, data)?;
+            }
+
+            Command::DeleteInstanceBuffer(handle) => {
+                visitor.delete_instance_buffer(handle)?;
+            }
+
+            Command::CreateShaderStorageBuffer(v) => {
+                let data = v.2.as_ref().map(|v| v.as_ref());
+                visitor.create_shader_storage_buffer(v.0, v.1, data)?;
+            }
+
+            Command::UpdateShaderStorageBuffer(handle, offset, ptr) => {
+                let data = bufs.as_slice(ptr);
+                visitor.update_shader_storage_buffer(handle, offset, data)?;
+            }
+
+            Command::ReadShaderStorageBuffer(handle) => {
+                visitor.read_shader_storage_buffer(handle)?;
+            }
+
+            Command::DeleteShaderStorageBuffer(handle) => {
+                visitor.delete_shader_storage_buffer(handle)?;
+            }
+
+            Command::CreateUniformBuffer(v) => {
+                let data = v.2.as_ref().map(|v| v.as_ref());
+                visitor.create_uniform_buffer(v.0, v.1, data)?;
+            }
+
+            Command::UpdateUniformBuffer(handle, offset, ptr) => {
+                let data = bufs.as_slice(ptr);
+                visitor.update_uniform_buffer(handle, offset, data)?;
+            }
+
+            Command::DeleteUniformBuffer(handle) => {
+                visitor.delete_uniform_buffer(handle)?;
+            }
+
+            Command::BeginGpuTimer(name) => {
+                visitor.begin_gpu_timer(&name)?;
+            }
+
+            Command::EndGpuTimer => {
+                visitor.end_gpu_timer()?;
+            }
+        }
+
+        Ok((0, 0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::headless::HeadlessVisitor;
+    use super::*;
+
+    #[test]
+    fn dispatch_budget() {
+        let mut visitor = HeadlessVisitor::new();
+        let mut frame = Frame::with_capacity(0);
+        for _ in 0..5 {
+            frame.cmds.push(Command::Bind(SurfaceHandle::default()));
+        }
+
+        let budget = DispatchBudget {
+            commands: Some(2),
+            duration: None,
+        };
+
+        let dims = Vector2::new(0, 0);
+        let mut uploads = UploadQueue::new();
+        let mut dispatch = |frame: &mut Frame| {
+            frame
+                .dispatch_budget(&mut visitor, &mut uploads, dims, budget)
+                .unwrap()
+        };
+
+        assert!(!dispatch(&mut frame));
+        assert!(frame.is_dispatching());
+        assert_eq!(frame.cmds.len(), 3);
+        assert!(!dispatch(&mut frame));
+        assert!(dispatch(&mut frame));
+        assert!(!frame.is_dispatching());
+        assert!(frame.cmds.is_empty());
+
+        // One command should be executed per call with zero budget.
+        for _ in 0..2 {
+            frame.cmds.push(Command::Bind(SurfaceHandle::default()));
+        }
+
+        let budget = DispatchBudget {
+            commands: Some(0),
+            duration: Some(Duration::from_secs(0)),
+        };
+
+        let mut dispatch = |frame: &mut Frame| {
+            frame
+                .dispatch_budget(&mut visitor, &mut uploads, dims, budget)
+                .unwrap()
+        };
+
+        assert!(!dispatch(&mut frame));
+        assert_eq!(frame.cmds.len(), 1);
+        assert!(dispatch(&mut frame));
+        assert!(frame.cmds.is_empty());
+    }
+
+    #[test]
+    fn usage() {
+        let mut visitor = HeadlessVisitor::new();
+        unsafe {
+            let params = MeshParams::default();
+            visitor
+                .create_mesh(MeshHandle::default(), params, None)
+                .unwrap();
+        }
+
+        let mut frame = Frame::with_capacity(64);
+        let vars = [(HashValue::from("u_Value"), UniformVariable::F32(1.0))];
+        let dc = DrawCommand {
+            shader: ShaderHandle::default(),
+            mesh: MeshHandle::default(),
+            mesh_index: MeshIndex::All,
+            scissor: None,
+            viewport: None,
+            instances: None,
+            num_instances: None,
+            per_object: None,
+        };
+
+        frame.cmds.push(Command::Bind(SurfaceHandle::default()));
+        for _ in 0..2 {
+            let ptr = frame.bufs.extend_from_slice(&vars);
+            frame.cmds.push(Command::Draw(dc, ptr));
+        }
+
+        let len = frame.bufs.len();
+        let mut uploads = UploadQueue::new();
+        frame
+            .dispatch(&mut visitor, &mut uploads, Vector2::new(0, 0))
+            .unwrap();
+
+        let stats = frame.stats(FrameStats::default());
+        assert_eq!(stats.commands, 3);
+        assert_eq!(stats.draws, 2);
+        assert_eq!(stats.buffer_bytes, len);
+        assert!(stats.buffer_capacity >= len);
+        assert_eq!(stats.peak_commands, 3);
+        assert_eq!(stats.peak_buffer_bytes, len);
+
+        // The peaks are retained across the smaller frames.
+        frame.clear(FrameShrinkPolicy::default());
+        frame.cmds.push(Command::Bind(SurfaceHandle::default()));
+        frame
+            .dispatch(&mut visitor, &mut uploads, Vector2::new(0, 0))
+            .unwrap();
+
+        let stats = frame.stats(FrameStats::default());
+        assert_eq!(stats.commands, 1);
+        assert_eq!(stats.buffer_bytes, 0);
+        assert_eq!(stats.peak_commands, 3);
+        assert_eq!(stats.peak_buffer_bytes, len);
+    }
+
+    #[test]
+    fn instanced_draw() {
+        let mut visitor = HeadlessVisitor::new();
+        let mut frame = Frame::with_capacity(64);
+        let mut params = MeshParams::default();
+        params.num_verts = 4;
+        params.num_idxes = 6;
+        let mesh = MeshHandle::default();
+        frame
+            .cmds
+            .push(Command::CreateMesh(Box::new((mesh, params, None))));
+
+        let dc = DrawCommand {
+            shader: ShaderHandle::default(),
+            mesh,
+            mesh_index: MeshIndex::All,
+            scissor: None,
+            viewport: None,
+            instances: None,
+            num_instances: None,
+            per_object: None,
+        };
+
+        let vars = [(HashValue::from("u_Value"), UniformVariable::F32(1.0))];
+        let ptr = frame.bufs.extend_from_slice(&vars);
+        frame.cmds.push(Command::Draw(dc, ptr));
+
+        let dc = DrawCommand {
+            num_instances: Some(16),
+            ..dc
+        };
+        frame.cmds.push(Command::Draw(dc, ptr));
+
+        let mut uploads = UploadQueue::new();
+        let (draws, tris) = frame
+            .dispatch(&mut visitor, &mut uploads, Vector2::new(0, 0))
+            .unwrap();
+
+        assert_eq!(draws, 2);
+        assert_eq!(tris, 2 + 2 * 16);
+    }
+
+    #[test]
+    fn shrink() {
+        let policy = FrameShrinkPolicy {
+            cmds: 4,
+            bufs: 16,
+            frames: 2,
+        };
+
+        let mut frame = Frame::with_capacity(0);
+        for _ in 0..64 {
+            frame.cmds.push(Command::Bind(SurfaceHandle::default()));
+            frame.bufs.extend(&0u32);
+        }
+
+        frame.clear(policy);
+        frame.clear(policy);
+        assert!(frame.cmds.capacity() >= 64);
+
+        frame.cmds.push(Command::Bind(SurfaceHandle::default()));
+        frame.clear(policy);
+        assert!(frame.cmds.capacity() < 64 && frame.cmds.capacity() >= 4);
+        assert!(frame.bufs.capacity() < 256 && frame.bufs.capacity() >= 16);
+    }
+
+    #[test]
+    fn merge_stale() {
+        let mut stale = Frame::with_capacity(0);
+        let handle = RenderTextureHandle::default();
+        let area = Aabb2::zero();
+        stale.cmds.push(Command::BeginGpuTimer("Scene".into()));
+        stale.cmds.push(Command::Bind(SurfaceHandle::default()));
+        stale.cmds.push(Command::ReadRenderTexture(handle, area));
+        stale.cmds.push(Command::EndGpuTimer);
+        stale.cmds.push(Command::DeleteRenderTexture(handle));
+
+        let mut fresh = Frame::with_capacity(0);
+        fresh.cmds.push(Command::Bind(SurfaceHandle::default()));
+
+        stale.merge_stale(&mut fresh);
+        assert_eq!(fresh.cmds.len(), 2);
+
+        match fresh.cmds[0] {
+            Command::DeleteRenderTexture(v) => assert_eq!(v, handle),
+            _ => unreachable!(),
+        }
+
+        match fresh.cmds[1] {
+            Command::Bind(_) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let mut frame = Frame::with_capacity(0);
+        let vars = [(HashValue::from("u_Value"), UniformVariable::F32(1.0))];
+        let dc = DrawCommand {
+            shader: ShaderHandle::default(),
+            mesh: MeshHandle::default(),
+            mesh_index: MeshIndex::All,
+            scissor: None,
+            viewport: None,
+            instances: None,
+            num_instances: None,
+            per_object: None,
+        };
+
+        frame.cmds.push(Command::Bind(SurfaceHandle::default()));
+        frame
+            .cmds
+            .push(Command::Draw(dc, frame.bufs.extend_from_slice(&vars)));
+
+        let log = frame.record();
+        let log: FrameLog = serde_json::from_str(&serde_json::to_string(&log).unwrap()).unwrap();
+        assert_eq!(log.len(), 2);
+
+        let mut replayed = Frame::with_capacity(0);
+        replayed.bufs.extend_from_slice(&vars);
+        replayed.replay(&log);
+        assert_eq!(replayed.cmds.len(), 2);
+
+        match replayed.cmds[1] {
+            Command::Draw(_, ptr) => match replayed.bufs.as_slice(ptr)[0] {
+                (name, UniformVariable::F32(v)) => {
+                    assert_eq!(name, HashValue::from("u_Value"));
+                    assert_eq!(v, 1.0);
+                }
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
         }
     }
 }
@@ -0,0 +1,409 @@
+//! Redirects the rendering of window framebuffer into an offscreen framebuffer, which
+//! will be resolved into the window framebuffer with MSAA or FXAA at the end of frame.
+//! The `OutputAdjustment` is applied by the full-screen pass of resolving as well.
+
+use gl;
+use gl::types::*;
+
+use crate::errors::*;
+use crate::math::prelude::Vector2;
+use crate::video::assets::prelude::{AaMode, OutputAdjustment};
+
+use super::capabilities::Capabilities;
+use super::visitor::{check, GLVisitor};
+
+const FULLSCREEN_VS: &str = include_str!("../shaders/fullscreen.vs");
+const FXAA_FS: &str = include_str!("../shaders/fxaa.fs");
+const COPY_FS: &str = include_str!("../shaders/copy.fs");
+const OUTPUT_FS: &str = include_str!("../shaders/output.fs");
+
+/// A full-screen pass that samples `u_Texture`, which is shared with the fallback of blits.
+pub(super) struct GLOutputPass {
+    program: GLuint,
+    vao: GLuint,
+    vbo: GLuint,
+    texture: GLint,
+    texel_size: GLint,
+    output: GLint,
+}
+
+pub struct GLAntialiasing {
+    mode: AaMode,
+    samples: GLsizei,
+    dimensions: Vector2<u32>,
+    fbo: GLuint,
+    color: GLuint,
+    depth_stencil: GLuint,
+    // The single-sampled framebuffer that MSAA resolves into before the output pass.
+    resolve_fbo: GLuint,
+    resolve_color: GLuint,
+    pass: Option<GLOutputPass>,
+    adjustment: OutputAdjustment,
+    used: bool,
+}
+
+impl GLAntialiasing {
+    pub unsafe fn new(
+        mode: AaMode,
+        adjustment: OutputAdjustment,
+        capabilities: &Capabilities,
+    ) -> Result<Self> {
+        if !Self::is_required(mode, adjustment) {
+            bail!("[GL] AaMode::None does not need offscreen framebuffer.");
+        }
+
+        let samples = match mode {
+            AaMode::None => 0,
+            AaMode::Msaa(n) => {
+                if capabilities.max_samples == 0 {
+                    bail!("[GL] The GL Context does not support multisampled renderbuffers.");
+                }
+
+                let n = (u32::from(n)).min(capabilities.max_samples);
+                n as GLsizei
+            }
+            AaMode::Fxaa => 0,
+        };
+
+        let pass = if mode == AaMode::Fxaa {
+            Some(GLOutputPass::new(FXAA_FS)?)
+        } else if !adjustment.is_identity() {
+            Some(GLOutputPass::new(COPY_FS)?)
+        } else {
+            None
+        };
+
+        let mut fbo = 0;
+        gl::GenFramebuffers(1, &mut fbo);
+        assert!(fbo != 0);
+
+        let mut resolve_fbo = 0;
+        if samples > 0 && pass.is_some() {
+            gl::GenFramebuffers(1, &mut resolve_fbo);
+            assert!(resolve_fbo != 0);
+        }
+
+        info!(
+            "[GL] Enables antialiasing {:?} with {} samples, and {:?}.",
+            mode, samples, adjustment
+        );
+
+        Ok(GLAntialiasing {
+            mode,
+            samples,
+            dimensions: Vector2::new(0, 0),
+            fbo,
+            color: 0,
+            depth_stencil: 0,
+            resolve_fbo,
+            resolve_color: 0,
+            pass,
+            adjustment,
+            used: false,
+        })
+    }
+
+    /// Returns true if the offscreen framebuffer is required to resolve with `mode` and
+    /// `adjustment`.
+    #[inline]
+    pub fn is_required(mode: AaMode, adjustment: OutputAdjustment) -> bool {
+        mode != AaMode::None || !adjustment.is_identity()
+    }
+
+    #[inline]
+    pub fn mode(&self) -> AaMode {
+        self.mode
+    }
+
+    /// Sets the adjustment of output, returns false if the offscreen framebuffer should be
+    /// recreated for it.
+    pub fn set_adjustment(&mut self, adjustment: OutputAdjustment) -> bool {
+        // The FXAA pass applies the adjustment anyway, while the other modes need a copy
+        // pass only if the adjustment is not the identity.
+        if self.mode != AaMode::Fxaa && self.pass.is_some() == adjustment.is_identity() {
+            return false;
+        }
+
+        self.adjustment = adjustment;
+        true
+    }
+
+    /// Returns true if there are something rendered into the offscreen framebuffer since
+    /// last resolving.
+    #[inline]
+    pub fn is_pending(&self) -> bool {
+        self.used
+    }
+
+    #[inline]
+    pub fn dimensions(&self) -> Vector2<u32> {
+        self.dimensions
+    }
+
+    /// Gets the offscreen framebuffer with `dimensions`, the attachments will be reallocated
+    /// if the dimensions changes.
+    pub unsafe fn framebuffer(&mut self, dimensions: Vector2<u32>) -> Result<GLuint> {
+        if self.dimensions != dimensions {
+            self.free_attachments();
+            self.dimensions = dimensions;
+
+            let (w, h) = (dimensions.x as GLsizei, dimensions.y as GLsizei);
+
+            if self.resolve_fbo != 0 {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, self.resolve_fbo);
+                self.resolve_color = Self::create_color_texture(w, h);
+
+                if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                    bail!(
+                        "[GL] The resolve framebuffer of {:?} is incomplete.",
+                        self.mode
+                    );
+                }
+            }
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+
+            if self.samples == 0 {
+                self.color = Self::create_color_texture(w, h);
+            } else {
+                gl::GenRenderbuffers(1, &mut self.color);
+                gl::BindRenderbuffer(gl::RENDERBUFFER, self.color);
+                gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, self.samples, gl::RGBA8, w, h);
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::RENDERBUFFER,
+                    self.color,
+                );
+            }
+
+            gl::GenRenderbuffers(1, &mut self.depth_stencil);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth_stencil);
+            gl::RenderbufferStorageMultisample(
+                gl::RENDERBUFFER,
+                self.samples,
+                gl::DEPTH24_STENCIL8,
+                w,
+                h,
+            );
+
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                self.depth_stencil,
+            );
+
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                bail!(
+                    "[GL] The offscreen framebuffer of {:?} is incomplete.",
+                    self.mode
+                );
+            }
+
+            check()?;
+        }
+
+        self.used = true;
+        Ok(self.fbo)
+    }
+
+    /// Resolves the offscreen framebuffer into the window framebuffer.
+    ///
+    /// Notes that the binded framebuffer, program, vertex array and the texture of unit 0
+    /// will be changed, and the fixed-function states are expected to be reset by the
+    /// caller before the full-screen pass.
+    pub unsafe fn resolve(&mut self) -> Result<()> {
+        self.used = false;
+        let (w, h) = (self.dimensions.x as GLint, self.dimensions.y as GLint);
+
+        let mut texture = self.color;
+        if self.samples > 0 {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.resolve_fbo);
+            gl::BlitFramebuffer(0, 0, w, h, 0, 0, w, h, gl::COLOR_BUFFER_BIT, gl::NEAREST);
+            texture = self.resolve_color;
+        }
+
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+        if let Some(ref pass) = self.pass {
+            pass.draw(texture, self.dimensions, self.adjustment);
+        }
+
+        check()
+    }
+
+    /// Deletes the GL objects.
+    pub unsafe fn delete(&mut self) -> Result<()> {
+        self.free_attachments();
+        gl::DeleteFramebuffers(1, &self.fbo);
+
+        if self.resolve_fbo != 0 {
+            gl::DeleteFramebuffers(1, &self.resolve_fbo);
+        }
+
+        if let Some(ref pass) = self.pass {
+            pass.delete();
+        }
+
+        check()
+    }
+
+    /// Creates a color texture and attaches it to the binded framebuffer.
+    unsafe fn create_color_texture(w: GLsizei, h: GLsizei) -> GLuint {
+        // Keeps the texture binding untouched, which is cached by the visitor.
+        let mut binding = 0;
+        gl::GetIntegerv(gl::TEXTURE_BINDING_2D, &mut binding);
+
+        let mut id = 0;
+        gl::GenTextures(1, &mut id);
+        gl::BindTexture(gl::TEXTURE_2D, id);
+        gl::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_WRAP_S,
+            gl::CLAMP_TO_EDGE as GLint,
+        );
+        gl::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_WRAP_T,
+            gl::CLAMP_TO_EDGE as GLint,
+        );
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA as GLint,
+            w,
+            h,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            ::std::ptr::null(),
+        );
+
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            id,
+            0,
+        );
+
+        gl::BindTexture(gl::TEXTURE_2D, binding as GLuint);
+        id
+    }
+
+    unsafe fn free_attachments(&mut self) {
+        if self.color != 0 {
+            if self.samples == 0 {
+                gl::DeleteTextures(1, &self.color);
+            } else {
+                gl::DeleteRenderbuffers(1, &self.color);
+            }
+
+            self.color = 0;
+        }
+
+        if self.resolve_color != 0 {
+            gl::DeleteTextures(1, &self.resolve_color);
+            self.resolve_color = 0;
+        }
+
+        if self.depth_stencil != 0 {
+            gl::DeleteRenderbuffers(1, &self.depth_stencil);
+            self.depth_stencil = 0;
+        }
+    }
+}
+
+impl GLOutputPass {
+    /// Compiles the pass with fragment shader `fs`, which could use the `output_adjust`.
+    pub(super) unsafe fn new(fs: &str) -> Result<Self> {
+        let header = "#version 100\nprecision mediump float;\n";
+        let vs = format!("{}{}", header, FULLSCREEN_VS);
+        let fs = format!("{}{}{}", header, OUTPUT_FS, fs);
+        let vs = GLVisitor::compile(gl::VERTEX_SHADER, &vs)?;
+        let fs = GLVisitor::compile(gl::FRAGMENT_SHADER, &fs)?;
+        let program = GLVisitor::link(&[vs, fs]);
+
+        gl::DeleteShader(vs);
+        gl::DeleteShader(fs);
+        let program = program?;
+
+        let c_str = |name: &str| ::std::ffi::CString::new(name.as_bytes()).unwrap();
+        let texture = gl::GetUniformLocation(program, c_str("u_Texture").as_ptr());
+        let texel_size = gl::GetUniformLocation(program, c_str("u_TexelSize").as_ptr());
+        let output = gl::GetUniformLocation(program, c_str("u_Output").as_ptr());
+        let position = gl::GetAttribLocation(program, c_str("a_Position").as_ptr());
+        if position < 0 {
+            bail!("[GL] Failed to locate the attribute of output pass.");
+        }
+
+        // A triangle that covers the whole viewport.
+        let verts: [GLfloat; 6] = [-1.0, -1.0, 3.0, -1.0, -1.0, 3.0];
+
+        let mut vao = 0;
+        gl::GenVertexArrays(1, &mut vao);
+        gl::BindVertexArray(vao);
+
+        let mut vbo = 0;
+        gl::GenBuffers(1, &mut vbo);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            ::std::mem::size_of_val(&verts) as isize,
+            verts.as_ptr() as *const ::std::os::raw::c_void,
+            gl::STATIC_DRAW,
+        );
+
+        gl::EnableVertexAttribArray(position as GLuint);
+        gl::VertexAttribPointer(
+            position as GLuint,
+            2,
+            gl::FLOAT,
+            gl::FALSE,
+            0,
+            ::std::ptr::null(),
+        );
+
+        gl::BindVertexArray(0);
+        check()?;
+
+        Ok(GLOutputPass {
+            program,
+            vao,
+            vbo,
+            texture,
+            texel_size,
+            output,
+        })
+    }
+    /// Draws `texture` of `dimensions` into the binded framebuffer. The program, vertex
+    /// array and the texture of unit 0 will be changed.
+    pub(super) unsafe fn draw(
+        &self,
+        texture: GLuint,
+        dimensions: Vector2<u32>,
+        adjustment: OutputAdjustment,
+    ) {
+        let (w, h) = (dimensions.x.max(1) as f32, dimensions.y.max(1) as f32);
+        let v = adjustment;
+
+        gl::UseProgram(self.program);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::Uniform1i(self.texture, 0);
+        gl::Uniform2f(self.texel_size, 1.0 / w, 1.0 / h);
+        gl::Uniform3f(self.output, 1.0 / v.gamma, v.brightness, v.contrast);
+        gl::BindVertexArray(self.vao);
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+    }
+
+    pub(super) unsafe fn delete(&self) {
+        gl::DeleteProgram(self.program);
+        gl::DeleteVertexArrays(1, &self.vao);
+        gl::DeleteBuffers(1, &self.vbo);
+    }
+}
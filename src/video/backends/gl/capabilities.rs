@@ -5,6 +5,10 @@ use std::ffi;
 use std::mem;
 
 use crate::errors::*;
+use crate::math::prelude::Vector2;
+use crate::video::assets::texture::{FormatUsage, RenderTextureFormat};
+
+const CONTEXT_FLAG_NO_ERROR_BIT_KHR: GLenum = 0x0000_0008;
 
 /// Describes the OpenGL context profile.
 #[derive(Debug, Copy, Clone)]
@@ -171,8 +175,41 @@ extensions! {
     "GL_ARB_ES3_compatibility" => gl_arb_es3_compatibility,
     "GL_OES_compressed_ETC2_RGB8_texture" => gl_oes_compressed_etc2_rgb8_texture,
     "GL_OES_compressed_ETC2_RGBA8_texture" => gl_oes_compressed_etc2_rgba8_texture,
+    "GL_ARB_viewport_array" => gl_arb_viewport_array,
+    "GL_ARB_texture_multisample" => gl_arb_texture_multisample,
+    "GL_ARB_internalformat_query2" => gl_arb_internalformat_query2,
+    "GL_ARB_instanced_arrays" => gl_arb_instanced_arrays,
+    "GL_ARB_draw_instanced" => gl_arb_draw_instanced,
+    "GL_ARB_timer_query" => gl_arb_timer_query,
+    "GL_EXT_unpack_subimage" => gl_ext_unpack_subimage,
+    "GL_EXT_framebuffer_multisample" => gl_ext_framebuffer_multisample,
+    "GL_EXT_color_buffer_half_float" => gl_ext_color_buffer_half_float,
+    "GL_EXT_color_buffer_float" => gl_ext_color_buffer_float,
+    "GL_ARB_framebuffer_sRGB" => gl_arb_framebuffer_srgb,
+    "GL_EXT_framebuffer_sRGB" => gl_ext_framebuffer_srgb,
+    "GL_EXT_sRGB_write_control" => gl_ext_srgb_write_control,
+    "GL_ARB_shader_image_load_store" => gl_arb_shader_image_load_store,
+    "GL_ARB_shader_storage_buffer_object" => gl_arb_shader_storage_buffer_object,
+    "GL_ARB_provoking_vertex" => gl_arb_provoking_vertex,
+    "GL_ARB_sampler_objects" => gl_arb_sampler_objects,
+    "GL_ARB_sync" => gl_arb_sync,
+    "GL_OES_element_index_uint" => gl_oes_element_index_uint,
+    "GL_OES_texture_npot" => gl_oes_texture_npot,
 }
 
+const RENDER_TEXTURE_FORMATS: [RenderTextureFormat; 10] = [
+    RenderTextureFormat::RGB8,
+    RenderTextureFormat::RGBA4,
+    RenderTextureFormat::RGBA8,
+    RenderTextureFormat::RGBA16F,
+    RenderTextureFormat::R32UI,
+    RenderTextureFormat::R32I,
+    RenderTextureFormat::Depth16,
+    RenderTextureFormat::Depth24,
+    RenderTextureFormat::Depth32,
+    RenderTextureFormat::Depth24Stencil8,
+];
+
 #[derive(Debug, Copy, Clone)]
 pub enum TextureCompression {
     ETC2,
@@ -212,9 +249,19 @@ pub struct Capabilities {
     /// will be supported.
     pub forward_compatible: bool,
 
+    /// The context is created without error reporting (`GL_KHR_no_error`), so any error
+    /// results in undefined behavior instead.
+    pub no_error: bool,
+
     /// Maximum width and height of `glViewport`.
     pub max_viewport_dims: (u32, u32),
 
+    /// Maximum width and height of 2D textures.
+    pub max_texture_size: u32,
+
+    /// Maximum number of generic vertex attributes.
+    pub max_vertex_attributes: u32,
+
     /// Maximum number of textures that can be bound to a program.
     ///
     /// `glActiveTexture` must be between `GL_TEXTURE0` and `GL_TEXTURE0` + this value - 1.
@@ -223,8 +270,19 @@ pub struct Capabilities {
     /// Number of available buffer bind points for `GL_UNIFORM_BUFFER`.
     pub max_indexed_uniform_buffer: u32,
 
+    /// The minimum required alignment of the offsets of `glBindBufferRange` for
+    /// `GL_UNIFORM_BUFFER`.
+    pub uniform_buffer_offset_alignment: u32,
+
     /// Maximum number of color attachment bind points.
     pub max_color_attachments: u32,
+
+    /// Maximum number of samples of multisampled renderbuffers. `0` if not supported.
+    pub max_samples: u32,
+
+    /// The supported usages of render texture formats, indexed by `RenderTextureFormat` and
+    /// `FormatUsage`.
+    pub render_texture_formats: [[bool; 3]; 10],
 }
 
 impl Capabilities {
@@ -232,16 +290,17 @@ impl Capabilities {
         let version = Version::parse()?;
         let extensions = Extensions::parse(version)?;
 
-        let (debug, forward_compatible) = if version >= Version::GL(3, 0) {
+        let (debug, forward_compatible, no_error) = if version >= Version::GL(3, 0) {
             let mut val = mem::uninitialized();
             gl::GetIntegerv(gl::CONTEXT_FLAGS, &mut val);
             let val = val as gl::types::GLenum;
             (
                 (val & gl::CONTEXT_FLAG_DEBUG_BIT) != 0,
                 (val & gl::CONTEXT_FLAG_FORWARD_COMPATIBLE_BIT) != 0,
+                (val & CONTEXT_FLAG_NO_ERROR_BIT_KHR) != 0,
             )
         } else {
-            (false, false)
+            (false, false, false)
         };
 
         Ok(Capabilities {
@@ -252,13 +311,29 @@ impl Capabilities {
             profile: Capabilities::parse_profile(version),
             debug,
             forward_compatible,
+            no_error,
             max_viewport_dims: Capabilities::parse_viewport_dims(),
+            max_texture_size: Capabilities::parse_texture_size(),
+            max_vertex_attributes: Capabilities::parse_vertex_attributes(),
             max_combined_texture_image_units: Capabilities::parse_texture_image_units(),
             max_indexed_uniform_buffer: Capabilities::parse_uniform_buffers(version, &extensions),
+            uniform_buffer_offset_alignment: Capabilities::parse_uniform_buffer_alignment(
+                version,
+                &extensions,
+            ),
             max_color_attachments: Capabilities::parse_color_attachments(version, &extensions),
+            max_samples: Capabilities::parse_samples(version, &extensions),
+            render_texture_formats: Capabilities::parse_render_texture_formats(
+                version,
+                &extensions,
+            ),
         })
     }
 
+    pub fn is_format_supported(&self, format: RenderTextureFormat, usage: FormatUsage) -> bool {
+        self.render_texture_formats[format as usize][usage as usize]
+    }
+
     pub fn has_compression(&self, compression: TextureCompression) -> bool {
         match compression {
             TextureCompression::ETC2 => {
@@ -272,9 +347,117 @@ impl Capabilities {
         }
     }
 
+    pub fn has_viewport_array(&self) -> bool {
+        self.version >= Version::GL(4, 1) || self.extensions.gl_arb_viewport_array
+    }
+
+    pub fn has_sample_mask(&self) -> bool {
+        self.version >= Version::GL(3, 2)
+            || self.version >= Version::ES(3, 1)
+            || self.extensions.gl_arb_texture_multisample
+    }
+
+    pub fn has_provoking_vertex(&self) -> bool {
+        self.version >= Version::GL(3, 2) || self.extensions.gl_arb_provoking_vertex
+    }
+
+    /// Returns true if the polygons could be rasterized as lines or points, which is not
+    /// supported in OpenGL ES.
+    pub fn has_polygon_mode(&self) -> bool {
+        self.version >= Version::GL(1, 0)
+    }
+
+    pub fn has_sampler_objects(&self) -> bool {
+        self.version >= Version::GL(3, 3)
+            || self.version >= Version::ES(3, 0)
+            || self.extensions.gl_arb_sampler_objects
+    }
+
+    pub fn has_instanced_arrays(&self) -> bool {
+        self.version >= Version::GL(3, 3)
+            || self.version >= Version::ES(3, 0)
+            || self.extensions.gl_arb_instanced_arrays
+    }
+
+    pub fn has_draw_instanced(&self) -> bool {
+        self.version >= Version::GL(3, 1)
+            || self.version >= Version::ES(3, 0)
+            || self.extensions.gl_arb_draw_instanced
+    }
+
+    pub fn has_timer_query(&self) -> bool {
+        self.version >= Version::GL(3, 3) || self.extensions.gl_arb_timer_query
+    }
+
+    pub fn has_unpack_row_length(&self) -> bool {
+        self.version >= Version::GL(1, 0)
+            || self.version >= Version::ES(3, 0)
+            || self.extensions.gl_ext_unpack_subimage
+    }
+
+    pub fn has_framebuffer_srgb(&self) -> bool {
+        self.version >= Version::GL(3, 0)
+            || self.extensions.gl_arb_framebuffer_srgb
+            || self.extensions.gl_ext_framebuffer_srgb
+            || self.extensions.gl_ext_srgb_write_control
+    }
+
+    pub fn has_image_load_store(&self) -> bool {
+        self.version >= Version::GL(4, 2)
+            || self.version >= Version::ES(3, 1)
+            || self.extensions.gl_arb_shader_image_load_store
+    }
+
+    pub fn has_shader_storage_buffer(&self) -> bool {
+        self.version >= Version::GL(4, 3)
+            || self.version >= Version::ES(3, 1)
+            || self.extensions.gl_arb_shader_storage_buffer_object
+    }
+
+    /// Returns true if `glBlitFramebuffer` converts between fixed-point and floating-point
+    /// color formats, which is an error in OpenGL ES.
+    pub fn has_blit_conversion(&self) -> bool {
+        self.version >= Version::GL(3, 0)
+    }
+
+    /// Returns true if the indices could be `IndexFormat::U32`, which is optional in
+    /// OpenGL ES 2.0.
+    pub fn has_element_index_uint(&self) -> bool {
+        self.version >= Version::GL(1, 0)
+            || self.version >= Version::ES(3, 0)
+            || self.extensions.gl_oes_element_index_uint
+    }
+
+    pub fn has_sync(&self) -> bool {
+        self.version >= Version::GL(3, 2)
+            || self.version >= Version::ES(3, 0)
+            || self.extensions.gl_arb_sync
+    }
+
+    /// Returns true if the mipmaps of texture with `dimensions` could be generated with
+    /// `glGenerateMipmap`. The non-power-of-two textures could not have mipmaps in OpenGL
+    /// ES 2.0 without `GL_OES_texture_npot`.
+    pub fn has_generate_mipmap(&self, dimensions: Vector2<u32>) -> bool {
+        let supported = self.version >= Version::GL(3, 0)
+            || self.version >= Version::ES(2, 0)
+            || self.extensions.gl_arb_framebuffer_object
+            || self.extensions.gl_ext_framebuffer_object;
+
+        let pot = dimensions.x.is_power_of_two() && dimensions.y.is_power_of_two();
+        let npot = self.version >= Version::GL(2, 0)
+            || self.version >= Version::ES(3, 0)
+            || self.extensions.gl_oes_texture_npot;
+
+        supported && (pot || npot)
+    }
+
+    pub fn has_layered_attachment(&self) -> bool {
+        self.version >= Version::GL(3, 2) || self.version >= Version::ES(3, 2)
+    }
+
     #[inline]
     unsafe fn parse_str(id: GLenum) -> Result<String> {
-        let s = gl::GetString(gl::RENDERER);
+        let s = gl::GetString(id);
         if s.is_null() {
             bail!("[GL] String of {} is null.", id);
         }
@@ -308,6 +491,20 @@ impl Capabilities {
         }
     }
 
+    #[inline]
+    unsafe fn parse_texture_size() -> u32 {
+        let mut val = 64;
+        gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut val);
+        val as u32
+    }
+
+    #[inline]
+    unsafe fn parse_vertex_attributes() -> u32 {
+        let mut val = 8;
+        gl::GetIntegerv(gl::MAX_VERTEX_ATTRIBS, &mut val);
+        val as u32
+    }
+
     #[inline]
     unsafe fn parse_texture_image_units() -> u8 {
         let mut val = 2;
@@ -326,6 +523,86 @@ impl Capabilities {
         }
     }
 
+    unsafe fn parse_uniform_buffer_alignment(version: Version, exts: &Extensions) -> u32 {
+        if version >= Version::GL(3, 1)
+            || version >= Version::ES(3, 0)
+            || exts.gl_arb_uniform_buffer_object
+        {
+            let mut val = 0;
+            gl::GetIntegerv(gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT, &mut val);
+            (val as u32).max(1)
+        } else {
+            // The maximum alignment that allowed by specification.
+            256
+        }
+    }
+
+    unsafe fn parse_render_texture_formats(version: Version, exts: &Extensions) -> [[bool; 3]; 10] {
+        let mut formats = [[false; 3]; 10];
+
+        if version >= Version::GL(4, 3) || exts.gl_arb_internalformat_query2 {
+            for &v in &RENDER_TEXTURE_FORMATS {
+                let (internal_format, _, _) = v.into();
+                let query = |pname| {
+                    let mut val = 0;
+                    gl::GetInternalformativ(gl::TEXTURE_2D, internal_format, pname, 1, &mut val);
+                    val as GLenum
+                };
+
+                let supported = query(gl::INTERNALFORMAT_SUPPORTED) == gl::TRUE as GLenum;
+                formats[v as usize] = [
+                    supported,
+                    supported && query(gl::FRAMEBUFFER_RENDERABLE) == gl::FULL_SUPPORT,
+                    supported && query(gl::FILTER) == gl::TRUE as GLenum,
+                ];
+            }
+        } else {
+            // Fallbacks to the requirements of specifications.
+            let es = if let Version::ES(_, _) = version {
+                true
+            } else {
+                false
+            };
+
+            for &v in &RENDER_TEXTURE_FORMATS {
+                let supported = !(es && v == RenderTextureFormat::Depth32);
+                formats[v as usize] = [supported, supported, supported && (!es || v.is_color())];
+            }
+
+            // Half float textures are core since GL 3.0 and ES 3.0, but they are not required
+            // to be color-renderable in ES until 3.2.
+            let texture = version >= Version::GL(3, 0) || version >= Version::ES(3, 0);
+            let render = version >= Version::GL(3, 0)
+                || version >= Version::ES(3, 2)
+                || exts.gl_ext_color_buffer_half_float
+                || exts.gl_ext_color_buffer_float;
+            formats[RenderTextureFormat::RGBA16F as usize] = [texture, texture && render, texture];
+
+            // Integer textures are color-renderable since GL 3.0 and ES 3.0, but could never
+            // be filtered.
+            for &v in &[RenderTextureFormat::R32UI, RenderTextureFormat::R32I] {
+                formats[v as usize] = [texture, texture, false];
+            }
+        }
+
+        formats
+    }
+
+    #[inline]
+    unsafe fn parse_samples(version: Version, exts: &Extensions) -> u32 {
+        if version >= Version::GL(3, 0)
+            || version >= Version::ES(3, 0)
+            || exts.gl_arb_framebuffer_object
+            || exts.gl_ext_framebuffer_multisample
+        {
+            let mut val = 0;
+            gl::GetIntegerv(gl::MAX_SAMPLES, &mut val);
+            val as u32
+        } else {
+            0
+        }
+    }
+
     #[inline]
     unsafe fn parse_color_attachments(version: Version, exts: &Extensions) -> u32 {
         if version >= Version::GL(3, 0)
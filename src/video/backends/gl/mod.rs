@@ -1,3 +1,6 @@
+pub mod antialiasing;
 pub mod capabilities;
+pub mod timer_query;
 pub mod types;
+pub mod uniform_ring;
 pub mod visitor;
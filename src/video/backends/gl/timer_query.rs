@@ -0,0 +1,99 @@
+//! Named GPU timers, which measure the elapsed time of the commands between `begin` and
+//! `end` with `GL_TIME_ELAPSED` queries.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use gl;
+use gl::types::*;
+
+use crate::errors::*;
+
+use super::visitor::check;
+
+pub struct GLTimerQueries {
+    active: Option<(String, GLuint)>,
+    pending: VecDeque<(String, GLuint)>,
+    free: Vec<GLuint>,
+    results: Vec<(String, Duration)>,
+}
+
+impl GLTimerQueries {
+    pub fn new() -> Self {
+        GLTimerQueries {
+            active: None,
+            pending: VecDeque::new(),
+            free: Vec::new(),
+            results: Vec::new(),
+        }
+    }
+
+    /// Starts the timer `name`. The timers could NOT be nested, since there is at most one
+    /// active `GL_TIME_ELAPSED` query at a time.
+    pub unsafe fn begin(&mut self, name: &str) -> Result<()> {
+        if let Some((ref v, _)) = self.active {
+            bail!(
+                "GPU timer {:?} is still running, timers could not be nested.",
+                v
+            );
+        }
+
+        let id = match self.free.pop() {
+            Some(id) => id,
+            None => {
+                let mut id = 0;
+                gl::GenQueries(1, &mut id);
+                id
+            }
+        };
+
+        gl::BeginQuery(gl::TIME_ELAPSED, id);
+        check()?;
+
+        self.active = Some((name.to_owned(), id));
+        Ok(())
+    }
+
+    /// Stops the running timer, its result would be available after a few frames.
+    pub unsafe fn end(&mut self) -> Result<()> {
+        let (name, id) = self
+            .active
+            .take()
+            .ok_or_else(|| format_err!("There is no running GPU timer."))?;
+
+        gl::EndQuery(gl::TIME_ELAPSED);
+        check()?;
+
+        self.pending.push_back((name, id));
+        Ok(())
+    }
+
+    /// Collects the results of queries that have been completed by GPU without blocking.
+    /// The queries are completed in the order of submission, so it stops at the first
+    /// one that is still in flight.
+    pub unsafe fn poll(&mut self) -> Result<()> {
+        while let Some(&(_, id)) = self.pending.front() {
+            let mut available = 0;
+            gl::GetQueryObjectiv(id, gl::QUERY_RESULT_AVAILABLE, &mut available);
+            if available == 0 {
+                break;
+            }
+
+            let mut ns = 0;
+            gl::GetQueryObjectui64v(id, gl::QUERY_RESULT, &mut ns);
+            check()?;
+
+            let (name, id) = self.pending.pop_front().unwrap();
+            self.results.push((name, Duration::from_nanos(ns)));
+            self.free.push(id);
+        }
+
+        Ok(())
+    }
+
+    /// Takes the results that have been collected.
+    #[inline]
+    pub fn take(&mut self) -> Vec<(String, Duration)> {
+        ::std::mem::replace(&mut self.results, Vec::new())
+    }
+}
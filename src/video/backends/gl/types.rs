@@ -1,9 +1,92 @@
 use gl;
 use gl::types::*;
 
+use crate::errors::*;
+
 use super::super::super::assets::prelude::*;
+use super::super::frame::MemoryBarrier;
+use super::super::utils::FramebufferStatus;
 use super::capabilities::{Capabilities, TextureCompression, Version};
 
+impl From<MemoryBarrier> for GLbitfield {
+    fn from(barriers: MemoryBarrier) -> Self {
+        if barriers == MemoryBarrier::ALL {
+            return gl::ALL_BARRIER_BITS;
+        }
+
+        let bits = [
+            (
+                MemoryBarrier::VERTEX_ATTRIB_ARRAY,
+                gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT,
+            ),
+            (MemoryBarrier::ELEMENT_ARRAY, gl::ELEMENT_ARRAY_BARRIER_BIT),
+            (MemoryBarrier::UNIFORM, gl::UNIFORM_BARRIER_BIT),
+            (MemoryBarrier::TEXTURE_FETCH, gl::TEXTURE_FETCH_BARRIER_BIT),
+            (
+                MemoryBarrier::SHADER_IMAGE_ACCESS,
+                gl::SHADER_IMAGE_ACCESS_BARRIER_BIT,
+            ),
+            (MemoryBarrier::COMMAND, gl::COMMAND_BARRIER_BIT),
+            (MemoryBarrier::PIXEL_BUFFER, gl::PIXEL_BUFFER_BARRIER_BIT),
+            (
+                MemoryBarrier::TEXTURE_UPDATE,
+                gl::TEXTURE_UPDATE_BARRIER_BIT,
+            ),
+            (MemoryBarrier::BUFFER_UPDATE, gl::BUFFER_UPDATE_BARRIER_BIT),
+            (MemoryBarrier::FRAMEBUFFER, gl::FRAMEBUFFER_BARRIER_BIT),
+            (
+                MemoryBarrier::SHADER_STORAGE,
+                gl::SHADER_STORAGE_BARRIER_BIT,
+            ),
+        ];
+
+        bits.iter()
+            .filter(|v| barriers.contains(v.0))
+            .fold(0, |acc, v| acc | v.1)
+    }
+}
+
+impl From<ImageAccess> for GLenum {
+    fn from(access: ImageAccess) -> Self {
+        match access {
+            ImageAccess::Read => gl::READ_ONLY,
+            ImageAccess::Write => gl::WRITE_ONLY,
+            ImageAccess::ReadWrite => gl::READ_WRITE,
+        }
+    }
+}
+
+/// Gets the internal format of image units.
+pub fn image_format(format: RenderTextureFormat) -> Result<GLenum> {
+    match format {
+        RenderTextureFormat::RGBA8 => Ok(gl::RGBA8),
+        RenderTextureFormat::RGBA16F => Ok(gl::RGBA16F),
+        RenderTextureFormat::R32UI => Ok(gl::R32UI),
+        RenderTextureFormat::R32I => Ok(gl::R32I),
+        _ => bail!("{:?} could not be used as image format.", format),
+    }
+}
+
+/// `GL_FRAMEBUFFER_INCOMPLETE_DIMENSIONS`, which is only defined in OpenGL ES 2.0.
+const FRAMEBUFFER_INCOMPLETE_DIMENSIONS: GLenum = 0x8CD9;
+
+pub fn framebuffer_status(status: GLenum) -> FramebufferStatus {
+    match status {
+        gl::FRAMEBUFFER_UNDEFINED => FramebufferStatus::Undefined,
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => FramebufferStatus::IncompleteAttachment,
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => {
+            FramebufferStatus::IncompleteMissingAttachment
+        }
+        FRAMEBUFFER_INCOMPLETE_DIMENSIONS => FramebufferStatus::IncompleteDimensions,
+        gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => FramebufferStatus::IncompleteDrawBuffer,
+        gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => FramebufferStatus::IncompleteReadBuffer,
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => FramebufferStatus::IncompleteMultisample,
+        gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => FramebufferStatus::IncompleteLayerTargets,
+        gl::FRAMEBUFFER_UNSUPPORTED => FramebufferStatus::Unsupported,
+        v => FramebufferStatus::Unknown(v),
+    }
+}
+
 impl From<MeshHint> for GLenum {
     fn from(hint: MeshHint) -> Self {
         match hint {
@@ -52,6 +135,52 @@ impl From<BlendFactor> for GLenum {
             BlendFactor::OneMinusValue(BlendValue::SourceAlpha) => gl::ONE_MINUS_SRC_ALPHA,
             BlendFactor::OneMinusValue(BlendValue::DestinationColor) => gl::ONE_MINUS_DST_COLOR,
             BlendFactor::OneMinusValue(BlendValue::DestinationAlpha) => gl::ONE_MINUS_DST_ALPHA,
+            BlendFactor::Value(BlendValue::ConstantColor) => gl::CONSTANT_COLOR,
+            BlendFactor::Value(BlendValue::ConstantAlpha) => gl::CONSTANT_ALPHA,
+            BlendFactor::OneMinusValue(BlendValue::ConstantColor) => gl::ONE_MINUS_CONSTANT_COLOR,
+            BlendFactor::OneMinusValue(BlendValue::ConstantAlpha) => gl::ONE_MINUS_CONSTANT_ALPHA,
+        }
+    }
+}
+
+impl From<LogicOp> for GLenum {
+    fn from(op: LogicOp) -> Self {
+        match op {
+            LogicOp::Clear => gl::CLEAR,
+            LogicOp::Set => gl::SET,
+            LogicOp::Copy => gl::COPY,
+            LogicOp::CopyInverted => gl::COPY_INVERTED,
+            LogicOp::Noop => gl::NOOP,
+            LogicOp::Invert => gl::INVERT,
+            LogicOp::And => gl::AND,
+            LogicOp::Nand => gl::NAND,
+            LogicOp::Or => gl::OR,
+            LogicOp::Nor => gl::NOR,
+            LogicOp::Xor => gl::XOR,
+            LogicOp::Equiv => gl::EQUIV,
+            LogicOp::AndReverse => gl::AND_REVERSE,
+            LogicOp::AndInverted => gl::AND_INVERTED,
+            LogicOp::OrReverse => gl::OR_REVERSE,
+            LogicOp::OrInverted => gl::OR_INVERTED,
+        }
+    }
+}
+
+impl From<PolygonMode> for GLenum {
+    fn from(mode: PolygonMode) -> Self {
+        match mode {
+            PolygonMode::Fill => gl::FILL,
+            PolygonMode::Line => gl::LINE,
+            PolygonMode::Point => gl::POINT,
+        }
+    }
+}
+
+impl From<ProvokingVertex> for GLenum {
+    fn from(v: ProvokingVertex) -> Self {
+        match v {
+            ProvokingVertex::First => gl::FIRST_VERTEX_CONVENTION,
+            ProvokingVertex::Last => gl::LAST_VERTEX_CONVENTION,
         }
     }
 }
@@ -95,6 +224,13 @@ pub fn texture_format(format: TextureFormat, caps: &Capabilities) -> (GLenum, GL
         Version::ES(major, _) => major >= 3,
     };
 
+    if !sized {
+        info!(
+            "[GL] Falls back to unsized internal format for {:?} on {:?}.",
+            format, caps.version
+        );
+    }
+
     // FIXME
     // gl::COMPRESSED_RGB_S3TC_DXT1_EXT = 0x83F0
     // gl::COMPRESSED_RGBA_S3TC_DXT5_EXT = 0x83F3
@@ -189,16 +325,33 @@ impl From<TextureWrap> for GLenum {
             TextureWrap::Mirror => gl::MIRRORED_REPEAT,
             TextureWrap::Clamp => gl::CLAMP_TO_EDGE,
             TextureWrap::MirrorClamp => gl::MIRROR_CLAMP_TO_EDGE,
+            TextureWrap::Border => gl::CLAMP_TO_BORDER,
         }
     }
 }
 
+/// Gets the filtering function of `filter`, which also picks the mipmap levels with `mip`
+/// when minifying.
+pub fn filter(filter: TextureFilter, mip: Option<TextureFilter>) -> GLenum {
+    match (filter, mip) {
+        (TextureFilter::Nearest, None) => gl::NEAREST,
+        (TextureFilter::Linear, None) => gl::LINEAR,
+        (TextureFilter::Nearest, Some(TextureFilter::Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+        (TextureFilter::Nearest, Some(TextureFilter::Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+        (TextureFilter::Linear, Some(TextureFilter::Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+        (TextureFilter::Linear, Some(TextureFilter::Linear)) => gl::LINEAR_MIPMAP_LINEAR,
+    }
+}
+
 impl From<RenderTextureFormat> for (GLenum, GLenum, GLenum) {
     fn from(format: RenderTextureFormat) -> Self {
         match format {
             RenderTextureFormat::RGB8 => (gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE),
             RenderTextureFormat::RGBA4 => (gl::RGBA4, gl::RGBA, gl::UNSIGNED_SHORT_4_4_4_4),
             RenderTextureFormat::RGBA8 => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            RenderTextureFormat::RGBA16F => (gl::RGBA16F, gl::RGBA, gl::HALF_FLOAT),
+            RenderTextureFormat::R32UI => (gl::R32UI, gl::RED_INTEGER, gl::UNSIGNED_INT),
+            RenderTextureFormat::R32I => (gl::R32I, gl::RED_INTEGER, gl::INT),
             RenderTextureFormat::Depth16 => (gl::DEPTH_COMPONENT16, gl::DEPTH_COMPONENT, gl::FLOAT),
             RenderTextureFormat::Depth24 => (gl::DEPTH_COMPONENT24, gl::DEPTH_COMPONENT, gl::FLOAT),
             RenderTextureFormat::Depth32 => (gl::DEPTH_COMPONENT32, gl::DEPTH_COMPONENT, gl::FLOAT),
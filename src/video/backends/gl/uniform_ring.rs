@@ -0,0 +1,90 @@
+//! A ring-buffered uniform buffer, which holds the per-object uniform blocks of draws in
+//! sub-ranges of one buffer object.
+
+use gl;
+use gl::types::*;
+
+use crate::errors::*;
+
+use super::visitor::check;
+
+/// The initial capacity in bytes of the ring.
+const MIN_CAPACITY: usize = 16 * 1024;
+
+pub struct GLUniformRing {
+    id: GLuint,
+    capacity: usize,
+    cursor: usize,
+    used: usize,
+}
+
+impl GLUniformRing {
+    pub fn new() -> Self {
+        GLUniformRing {
+            id: 0,
+            capacity: 0,
+            cursor: 0,
+            used: 0,
+        }
+    }
+
+    /// Writes `bytes` at the next offset that aligned with `alignment`, and reserves `size`
+    /// bytes for it. Returns the buffer object and the offset of this write.
+    ///
+    /// The storage is orphaned instead of being overwritten when the ring wraps around, so
+    /// it never stalls on the draws in flight. And it grows to fit the usage of a whole
+    /// frame, which makes the orphaning happen at most once per frame eventually.
+    pub unsafe fn push(
+        &mut self,
+        bytes: &[u8],
+        size: usize,
+        alignment: usize,
+    ) -> Result<(GLuint, usize)> {
+        let size = size.max(bytes.len());
+        let mut offset = align(self.cursor, alignment);
+
+        if offset + size > self.capacity {
+            if self.id == 0 {
+                gl::GenBuffers(1, &mut self.id);
+            }
+
+            let capacity = (self.used + size).next_power_of_two().max(MIN_CAPACITY);
+            self.capacity = self.capacity.max(capacity);
+
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.id);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                self.capacity as GLsizeiptr,
+                ::std::ptr::null(),
+                gl::STREAM_DRAW,
+            );
+            offset = 0;
+        } else {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.id);
+        }
+
+        if !bytes.is_empty() {
+            let ptr = bytes.as_ptr() as *const ::std::os::raw::c_void;
+            let len = bytes.len() as GLsizeiptr;
+            gl::BufferSubData(gl::UNIFORM_BUFFER, offset as GLintptr, len, ptr);
+        }
+
+        check()?;
+
+        self.used += offset + size - self.cursor.min(offset);
+        self.cursor = offset + size;
+        Ok((self.id, offset))
+    }
+
+    /// Resets the statistics of usage at the begining of frame.
+    #[inline]
+    pub fn advance(&mut self) {
+        self.used = 0;
+    }
+}
+
+#[inline]
+fn align(offset: usize, alignment: usize) -> usize {
+    let alignment = alignment.max(1);
+    (offset + alignment - 1) / alignment * alignment
+}
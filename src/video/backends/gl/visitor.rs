@@ -1,4 +1,7 @@
 use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use gl;
 use gl::types::*;
@@ -10,10 +13,20 @@ use crate::utils::hash::{FastHashMap, FastHashSet};
 use crate::utils::hash_value::HashValue;
 
 use super::super::super::assets::prelude::*;
-use super::super::utils::DataVec;
-use super::super::{UniformVar, Visitor};
+use super::super::super::{MAX_UNIFORM_TEXTURE_SLOTS, PER_OBJECT_BLOCK, PER_OBJECT_BLOCK_BINDING};
+use super::super::frame::{DrawCommand, FrameStats, MemoryBarrier};
+use super::super::utils::{
+    incomplete_framebuffer, mesh_elements, texture_units, validate_attachments, ClearBuffer,
+    ClearCache, ClearStates, DataVec, SurfaceAttachment,
+};
+use super::super::{DeviceLimits, UniformVar, Visitor};
+use super::antialiasing::{GLAntialiasing, GLOutputPass};
 use super::capabilities::{Capabilities, Version};
+use super::timer_query::GLTimerQueries;
 use super::types;
+use super::uniform_ring::GLUniformRing;
+
+const BLIT_FS: &str = include_str!("../shaders/blit.fs");
 
 #[derive(Debug, Clone)]
 struct GLSurfaceData {
@@ -21,6 +34,7 @@ struct GLSurfaceData {
     id: Option<GLuint>,
     dimensions: Option<Vector2<u32>>,
     params: SurfaceParams,
+    color_format: Option<RenderTextureFormat>,
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +44,10 @@ struct GLShaderData {
     params: ShaderParams,
     uniforms: RefCell<FastHashMap<HashValue<str>, GLint>>,
     attributes: RefCell<FastHashMap<HashValue<str>, GLint>>,
+    /// The data size of the per-object uniform block, if there is one.
+    per_object_block: Option<usize>,
+    /// The indices and data sizes of the uniform blocks that binded with uniform buffers.
+    uniform_blocks: FastHashMap<HashValue<str>, (GLuint, usize)>,
 }
 
 impl GLShaderData {
@@ -53,6 +71,25 @@ impl GLShaderData {
         }
     }
 
+    unsafe fn storage_block_index(&self, name: &str) -> Result<GLint> {
+        let hash = name.into();
+        let mut uniforms = self.uniforms.borrow_mut();
+        match uniforms.get(&hash).cloned() {
+            Some(index) => Ok(index),
+            None => {
+                let c_name = ::std::ffi::CString::new(name.as_bytes()).unwrap();
+                let index =
+                    gl::GetProgramResourceIndex(self.id, gl::SHADER_STORAGE_BLOCK, c_name.as_ptr());
+                check()?;
+
+                // `GL_INVALID_INDEX` is reinterpreted as -1, just like the locations.
+                let index = index as GLint;
+                uniforms.insert(hash, index);
+                Ok(index)
+            }
+        }
+    }
+
     unsafe fn attribute_location(&self, name: &str) -> Result<GLint> {
         let hash = name.into();
         let mut attributes = self.attributes.borrow_mut();
@@ -78,12 +115,34 @@ struct GLMeshData {
     params: MeshParams,
 }
 
+#[derive(Debug, Clone)]
+struct GLInstanceBufferData {
+    handle: InstanceBufferHandle,
+    vbo: GLuint,
+    params: InstanceBufferParams,
+}
+
+type VAOKey = (ShaderHandle, MeshHandle, Option<InstanceBufferHandle>);
+
+#[derive(Debug, Clone)]
+struct GLStorageBufferData {
+    id: GLuint,
+    params: ShaderStorageBufferParams,
+}
+
+#[derive(Debug, Clone)]
+struct GLUniformBufferData {
+    id: GLuint,
+    params: UniformBufferParams,
+}
+
 #[derive(Debug, Clone)]
 struct GLTextureData {
     handle: TextureHandle,
     id: GLuint,
     params: TextureParams,
     allocated: RefCell<bool>,
+    owned: bool,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -97,19 +156,72 @@ struct GLRenderTextureData {
 enum Sampler {
     RenderTexture(RenderTextureHandle),
     Texture(TextureHandle),
+    Cube(TextureHandle),
 }
 
 struct GLMutableState {
     render_state: RenderState,
     scissor: SurfaceScissor,
     view: SurfaceViewport,
+    view_array: bool,
     cleared_surfaces: FastHashSet<SurfaceHandle>,
-    vaos: FastHashMap<(ShaderHandle, MeshHandle), GLuint>,
+    clears: ClearCache,
+    vaos: FastHashMap<VAOKey, GLuint>,
     binded_surface: Option<SurfaceHandle>,
+    binded_framebuffer: Option<GLuint>,
     binded_shader: Option<ShaderHandle>,
-    binded_vao: Option<(ShaderHandle, MeshHandle)>,
+    binded_vao: Option<VAOKey>,
     binded_texture_index: usize,
     binded_textures: SmallVec<[Option<Sampler>; 8]>,
+    unpack_alignment: GLint,
+    framebuffer_srgb: bool,
+    cull_override: Option<CullFace>,
+    sampler: GLuint,
+}
+
+impl GLMutableState {
+    fn new() -> Self {
+        GLMutableState {
+            render_state: RenderState::default(),
+            scissor: SurfaceScissor::Disable,
+            view: SurfaceViewport {
+                position: Vector2::new(0, 0),
+                size: Vector2::new(0, 0),
+            },
+            view_array: false,
+            cleared_surfaces: FastHashSet::default(),
+            clears: ClearCache::default(),
+            vaos: FastHashMap::default(),
+            binded_surface: None,
+            binded_framebuffer: None,
+            binded_shader: None,
+            binded_vao: None,
+            binded_texture_index: 0,
+            binded_textures: SmallVec::new(),
+            unpack_alignment: 0,
+            framebuffer_srgb: false,
+            cull_override: None,
+            sampler: 0,
+        }
+    }
+
+    /// Resets the cached states to the ones issued by `GLVisitor::apply_default_states`,
+    /// and forgets the binded objects, so they would be binded again by next use.
+    fn invalidate(&mut self) {
+        self.render_state = RenderState::default();
+        self.scissor = SurfaceScissor::Disable;
+        self.view_array = false;
+        self.clears.reset();
+        self.binded_surface = None;
+        self.binded_framebuffer = None;
+        self.binded_shader = None;
+        self.binded_vao = None;
+        self.binded_texture_index = 0;
+        self.binded_textures.clear();
+        self.unpack_alignment = 1;
+        self.framebuffer_srgb = false;
+        self.sampler = 0;
+    }
 }
 
 pub struct GLVisitor {
@@ -120,6 +232,21 @@ pub struct GLVisitor {
     meshes: DataVec<GLMeshData>,
     textures: DataVec<GLTextureData>,
     render_textures: DataVec<GLRenderTextureData>,
+    instance_buffers: DataVec<GLInstanceBufferData>,
+    storage_buffers: DataVec<GLStorageBufferData>,
+    uniform_buffers: DataVec<GLUniformBufferData>,
+    readbacks: Vec<(VideoHandle, Vec<u8>)>,
+    per_object_ring: GLUniformRing,
+    timers: GLTimerQueries,
+    samplers: FastHashMap<SurfaceSampler, GLuint>,
+    antialiasing: Option<GLAntialiasing>,
+    blit_pass: Option<GLOutputPass>,
+    output: OutputAdjustment,
+    // The fences of frames that have been flushed but might not be completed by GPU.
+    fences: VecDeque<GLsync>,
+    max_frame_latency: u32,
+    fallback_texture: Option<TextureHandle>,
+    stats: FrameStats,
 }
 
 impl GLVisitor {
@@ -127,31 +254,30 @@ impl GLVisitor {
         let capabilities = Capabilities::parse()?;
         info!("GLVisitor {:#?}", capabilities);
         check_capabilities(&capabilities)?;
-
-        let state = GLMutableState {
-            render_state: RenderState::default(),
-            scissor: SurfaceScissor::Disable,
-            view: SurfaceViewport {
-                position: Vector2::new(0, 0),
-                size: Vector2::new(0, 0),
-            },
-            cleared_surfaces: FastHashSet::default(),
-            vaos: FastHashMap::default(),
-            binded_surface: None,
-            binded_shader: None,
-            binded_vao: None,
-            binded_texture_index: 0,
-            binded_textures: SmallVec::new(),
-        };
+        NO_ERROR.store(capabilities.no_error, Ordering::Relaxed);
 
         let mut visitor = GLVisitor {
-            state,
+            state: GLMutableState::new(),
             capabilities,
             surfaces: DataVec::new(),
             shaders: DataVec::new(),
             meshes: DataVec::new(),
             textures: DataVec::new(),
             render_textures: DataVec::new(),
+            instance_buffers: DataVec::new(),
+            storage_buffers: DataVec::new(),
+            uniform_buffers: DataVec::new(),
+            readbacks: Vec::new(),
+            per_object_ring: GLUniformRing::new(),
+            timers: GLTimerQueries::new(),
+            samplers: FastHashMap::default(),
+            antialiasing: None,
+            blit_pass: None,
+            output: OutputAdjustment::default(),
+            fences: VecDeque::new(),
+            max_frame_latency: 0,
+            fallback_texture: None,
+            stats: FrameStats::default(),
         };
 
         Self::reset_render_state(&mut visitor.state)?;
@@ -162,7 +288,16 @@ impl GLVisitor {
 impl Visitor for GLVisitor {
     unsafe fn advance(&mut self) -> Result<()> {
         self.state.cleared_surfaces.clear();
+        self.state.clears.reset();
         self.state.binded_surface = None;
+        self.state.binded_framebuffer = None;
+        self.per_object_ring.advance();
+        self.stats = FrameStats::default();
+
+        if self.capabilities.has_timer_query() {
+            self.timers.poll()?;
+        }
+
         Ok(())
     }
 
@@ -171,104 +306,101 @@ impl Visitor for GLVisitor {
         handle: SurfaceHandle,
         params: SurfaceParams,
     ) -> Result<()> {
+        if params.viewports[0].is_some() && !self.capabilities.has_viewport_array() {
+            bail!("The GL Context does not support viewport arrays.");
+        }
+
+        if params.layered && !self.capabilities.has_layered_attachment() {
+            bail!("The GL Context does not support layered attachments.");
+        }
+
+        if params.srgb_write && !self.capabilities.has_framebuffer_srgb() {
+            bail!("The GL Context does not support sRGB write control.");
+        }
+
+        if let Some(v) = params.sampler_override {
+            if !self.capabilities.has_sampler_objects() {
+                bail!("The GL Context does not support sampler objects.");
+            }
+
+            if !self.samplers.contains_key(&v) {
+                let id = Self::create_sampler(v)?;
+                self.samplers.insert(v, id);
+            }
+        }
+
         let mut data = GLSurfaceData {
             handle,
             params,
             id: None,
             dimensions: None,
+            color_format: None,
         };
 
         if params.colors[0].is_some() || params.depth_stencil.is_some() {
-            let mut id = 0;
-            gl::GenFramebuffers(1, &mut id);
-            assert!(id != 0);
-
-            gl::BindFramebuffer(gl::FRAMEBUFFER, id);
-            self.state.binded_surface = None;
-
-            let mut dimensions = None;
-            for (i, attachment) in params.colors.iter().enumerate() {
-                if let Some(v) = *attachment {
+            let mut attachments = Vec::new();
+            let depth_stencil = (SurfaceAttachment::DepthStencil, params.depth_stencil);
+            let points = params
+                .colors
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (SurfaceAttachment::Color(i), *v))
+                .chain(Some(depth_stencil));
+
+            for (point, v) in points {
+                if let Some(v) = v {
                     let rt = self
                         .render_textures
                         .get(v)
                         .ok_or_else(|| format_err!("RenderTexture handle {:?} is invalid.", v))?;
 
-                    if !rt.params.format.is_color() {
-                        bail!(
-                            "Incompitable(mismatch format) attachments of SurfaceObject {:?}",
-                            id
-                        );
-                    }
-
-                    if dimensions.is_some() && dimensions != Some(rt.params.dimensions) {
-                        bail!(
-                            "Incompitable(mismatch dimensons) attachments of SurfaceObject {:?}",
-                            id
-                        );
-                    }
-
-                    dimensions = Some(rt.params.dimensions);
-                    self.update_framebuffer_render_texture(rt.id, rt.params, i)?;
+                    attachments.push((point, v, rt.params));
                 }
             }
 
-            if let Some(v) = params.depth_stencil {
-                let rt = self
-                    .render_textures
-                    .get(v)
-                    .ok_or_else(|| format_err!("RenderTexture handle {:?} is invalid.", v))?;
-
-                if rt.params.format.is_color() {
-                    bail!(
-                        "Incompitable(mismatch format) attachments of SurfaceObject {:?}",
-                        id
-                    );
-                }
-
-                if dimensions.is_some() && dimensions != Some(rt.params.dimensions) {
-                    bail!(
-                        "Incompitable(mismatch dimensions) attachments of SurfaceObject {:?}",
-                        id
-                    );
-                }
-
-                dimensions = Some(rt.params.dimensions);
-                self.update_framebuffer_render_texture(rt.id, rt.params, 0)?;
+            if let Some(status) = validate_attachments(&attachments) {
+                return Err(incomplete_framebuffer(handle, status, &attachments));
             }
 
-            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
-            if status != gl::FRAMEBUFFER_COMPLETE {
-                gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
-                self.state.binded_surface = None;
+            let mut id = 0;
+            gl::GenFramebuffers(1, &mut id);
+            assert!(id != 0);
 
-                match status {
-                    gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => {
-                        bail!("[GL] Surface is incomplete. Not all framebuffer attachment points \
-                        are framebuffer attachment complete. This means that at least one attachment point with a \
-                        renderbuffer or texture attached has its attached object no longer in existence or has an \
-                        attached image with a width or height of zero, or the color attachment point has a non-color-renderable \
-                        image attached, or the depth attachment point has a non-depth-renderable image attached, or \
-                        the stencil attachment point has a non-stencil-renderable image attached. ");
-                    }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+            self.state.binded_surface = None;
+            self.state.binded_framebuffer = None;
 
-                    gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => {
-                        bail!("[GL] Surface is incomplete. No images are attached to the framebuffer.");
-                    }
+            let attached = attachments.iter().try_for_each(|&(point, v, rt_params)| {
+                let index = match point {
+                    SurfaceAttachment::Color(i) => i,
+                    SurfaceAttachment::DepthStencil => 0,
+                };
 
-                    gl::FRAMEBUFFER_UNSUPPORTED => {
-                        bail!("[GL] Surface is incomplete. The combination of internal formats \
-                        of the attached images violates an implementation-dependent set of restrictions. ");
-                    }
+                let rt = self.render_textures.get(v).unwrap();
+                self.update_framebuffer_render_texture(
+                    gl::FRAMEBUFFER,
+                    rt.id,
+                    rt_params,
+                    index,
+                    params.layered,
+                )
+            });
 
-                    _ => {
-                        bail!("[GL] Surface is incomplete.");
-                    }
-                }
+            let status = attached.map(|_| gl::CheckFramebufferStatus(gl::FRAMEBUFFER));
+            if status.as_ref().ok() != Some(&gl::FRAMEBUFFER_COMPLETE) {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                gl::DeleteFramebuffers(1, &id);
+
+                let status = types::framebuffer_status(status?);
+                return Err(incomplete_framebuffer(handle, status, &attachments));
             }
 
+            data.color_format = attachments
+                .iter()
+                .find(|v| v.0 == SurfaceAttachment::Color(0))
+                .map(|v| v.2.format);
             data.id = Some(id);
-            data.dimensions = dimensions;
+            data.dimensions = attachments.first().map(|v| v.2.dimensions);
         };
 
         self.surfaces.create(handle, data);
@@ -284,6 +416,7 @@ impl Visitor for GLVisitor {
 
         if self.state.binded_surface == Some(handle) {
             self.state.binded_surface = None;
+            self.state.binded_framebuffer = None;
         }
 
         if let Some(id) = surface.id {
@@ -301,9 +434,39 @@ impl Visitor for GLVisitor {
         vs: &str,
         fs: &str,
     ) -> Result<()> {
+        if let Version::ES(_, _) = self.capabilities.version {
+            if params.state.logic_op.is_some() {
+                bail!("Logical operation is not supported in OpenGL ES.");
+            }
+        }
+
+        if params.state.sample_mask != !0 && !self.capabilities.has_sample_mask() {
+            bail!(
+                "Sample mask is not supported in {:?}.",
+                self.capabilities.version
+            );
+        }
+
+        if params.state.provoking_vertex != ProvokingVertex::Last
+            && !self.capabilities.has_provoking_vertex()
+        {
+            bail!(
+                "Provoking vertex is not configurable in {:?}.",
+                self.capabilities.version
+            );
+        }
+
+        if params.state.polygon_mode != PolygonMode::Fill && !self.capabilities.has_polygon_mode() {
+            bail!(
+                "Polygon mode {:?} is not supported in {:?}.",
+                params.state.polygon_mode,
+                self.capabilities.version
+            );
+        }
+
         let vs = Self::compile(gl::VERTEX_SHADER, vs)?;
         let fs = Self::compile(gl::FRAGMENT_SHADER, fs)?;
-        let id = Self::link(&[vs, fs])?;
+        let id = Self::link_with_locations(&[vs, fs], &params.attribute_locations)?;
 
         gl::DetachShader(id, vs);
         gl::DeleteShader(vs);
@@ -311,12 +474,30 @@ impl Visitor for GLVisitor {
         gl::DeleteShader(fs);
         check()?;
 
+        let per_object_block = match Self::per_object_block(id) {
+            Ok(v) => v,
+            Err(err) => {
+                gl::DeleteProgram(id);
+                return Err(err);
+            }
+        };
+
+        let uniform_blocks = match Self::uniform_blocks(id, &params) {
+            Ok(v) => v,
+            Err(err) => {
+                gl::DeleteProgram(id);
+                return Err(err);
+            }
+        };
+
         let shader = GLShaderData {
             handle,
             id,
             params,
             uniforms: RefCell::new(FastHashMap::default()),
             attributes: RefCell::new(FastHashMap::default()),
+            per_object_block,
+            uniform_blocks,
         };
 
         for (name, _, _) in shader.params.attributes.iter() {
@@ -328,8 +509,22 @@ impl Visitor for GLVisitor {
             }
         }
 
-        for &(ref name, _) in shader.params.uniforms.iter() {
-            let location = shader.uniform_location(name)?;
+        for &(ref name, tp) in shader.params.uniforms.iter() {
+            if tp == UniformVariableType::UniformBuffer {
+                continue;
+            }
+
+            let location = if tp == UniformVariableType::StorageBuffer {
+                if !self.capabilities.has_shader_storage_buffer() {
+                    gl::DeleteProgram(id);
+                    bail!("The GL Context does not support shader storage buffer objects.");
+                }
+
+                shader.storage_block_index(name)?
+            } else {
+                shader.uniform_location(name)?
+            };
+
             if location == -1 {
                 gl::DeleteProgram(id);
                 bail!("Uniform({:?}) is undefined in shader sources.", name);
@@ -347,7 +542,7 @@ impl Visitor for GLVisitor {
             .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
 
         // Removes deprecated `VertexArrayObject`s.
-        self.state.vaos.retain(|&(h, _), vao| {
+        self.state.vaos.retain(|&(h, _, _), vao| {
             if h == shader.handle {
                 gl::DeleteVertexArrays(1, vao as *mut u32);
                 false
@@ -372,68 +567,111 @@ impl Visitor for GLVisitor {
     ) -> Result<()> {
         // Maybe we should implements some software decoder for common texture compression format.
         if !params.format.is_support(&self.capabilities) {
+            warn!(
+                "[GL] Texture format {:?} of {:?} is not supported by {}.",
+                params.format, handle, self.capabilities.renderer
+            );
+
             bail!(
                 "The GL Context does not support the texture format {:?}.",
                 params.format
             );
         }
 
+        let params = self.resolve_mipmap(handle, params);
+
         let mut id = 0;
         gl::GenTextures(1, &mut id);
         assert!(id != 0);
 
-        let (internal_format, format, pixel_type) =
-            types::texture_format(params.format, &self.capabilities);
-        let compressed = params.format.compressed();
         let mut allocated = false;
-
-        if let Some(mut data) = data {
+        if let Some(data) = data {
             let len = data.bytes.len();
             if len > 0 {
                 Self::bind_texture(&mut self.state, Some(Sampler::Texture(handle)), 0, id)?;
-                Self::bind_texture_params(params.wrap, params.filter, len as u32)?;
+                let levels = params.mip_levels(len as u32);
+                let wrap = (params.wrap_s, params.wrap_t);
+                let filter = (params.min_filter, params.mag_filter, params.mip_filter);
+                Self::bind_texture_params(gl::TEXTURE_2D, wrap, filter, levels)?;
+                self.upload_texture(gl::TEXTURE_2D, params, &data)?;
+                if params.mipmap {
+                    gl::GenerateMipmap(gl::TEXTURE_2D);
+                }
 
-                let mut dims = (
-                    params.dimensions.x as GLsizei,
-                    params.dimensions.y as GLsizei,
-                );
+                allocated = true;
+            }
+        }
 
-                if compressed {
-                    for (i, v) in data.bytes.drain(..).enumerate() {
-                        gl::CompressedTexImage2D(
-                            gl::TEXTURE_2D,
-                            i as GLint,
-                            internal_format,
-                            dims.0,
-                            dims.1,
-                            0,
-                            v.len() as GLint,
-                            &v[0] as *const u8 as *const ::std::os::raw::c_void,
-                        );
+        check()?;
 
-                        dims.0 = (dims.0 / 2).max(1);
-                        dims.1 = (dims.1 / 2).max(1);
-                    }
-                } else {
-                    for (i, v) in data.bytes.drain(..).enumerate() {
-                        gl::TexImage2D(
-                            gl::TEXTURE_2D,
-                            i as GLint,
-                            internal_format as GLint,
-                            dims.0,
-                            dims.1,
-                            0,
-                            format,
-                            pixel_type,
-                            &v[0] as *const u8 as *const ::std::os::raw::c_void,
-                        );
+        self.textures.create(
+            handle,
+            GLTextureData {
+                handle,
+                id,
+                params,
+                allocated: RefCell::new(allocated),
+                owned: true,
+            },
+        );
 
-                        dims.0 = (dims.0 / 2).max(1);
-                        dims.1 = (dims.1 / 2).max(1);
-                    }
-                }
+        Ok(())
+    }
 
-                allocated = true;
+    unsafe fn create_cube_texture(
+        &mut self,
+        handle: TextureHandle,
+        params: TextureParams,
+        data: Option<CubeTextureData>,
+    ) -> Result<()> {
+        if !params.format.is_support(&self.capabilities) {
+            bail!(
+                "The GL Context does not support the texture format {:?}.",
+                params.format
+            );
+        }
+
+        let params = self.resolve_mipmap(handle, params);
+
+        let mut id = 0;
+        gl::GenTextures(1, &mut id);
+        assert!(id != 0);
+
+        Self::bind_texture(&mut self.state, Some(Sampler::Cube(handle)), 0, id)?;
+
+        let len = data.as_ref().map(|v| v.faces[0].bytes.len()).unwrap_or(0);
+        let levels = params.mip_levels(len as u32);
+        let wrap = (params.wrap_s, params.wrap_t);
+        let filter = (params.min_filter, params.mag_filter, params.mip_filter);
+        Self::bind_texture_params(gl::TEXTURE_CUBE_MAP, wrap, filter, levels)?;
+
+        // The storage of faces are always allocated, since the cube maps could not be
+        // updated partially.
+        if let Some(data) = data.filter(|_| len > 0) {
+            for (i, face) in data.faces.iter().enumerate() {
+                let target = gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as GLenum;
+                self.upload_texture(target, params, face)?;
+            }
+
+            if params.mipmap {
+                gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+            }
+        } else {
+            let (internal_format, format, pixel_type) =
+                types::texture_format(params.format, &self.capabilities);
+
+            for i in 0..6 {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + i,
+                    0,
+                    internal_format as GLint,
+                    params.dimensions.x as GLsizei,
+                    params.dimensions.y as GLsizei,
+                    0,
+                    format,
+                    pixel_type,
+                    ::std::ptr::null(),
+                );
             }
         }
 
@@ -445,7 +683,32 @@ impl Visitor for GLVisitor {
                 handle,
                 id,
                 params,
-                allocated: RefCell::new(allocated),
+                allocated: RefCell::new(true),
+                owned: true,
+            },
+        );
+
+        Ok(())
+    }
+
+    unsafe fn import_texture(
+        &mut self,
+        handle: TextureHandle,
+        params: TextureParams,
+        id: u32,
+    ) -> Result<()> {
+        if gl::IsTexture(id) == gl::FALSE {
+            bail!("{} is not a name of texture object.", id);
+        }
+
+        self.textures.create(
+            handle,
+            GLTextureData {
+                handle,
+                id,
+                params,
+                allocated: RefCell::new(true),
+                owned: false,
             },
         );
 
@@ -456,6 +719,7 @@ impl Visitor for GLVisitor {
         &mut self,
         handle: TextureHandle,
         area: Aabb2<u32>,
+        row_length: u32,
         data: &[u8],
     ) -> Result<()> {
         let texture = self
@@ -471,13 +735,21 @@ impl Visitor for GLVisitor {
             bail!("Trying to update compressed texture.");
         }
 
-        if data.len() > area.volume() as usize
-            || area.min.x >= texture.params.dimensions.x
-            || area.min.y >= texture.params.dimensions.y
-        {
+        if texture.params.kind == TextureKind::Cube {
+            bail!("Trying to update cube texture.");
+        }
+
+        if area.min.x >= texture.params.dimensions.x || area.min.y >= texture.params.dimensions.y {
             bail!("Trying to update texture data out of bounds.");
         }
 
+        let dims = area.dim();
+        let pitch = texture.params.format.size(Vector2::new(row_length, 1)) as usize;
+        let last = texture.params.format.size(Vector2::new(dims.x, 1)) as usize;
+        if data.len() < pitch * (dims.y.max(1) - 1) as usize + last {
+            bail!("Trying to update texture with insufficient data.");
+        }
+
         let (internal_format, format, pixel_type) =
             types::texture_format(texture.params.format, &self.capabilities);
 
@@ -489,7 +761,11 @@ impl Visitor for GLVisitor {
         )?;
 
         if !*texture.allocated.borrow() {
-            Self::bind_texture_params(texture.params.wrap, texture.params.filter, 1)?;
+            let levels = texture.params.mip_levels(1);
+            let params = texture.params;
+            let wrap = (params.wrap_s, params.wrap_t);
+            let filter = (params.min_filter, params.mag_filter, params.mip_filter);
+            Self::bind_texture_params(gl::TEXTURE_2D, wrap, filter, levels)?;
 
             gl::TexImage2D(
                 gl::TEXTURE_2D,
@@ -506,17 +782,50 @@ impl Visitor for GLVisitor {
             *texture.allocated.borrow_mut() = true;
         }
 
-        gl::TexSubImage2D(
-            gl::TEXTURE_2D,
-            0,
-            area.min.x as i32,
-            area.min.y as i32,
-            area.dim().x as i32,
-            area.dim().y as i32,
-            format,
-            pixel_type,
-            &data[0] as *const u8 as *const ::std::os::raw::c_void,
-        );
+        let alignment = texture.params.format.unpack_alignment(row_length);
+        Self::set_unpack_alignment(&mut self.state, alignment)?;
+
+        if row_length == dims.x || self.capabilities.has_unpack_row_length() {
+            if row_length != dims.x {
+                gl::PixelStorei(gl::UNPACK_ROW_LENGTH, row_length as GLint);
+            }
+
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                area.min.x as i32,
+                area.min.y as i32,
+                dims.x as i32,
+                dims.y as i32,
+                format,
+                pixel_type,
+                &data[0] as *const u8 as *const ::std::os::raw::c_void,
+            );
+
+            if row_length != dims.x {
+                gl::PixelStorei(gl::UNPACK_ROW_LENGTH, 0);
+            }
+        } else {
+            // Uploads row by row if UNPACK_ROW_LENGTH is not available (e.g. ES2).
+            for i in 0..dims.y {
+                gl::TexSubImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    area.min.x as i32,
+                    (area.min.y + i) as i32,
+                    dims.x as i32,
+                    1,
+                    format,
+                    pixel_type,
+                    &data[i as usize * pitch] as *const u8 as *const ::std::os::raw::c_void,
+                );
+            }
+        }
+
+        // Regenerates the whole chain, since the mipmaps could not be generated partially.
+        if texture.params.mipmap {
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+        }
 
         check()
     }
@@ -528,12 +837,15 @@ impl Visitor for GLVisitor {
             .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
 
         for v in self.state.binded_textures.iter_mut() {
-            if *v == Some(Sampler::Texture(handle)) {
+            if *v == Some(Sampler::Texture(handle)) || *v == Some(Sampler::Cube(handle)) {
                 *v = None;
             }
         }
 
-        gl::DeleteTextures(1, &texture.id);
+        if texture.owned {
+            gl::DeleteTextures(1, &texture.id);
+        }
+
         check()
     }
 
@@ -548,7 +860,9 @@ impl Visitor for GLVisitor {
             assert!(id != 0);
 
             Self::bind_texture(&mut self.state, Some(Sampler::RenderTexture(handle)), 0, id)?;
-            Self::bind_texture_params(params.wrap, params.filter, 1)?;
+            let wrap = (params.wrap, params.wrap);
+            let filter = (params.filter, params.filter, None);
+            Self::bind_texture_params(gl::TEXTURE_2D, wrap, filter, (0, 0))?;
 
             let (internal_format, format, pixel_type) = params.format.into();
             gl::TexImage2D(
@@ -563,11 +877,25 @@ impl Visitor for GLVisitor {
                 ::std::ptr::null(),
             );
 
-            id
-        } else {
-            let mut id = 0;
-            gl::GenRenderbuffers(1, &mut id);
-            assert!(id != 0);
+            if let Some(cmp) = params.compare {
+                if !params.format.is_color() {
+                    let func: GLenum = cmp.into();
+                    gl::TexParameteri(
+                        gl::TEXTURE_2D,
+                        gl::TEXTURE_COMPARE_MODE,
+                        gl::COMPARE_REF_TO_TEXTURE as GLint,
+                    );
+                    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_FUNC, func as GLint);
+                } else {
+                    warn!("[GL] Ignores compare mode of color {:?}.", handle);
+                }
+            }
+
+            id
+        } else {
+            let mut id = 0;
+            gl::GenRenderbuffers(1, &mut id);
+            assert!(id != 0);
             gl::BindRenderbuffer(gl::RENDERBUFFER, id);
 
             let (internal_format, _, _) = params.format.into();
@@ -588,6 +916,58 @@ impl Visitor for GLVisitor {
         Ok(())
     }
 
+    unsafe fn resize_render_texture(
+        &mut self,
+        handle: RenderTextureHandle,
+        dimensions: Vector2<u32>,
+    ) -> Result<()> {
+        let rt = self
+            .render_textures
+            .get_mut(handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        rt.params.dimensions = dimensions;
+        let (internal_format, format, pixel_type) = rt.params.format.into();
+
+        // Respecifies the storage of the same object, so it stays attached to framebuffers.
+        if rt.params.sampler {
+            let sampler = Some(Sampler::RenderTexture(handle));
+            Self::bind_texture(&mut self.state, sampler, 0, rt.id)?;
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                internal_format as GLint,
+                dimensions.x as GLsizei,
+                dimensions.y as GLsizei,
+                0,
+                format,
+                pixel_type,
+                ::std::ptr::null(),
+            );
+        } else {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, rt.id);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                internal_format,
+                dimensions.x as GLint,
+                dimensions.y as GLint,
+            );
+        }
+
+        check()?;
+
+        for surface in self.surfaces.buf.iter_mut().filter_map(|v| v.as_mut()) {
+            if surface.params.is_attached(handle) {
+                surface.dimensions = Some(dimensions);
+                if self.state.binded_surface == Some(surface.handle) {
+                    self.state.binded_surface = None;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     unsafe fn delete_render_texture(&mut self, handle: RenderTextureHandle) -> Result<()> {
         let rt = self
             .render_textures
@@ -615,6 +995,13 @@ impl Visitor for GLVisitor {
         params: MeshParams,
         data: Option<MeshData>,
     ) -> Result<()> {
+        if params.is_indexed()
+            && params.index_format == IndexFormat::U32
+            && !self.capabilities.has_element_index_uint()
+        {
+            bail!("The GL Context does not support IndexFormat::U32.");
+        }
+
         let vbo = self.create_buffer(
             gl::ARRAY_BUFFER,
             params.hint,
@@ -622,12 +1009,17 @@ impl Visitor for GLVisitor {
             data.as_ref().map(|v| v.vptr.as_ref()),
         )?;
 
-        let ibo = self.create_buffer(
-            gl::ELEMENT_ARRAY_BUFFER,
-            params.hint,
-            params.index_buffer_len(),
-            data.as_ref().map(|v| v.iptr.as_ref()),
-        )?;
+        // Non-indexed meshes have no index buffer at all.
+        let ibo = if params.is_indexed() {
+            self.create_buffer(
+                gl::ELEMENT_ARRAY_BUFFER,
+                params.hint,
+                params.index_buffer_len(),
+                data.as_ref().map(|v| v.iptr.as_ref()),
+            )?
+        } else {
+            0
+        };
 
         self.meshes.create(
             handle,
@@ -681,6 +1073,10 @@ impl Visitor for GLVisitor {
                 bail!("Trying to update immutable buffer");
             }
 
+            if !mesh.params.is_indexed() {
+                bail!("Trying to update index buffer of non-indexed mesh.");
+            }
+
             mesh.ibo
         };
 
@@ -695,7 +1091,7 @@ impl Visitor for GLVisitor {
             .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
 
         // Removes deprecated `VertexArrayObject`s.
-        self.state.vaos.retain(|&(_, h), vao| {
+        self.state.vaos.retain(|&(_, h, _), vao| {
             if h == mesh.handle {
                 gl::DeleteVertexArrays(1, vao as *mut u32);
                 false
@@ -705,10 +1101,282 @@ impl Visitor for GLVisitor {
         });
 
         gl::DeleteBuffers(1, &mesh.vbo);
-        gl::DeleteBuffers(1, &mesh.ibo);
+        if mesh.params.is_indexed() {
+            gl::DeleteBuffers(1, &mesh.ibo);
+        }
+        check()
+    }
+
+    unsafe fn create_instance_buffer(
+        &mut self,
+        handle: InstanceBufferHandle,
+        params: InstanceBufferParams,
+        data: Option<&[u8]>,
+    ) -> Result<()> {
+        if !self.capabilities.has_instanced_arrays() {
+            bail!("The OpenGL implementation does not supports instanced arrays.");
+        }
+
+        let vbo = self.create_buffer(gl::ARRAY_BUFFER, params.hint, params.buffer_len(), data)?;
+        self.instance_buffers.create(
+            handle,
+            GLInstanceBufferData {
+                handle,
+                vbo,
+                params,
+            },
+        );
+
+        Ok(())
+    }
+
+    unsafe fn update_instance_buffer(
+        &mut self,
+        handle: InstanceBufferHandle,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<()> {
+        let vbo = {
+            let instances = self
+                .instance_buffers
+                .get(handle)
+                .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+            if instances.params.hint == MeshHint::Immutable {
+                bail!("Trying to update immutable buffer");
+            }
+
+            instances.vbo
+        };
+
+        Self::update_buffer(gl::ARRAY_BUFFER, vbo, offset, data)?;
+        Ok(())
+    }
+
+    unsafe fn delete_instance_buffer(&mut self, handle: InstanceBufferHandle) -> Result<()> {
+        let instances = self
+            .instance_buffers
+            .free(handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        // Removes deprecated `VertexArrayObject`s.
+        self.state.vaos.retain(|&(_, _, h), vao| {
+            if h == Some(instances.handle) {
+                gl::DeleteVertexArrays(1, vao as *mut u32);
+                false
+            } else {
+                true
+            }
+        });
+
+        gl::DeleteBuffers(1, &instances.vbo);
+        check()
+    }
+
+    unsafe fn create_shader_storage_buffer(
+        &mut self,
+        handle: ShaderStorageBufferHandle,
+        params: ShaderStorageBufferParams,
+        data: Option<&[u8]>,
+    ) -> Result<()> {
+        if !self.capabilities.has_shader_storage_buffer() {
+            bail!("The GL Context does not support shader storage buffer objects.");
+        }
+
+        let tp = gl::SHADER_STORAGE_BUFFER;
+        let id = self.create_buffer(tp, params.hint, params.size, data)?;
+        self.storage_buffers
+            .create(handle, GLStorageBufferData { id, params });
+
+        Ok(())
+    }
+
+    unsafe fn update_shader_storage_buffer(
+        &mut self,
+        handle: ShaderStorageBufferHandle,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<()> {
+        let id = {
+            let buffer = self
+                .storage_buffers
+                .get(handle)
+                .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+            if buffer.params.hint == MeshHint::Immutable {
+                bail!("Trying to update immutable buffer");
+            }
+
+            buffer.id
+        };
+
+        Self::update_buffer(gl::SHADER_STORAGE_BUFFER, id, offset, data)?;
+        Ok(())
+    }
+
+    unsafe fn delete_shader_storage_buffer(
+        &mut self,
+        handle: ShaderStorageBufferHandle,
+    ) -> Result<()> {
+        let buffer = self
+            .storage_buffers
+            .free(handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        gl::DeleteBuffers(1, &buffer.id);
+        check()
+    }
+
+    unsafe fn read_shader_storage_buffer(
+        &mut self,
+        handle: ShaderStorageBufferHandle,
+    ) -> Result<()> {
+        let (id, size) = {
+            let buffer = self
+                .storage_buffers
+                .get(handle)
+                .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+            (buffer.id, buffer.params.size)
+        };
+
+        let tp = gl::SHADER_STORAGE_BUFFER;
+        gl::BindBuffer(tp, id);
+        let ptr = gl::MapBufferRange(tp, 0, size as isize, gl::MAP_READ_BIT);
+        if ptr.is_null() {
+            check()?;
+            bail!("Failed to map {:?} for reading.", handle);
+        }
+
+        let mut bytes = vec![0u8; size];
+        ::std::ptr::copy_nonoverlapping(ptr as *const u8, bytes.as_mut_ptr(), size);
+        gl::UnmapBuffer(tp);
+        check()?;
+
+        self.readbacks.push((handle.into(), bytes));
+        Ok(())
+    }
+
+    unsafe fn create_uniform_buffer(
+        &mut self,
+        handle: UniformBufferHandle,
+        params: UniformBufferParams,
+        data: Option<&[u8]>,
+    ) -> Result<()> {
+        let tp = gl::UNIFORM_BUFFER;
+        let id = self.create_buffer(tp, params.hint, params.size, data)?;
+        self.uniform_buffers
+            .create(handle, GLUniformBufferData { id, params });
+
+        Ok(())
+    }
+
+    unsafe fn update_uniform_buffer(
+        &mut self,
+        handle: UniformBufferHandle,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<()> {
+        let id = {
+            let buffer = self
+                .uniform_buffers
+                .get(handle)
+                .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+            if buffer.params.hint == MeshHint::Immutable {
+                bail!("Trying to update immutable buffer");
+            }
+
+            buffer.id
+        };
+
+        Self::update_buffer(gl::UNIFORM_BUFFER, id, offset, data)?;
+        Ok(())
+    }
+
+    unsafe fn delete_uniform_buffer(&mut self, handle: UniformBufferHandle) -> Result<()> {
+        let buffer = self
+            .uniform_buffers
+            .free(handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        gl::DeleteBuffers(1, &buffer.id);
         check()
     }
 
+    unsafe fn read_render_texture(
+        &mut self,
+        handle: RenderTextureHandle,
+        area: Aabb2<u32>,
+    ) -> Result<()> {
+        let rt = *self
+            .render_textures
+            .get(handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        let format = rt.params.format;
+        if !format.is_color() {
+            bail!("{:?} of {:?} could not be read back.", format, handle);
+        }
+
+        // Reads through a temporary framebuffer, so it works even if the render texture
+        // is attached to the binded surface.
+        let mut fbo = 0;
+        gl::GenFramebuffers(1, &mut fbo);
+        self.state.binded_surface = None;
+        self.state.binded_framebuffer = None;
+
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo);
+        let target = gl::READ_FRAMEBUFFER;
+        self.update_framebuffer_render_texture(target, rt.id, rt.params, 0, false)?;
+
+        let dimensions = area.dim();
+        let mut bytes = vec![0u8; format.size(dimensions) as usize];
+        let (_, format, pixel_type) = format.into();
+
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            area.min.x as GLint,
+            area.min.y as GLint,
+            dimensions.x as GLsizei,
+            dimensions.y as GLsizei,
+            format,
+            pixel_type,
+            bytes.as_mut_ptr() as *mut ::std::os::raw::c_void,
+        );
+
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl::DeleteFramebuffers(1, &fbo);
+        check()?;
+
+        self.readbacks.push((handle.into(), bytes));
+        Ok(())
+    }
+
+    fn take_readbacks(&mut self) -> Vec<(VideoHandle, Vec<u8>)> {
+        ::std::mem::replace(&mut self.readbacks, Vec::new())
+    }
+
+    unsafe fn begin_gpu_timer(&mut self, name: &str) -> Result<()> {
+        if self.capabilities.has_timer_query() {
+            self.timers.begin(name)?;
+        }
+
+        Ok(())
+    }
+
+    unsafe fn end_gpu_timer(&mut self) -> Result<()> {
+        if self.capabilities.has_timer_query() {
+            self.timers.end()?;
+        }
+
+        Ok(())
+    }
+
+    fn take_gpu_timers(&mut self) -> Vec<(String, Duration)> {
+        self.timers.take()
+    }
+
     unsafe fn bind(&mut self, handle: SurfaceHandle, dimensions: Vector2<u32>) -> Result<()> {
         if self.state.binded_surface == Some(handle) {
             return Ok(());
@@ -719,10 +1387,20 @@ impl Visitor for GLVisitor {
             .get(handle)
             .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
 
-        // Bind frame buffer.
-        let id = surface.id.unwrap_or(0);
+        // Bind frame buffer, the window framebuffer will be redirected into the offscreen
+        // framebuffer if antialiasing is enabled.
+        let id = match (surface.id, self.antialiasing.as_mut()) {
+            (Some(id), _) => id,
+            (None, Some(aa)) => aa.framebuffer(dimensions)?,
+            (None, None) => 0,
+        };
+
         let dimensions = surface.dimensions.unwrap_or(dimensions);
-        gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+        if self.state.binded_framebuffer != Some(id) {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+            self.state.binded_framebuffer = Some(id);
+            self.stats.framebuffer_binds += 1;
+        }
 
         // Reset the viewport and scissor box.
         let vp = SurfaceViewport {
@@ -731,21 +1409,64 @@ impl Visitor for GLVisitor {
         };
 
         Self::set_viewport(&mut self.state, vp)?;
+        Self::set_viewport_array(&mut self.state, &surface.params.viewports)?;
         Self::set_scissor(&mut self.state, SurfaceScissor::Disable)?;
+        Self::set_framebuffer_srgb(&mut self.state, surface.params.srgb_write)?;
+        self.state.cull_override = surface.params.cull_override;
+
+        let sampler = surface
+            .params
+            .sampler_override
+            .and_then(|v| self.samplers.get(&v).cloned());
+        Self::set_sampler(&mut self.state, sampler.unwrap_or(0))?;
+
+        self.state.clears.bind(&surface.params);
 
         if !self.state.cleared_surfaces.contains(&handle) {
-            // Sets depth write enable to make sure that we can clear depth buffer properly.
-            if surface.params.clear_depth.is_some() {
+            // Skips the clears of attachments which hold the same values already, e.g. the
+            // surfaces sharing the window framebuffer. The integer color attachment could
+            // only be cleared with `glClearBuffer[u]iv`.
+            let params = &surface.params;
+            let clears = &mut self.state.clears;
+            let (color, integer) = match surface.color_format {
+                Some(format) if format.is_integer() => {
+                    let v = clears.filter(ClearBuffer::Color, params.clear_integer);
+                    (None, v.map(|v| (format, v)))
+                }
+                _ => (clears.filter(ClearBuffer::Color, params.clear_color), None),
+            };
+
+            let depth = clears.filter(ClearBuffer::Depth, params.clear_depth);
+            let stencil = clears.filter(ClearBuffer::Stencil, params.clear_stencil);
+
+            // The clear operation ignores the viewport, but respects the scissor box and
+            // write masks. So we make sure that the whole attachments will be cleared, and
+            // the masks of shader would be re-applied by the next draw.
+            let last = ClearStates::new(self.state.scissor, &self.state.render_state);
+            let states = last.clear(color.is_some() || integer.is_some(), depth.is_some());
+            Self::set_scissor(&mut self.state, states.scissor)?;
+            Self::set_color_write(&mut self.state, states.color_write)?;
+            Self::set_depth_test(&mut self.state, states.depth_write, states.depth_test)?;
+
+            if last.is_overwritten_by(&states) {
                 self.state.binded_shader = None;
-                Self::set_depth_test(&mut self.state, true, Comparison::Always)?;
             }
 
-            // Clears frame buffer.
-            Self::clear(
-                surface.params.clear_color,
-                surface.params.clear_depth,
-                surface.params.clear_stencil,
-            )?;
+            if let Some((format, v)) = integer {
+                Self::clear_integer(format, v)?;
+            }
+
+            Self::clear(color, depth, stencil)?;
+
+            if color.is_some() || integer.is_some() || depth.is_some() || stencil.is_some() {
+                self.stats.clears += 1;
+            } else if params.clear_color.is_some()
+                || params.clear_integer.is_some()
+                || params.clear_depth.is_some()
+                || params.clear_stencil.is_some()
+            {
+                self.stats.skipped_clears += 1;
+            }
 
             self.state.cleared_surfaces.insert(handle);
         }
@@ -762,25 +1483,150 @@ impl Visitor for GLVisitor {
         Self::set_viewport(&mut self.state, vp)
     }
 
+    unsafe fn finish(&mut self) -> Result<()> {
+        gl::Finish();
+        check()
+    }
+
+    unsafe fn flush_gpu(&mut self) -> Result<()> {
+        gl::Flush();
+        check()
+    }
+
+    unsafe fn memory_barrier(&mut self, barriers: MemoryBarrier) -> Result<()> {
+        // The incoherent memory writes are impossible without image load/store or shader
+        // storage buffers, so there is nothing to synchronize.
+        if barriers.is_empty() || !self.capabilities.has_image_load_store() {
+            return Ok(());
+        }
+
+        gl::MemoryBarrier(barriers.into());
+        check()
+    }
+
+    unsafe fn blit(
+        &mut self,
+        src: RenderTextureHandle,
+        dst: RenderTextureHandle,
+        filter: TextureFilter,
+    ) -> Result<()> {
+        let src = *self
+            .render_textures
+            .get(src)
+            .ok_or_else(|| format_err!("{:?} is invalid.", src))?;
+
+        let handle = dst;
+        let dst = *self
+            .render_textures
+            .get(dst)
+            .ok_or_else(|| format_err!("{:?} is invalid.", dst))?;
+
+        src.params.format.validate_blit(dst.params.format, filter)?;
+
+        // Both the blit and its fallback pass overwrite the cleared values of `dst`.
+        self.state.clears.forget(handle);
+
+        let float = |v: RenderTextureFormat| v == RenderTextureFormat::RGBA16F;
+        if float(src.params.format) != float(dst.params.format)
+            && !self.capabilities.has_blit_conversion()
+        {
+            return self.blit_with_pass(src, dst, filter);
+        }
+
+        let mask = match src.params.format {
+            RenderTextureFormat::Depth16
+            | RenderTextureFormat::Depth24
+            | RenderTextureFormat::Depth32 => gl::DEPTH_BUFFER_BIT,
+            RenderTextureFormat::Depth24Stencil8 => gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT,
+            _ => gl::COLOR_BUFFER_BIT,
+        };
+
+        let filter = match filter {
+            TextureFilter::Nearest => gl::NEAREST,
+            TextureFilter::Linear => gl::LINEAR,
+        };
+
+        let mut fbos = [0; 2];
+        gl::GenFramebuffers(2, fbos.as_mut_ptr());
+        self.state.binded_surface = None;
+        self.state.binded_framebuffer = None;
+
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbos[0]);
+        let target = gl::READ_FRAMEBUFFER;
+        self.update_framebuffer_render_texture(target, src.id, src.params, 0, false)?;
+
+        gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, fbos[1]);
+        let target = gl::DRAW_FRAMEBUFFER;
+        self.update_framebuffer_render_texture(target, dst.id, dst.params, 0, false)?;
+
+        // The blits are clipped by the scissor test.
+        Self::set_scissor(&mut self.state, SurfaceScissor::Disable)?;
+
+        let (sw, sh) = (src.params.dimensions.x, src.params.dimensions.y);
+        let (dw, dh) = (dst.params.dimensions.x, dst.params.dimensions.y);
+        gl::BlitFramebuffer(
+            0,
+            0,
+            sw as GLint,
+            sh as GLint,
+            0,
+            0,
+            dw as GLint,
+            dh as GLint,
+            mask,
+            filter,
+        );
+
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl::DeleteFramebuffers(2, fbos.as_ptr());
+        check()
+    }
+
+    unsafe fn invalidate_state_cache(&mut self) -> Result<()> {
+        // The cache itself is stale after native OpenGL calls, so every state is issued
+        // regardless of the cached values.
+        Self::apply_default_states(&self.capabilities)?;
+        self.state.invalidate();
+
+        let vp = self.state.view;
+        gl::Viewport(
+            vp.position.x,
+            vp.position.y,
+            vp.size.x as i32,
+            vp.size.y as i32,
+        );
+
+        gl::ActiveTexture(gl::TEXTURE0);
+        check()
+    }
+
+    unsafe fn set_fallback_texture(&mut self, handle: Option<TextureHandle>) -> Result<()> {
+        self.fallback_texture = handle;
+        Ok(())
+    }
+
     unsafe fn draw(
         &mut self,
-        shader: ShaderHandle,
-        mesh: MeshHandle,
-        mesh_index: MeshIndex,
+        dc: DrawCommand,
         uniforms: &[UniformVar],
+        per_object: Option<&[u8]>,
     ) -> Result<u32> {
         // Bind program and associated uniforms and textures.
         let shader = self
             .shaders
-            .get(shader)
-            .ok_or_else(|| format_err!("{:?} is invalid.", shader))?;
+            .get(dc.shader)
+            .ok_or_else(|| format_err!("{:?} is invalid.", dc.shader))?;
 
         Self::bind_shader(&mut self.state, &shader)?;
+        let face = self.state.cull_override;
+        let face = face.unwrap_or(shader.params.state.cull_face);
+        Self::set_cull_face(&mut self.state, face)?;
+        self.state.clears.touch();
 
-        let mut index = 0usize;
-        for &(field, variable) in uniforms {
+        let units = texture_units(uniforms)?;
+        for (i, &(field, variable)) in uniforms.iter().enumerate() {
             if let Some(tp) = shader.params.uniforms.variable_type(field) {
-                if tp != variable.variable_type() {
+                if !tp.accepts(variable.variable_type()) {
                     let name = shader.params.uniforms.variable_name(field).unwrap();
                     bail!(
                         "The uniform {} needs a {:?} instead of {:?}.",
@@ -790,26 +1636,64 @@ impl Visitor for GLVisitor {
                     );
                 }
 
+                // The uniform blocks are binded by their indices instead of locations.
+                if let UniformVariable::UniformBuffer(binding, handle) = variable {
+                    let (index, size) = shader.uniform_blocks[&field];
+                    let buffer = self
+                        .uniform_buffers
+                        .get(handle)
+                        .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+                    if buffer.params.size < size {
+                        let name = shader.params.uniforms.variable_name(field).unwrap();
+                        bail!(
+                            "The uniform block {} needs {} bytes, but {:?} only has {}.",
+                            name,
+                            size,
+                            handle,
+                            buffer.params.size
+                        );
+                    }
+
+                    let binding = GLuint::from(binding);
+                    if binding >= self.capabilities.max_indexed_uniform_buffer {
+                        bail!("Uniform buffer binding {} is out of bounds.", binding);
+                    }
+
+                    gl::UniformBlockBinding(shader.id, index, binding);
+                    gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, buffer.id);
+                    check()?;
+                    continue;
+                }
+
                 let location = shader.hash_uniform_location(field).unwrap();
                 match variable {
-                    UniformVariable::Texture(handle) => {
+                    UniformVariable::Texture(handle) | UniformVariable::TextureUnit(_, handle) => {
+                        let index = units[i];
                         let v = UniformVariable::I32(index as i32);
                         Self::bind_uniform_variable(location, &v)?;
 
+                        // Substitutes the fallback texture if the resource is not ready,
+                        // e.g. still loading or failed to load.
+                        let handle = match self.fallback_texture {
+                            Some(fallback) if self.textures.get(handle).is_none() => fallback,
+                            _ => handle,
+                        };
+
                         if let Some(texture) = self.textures.get(handle) {
-                            Self::bind_texture(
-                                &mut self.state,
-                                Some(Sampler::Texture(handle)),
-                                index,
-                                texture.id,
-                            )?;
+                            let sampler = match texture.params.kind {
+                                TextureKind::Texture2D => Sampler::Texture(handle),
+                                TextureKind::Cube => Sampler::Cube(handle),
+                            };
+
+                            Self::bind_texture(&mut self.state, Some(sampler), index, texture.id)?;
                         } else {
                             Self::bind_texture(&mut self.state, None, index, 0)?;
                         }
-
-                        index += 1;
                     }
-                    UniformVariable::RenderTexture(handle) => {
+                    UniformVariable::RenderTexture(handle)
+                    | UniformVariable::RenderTextureUnit(_, handle) => {
+                        let index = units[i];
                         let v = UniformVariable::I32(index as i32);
                         Self::bind_uniform_variable(location, &v)?;
 
@@ -827,8 +1711,60 @@ impl Visitor for GLVisitor {
                         } else {
                             Self::bind_texture(&mut self.state, None, index, 0)?;
                         }
+                    }
+                    UniformVariable::Image(unit, image) => {
+                        if !self.capabilities.has_image_load_store() {
+                            bail!("The GL Context does not support image load/store.");
+                        }
+
+                        let texture = self
+                            .render_textures
+                            .get(image.texture)
+                            .ok_or_else(|| format_err!("{:?} is invalid.", image.texture))?;
 
-                        index += 1;
+                        if !texture.params.sampler {
+                            bail!("The render buffer could not be binded as image.");
+                        }
+
+                        // The image uniforms of GLSL ES could only be assigned with the
+                        // layout qualifier.
+                        if let Version::GL(_, _) = self.capabilities.version {
+                            let v = UniformVariable::I32(i32::from(unit));
+                            Self::bind_uniform_variable(location, &v)?;
+                        }
+
+                        gl::BindImageTexture(
+                            GLuint::from(unit),
+                            texture.id,
+                            GLint::from(image.level),
+                            gl::FALSE,
+                            0,
+                            image.access.into(),
+                            types::image_format(image.format)?,
+                        );
+
+                        if image.access != ImageAccess::Read {
+                            self.state.clears.forget(image.texture);
+                        }
+
+                        check()?;
+                    }
+                    UniformVariable::StorageBuffer(binding, handle) => {
+                        let buffer = self
+                            .storage_buffers
+                            .get(handle)
+                            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+                        // The storage blocks of GLSL ES could only be assigned with the
+                        // layout qualifier.
+                        if let Version::GL(_, _) = self.capabilities.version {
+                            let binding = GLuint::from(binding);
+                            gl::ShaderStorageBlockBinding(shader.id, location as GLuint, binding);
+                        }
+
+                        let binding = GLuint::from(binding);
+                        gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, buffer.id);
+                        check()?;
                     }
                     _ => {
                         Self::bind_uniform_variable(location, &variable)?;
@@ -839,59 +1775,242 @@ impl Visitor for GLVisitor {
             }
         }
 
-        if let Some(mesh) = self.meshes.get(mesh) {
-            // Bind vertex buffer and vertex array object.
-            Self::bind_mesh(&mut self.state, &shader, &mesh)?;
+        if let Some(bytes) = per_object {
+            let size = shader.per_object_block.ok_or_else(|| {
+                format_err!(
+                    "Uniform block {} is undefined in shader sources.",
+                    PER_OBJECT_BLOCK
+                )
+            })?;
+
+            let alignment = self.capabilities.uniform_buffer_offset_alignment as usize;
+            let (id, offset) = self.per_object_ring.push(bytes, size, alignment)?;
+            gl::BindBufferRange(
+                gl::UNIFORM_BUFFER,
+                PER_OBJECT_BLOCK_BINDING,
+                id,
+                offset as GLintptr,
+                size.max(bytes.len()) as GLsizeiptr,
+            );
 
-            let (from, len) = match mesh_index {
-                MeshIndex::Ptr(from, len) => {
-                    if (from + len) > mesh.params.num_idxes {
-                        bail!("MeshIndex is out of bounds");
-                    }
+            check()?;
+        }
+
+        if let Some(mesh) = self.meshes.get(dc.mesh) {
+            let instances = match dc.instances {
+                Some(handle) => Some(
+                    self.instance_buffers
+                        .get(handle)
+                        .ok_or_else(|| format_err!("{:?} is invalid.", handle))?,
+                ),
+                None => None,
+            };
 
-                    ((from * mesh.params.index_format.stride()), len)
+            if let Some(num) = dc.num_instances {
+                if !self.capabilities.has_draw_instanced() {
+                    bail!("The OpenGL implementation does not supports instanced draws.");
                 }
-                MeshIndex::SubMesh(index) => {
-                    let num = mesh.params.sub_mesh_offsets.len();
-                    let from = mesh
-                        .params
-                        .sub_mesh_offsets
-                        .get(index)
-                        .ok_or_else(|| format_err!("MeshIndex is out of bounds"))?;
-
-                    let to = if index == (num - 1) {
-                        mesh.params.num_idxes
-                    } else {
-                        mesh.params.sub_mesh_offsets[index + 1]
-                    };
-
-                    ((from * mesh.params.index_format.stride()), (to - from))
+
+                if let Some(instances) = instances {
+                    if num as usize > instances.params.num_instances {
+                        bail!(
+                            "Drawing {} instances with {:?} that only has {}.",
+                            num,
+                            instances.handle,
+                            instances.params.num_instances
+                        );
+                    }
                 }
-                MeshIndex::All => (0, mesh.params.num_idxes),
-            };
+            }
 
-            gl::DrawElements(
-                mesh.params.primitive.into(),
-                len as i32,
-                mesh.params.index_format.into(),
-                from as *const u32 as *const ::std::os::raw::c_void,
-            );
+            // Bind vertex buffer and vertex array object.
+            Self::bind_mesh(&mut self.state, &shader, &mesh, instances)?;
+
+            let (from, len) = mesh_elements(&mesh.params, dc.mesh_index)?;
+
+            // Applies the scissor and viewport of this draw, and restores the ones of surface
+            // afterwards.
+            let last_scissor = self.state.scissor;
+            if let Some(v) = dc.scissor {
+                Self::set_scissor(&mut self.state, v)?;
+            }
+
+            let (last_view, last_view_array) = (self.state.view, self.state.view_array);
+            if let Some(v) = dc.viewport {
+                Self::set_viewport(&mut self.state, v)?;
+            }
+
+            let primitive = mesh.params.primitive.into();
+            if mesh.params.is_indexed() {
+                let offset = from * mesh.params.index_format.stride();
+                let offset = offset as *const u32 as *const ::std::os::raw::c_void;
+                let format = mesh.params.index_format.into();
+                if let Some(num) = dc.num_instances {
+                    gl::DrawElementsInstanced(primitive, len as i32, format, offset, num as i32);
+                } else {
+                    gl::DrawElements(primitive, len as i32, format, offset);
+                }
+            } else if let Some(num) = dc.num_instances {
+                gl::DrawArraysInstanced(primitive, from as i32, len as i32, num as i32);
+            } else {
+                gl::DrawArrays(primitive, from as i32, len as i32);
+            }
 
             check()?;
-            Ok(mesh.params.primitive.assemble(len as u32))
+
+            if dc.scissor.is_some() {
+                Self::set_scissor(&mut self.state, last_scissor)?;
+            }
+
+            if dc.viewport.is_some() {
+                Self::set_viewport(&mut self.state, last_view)?;
+
+                let surfaces = &self.surfaces;
+                let surface = self.state.binded_surface.and_then(|v| surfaces.get(v));
+                if let Some(surface) = surface.filter(|_| last_view_array) {
+                    Self::set_viewport_array(&mut self.state, &surface.params.viewports)?;
+                }
+            }
+
+            let num = dc.num_instances.unwrap_or(1);
+            Ok(mesh.params.primitive.assemble(len as u32) * num)
         } else {
             Ok(0)
         }
     }
 
     unsafe fn flush(&mut self) -> Result<()> {
+        if let Some(aa) = self.antialiasing.as_mut() {
+            if aa.is_pending() {
+                let vp = SurfaceViewport {
+                    position: Vector2::new(0, 0),
+                    size: aa.dimensions(),
+                };
+
+                Self::reset_render_state(&mut self.state)?;
+                Self::set_viewport(&mut self.state, vp)?;
+                aa.resolve()?;
+
+                self.state.binded_surface = None;
+                self.state.binded_framebuffer = None;
+                self.state.binded_shader = None;
+                self.state.binded_vao = None;
+                self.state.binded_texture_index = 0;
+                if let Some(v) = self.state.binded_textures.get_mut(0) {
+                    *v = None;
+                }
+            }
+        }
+
         if self.state.cleared_surfaces.is_empty() {
             Self::clear(Color::black(), None, None)?;
         }
 
-        gl::Finish();
+        if self.max_frame_latency == 0 || !self.capabilities.has_sync() {
+            gl::Finish();
+            return check();
+        }
+
+        let fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+        self.fences.push_back(fence);
+
+        while self.fences.len() > self.max_frame_latency as usize {
+            let fence = self.fences.pop_front().unwrap();
+            gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, gl::TIMEOUT_IGNORED);
+            gl::DeleteSync(fence);
+        }
+
         check()
     }
+
+    unsafe fn set_max_frame_latency(&mut self, frames: u32) -> Result<()> {
+        self.max_frame_latency = frames;
+
+        // The frames flushed with fences are completed by `glFinish` anyway.
+        if frames == 0 {
+            for fence in self.fences.drain(..) {
+                gl::DeleteSync(fence);
+            }
+        }
+
+        Ok(())
+    }
+
+    unsafe fn set_antialiasing(&mut self, mode: AaMode) -> Result<()> {
+        let current = self.antialiasing.as_ref().map(|v| v.mode());
+        if current.unwrap_or(AaMode::None) == mode {
+            return Ok(());
+        }
+
+        self.update_offscreen(mode)
+    }
+
+    unsafe fn set_output_adjustment(&mut self, adjustment: OutputAdjustment) -> Result<()> {
+        self.output = adjustment;
+
+        let mode = match self.antialiasing.as_mut() {
+            Some(aa) => {
+                if aa.set_adjustment(adjustment) {
+                    return Ok(());
+                }
+
+                aa.mode()
+            }
+            None if adjustment.is_identity() => return Ok(()),
+            None => AaMode::None,
+        };
+
+        self.update_offscreen(mode)
+    }
+
+    fn is_format_supported(&self, format: RenderTextureFormat, usage: FormatUsage) -> bool {
+        self.capabilities.is_format_supported(format, usage)
+    }
+
+    fn is_texture_format_supported(&self, format: TextureFormat) -> bool {
+        format.is_support(&self.capabilities)
+    }
+
+    fn limits(&self) -> DeviceLimits {
+        let caps = &self.capabilities;
+        DeviceLimits {
+            max_texture_size: caps.max_texture_size,
+            max_color_attachments: caps.max_color_attachments,
+            max_vertex_attributes: caps.max_vertex_attributes,
+            max_combined_texture_units: u32::from(caps.max_combined_texture_image_units),
+            max_samples: caps.max_samples,
+        }
+    }
+
+    fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    fn native_texture(&self, handle: TextureHandle) -> Option<u32> {
+        self.textures.get(handle).map(|v| v.id)
+    }
+
+    unsafe fn capture(&mut self, dimensions: Vector2<u32>) -> Result<Image> {
+        let mut pixels = vec![0u8; (dimensions.x * dimensions.y * 4) as usize];
+
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        self.state.binded_surface = None;
+        self.state.binded_framebuffer = None;
+
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            0,
+            0,
+            dimensions.x as GLsizei,
+            dimensions.y as GLsizei,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut ::std::os::raw::c_void,
+        );
+
+        check()?;
+        Ok(Image { dimensions, pixels })
+    }
 }
 
 impl GLVisitor {
@@ -904,12 +2023,18 @@ impl GLVisitor {
         check()?;
 
         let rs = shader.params.state;
-        Self::set_cull_face(state, rs.cull_face)?;
+        Self::set_cull_face(state, state.cull_override.unwrap_or(rs.cull_face))?;
         Self::set_front_face_order(state, rs.front_face_order)?;
         Self::set_depth_test(state, rs.depth_write, rs.depth_test)?;
         Self::set_depth_write_offset(state, rs.depth_write_offset)?;
         Self::set_color_blend(state, rs.color_blend)?;
+        Self::set_blend_color(state, rs.blend_color)?;
+        Self::set_logic_op(state, rs.logic_op)?;
         Self::set_color_write(state, rs.color_write)?;
+        Self::set_sample_coverage(state, rs.sample_coverage)?;
+        Self::set_sample_mask(state, rs.sample_mask)?;
+        Self::set_provoking_vertex(state, rs.provoking_vertex)?;
+        Self::set_polygon_mode(state, rs.polygon_mode)?;
 
         state.binded_shader = Some(shader.handle);
         Ok(())
@@ -919,6 +2044,11 @@ impl GLVisitor {
         match *variable {
             UniformVariable::Texture(_) => unreachable!(),
             UniformVariable::RenderTexture(_) => unreachable!(),
+            UniformVariable::TextureUnit(_, _) => unreachable!(),
+            UniformVariable::RenderTextureUnit(_, _) => unreachable!(),
+            UniformVariable::Image(_, _) => unreachable!(),
+            UniformVariable::StorageBuffer(_, _) => unreachable!(),
+            UniformVariable::UniformBuffer(_, _) => unreachable!(),
             UniformVariable::I32(v) => gl::Uniform1i(location, v),
             UniformVariable::F32(v) => gl::Uniform1f(location, v),
             UniformVariable::Vector2f(v) => gl::Uniform2f(location, v[0], v[1]),
@@ -958,7 +2088,12 @@ impl GLVisitor {
 
         if state.binded_textures[index] != sampler {
             state.binded_textures[index] = sampler;
-            gl::BindTexture(gl::TEXTURE_2D, id);
+            let target = match sampler {
+                Some(Sampler::Cube(_)) => gl::TEXTURE_CUBE_MAP,
+                _ => gl::TEXTURE_2D,
+            };
+
+            gl::BindTexture(target, id);
         }
 
         check()
@@ -968,10 +2103,11 @@ impl GLVisitor {
         state: &mut GLMutableState,
         shader: &GLShaderData,
         mesh: &GLMeshData,
+        instances: Option<&GLInstanceBufferData>,
     ) -> Result<()> {
         assert!(state.binded_shader == Some(shader.handle));
 
-        let k = (shader.handle, mesh.handle);
+        let k = (shader.handle, mesh.handle, instances.map(|v| v.handle));
         if state.binded_vao != Some(k) {
             if let Some(vao) = state.vaos.get(&k).cloned() {
                 gl::BindVertexArray(vao);
@@ -1006,6 +2142,36 @@ impl GLVisitor {
                             GLsizei::from(stride),
                             offset as *const u8 as *const ::std::os::raw::c_void,
                         );
+                    } else if let Some((instances, element)) =
+                        instances.and_then(|v| v.params.layout.element(name).map(|e| (v, e)))
+                    {
+                        // Sources the attribute from instance buffer, which advances once per
+                        // instance.
+                        if element.size < size {
+                            bail!(
+                                "Instance buffer has incompatible attribute `{:?}` [{:?} - {:?}].",
+                                name,
+                                element.size,
+                                size
+                            );
+                        }
+
+                        let offset = instances.params.layout.offset(name).unwrap();
+                        let stride = instances.params.layout.stride();
+
+                        let location = shader.attribute_location(name.into())?;
+                        gl::BindBuffer(gl::ARRAY_BUFFER, instances.vbo);
+                        gl::EnableVertexAttribArray(location as GLuint);
+                        gl::VertexAttribPointer(
+                            location as GLuint,
+                            GLsizei::from(element.size),
+                            element.format.into(),
+                            element.normalized as u8,
+                            GLsizei::from(stride),
+                            offset as *const u8 as *const ::std::os::raw::c_void,
+                        );
+                        gl::VertexAttribDivisor(location as GLuint, 1);
+                        gl::BindBuffer(gl::ARRAY_BUFFER, mesh.vbo);
                     } else if required {
                         bail!(
                             "Can't find attribute {:?} description in vertex buffer.",
@@ -1042,18 +2208,119 @@ impl GLVisitor {
         gl::Disable(gl::POLYGON_OFFSET_FILL);
         state.render_state.depth_write_offset = None;
 
-        gl::Disable(gl::BLEND);
-        state.render_state.color_blend = None;
+        gl::Disable(gl::BLEND);
+        state.render_state.color_blend = None;
+        gl::BlendColor(0.0, 0.0, 0.0, 0.0);
+        state.render_state.blend_color = Color::transparent();
+        // The logical operation could only be enabled if it's supported.
+        if state.render_state.logic_op.is_some() {
+            gl::Disable(gl::COLOR_LOGIC_OP);
+            state.render_state.logic_op = None;
+        }
+
+        gl::ColorMask(1, 1, 1, 1);
+        state.render_state.color_write = (true, true, true, true);
+
+        gl::Disable(gl::SAMPLE_COVERAGE);
+        state.render_state.sample_coverage = None;
+        // The sample mask could only be enabled if it's supported.
+        if state.render_state.sample_mask != !0 {
+            gl::Disable(gl::SAMPLE_MASK);
+            state.render_state.sample_mask = !0;
+        }
+
+        // The provoking vertex could only be changed if it's supported.
+        if state.render_state.provoking_vertex != ProvokingVertex::Last {
+            gl::ProvokingVertex(gl::LAST_VERTEX_CONVENTION);
+            state.render_state.provoking_vertex = ProvokingVertex::Last;
+        }
+
+        // The polygon mode could only be changed if it's supported.
+        if state.render_state.polygon_mode != PolygonMode::Fill {
+            gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            state.render_state.polygon_mode = PolygonMode::Fill;
+        }
+
+        gl::Disable(gl::SCISSOR_TEST);
+        state.scissor = SurfaceScissor::Disable;
+
+        // The sRGB write control is a global state in older GL, which must be restored
+        // so it won't leak into other framebuffers.
+        if state.framebuffer_srgb {
+            gl::Disable(gl::FRAMEBUFFER_SRGB);
+            state.framebuffer_srgb = false;
+        }
+
+        // The sampler objects could only be binded if they are supported.
+        if state.sampler != 0 {
+            for unit in 0..MAX_UNIFORM_TEXTURE_SLOTS {
+                gl::BindSampler(unit as GLuint, 0);
+            }
+
+            state.sampler = 0;
+        }
+
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        state.unpack_alignment = 1;
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        state.binded_framebuffer = None;
+
+        check()
+    }
+
+    /// Issues the default value of every state that the cache tracks unconditionally, which
+    /// are the ones `GLMutableState::invalidate` resets to.
+    unsafe fn apply_default_states(caps: &Capabilities) -> Result<()> {
+        gl::Disable(gl::CULL_FACE);
+        gl::FrontFace(gl::CCW);
+        gl::Disable(gl::DEPTH_TEST);
+        gl::DepthMask(gl::FALSE);
+        gl::DepthFunc(gl::ALWAYS);
+        gl::Disable(gl::POLYGON_OFFSET_FILL);
+        gl::Disable(gl::BLEND);
+        gl::BlendColor(0.0, 0.0, 0.0, 0.0);
+        gl::ColorMask(1, 1, 1, 1);
+        gl::Disable(gl::SAMPLE_COVERAGE);
+        gl::Disable(gl::SCISSOR_TEST);
+
+        if let Version::GL(_, _) = caps.version {
+            gl::Disable(gl::COLOR_LOGIC_OP);
+        }
 
-        gl::ColorMask(1, 1, 1, 1);
-        state.render_state.color_write = (true, true, true, true);
+        if caps.has_sample_mask() {
+            gl::Disable(gl::SAMPLE_MASK);
+        }
 
-        gl::Disable(gl::SCISSOR_TEST);
-        state.scissor = SurfaceScissor::Disable;
+        if caps.has_provoking_vertex() {
+            gl::ProvokingVertex(gl::LAST_VERTEX_CONVENTION);
+        }
+
+        if caps.has_polygon_mode() {
+            gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+        }
+
+        if caps.has_framebuffer_srgb() {
+            gl::Disable(gl::FRAMEBUFFER_SRGB);
+        }
+
+        if caps.has_sampler_objects() {
+            for unit in 0..MAX_UNIFORM_TEXTURE_SLOTS {
+                gl::BindSampler(unit as GLuint, 0);
+            }
+        }
 
         gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
-        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        if caps.has_unpack_row_length() {
+            gl::PixelStorei(gl::UNPACK_ROW_LENGTH, 0);
+        }
+
+        // The per-object uniform blocks are binded into the ring buffer by every draw.
+        if caps.max_indexed_uniform_buffer > 0 {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, PER_OBJECT_BLOCK_BINDING, 0);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
 
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
         check()
     }
 
@@ -1189,6 +2456,41 @@ impl GLVisitor {
         Ok(())
     }
 
+    /// Specify the constant color used by constant blend factors.
+    unsafe fn set_blend_color(state: &mut GLMutableState, color: Color<f32>) -> Result<()> {
+        let rs = &mut state.render_state;
+
+        if rs.blend_color != color {
+            gl::BlendColor(color.r, color.g, color.b, color.a);
+            rs.blend_color = color;
+            check()?;
+        }
+
+        Ok(())
+    }
+
+    /// Specify the logical pixel operation for rendering.
+    unsafe fn set_logic_op(state: &mut GLMutableState, op: Option<LogicOp>) -> Result<()> {
+        let rs = &mut state.render_state;
+
+        if rs.logic_op != op {
+            if let Some(op) = op {
+                if rs.logic_op == None {
+                    gl::Enable(gl::COLOR_LOGIC_OP);
+                }
+
+                gl::LogicOp(op.into());
+            } else if rs.logic_op != None {
+                gl::Disable(gl::COLOR_LOGIC_OP);
+            }
+
+            rs.logic_op = op;
+            check()?;
+        }
+
+        Ok(())
+    }
+
     /// Enable or disable writing color elements into the color buffer.
     unsafe fn set_color_write(
         state: &mut GLMutableState,
@@ -1205,6 +2507,110 @@ impl GLVisitor {
         Ok(())
     }
 
+    /// Specify the temporary coverage value of multisampled rasterization.
+    unsafe fn set_sample_coverage(
+        state: &mut GLMutableState,
+        coverage: Option<(f32, bool)>,
+    ) -> Result<()> {
+        let rs = &mut state.render_state;
+
+        if rs.sample_coverage != coverage {
+            if let Some((value, invert)) = coverage {
+                if rs.sample_coverage == None {
+                    gl::Enable(gl::SAMPLE_COVERAGE);
+                }
+
+                gl::SampleCoverage(value, invert as u8);
+            } else if rs.sample_coverage != None {
+                gl::Disable(gl::SAMPLE_COVERAGE);
+            }
+
+            rs.sample_coverage = coverage;
+            check()?;
+        }
+
+        Ok(())
+    }
+
+    /// Specify the mask of samples that could be updated by fragments.
+    unsafe fn set_sample_mask(state: &mut GLMutableState, mask: u32) -> Result<()> {
+        let rs = &mut state.render_state;
+
+        if rs.sample_mask != mask {
+            if mask != !0 {
+                if rs.sample_mask == !0 {
+                    gl::Enable(gl::SAMPLE_MASK);
+                }
+
+                gl::SampleMaski(0, mask);
+            } else {
+                gl::Disable(gl::SAMPLE_MASK);
+            }
+
+            rs.sample_mask = mask;
+            check()?;
+        }
+
+        Ok(())
+    }
+
+    /// Specify the vertex that provides the values of flat varyings.
+    unsafe fn set_provoking_vertex(state: &mut GLMutableState, v: ProvokingVertex) -> Result<()> {
+        let rs = &mut state.render_state;
+
+        if rs.provoking_vertex != v {
+            gl::ProvokingVertex(v.into());
+            rs.provoking_vertex = v;
+            check()?;
+        }
+
+        Ok(())
+    }
+
+    /// Specify the rasterization mode of polygons.
+    unsafe fn set_polygon_mode(state: &mut GLMutableState, mode: PolygonMode) -> Result<()> {
+        let rs = &mut state.render_state;
+
+        if rs.polygon_mode != mode {
+            gl::PolygonMode(gl::FRONT_AND_BACK, mode.into());
+            rs.polygon_mode = mode;
+            check()?;
+        }
+
+        Ok(())
+    }
+
+    /// Specify whether the colors written into sRGB attachments should be encoded.
+    unsafe fn set_framebuffer_srgb(state: &mut GLMutableState, enable: bool) -> Result<()> {
+        if state.framebuffer_srgb != enable {
+            if enable {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            } else {
+                gl::Disable(gl::FRAMEBUFFER_SRGB);
+            }
+
+            state.framebuffer_srgb = enable;
+            check()?;
+        }
+
+        Ok(())
+    }
+
+    /// Binds the sampler object to all the texture units that could be used by draws, it
+    /// overrides the sampling parameters of textures until being unbinded with 0.
+    unsafe fn set_sampler(state: &mut GLMutableState, id: GLuint) -> Result<()> {
+        if state.sampler != id {
+            for unit in 0..MAX_UNIFORM_TEXTURE_SLOTS {
+                gl::BindSampler(unit as GLuint, id);
+            }
+
+            state.sampler = id;
+            check()?;
+        }
+
+        Ok(())
+    }
+
     /// Set the scissor box relative to the top-lef corner of th window, in pixels.
     unsafe fn set_scissor(state: &mut GLMutableState, scissor: SurfaceScissor) -> Result<()> {
         match scissor {
@@ -1228,7 +2634,7 @@ impl GLVisitor {
 
     /// Set the viewport relative to the top-lef corner of th window, in pixels.
     unsafe fn set_viewport(state: &mut GLMutableState, vp: SurfaceViewport) -> Result<()> {
-        if state.view != vp {
+        if state.view != vp || state.view_array {
             gl::Viewport(
                 vp.position.x,
                 vp.position.y,
@@ -1237,6 +2643,29 @@ impl GLVisitor {
             );
 
             state.view = vp;
+            state.view_array = false;
+            check()?;
+        }
+
+        Ok(())
+    }
+
+    unsafe fn set_viewport_array(
+        state: &mut GLMutableState,
+        viewports: &[Option<SurfaceViewport>],
+    ) -> Result<()> {
+        let mut v: SmallVec<[GLfloat; 16]> = SmallVec::new();
+        for vp in viewports.iter().filter_map(|v| *v) {
+            v.push(vp.position.x as GLfloat);
+            v.push(vp.position.y as GLfloat);
+            v.push(vp.size.x as GLfloat);
+            v.push(vp.size.y as GLfloat);
+        }
+
+        if !v.is_empty() {
+            gl::ViewportArrayv(0, (v.len() / 4) as GLsizei, v.as_ptr());
+            state.view = viewports[0].unwrap();
+            state.view_array = true;
             check()?;
         }
 
@@ -1276,69 +2705,75 @@ impl GLVisitor {
             Ok(())
         }
     }
+
+    unsafe fn clear_integer(format: RenderTextureFormat, value: SurfaceIntegerClear) -> Result<()> {
+        if format == RenderTextureFormat::R32I {
+            gl::ClearBufferiv(gl::COLOR, 0, value.to_i32().as_ptr());
+        } else {
+            gl::ClearBufferuiv(gl::COLOR, 0, value.to_u32().as_ptr());
+        }
+
+        check()
+    }
 }
 
 impl GLVisitor {
+    /// Recreates the offscreen framebuffer that the window framebuffer is redirected into.
+    unsafe fn update_offscreen(&mut self, mode: AaMode) -> Result<()> {
+        if let Some(mut aa) = self.antialiasing.take() {
+            aa.delete()?;
+        }
+
+        if GLAntialiasing::is_required(mode, self.output) {
+            let aa = GLAntialiasing::new(mode, self.output, &self.capabilities)?;
+            self.antialiasing = Some(aa);
+        }
+
+        self.state.clears.reset();
+        self.state.binded_surface = None;
+        self.state.binded_framebuffer = None;
+        Ok(())
+    }
+
     unsafe fn update_framebuffer_render_texture(
         &self,
+        target: GLenum,
         id: GLuint,
         params: RenderTextureParams,
         index: usize,
+        layered: bool,
     ) -> Result<()> {
-        match params.format {
-            RenderTextureFormat::RGB8 | RenderTextureFormat::RGBA4 | RenderTextureFormat::RGBA8 => {
-                let location = gl::COLOR_ATTACHMENT0 + index as u32;
-
-                if params.sampler {
-                    gl::FramebufferTexture2D(gl::FRAMEBUFFER, location, gl::TEXTURE_2D, id, 0);
-                } else {
-                    gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, location, gl::RENDERBUFFER, id);
-                }
-            }
+        let location = match params.format {
+            RenderTextureFormat::RGB8
+            | RenderTextureFormat::RGBA4
+            | RenderTextureFormat::RGBA8
+            | RenderTextureFormat::RGBA16F
+            | RenderTextureFormat::R32UI
+            | RenderTextureFormat::R32I => gl::COLOR_ATTACHMENT0 + index as u32,
             RenderTextureFormat::Depth16
             | RenderTextureFormat::Depth24
-            | RenderTextureFormat::Depth32 => {
-                if params.sampler {
-                    gl::FramebufferTexture2D(
-                        gl::FRAMEBUFFER,
-                        gl::DEPTH_ATTACHMENT,
-                        gl::TEXTURE_2D,
-                        id,
-                        0,
-                    );
-                } else {
-                    gl::FramebufferRenderbuffer(
-                        gl::FRAMEBUFFER,
-                        gl::DEPTH_ATTACHMENT,
-                        gl::RENDERBUFFER,
-                        id,
-                    );
-                }
+            | RenderTextureFormat::Depth32 => gl::DEPTH_ATTACHMENT,
+            RenderTextureFormat::Depth24Stencil8 => gl::DEPTH_STENCIL_ATTACHMENT,
+        };
+
+        if params.sampler {
+            if layered {
+                gl::FramebufferTexture(target, location, id, 0);
+            } else {
+                gl::FramebufferTexture2D(target, location, gl::TEXTURE_2D, id, 0);
             }
-            RenderTextureFormat::Depth24Stencil8 => {
-                if params.sampler {
-                    gl::FramebufferTexture2D(
-                        gl::FRAMEBUFFER,
-                        gl::DEPTH_STENCIL_ATTACHMENT,
-                        gl::TEXTURE_2D,
-                        id,
-                        0,
-                    );
-                } else {
-                    gl::FramebufferRenderbuffer(
-                        gl::FRAMEBUFFER,
-                        gl::DEPTH_STENCIL_ATTACHMENT,
-                        gl::RENDERBUFFER,
-                        id,
-                    );
-                }
+        } else {
+            if layered {
+                bail!("Render buffer could not be attached as layered attachment.");
             }
+
+            gl::FramebufferRenderbuffer(target, location, gl::RENDERBUFFER, id);
         }
 
         check()
     }
 
-    unsafe fn compile(shader: GLenum, src: &str) -> Result<GLuint> {
+    pub(super) unsafe fn compile(shader: GLenum, src: &str) -> Result<GLuint> {
         let shader = gl::CreateShader(shader);
         // Attempt to compile the shader
         let c_str = ::std::ffi::CString::new(src.as_bytes()).unwrap();
@@ -1368,7 +2803,18 @@ impl GLVisitor {
         }
     }
 
-    unsafe fn link<'a, T>(shaders: T) -> Result<GLuint>
+    #[inline]
+    pub(super) unsafe fn link<'a, T>(shaders: T) -> Result<GLuint>
+    where
+        T: IntoIterator<Item = &'a GLuint>,
+    {
+        Self::link_with_locations(shaders, &AttributeLocations::default())
+    }
+
+    unsafe fn link_with_locations<'a, T>(
+        shaders: T,
+        locations: &AttributeLocations,
+    ) -> Result<GLuint>
     where
         T: IntoIterator<Item = &'a GLuint>,
     {
@@ -1377,6 +2823,14 @@ impl GLVisitor {
             gl::AttachShader(program, *shader)
         }
 
+        // The explicit locations only take effects after linking.
+        for (name, location) in locations.iter() {
+            let name: &'static str = name.into();
+            let c_name = ::std::ffi::CString::new(name.as_bytes()).unwrap();
+            gl::BindAttribLocation(program, GLuint::from(location), c_name.as_ptr());
+        }
+
+        check()?;
         gl::LinkProgram(program);
         // Get the link status
         let mut status = GLint::from(gl::FALSE);
@@ -1401,6 +2855,56 @@ impl GLVisitor {
         }
     }
 
+    /// Assigns the per-object uniform block of program to `PER_OBJECT_BLOCK_BINDING`, and
+    /// returns the data size of it.
+    unsafe fn per_object_block(program: GLuint) -> Result<Option<usize>> {
+        let c_name = ::std::ffi::CString::new(PER_OBJECT_BLOCK.as_bytes()).unwrap();
+        let index = gl::GetUniformBlockIndex(program, c_name.as_ptr());
+        if index == gl::INVALID_INDEX {
+            return Ok(None);
+        }
+
+        gl::UniformBlockBinding(program, index, PER_OBJECT_BLOCK_BINDING);
+
+        let mut size = 0;
+        gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_DATA_SIZE, &mut size);
+        check()?;
+
+        Ok(Some(size as usize))
+    }
+
+    /// Gets the indices and data sizes of the uniform blocks declared in `params`.
+    unsafe fn uniform_blocks(
+        program: GLuint,
+        params: &ShaderParams,
+    ) -> Result<FastHashMap<HashValue<str>, (GLuint, usize)>> {
+        let mut blocks = FastHashMap::default();
+        for &(ref name, tp) in params.uniforms.iter() {
+            if tp != UniformVariableType::UniformBuffer {
+                continue;
+            }
+
+            if name == PER_OBJECT_BLOCK {
+                bail!("Uniform block {} is reserved for per-object data.", name);
+            }
+
+            let c_name = ::std::ffi::CString::new(name.as_bytes()).unwrap();
+            let index = gl::GetUniformBlockIndex(program, c_name.as_ptr());
+            if index == gl::INVALID_INDEX {
+                bail!("Uniform({:?}) is undefined in shader sources.", name);
+            }
+
+            let mut size = 0;
+            gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_DATA_SIZE, &mut size);
+            check()?;
+
+            let hash: HashValue<str> = name.as_str().into();
+            blocks.insert(hash, (index, size as usize));
+        }
+
+        Ok(blocks)
+    }
+
     unsafe fn create_buffer(
         &mut self,
         tp: GLuint,
@@ -1435,41 +2939,181 @@ impl GLVisitor {
         check()
     }
 
-    unsafe fn bind_texture_params(
-        wrap: TextureWrap,
+    /// Specify the alignment requirements for the start of each pixel row in memory.
+    unsafe fn set_unpack_alignment(state: &mut GLMutableState, alignment: u32) -> Result<()> {
+        if state.unpack_alignment != alignment as GLint {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment as GLint);
+            state.unpack_alignment = alignment as GLint;
+            check()?;
+        }
+
+        Ok(())
+    }
+
+    unsafe fn create_sampler(sampler: SurfaceSampler) -> Result<GLuint> {
+        let mut id = 0;
+        gl::GenSamplers(1, &mut id);
+        assert!(id != 0);
+
+        let wrap: GLenum = sampler.wrap.into();
+        let filter = match sampler.filter {
+            TextureFilter::Nearest => gl::NEAREST,
+            TextureFilter::Linear => gl::LINEAR,
+        };
+
+        gl::SamplerParameteri(id, gl::TEXTURE_WRAP_S, wrap as GLint);
+        gl::SamplerParameteri(id, gl::TEXTURE_WRAP_T, wrap as GLint);
+        gl::SamplerParameteri(id, gl::TEXTURE_MIN_FILTER, filter as GLint);
+        gl::SamplerParameteri(id, gl::TEXTURE_MAG_FILTER, filter as GLint);
+        check()?;
+
+        Ok(id)
+    }
+
+    /// Copies the color of `src` into `dst` with a full-screen pass, which is the fallback
+    /// of blits that `glBlitFramebuffer` could not convert the formats of.
+    unsafe fn blit_with_pass(
+        &mut self,
+        src: GLRenderTextureData,
+        dst: GLRenderTextureData,
         filter: TextureFilter,
-        levels: u32,
     ) -> Result<()> {
-        let wrap: GLenum = wrap.into();
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap as GLint);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap as GLint);
-
-        match filter {
-            TextureFilter::Nearest => {
-                let min_filter = if levels > 1 {
-                    gl::NEAREST_MIPMAP_NEAREST
-                } else {
-                    gl::NEAREST
-                };
+        if !src.params.sampler {
+            bail!(
+                "[GL] {:?} could not be blitted into {:?} by this GL Context, unless the \
+                 source could be sampled.",
+                src.params.format,
+                dst.params.format
+            );
+        }
 
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
-            }
-            TextureFilter::Linear => {
-                let min_filter = if levels > 1 {
-                    gl::LINEAR_MIPMAP_LINEAR
-                } else {
-                    gl::LINEAR
-                };
+        if self.blit_pass.is_none() {
+            self.blit_pass = Some(GLOutputPass::new(BLIT_FS)?);
+        }
+
+        Self::reset_render_state(&mut self.state)?;
+        self.state.binded_surface = None;
+
+        let mut fbo = 0;
+        gl::GenFramebuffers(1, &mut fbo);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+        self.update_framebuffer_render_texture(gl::FRAMEBUFFER, dst.id, dst.params, 0, false)?;
+
+        let vp = SurfaceViewport {
+            position: Vector2::new(0, 0),
+            size: dst.params.dimensions,
+        };
+
+        Self::set_viewport(&mut self.state, vp)?;
+
+        // Samples the source with the filter of blit temporarily.
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, src.id);
+        let wrap = (src.params.wrap, src.params.wrap);
+        Self::bind_texture_params(gl::TEXTURE_2D, wrap, (filter, filter, None), (0, 0))?;
+
+        let pass = self.blit_pass.as_ref().unwrap();
+        pass.draw(src.id, src.params.dimensions, OutputAdjustment::default());
+        let filter = (src.params.filter, src.params.filter, None);
+        Self::bind_texture_params(gl::TEXTURE_2D, wrap, filter, (0, 0))?;
+
+        self.state.binded_shader = None;
+        self.state.binded_vao = None;
+        self.state.binded_texture_index = 0;
+        if let Some(v) = self.state.binded_textures.get_mut(0) {
+            *v = None;
+        }
+
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl::DeleteFramebuffers(1, &fbo);
+        check()
+    }
+
+    /// Disables the generation of mipmaps if it's not supported for texture of `params`.
+    fn resolve_mipmap(&self, handle: TextureHandle, params: TextureParams) -> TextureParams {
+        let mut params = params;
+        if params.mipmap && !self.capabilities.has_generate_mipmap(params.dimensions) {
+            warn!(
+                "[GL] Mipmaps of {:?} could not be generated by {}.",
+                handle, self.capabilities.renderer
+            );
+
+            params.mipmap = false;
+        }
+
+        params
+    }
+
+    /// Uploads the mipmap levels in `data` into `target`, which is either `TEXTURE_2D` or
+    /// one of the faces of cube map.
+    unsafe fn upload_texture(
+        &mut self,
+        target: GLenum,
+        params: TextureParams,
+        data: &TextureData,
+    ) -> Result<()> {
+        let (internal_format, format, pixel_type) =
+            types::texture_format(params.format, &self.capabilities);
 
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+        let mut dims = (
+            params.dimensions.x as GLsizei,
+            params.dimensions.y as GLsizei,
+        );
+
+        for (i, v) in data.bytes.iter().enumerate() {
+            if params.format.compressed() {
+                gl::CompressedTexImage2D(
+                    target,
+                    i as GLint,
+                    internal_format,
+                    dims.0,
+                    dims.1,
+                    0,
+                    v.len() as GLint,
+                    &v[0] as *const u8 as *const ::std::os::raw::c_void,
+                );
+            } else {
+                let alignment = params.format.unpack_alignment(dims.0 as u32);
+                Self::set_unpack_alignment(&mut self.state, alignment)?;
+
+                gl::TexImage2D(
+                    target,
+                    i as GLint,
+                    internal_format as GLint,
+                    dims.0,
+                    dims.1,
+                    0,
+                    format,
+                    pixel_type,
+                    &v[0] as *const u8 as *const ::std::os::raw::c_void,
+                );
             }
+
+            dims.0 = (dims.0 / 2).max(1);
+            dims.1 = (dims.1 / 2).max(1);
         }
 
-        if levels > 1 {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_BASE_LEVEL, 0);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, (levels - 1) as GLint);
+        Ok(())
+    }
+
+    unsafe fn bind_texture_params(
+        target: GLenum,
+        (wrap_s, wrap_t): (TextureWrap, TextureWrap),
+        (min, mag, mip): (TextureFilter, TextureFilter, Option<TextureFilter>),
+        (base, max): (u32, u32),
+    ) -> Result<()> {
+        let (wrap_s, wrap_t): (GLenum, GLenum) = (wrap_s.into(), wrap_t.into());
+        gl::TexParameteri(target, gl::TEXTURE_WRAP_S, wrap_s as GLint);
+        gl::TexParameteri(target, gl::TEXTURE_WRAP_T, wrap_t as GLint);
+
+        let min_filter = types::filter(min, mip.filter(|_| max > base));
+        let mag_filter = types::filter(mag, None);
+        gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+        gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, mag_filter as GLint);
+
+        if max > 0 {
+            gl::TexParameteri(target, gl::TEXTURE_BASE_LEVEL, base as GLint);
+            gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, max as GLint);
         }
 
         Ok(())
@@ -1525,10 +3169,36 @@ unsafe fn check_capabilities(caps: &Capabilities) -> Result<()> {
         bail!("The OpenGL implementation does not supports vertex array objects.");
     }
 
+    if caps.version < Version::GL(3, 0) {
+        if !caps.extensions.gl_arb_vertex_array_object {
+            info!("[GL] Falls back to vendor specific vertex array object extension.");
+        }
+
+        if !caps.extensions.gl_arb_framebuffer_object && caps.extensions.gl_ext_framebuffer_object
+        {
+            info!("[GL] Falls back to EXT_framebuffer_object.");
+        }
+    }
+
+    if caps.max_color_attachments <= 1 {
+        warn!(
+            "[GL] Only {} color attachment(s) is available, MRT is disabled.",
+            caps.max_color_attachments
+        );
+    }
+
     Ok(())
 }
 
-unsafe fn check() -> Result<()> {
+/// Whether the context is created without error reporting, the errors are never checked in
+/// that case since `glGetError` is meaningless.
+static NO_ERROR: AtomicBool = AtomicBool::new(false);
+
+pub(super) unsafe fn check() -> Result<()> {
+    if NO_ERROR.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+
     match gl::GetError() {
         gl::NO_ERROR => Ok(()),
 
@@ -1551,3 +3221,42 @@ unsafe fn check() -> Result<()> {
         _ => bail!("[GL] Oops, Unknown OpenGL error."),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn invalidate() {
+        let mut state = GLMutableState::new();
+        state.render_state.polygon_mode = PolygonMode::Line;
+        state.render_state.logic_op = Some(LogicOp::Xor);
+        state.render_state.sample_mask = 0x0f;
+        state.scissor = SurfaceScissor::Enable {
+            position: Vector2::new(0, 0),
+            size: Vector2::new(8, 8),
+        };
+        state.view_array = true;
+        state.binded_surface = Some(SurfaceHandle::default());
+        state.binded_framebuffer = Some(1);
+        state.binded_shader = Some(ShaderHandle::default());
+        state.binded_texture_index = 2;
+        state.binded_textures.push(None);
+        state.unpack_alignment = 4;
+        state.framebuffer_srgb = true;
+        state.sampler = 3;
+
+        state.invalidate();
+        assert_eq!(state.render_state, RenderState::default());
+        assert_eq!(state.scissor, SurfaceScissor::Disable);
+        assert!(!state.view_array);
+        assert_eq!(state.binded_surface, None);
+        assert_eq!(state.binded_framebuffer, None);
+        assert_eq!(state.binded_shader, None);
+        assert_eq!(state.binded_texture_index, 0);
+        assert!(state.binded_textures.is_empty());
+        assert_eq!(state.unpack_alignment, 1);
+        assert!(!state.framebuffer_srgb);
+        assert_eq!(state.sampler, 0);
+    }
+}
@@ -1,14 +1,24 @@
+use std::time::Duration;
+
 use super::super::assets::prelude::*;
-use super::{UniformVar, Visitor};
+use super::frame::{DrawCommand, FrameStats, MemoryBarrier};
+use super::utils::{mesh_elements, DataVec};
+use super::{DeviceLimits, UniformVar, Visitor};
 
 use crate::errors::*;
 use crate::math::prelude::{Aabb2, Vector2};
 
-pub struct HeadlessVisitor {}
+/// A visitor without any device. It keeps the parameters of meshes only, so draws could
+/// report the number of primitives they would assemble.
+pub struct HeadlessVisitor {
+    meshes: DataVec<MeshParams>,
+}
 
 impl HeadlessVisitor {
     pub fn new() -> Self {
-        HeadlessVisitor {}
+        HeadlessVisitor {
+            meshes: DataVec::new(),
+        }
     }
 }
 
@@ -44,7 +54,26 @@ impl Visitor for HeadlessVisitor {
         Ok(())
     }
 
-    unsafe fn update_texture(&mut self, _: TextureHandle, _: Aabb2<u32>, _: &[u8]) -> Result<()> {
+    unsafe fn create_cube_texture(
+        &mut self,
+        _: TextureHandle,
+        _: TextureParams,
+        _: Option<CubeTextureData>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn import_texture(&mut self, _: TextureHandle, _: TextureParams, _: u32) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn update_texture(
+        &mut self,
+        _: TextureHandle,
+        _: Aabb2<u32>,
+        _: u32,
+        _: &[u8],
+    ) -> Result<()> {
         Ok(())
     }
 
@@ -60,16 +89,25 @@ impl Visitor for HeadlessVisitor {
         Ok(())
     }
 
+    unsafe fn resize_render_texture(
+        &mut self,
+        _: RenderTextureHandle,
+        _: Vector2<u32>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
     unsafe fn delete_render_texture(&mut self, _: RenderTextureHandle) -> Result<()> {
         Ok(())
     }
 
     unsafe fn create_mesh(
         &mut self,
-        _: MeshHandle,
-        _: MeshParams,
+        handle: MeshHandle,
+        params: MeshParams,
         _: Option<MeshData>,
     ) -> Result<()> {
+        self.meshes.create(handle, params);
         Ok(())
     }
 
@@ -81,22 +119,114 @@ impl Visitor for HeadlessVisitor {
         Ok(())
     }
 
-    unsafe fn delete_mesh(&mut self, _: MeshHandle) -> Result<()> {
+    unsafe fn delete_mesh(&mut self, handle: MeshHandle) -> Result<()> {
+        self.meshes.free(handle);
         Ok(())
     }
 
-    unsafe fn bind(&mut self, _: SurfaceHandle, _: Vector2<u32>) -> Result<()> {
+    unsafe fn create_instance_buffer(
+        &mut self,
+        _: InstanceBufferHandle,
+        _: InstanceBufferParams,
+        _: Option<&[u8]>,
+    ) -> Result<()> {
         Ok(())
     }
 
-    unsafe fn draw(
+    unsafe fn update_instance_buffer(
         &mut self,
-        _: ShaderHandle,
-        _: MeshHandle,
-        _: MeshIndex,
-        _: &[UniformVar],
-    ) -> Result<u32> {
-        Ok(0)
+        _: InstanceBufferHandle,
+        _: usize,
+        _: &[u8],
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn delete_instance_buffer(&mut self, _: InstanceBufferHandle) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn create_shader_storage_buffer(
+        &mut self,
+        _: ShaderStorageBufferHandle,
+        _: ShaderStorageBufferParams,
+        _: Option<&[u8]>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn update_shader_storage_buffer(
+        &mut self,
+        _: ShaderStorageBufferHandle,
+        _: usize,
+        _: &[u8],
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn delete_shader_storage_buffer(&mut self, _: ShaderStorageBufferHandle) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn read_shader_storage_buffer(&mut self, _: ShaderStorageBufferHandle) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn create_uniform_buffer(
+        &mut self,
+        _: UniformBufferHandle,
+        _: UniformBufferParams,
+        _: Option<&[u8]>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn update_uniform_buffer(
+        &mut self,
+        _: UniformBufferHandle,
+        _: usize,
+        _: &[u8],
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn delete_uniform_buffer(&mut self, _: UniformBufferHandle) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn read_render_texture(&mut self, _: RenderTextureHandle, _: Aabb2<u32>) -> Result<()> {
+        Ok(())
+    }
+
+    fn take_readbacks(&mut self) -> Vec<(VideoHandle, Vec<u8>)> {
+        Vec::new()
+    }
+
+    unsafe fn begin_gpu_timer(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn end_gpu_timer(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn take_gpu_timers(&mut self) -> Vec<(String, Duration)> {
+        Vec::new()
+    }
+
+    unsafe fn bind(&mut self, _: SurfaceHandle, _: Vector2<u32>) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn draw(&mut self, dc: DrawCommand, _: &[UniformVar], _: Option<&[u8]>) -> Result<u32> {
+        let params = self
+            .meshes
+            .get(dc.mesh)
+            .ok_or_else(|| format_err!("{:?} is invalid.", dc.mesh))?;
+
+        let (_, len) = mesh_elements(params, dc.mesh_index)?;
+        let num = dc.num_instances.unwrap_or(1);
+        Ok(params.primitive.assemble(len as u32) * num)
     }
 
     unsafe fn update_surface_scissor(&mut self, _: SurfaceScissor) -> Result<()> {
@@ -107,6 +237,35 @@ impl Visitor for HeadlessVisitor {
         Ok(())
     }
 
+    unsafe fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn flush_gpu(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn memory_barrier(&mut self, _: MemoryBarrier) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn blit(
+        &mut self,
+        _: RenderTextureHandle,
+        _: RenderTextureHandle,
+        _: TextureFilter,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn invalidate_state_cache(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn set_fallback_texture(&mut self, _: Option<TextureHandle>) -> Result<()> {
+        Ok(())
+    }
+
     unsafe fn flush(&mut self) -> Result<()> {
         Ok(())
     }
@@ -114,4 +273,50 @@ impl Visitor for HeadlessVisitor {
     unsafe fn advance(&mut self) -> Result<()> {
         Ok(())
     }
+
+    unsafe fn set_antialiasing(&mut self, _: AaMode) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn set_output_adjustment(&mut self, _: OutputAdjustment) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn set_max_frame_latency(&mut self, _: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_format_supported(&self, _: RenderTextureFormat, _: FormatUsage) -> bool {
+        true
+    }
+
+    fn is_texture_format_supported(&self, _: TextureFormat) -> bool {
+        true
+    }
+
+    fn limits(&self) -> DeviceLimits {
+        DeviceLimits {
+            max_texture_size: 16384,
+            max_color_attachments: 8,
+            max_vertex_attributes: 16,
+            max_combined_texture_units: 32,
+            max_samples: 8,
+        }
+    }
+
+    fn stats(&self) -> FrameStats {
+        FrameStats::default()
+    }
+
+    fn native_texture(&self, _: TextureHandle) -> Option<u32> {
+        None
+    }
+
+    unsafe fn capture(&mut self, dimensions: Vector2<u32>) -> Result<Image> {
+        let len = (dimensions.x * dimensions.y * 4) as usize;
+        Ok(Image {
+            dimensions,
+            pixels: vec![0; len],
+        })
+    }
 }
@@ -3,16 +3,37 @@
 
 pub mod frame;
 pub mod headless;
+pub mod upload;
 mod utils;
 
+use std::time::Duration;
+
 use super::assets::prelude::*;
 
 use crate::errors::*;
 use crate::math::prelude::{Aabb2, Vector2};
 use crate::utils::hash_value::HashValue;
 
+use self::frame::{DrawCommand, FrameStats, MemoryBarrier};
+
 pub type UniformVar = (HashValue<str>, UniformVariable);
 
+/// The implementation-dependent limits of device, which never change once the context
+/// has been created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceLimits {
+    /// Maximum width and height of textures and render textures.
+    pub max_texture_size: u32,
+    /// Maximum number of color attachments of surfaces.
+    pub max_color_attachments: u32,
+    /// Maximum number of vertex attributes of shaders.
+    pub max_vertex_attributes: u32,
+    /// Maximum number of textures that could be sampled by a shader.
+    pub max_combined_texture_units: u32,
+    /// Maximum number of samples of multisampled framebuffers, `0` if not supported.
+    pub max_samples: u32,
+}
+
 pub trait Visitor {
     unsafe fn create_surface(&mut self, handle: SurfaceHandle, params: SurfaceParams)
         -> Result<()>;
@@ -36,10 +57,29 @@ pub trait Visitor {
         bytes: Option<TextureData>,
     ) -> Result<()>;
 
+    /// Creates a cube map with the data of six faces.
+    unsafe fn create_cube_texture(
+        &mut self,
+        handle: TextureHandle,
+        params: TextureParams,
+        data: Option<CubeTextureData>,
+    ) -> Result<()>;
+
+    /// Wraps the texture object `id` which is created outside, it will not be deleted
+    /// along with the `handle`.
+    unsafe fn import_texture(
+        &mut self,
+        handle: TextureHandle,
+        params: TextureParams,
+        id: u32,
+    ) -> Result<()>;
+
+    /// Updates the `area` of texture with `bytes`, whose rows are `row_length` pixels apart.
     unsafe fn update_texture(
         &mut self,
         handle: TextureHandle,
         area: Aabb2<u32>,
+        row_length: u32,
         bytes: &[u8],
     ) -> Result<()>;
 
@@ -51,6 +91,14 @@ pub trait Visitor {
         params: RenderTextureParams,
     ) -> Result<()>;
 
+    /// Reallocates the storage of render texture with `dimensions`, the contents are
+    /// discarded while the surfaces that attach it are kept.
+    unsafe fn resize_render_texture(
+        &mut self,
+        handle: RenderTextureHandle,
+        dimensions: Vector2<u32>,
+    ) -> Result<()>;
+
     unsafe fn delete_render_texture(&mut self, handle: RenderTextureHandle) -> Result<()>;
 
     unsafe fn create_mesh(
@@ -76,26 +124,160 @@ pub trait Visitor {
 
     unsafe fn delete_mesh(&mut self, handle: MeshHandle) -> Result<()>;
 
+    unsafe fn create_instance_buffer(
+        &mut self,
+        handle: InstanceBufferHandle,
+        params: InstanceBufferParams,
+        data: Option<&[u8]>,
+    ) -> Result<()>;
+
+    unsafe fn update_instance_buffer(
+        &mut self,
+        handle: InstanceBufferHandle,
+        o: usize,
+        bytes: &[u8],
+    ) -> Result<()>;
+
+    unsafe fn delete_instance_buffer(&mut self, handle: InstanceBufferHandle) -> Result<()>;
+
+    unsafe fn create_shader_storage_buffer(
+        &mut self,
+        handle: ShaderStorageBufferHandle,
+        params: ShaderStorageBufferParams,
+        data: Option<&[u8]>,
+    ) -> Result<()>;
+
+    unsafe fn update_shader_storage_buffer(
+        &mut self,
+        handle: ShaderStorageBufferHandle,
+        o: usize,
+        bytes: &[u8],
+    ) -> Result<()>;
+
+    unsafe fn delete_shader_storage_buffer(
+        &mut self,
+        handle: ShaderStorageBufferHandle,
+    ) -> Result<()>;
+
+    /// Reads back the contents of shader storage buffer, the results are retained until
+    /// being taken with `take_readbacks`.
+    unsafe fn read_shader_storage_buffer(
+        &mut self,
+        handle: ShaderStorageBufferHandle,
+    ) -> Result<()>;
+
+    unsafe fn create_uniform_buffer(
+        &mut self,
+        handle: UniformBufferHandle,
+        params: UniformBufferParams,
+        data: Option<&[u8]>,
+    ) -> Result<()>;
+
+    unsafe fn update_uniform_buffer(
+        &mut self,
+        handle: UniformBufferHandle,
+        o: usize,
+        bytes: &[u8],
+    ) -> Result<()>;
+
+    unsafe fn delete_uniform_buffer(&mut self, handle: UniformBufferHandle) -> Result<()>;
+
+    /// Reads back the pixels of `area` in render texture, the results are retained until
+    /// being taken with `take_readbacks`.
+    unsafe fn read_render_texture(
+        &mut self,
+        handle: RenderTextureHandle,
+        area: Aabb2<u32>,
+    ) -> Result<()>;
+
+    /// Takes the contents of shader storage buffers and render textures that have been
+    /// read back.
+    fn take_readbacks(&mut self) -> Vec<(VideoHandle, Vec<u8>)>;
+
+    /// Starts the GPU timer `name`, which measures the elapsed time of the following
+    /// commands until `end_gpu_timer`. It's a no-op if timer queries are not supported.
+    unsafe fn begin_gpu_timer(&mut self, name: &str) -> Result<()>;
+
+    /// Stops the running GPU timer.
+    unsafe fn end_gpu_timer(&mut self) -> Result<()>;
+
+    /// Takes the measurements of GPU timers that have been completed since last taking.
+    fn take_gpu_timers(&mut self) -> Vec<(String, Duration)>;
+
     unsafe fn bind(&mut self, surface: SurfaceHandle, dimensions: Vector2<u32>) -> Result<()>;
 
+    /// Draws with the uniform variables, and the optional data of per-object uniform block.
     unsafe fn draw(
         &mut self,
-        shader: ShaderHandle,
-        mesh: MeshHandle,
-        mesh_index: MeshIndex,
+        dc: DrawCommand,
         vars: &[UniformVar],
+        per_object: Option<&[u8]>,
     ) -> Result<u32>;
 
     unsafe fn update_surface_scissor(&mut self, scissor: SurfaceScissor) -> Result<()>;
 
     unsafe fn update_surface_viewport(&mut self, vp: SurfaceViewport) -> Result<()>;
 
-    /// Blocks until all execution is complete. Such effects include all changes to render state, all
-    /// changes to connection state, and all changes to the frame buffer contents.
+    /// Ends the frame, and blocks until the GPU catches up with the latency bound set by
+    /// `set_max_frame_latency`. With zero latency, it blocks until all execution is complete.
+    /// Otherwise a fence is inserted after the commands of this frame, and it only waits on
+    /// the fence inserted `max_frame_latency` frames back.
     unsafe fn flush(&mut self) -> Result<()>;
 
+    /// Blocks until all the previously issued commands have been completed by GPU.
+    unsafe fn finish(&mut self) -> Result<()>;
+
+    /// Forces all the previously issued commands to be submitted to GPU without waiting.
+    unsafe fn flush_gpu(&mut self) -> Result<()>;
+
+    /// Orders the memory accesses of `barriers` after the incoherent writes of shaders.
+    unsafe fn memory_barrier(&mut self, barriers: MemoryBarrier) -> Result<()>;
+
+    /// Copies the whole `src` into `dst`, scaling with `filter` if their dimensions differ.
+    unsafe fn blit(
+        &mut self,
+        src: RenderTextureHandle,
+        dst: RenderTextureHandle,
+        filter: TextureFilter,
+    ) -> Result<()>;
+
+    /// Discards the cached states of context, and applies them again, which is necessary
+    /// after the context is modified by the native OpenGL calls outside.
+    unsafe fn invalidate_state_cache(&mut self) -> Result<()>;
+
+    /// Sets the texture which is bound instead of the textures that are not ready.
+    unsafe fn set_fallback_texture(&mut self, handle: Option<TextureHandle>) -> Result<()>;
+
     /// Advance one frame, it will be called every frames.
     unsafe fn advance(&mut self) -> Result<()>;
+
+    /// Sets the antialiasing mode of the window framebuffer.
+    unsafe fn set_antialiasing(&mut self, mode: AaMode) -> Result<()>;
+
+    /// Sets the adjustment of colors that presented to the window framebuffer.
+    unsafe fn set_output_adjustment(&mut self, adjustment: OutputAdjustment) -> Result<()>;
+
+    /// Sets the maximum number of frames that could be queued on GPU when flushing, the
+    /// flushes block until the GPU catches up once it's exceeded.
+    unsafe fn set_max_frame_latency(&mut self, frames: u32) -> Result<()>;
+
+    /// Checks if the render texture format could be used for `usage`.
+    fn is_format_supported(&self, format: RenderTextureFormat, usage: FormatUsage) -> bool;
+
+    /// Checks if textures could be created with `format`.
+    fn is_texture_format_supported(&self, format: TextureFormat) -> bool;
+
+    /// Gets the limits of device.
+    fn limits(&self) -> DeviceLimits;
+
+    /// Gets the statistics of the frame that has been dispatched since last `advance`.
+    fn stats(&self) -> FrameStats;
+
+    /// Gets the native texture object of `handle`, if it has been created.
+    fn native_texture(&self, handle: TextureHandle) -> Option<u32>;
+
+    /// Reads back the pixels of default framebuffer.
+    unsafe fn capture(&mut self, dimensions: Vector2<u32>) -> Result<Image>;
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -0,0 +1,6 @@
+uniform sampler2D u_Texture;
+varying vec2 v_Texcoord;
+
+void main() {
+    gl_FragColor = texture2D(u_Texture, v_Texcoord);
+}
@@ -0,0 +1,7 @@
+attribute vec2 a_Position;
+varying vec2 v_Texcoord;
+
+void main() {
+    v_Texcoord = a_Position * 0.5 + 0.5;
+    gl_Position = vec4(a_Position, 0.0, 1.0);
+}
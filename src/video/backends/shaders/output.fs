@@ -0,0 +1,8 @@
+// The reciprocal of gamma, brightness and contrast.
+uniform vec3 u_Output;
+
+vec4 output_adjust(vec4 color) {
+    vec3 rgb = pow(max(color.rgb, vec3(0.0)), vec3(u_Output.x));
+    rgb = (rgb - 0.5) * u_Output.z + 0.5 + u_Output.y;
+    return vec4(clamp(rgb, 0.0, 1.0), color.a);
+}
@@ -0,0 +1,213 @@
+//! Defers the uploads of resources to subsequent frames once the bytes uploaded in current
+//! frame exceed the budget, which smooths the frame times during streaming.
+
+use std::mem;
+
+use crate::errors::*;
+use crate::math::prelude::Vector2;
+use crate::utils::hash::FastHashSet;
+use crate::utils::prelude::DataBuffer;
+
+use super::super::assets::prelude::*;
+use super::frame::{Command, Frame};
+use super::Visitor;
+
+/// The queue of deferred uploads, which is carried across frames by the device.
+///
+/// Only the creations of textures and meshes with initial data are counted as uploads. The
+/// following commands that touch the deferred resources (e.g. updates and deletions) are
+/// deferred along with them in order, while the draws just skip the resources that have not
+/// been created yet.
+#[derive(Default)]
+pub(crate) struct UploadQueue {
+    pending: Frame,
+    textures: FastHashSet<TextureHandle>,
+    meshes: FastHashSet<MeshHandle>,
+    budget: Option<usize>,
+    uploaded: usize,
+}
+
+impl UploadQueue {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the maximum bytes of uploads per frame. The uploads are never deferred if it's
+    /// `None`.
+    #[inline]
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+    }
+
+    /// Gets the number of commands that have been deferred.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pending.cmds.len()
+    }
+
+    /// Starts a new frame, and executes the deferred commands within the budget.
+    pub unsafe fn advance(
+        &mut self,
+        visitor: &mut dyn Visitor,
+        dimensions: Vector2<u32>,
+    ) -> Result<()> {
+        self.uploaded = 0;
+        self.textures.clear();
+        self.meshes.clear();
+
+        let mut pending = mem::replace(&mut self.pending, Frame::with_capacity(0));
+        for cmd in mem::replace(&mut pending.cmds, Vec::new()) {
+            self.execute(&pending.bufs, visitor, dimensions, cmd)?;
+        }
+
+        Ok(())
+    }
+
+    /// Executes `cmd`, or defers it if the budget has been exhausted.
+    pub unsafe fn execute(
+        &mut self,
+        bufs: &DataBuffer,
+        visitor: &mut dyn Visitor,
+        dimensions: Vector2<u32>,
+        cmd: Command,
+    ) -> Result<(u32, u32)> {
+        if !self.defer(&cmd) {
+            return Frame::execute(bufs, visitor, dimensions, cmd);
+        }
+
+        match cmd {
+            Command::UpdateTexture(..)
+            | Command::UpdateVertexBuffer(..)
+            | Command::UpdateIndexBuffer(..) => self.pending.append(bufs, &cmd),
+            _ => self.pending.cmds.push(cmd),
+        }
+
+        Ok((0, 0))
+    }
+
+    fn defer(&mut self, cmd: &Command) -> bool {
+        match *cmd {
+            Command::CreateTexture(ref v) => {
+                let deferred = !self.consume(v.2.as_ref().map(texture_len).unwrap_or(0));
+                if deferred {
+                    self.textures.insert(v.0);
+                }
+
+                deferred
+            }
+            Command::CreateTextures(ref v) => {
+                let len = v.iter().filter_map(|v| v.2.as_ref()).map(texture_len).sum();
+                let deferred = !self.consume(len);
+                if deferred {
+                    self.textures.extend(v.iter().map(|v| v.0));
+                }
+
+                deferred
+            }
+            Command::CreateCubeTexture(ref v) => {
+                let len = v.2.as_ref().map(|v| v.faces.iter().map(texture_len).sum());
+                let deferred = !self.consume(len.unwrap_or(0));
+                if deferred {
+                    self.textures.insert(v.0);
+                }
+
+                deferred
+            }
+            Command::CreateMesh(ref v) => {
+                let deferred = !self.consume(mesh_len(&v.2));
+                if deferred {
+                    self.meshes.insert(v.0);
+                }
+
+                deferred
+            }
+            Command::CreateMeshes(ref v) => {
+                let deferred = !self.consume(v.iter().map(|v| mesh_len(&v.2)).sum());
+                if deferred {
+                    self.meshes.extend(v.iter().map(|v| v.0));
+                }
+
+                deferred
+            }
+            Command::UpdateTexture(handle, _, _, _) | Command::DeleteTexture(handle) => {
+                self.textures.contains(&handle)
+            }
+            Command::UpdateVertexBuffer(handle, _, _)
+            | Command::UpdateIndexBuffer(handle, _, _)
+            | Command::DeleteMesh(handle) => self.meshes.contains(&handle),
+            _ => false,
+        }
+    }
+
+    /// Consumes `len` bytes of the budget, returns false if it has been exhausted. The first
+    /// upload of frame is always allowed, so the ones larger than budget still progress.
+    fn consume(&mut self, len: usize) -> bool {
+        if let Some(budget) = self.budget {
+            if self.uploaded > 0 && self.uploaded + len > budget {
+                return false;
+            }
+        }
+
+        self.uploaded += len;
+        true
+    }
+}
+
+fn texture_len(data: &TextureData) -> usize {
+    data.bytes.iter().map(|v| v.len()).sum()
+}
+
+fn mesh_len(data: &Option<MeshData>) -> usize {
+    data.as_ref()
+        .map(|v| v.vptr.len() + v.iptr.len())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::headless::HeadlessVisitor;
+    use super::*;
+    use crate::math::prelude::Aabb2;
+    use crate::utils::handle::HandleLike;
+
+    #[test]
+    fn budget() {
+        let mut visitor = HeadlessVisitor::new();
+        let mut uploads = UploadQueue::new();
+        uploads.set_budget(Some(4));
+
+        let bufs = DataBuffer::with_capacity(0);
+        let dims = Vector2::new(0, 0);
+        let create = |handle| {
+            let data = TextureData {
+                bytes: vec![vec![0; 4].into_boxed_slice()],
+            };
+
+            Command::CreateTexture(Box::new((handle, TextureParams::default(), Some(data))))
+        };
+
+        unsafe {
+            let (a, b) = (TextureHandle::new(1, 1), TextureHandle::new(2, 1));
+            uploads.advance(&mut visitor, dims).unwrap();
+            uploads
+                .execute(&bufs, &mut visitor, dims, create(a))
+                .unwrap();
+            uploads
+                .execute(&bufs, &mut visitor, dims, create(b))
+                .unwrap();
+            assert_eq!(uploads.len(), 1);
+
+            let mut bufs = DataBuffer::with_capacity(0);
+            let ptr = bufs.extend_from_slice(&[0u8; 4]);
+            let area = Aabb2::new([0, 0].into(), [1, 1].into());
+            let update = Command::UpdateTexture(b, area, 1, ptr);
+            uploads.execute(&bufs, &mut visitor, dims, update).unwrap();
+            let update = Command::UpdateTexture(a, area, 1, ptr);
+            uploads.execute(&bufs, &mut visitor, dims, update).unwrap();
+            assert_eq!(uploads.len(), 2);
+
+            uploads.advance(&mut visitor, dims).unwrap();
+            assert_eq!(uploads.len(), 0);
+        }
+    }
+}
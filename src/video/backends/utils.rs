@@ -1,6 +1,434 @@
 use std::borrow::Borrow;
+use std::fmt;
 
+use smallvec::SmallVec;
+
+use crate::errors::*;
+use crate::math::prelude::Color;
 use crate::utils::handle::Handle;
+use crate::utils::hash::FastHashMap;
+use crate::video::assets::prelude::{
+    Comparison, MeshIndex, MeshParams, RenderState, RenderTextureHandle, RenderTextureParams,
+    SurfaceHandle, SurfaceIntegerClear, SurfaceParams, SurfaceScissor, UniformVariable,
+};
+use crate::video::{MAX_UNIFORM_TEXTURE_SLOTS, MAX_UNIFORM_VARIABLES};
+
+use super::UniformVar;
+
+/// Assigns texture units to the texture variables of a draw. Variables with explicit units
+/// are bound to them, and the others take the lowest free units in order.
+pub fn texture_units(vars: &[UniformVar]) -> Result<[usize; MAX_UNIFORM_VARIABLES]> {
+    let mut units = [0; MAX_UNIFORM_VARIABLES];
+    let mut used = [false; MAX_UNIFORM_TEXTURE_SLOTS];
+
+    for (i, &(_, v)) in vars.iter().enumerate() {
+        match v {
+            UniformVariable::TextureUnit(unit, _) | UniformVariable::RenderTextureUnit(unit, _) => {
+                let unit = unit as usize;
+                if unit >= MAX_UNIFORM_TEXTURE_SLOTS {
+                    bail!("Texture unit {} is out of bounds.", unit);
+                }
+
+                if used[unit] {
+                    bail!("Texture unit {} has been binded more than once.", unit);
+                }
+
+                used[unit] = true;
+                units[i] = unit;
+            }
+            _ => {}
+        }
+    }
+
+    for (i, &(_, v)) in vars.iter().enumerate() {
+        match v {
+            UniformVariable::Texture(_) | UniformVariable::RenderTexture(_) => {
+                let unit = used
+                    .iter()
+                    .position(|v| !v)
+                    .ok_or_else(|| format_err!("Too many textures in one draw."))?;
+
+                used[unit] = true;
+                units[i] = unit;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(units)
+}
+
+/// The kinds of buffer that could be cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClearBuffer {
+    Color,
+    Depth,
+    Stencil,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClearValue {
+    /// The clear color, and whether it's encoded into sRGB when written.
+    Color(Color<f32>, bool),
+    Integer(SurfaceIntegerClear),
+    Depth(f32),
+    Stencil(i32),
+}
+
+impl From<Color<f32>> for ClearValue {
+    fn from(v: Color<f32>) -> Self {
+        ClearValue::Color(v, false)
+    }
+}
+
+impl From<SurfaceIntegerClear> for ClearValue {
+    fn from(v: SurfaceIntegerClear) -> Self {
+        ClearValue::Integer(v)
+    }
+}
+
+impl From<f32> for ClearValue {
+    fn from(v: f32) -> Self {
+        ClearValue::Depth(v)
+    }
+}
+
+impl From<i32> for ClearValue {
+    fn from(v: i32) -> Self {
+        ClearValue::Stencil(v)
+    }
+}
+
+/// An attachment, the `None` stands for the window framebuffer.
+type ClearTarget = (ClearBuffer, Option<RenderTextureHandle>);
+
+/// Tracks the values that the attachments hold right after clears in current frame, so the
+/// clears of surfaces sharing the same attachments could be skipped if nothing has been
+/// drawn into them since then.
+#[derive(Debug, Default)]
+pub struct ClearCache {
+    values: FastHashMap<ClearTarget, ClearValue>,
+    binded: SmallVec<[ClearTarget; 8]>,
+    srgb_write: bool,
+}
+
+impl ClearCache {
+    /// Binds the attachments of surface, which would be modified by the following draws.
+    pub fn bind(&mut self, params: &SurfaceParams) {
+        self.binded.clear();
+        self.srgb_write = params.srgb_write;
+
+        if params.colors[0].is_none() && params.depth_stencil.is_none() {
+            self.binded.push((ClearBuffer::Color, None));
+            self.binded.push((ClearBuffer::Depth, None));
+            self.binded.push((ClearBuffer::Stencil, None));
+            return;
+        }
+
+        for &v in params.colors.iter().filter(|v| v.is_some()) {
+            self.binded.push((ClearBuffer::Color, v));
+        }
+
+        if let Some(v) = params.depth_stencil {
+            self.binded.push((ClearBuffer::Depth, Some(v)));
+            self.binded.push((ClearBuffer::Stencil, Some(v)));
+        }
+    }
+
+    /// Filters out the clear `value` of `buffer` if all the binded attachments hold it
+    /// already, otherwise records it as their content.
+    pub fn filter<T>(&mut self, buffer: ClearBuffer, value: Option<T>) -> Option<T>
+    where
+        T: Into<ClearValue> + Copy,
+    {
+        let mut cv = value?.into();
+        if let ClearValue::Color(_, ref mut srgb) = cv {
+            *srgb = self.srgb_write;
+        }
+
+        let mut redundant = true;
+
+        for &target in self.binded.iter().filter(|v| v.0 == buffer) {
+            if self.values.insert(target, cv) != Some(cv) {
+                redundant = false;
+            }
+        }
+
+        if redundant {
+            None
+        } else {
+            value
+        }
+    }
+
+    /// Marks the binded attachments as modified.
+    #[inline]
+    pub fn touch(&mut self) {
+        if !self.values.is_empty() {
+            for v in &self.binded {
+                self.values.remove(v);
+            }
+        }
+    }
+
+    /// Forgets the attachments of render texture, which is modified outside of surfaces,
+    /// e.g. by blits or image stores.
+    pub fn forget(&mut self, handle: RenderTextureHandle) {
+        self.values.retain(|&(_, v), _| v != Some(handle));
+    }
+
+    /// Forgets all the attachments, e.g. at the beginning of frame.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.values.clear();
+        self.binded.clear();
+    }
+}
+
+/// The states that the clear operation respects. Notes that the viewport is ignored by
+/// clears, while the scissor box and write masks are not.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClearStates {
+    pub scissor: SurfaceScissor,
+    pub color_write: (bool, bool, bool, bool),
+    pub depth_write: bool,
+    pub depth_test: Comparison,
+}
+
+impl ClearStates {
+    pub fn new(scissor: SurfaceScissor, rs: &RenderState) -> Self {
+        ClearStates {
+            scissor,
+            color_write: rs.color_write,
+            depth_write: rs.depth_write,
+            depth_test: rs.depth_test,
+        }
+    }
+
+    /// Gets the states with which the `color` and `depth` buffers would be cleared
+    /// entirely, regardless of the render state of last draw.
+    pub fn clear(self, color: bool, depth: bool) -> Self {
+        let mut states = self;
+        states.scissor = SurfaceScissor::Disable;
+
+        if color {
+            states.color_write = (true, true, true, true);
+        }
+
+        // The depth buffer is not updated if the depth test is disabled.
+        if depth {
+            states.depth_write = true;
+            states.depth_test = Comparison::Always;
+        }
+
+        states
+    }
+
+    /// Returns true if the write masks have been changed in `other`, so the render state
+    /// of shader should be re-applied by the next draw.
+    #[inline]
+    pub fn is_overwritten_by(&self, other: &Self) -> bool {
+        self.color_write != other.color_write
+            || self.depth_write != other.depth_write
+            || self.depth_test != other.depth_test
+    }
+}
+
+/// The attachment points of framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceAttachment {
+    Color(usize),
+    DepthStencil,
+}
+
+impl fmt::Display for SurfaceAttachment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SurfaceAttachment::Color(i) => write!(f, "COLOR_ATTACHMENT{}", i),
+            SurfaceAttachment::DepthStencil => write!(f, "DEPTH_STENCIL_ATTACHMENT"),
+        }
+    }
+}
+
+/// An attached render texture of surface.
+pub type SurfaceAttachmentInfo = (SurfaceAttachment, RenderTextureHandle, RenderTextureParams);
+
+/// The status of incomplete framebuffers, which are named after the ones returned by
+/// `glCheckFramebufferStatus`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramebufferStatus {
+    Undefined,
+    IncompleteAttachment,
+    IncompleteMissingAttachment,
+    IncompleteDimensions,
+    IncompleteDrawBuffer,
+    IncompleteReadBuffer,
+    IncompleteMultisample,
+    IncompleteLayerTargets,
+    Unsupported,
+    Unknown(u32),
+}
+
+impl fmt::Display for FramebufferStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            FramebufferStatus::Undefined => "FRAMEBUFFER_UNDEFINED",
+            FramebufferStatus::IncompleteAttachment => "FRAMEBUFFER_INCOMPLETE_ATTACHMENT",
+            FramebufferStatus::IncompleteMissingAttachment => {
+                "FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT"
+            }
+            FramebufferStatus::IncompleteDimensions => "FRAMEBUFFER_INCOMPLETE_DIMENSIONS",
+            FramebufferStatus::IncompleteDrawBuffer => "FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER",
+            FramebufferStatus::IncompleteReadBuffer => "FRAMEBUFFER_INCOMPLETE_READ_BUFFER",
+            FramebufferStatus::IncompleteMultisample => "FRAMEBUFFER_INCOMPLETE_MULTISAMPLE",
+            FramebufferStatus::IncompleteLayerTargets => "FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS",
+            FramebufferStatus::Unsupported => "FRAMEBUFFER_UNSUPPORTED",
+            FramebufferStatus::Unknown(v) => return write!(f, "0x{:X}", v),
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Resolves the range `(from, len)` of elements that would be drawn with `index`.
+pub fn mesh_elements(params: &MeshParams, index: MeshIndex) -> Result<(usize, usize)> {
+    match index {
+        MeshIndex::Ptr(from, len) => {
+            if (from + len) > params.num_elements() {
+                bail!("MeshIndex is out of bounds");
+            }
+
+            Ok((from, len))
+        }
+        MeshIndex::SubMesh(index) => {
+            let num = params.sub_mesh_offsets.len();
+            let from = params
+                .sub_mesh_offsets
+                .get(index)
+                .ok_or_else(|| format_err!("MeshIndex is out of bounds"))?;
+
+            let to = if index == (num - 1) {
+                params.num_elements()
+            } else {
+                params.sub_mesh_offsets[index + 1]
+            };
+
+            Ok((*from, (to - from)))
+        }
+        MeshIndex::All => Ok((0, params.num_elements())),
+    }
+}
+
+/// Validates the attachments of surface before creating the framebuffer, which catches
+/// the mistakes that every implementation would reject.
+pub fn validate_attachments(attachments: &[SurfaceAttachmentInfo]) -> Option<FramebufferStatus> {
+    let misplaced = attachments.iter().any(|&(point, _, params)| match point {
+        SurfaceAttachment::Color(_) => !params.format.is_color(),
+        SurfaceAttachment::DepthStencil => params.format.is_color(),
+    });
+
+    let empty = attachments
+        .iter()
+        .any(|v| v.2.dimensions.x == 0 || v.2.dimensions.y == 0);
+
+    if misplaced || empty {
+        return Some(FramebufferStatus::IncompleteAttachment);
+    }
+
+    let dimensions = attachments.first().map(|v| v.2.dimensions);
+    if attachments
+        .iter()
+        .any(|v| Some(v.2.dimensions) != dimensions)
+    {
+        return Some(FramebufferStatus::IncompleteDimensions);
+    }
+
+    None
+}
+
+/// Builds the error of the incomplete framebuffer of `surface`, which names the status and
+/// the attachment that is responsible for it if possible.
+pub fn incomplete_framebuffer(
+    surface: SurfaceHandle,
+    status: FramebufferStatus,
+    attachments: &[SurfaceAttachmentInfo],
+) -> failure::Error {
+    let culprit = match status {
+        FramebufferStatus::IncompleteAttachment => attachments
+            .iter()
+            .filter_map(|&(point, handle, params)| {
+                let reason = match point {
+                    _ if params.dimensions.x == 0 || params.dimensions.y == 0 => {
+                        "has zero width or height"
+                    }
+                    SurfaceAttachment::Color(_) if !params.format.is_color() => {
+                        "is not color-renderable"
+                    }
+                    SurfaceAttachment::DepthStencil if params.format.is_color() => {
+                        "is not depth-renderable"
+                    }
+                    _ => return None,
+                };
+
+                Some(format!(
+                    "{} ({:?}, {:?}) {}",
+                    point, handle, params.format, reason
+                ))
+            })
+            .next()
+            .unwrap_or_else(|| {
+                "An attached image is not renderable with its format on this implementation".into()
+            }),
+        FramebufferStatus::IncompleteMissingAttachment => "No images are attached".into(),
+        FramebufferStatus::IncompleteDimensions => {
+            let first = attachments[0];
+            attachments
+                .iter()
+                .find(|v| v.2.dimensions != first.2.dimensions)
+                .map(|v| {
+                    format!(
+                        "{} ({:?}) is {}x{} while {} ({:?}) is {}x{}",
+                        v.0,
+                        v.1,
+                        v.2.dimensions.x,
+                        v.2.dimensions.y,
+                        first.0,
+                        first.1,
+                        first.2.dimensions.x,
+                        first.2.dimensions.y
+                    )
+                })
+                .unwrap_or_else(|| "The attached images have different dimensions".into())
+        }
+        FramebufferStatus::IncompleteMultisample => {
+            "The attached images have different numbers of samples".into()
+        }
+        FramebufferStatus::Unsupported => {
+            "The combination of formats of the attached images is not supported by this \
+             implementation"
+                .into()
+        }
+        _ => "The framebuffer is incomplete".into(),
+    };
+
+    let list: Vec<_> = attachments
+        .iter()
+        .map(|&(point, handle, params)| {
+            format!(
+                "{}: {:?} {:?} {}x{}",
+                point, handle, params.format, params.dimensions.x, params.dimensions.y
+            )
+        })
+        .collect();
+
+    format_err!(
+        "[GL] Surface {:?} is incomplete ({}). {}. Attachments: [{}].",
+        surface,
+        status,
+        culprit,
+        list.join(", ")
+    )
+}
 
 #[derive(Debug)]
 pub struct DataVec<T>
@@ -36,6 +464,20 @@ where
         None
     }
 
+    pub fn get_mut<H>(&mut self, handle: H) -> Option<&mut T>
+    where
+        H: Borrow<Handle>,
+    {
+        let index = handle.borrow().index() as usize;
+        if let Some(&v) = self.versions.get(index) {
+            if v == handle.borrow().version() {
+                return self.buf[index].as_mut();
+            }
+        }
+
+        None
+    }
+
     pub fn create<H>(&mut self, handle: H, value: T)
     where
         H: Borrow<Handle>,
@@ -66,3 +508,130 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::handle::HandleLike;
+    use crate::video::assets::prelude::TextureHandle;
+
+    #[test]
+    fn units() {
+        let h = TextureHandle::default();
+        let vars = [
+            ("a".into(), UniformVariable::Texture(h)),
+            ("b".into(), UniformVariable::TextureUnit(0, h)),
+            ("c".into(), UniformVariable::F32(0.0)),
+            ("d".into(), UniformVariable::TextureUnit(2, h)),
+            ("e".into(), UniformVariable::Texture(h)),
+        ];
+
+        let units = texture_units(&vars).unwrap();
+        assert_eq!(&units[0..5], &[1, 0, 0, 2, 3]);
+
+        let vars = [
+            ("a".into(), UniformVariable::TextureUnit(1, h)),
+            ("b".into(), UniformVariable::TextureUnit(1, h)),
+        ];
+
+        assert!(texture_units(&vars).is_err());
+    }
+
+    #[test]
+    fn clears() {
+        let mut cache = ClearCache::default();
+        let params = SurfaceParams::default();
+        let black = Some(Color::black());
+
+        cache.bind(&params);
+        assert_eq!(cache.filter(ClearBuffer::Color, black), black);
+        assert_eq!(cache.filter(ClearBuffer::Depth, Some(1.0)), Some(1.0));
+
+        cache.bind(&params);
+        assert_eq!(cache.filter(ClearBuffer::Color, black), None);
+        assert_eq!(cache.filter(ClearBuffer::Depth, Some(0.5)), Some(0.5));
+
+        cache.touch();
+        assert_eq!(cache.filter(ClearBuffer::Color, black), black);
+
+        let handle = RenderTextureHandle::new(1, 1);
+        let mut params = SurfaceParams::default();
+        params.set_attachments(&[handle], None).unwrap();
+        cache.bind(&params);
+        assert_eq!(cache.filter(ClearBuffer::Color, black), black);
+
+        cache.forget(RenderTextureHandle::new(2, 1));
+        cache.bind(&params);
+        assert_eq!(cache.filter(ClearBuffer::Color, black), None);
+
+        cache.forget(handle);
+        cache.bind(&params);
+        assert_eq!(cache.filter(ClearBuffer::Color, black), black);
+    }
+
+    #[test]
+    fn clear_states() {
+        use crate::math::prelude::Vector2;
+
+        let mut rs = RenderState::default();
+        rs.color_write = (true, false, false, true);
+        let scissor = SurfaceScissor::Enable {
+            position: Vector2::new(8, 8),
+            size: Vector2::new(16, 16),
+        };
+
+        // The scissor test and color mask are disabled around the color clear, and the
+        // masks of shader would be re-applied afterwards.
+        let last = ClearStates::new(scissor, &rs);
+        let states = last.clear(true, false);
+        assert_eq!(states.scissor, SurfaceScissor::Disable);
+        assert_eq!(states.color_write, (true, true, true, true));
+        assert_eq!(states.depth_write, rs.depth_write);
+        assert!(last.is_overwritten_by(&states));
+
+        let states = last.clear(false, true);
+        assert_eq!(states.scissor, SurfaceScissor::Disable);
+        assert_eq!(states.color_write, rs.color_write);
+        assert_eq!(states.depth_write, true);
+        assert_eq!(states.depth_test, Comparison::Always);
+        assert!(last.is_overwritten_by(&states));
+
+        // Nothing should be re-applied if the masks are enabled already.
+        let last = ClearStates::new(scissor, &RenderState::default());
+        let states = last.clear(true, false);
+        assert_eq!(states.scissor, SurfaceScissor::Disable);
+        assert!(!last.is_overwritten_by(&states));
+    }
+
+    #[test]
+    fn attachments() {
+        use crate::math::prelude::Vector2;
+        use crate::video::assets::prelude::RenderTextureFormat;
+
+        let mut color = RenderTextureParams::default();
+        color.dimensions = Vector2::new(256, 256);
+        let mut depth = color;
+        depth.format = RenderTextureFormat::Depth24;
+
+        let h = RenderTextureHandle::default();
+        let mut attachments = vec![
+            (SurfaceAttachment::Color(0), h, color),
+            (SurfaceAttachment::DepthStencil, h, depth),
+        ];
+
+        assert_eq!(validate_attachments(&attachments), None);
+
+        attachments[1].2.dimensions = Vector2::new(128, 128);
+        let status = validate_attachments(&attachments).unwrap();
+        assert_eq!(status, FramebufferStatus::IncompleteDimensions);
+
+        let err = incomplete_framebuffer(SurfaceHandle::default(), status, &attachments);
+        let err = err.to_string();
+        assert!(err.contains("FRAMEBUFFER_INCOMPLETE_DIMENSIONS"));
+        assert!(err.contains("DEPTH_STENCIL_ATTACHMENT"));
+
+        attachments[1].2 = color;
+        let status = validate_attachments(&attachments);
+        assert_eq!(status, Some(FramebufferStatus::IncompleteAttachment));
+    }
+}
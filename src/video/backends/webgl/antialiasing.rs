@@ -0,0 +1,424 @@
+//! Redirects the rendering of window framebuffer into an offscreen framebuffer, which
+//! will be resolved into the window framebuffer with MSAA or FXAA at the end of frame.
+//! The `OutputAdjustment` is applied by the full-screen pass of resolving as well.
+
+use wasm_bindgen::JsCast;
+use web_sys::{
+    WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlRenderbuffer, WebGlTexture,
+    WebGlUniformLocation, WebGlVertexArrayObject,
+};
+
+use web_sys::WebGl2RenderingContext as WebGL;
+
+use crate::errors::*;
+use crate::math::prelude::Vector2;
+use crate::video::assets::prelude::{AaMode, OutputAdjustment};
+
+use super::capabilities::Capabilities;
+use super::visitor::{check, WebGLVisitor};
+
+const FULLSCREEN_VS: &str = include_str!("../shaders/fullscreen.vs");
+const FXAA_FS: &str = include_str!("../shaders/fxaa.fs");
+const COPY_FS: &str = include_str!("../shaders/copy.fs");
+const OUTPUT_FS: &str = include_str!("../shaders/output.fs");
+
+/// A full-screen pass that samples `u_Texture`, which is shared with the fallback of blits.
+pub(super) struct WebGLOutputPass {
+    program: WebGlProgram,
+    vao: WebGlVertexArrayObject,
+    vbo: WebGlBuffer,
+    texture: Option<WebGlUniformLocation>,
+    texel_size: Option<WebGlUniformLocation>,
+    output: Option<WebGlUniformLocation>,
+}
+
+enum WebGLColorTarget {
+    T(WebGlTexture),
+    R(WebGlRenderbuffer),
+}
+
+pub struct WebGLAntialiasing {
+    mode: AaMode,
+    samples: i32,
+    dimensions: Vector2<u32>,
+    fbo: WebGlFramebuffer,
+    color: Option<WebGLColorTarget>,
+    depth_stencil: Option<WebGlRenderbuffer>,
+    // The single-sampled framebuffer that MSAA resolves into before the output pass.
+    resolve_fbo: Option<WebGlFramebuffer>,
+    resolve_color: Option<WebGlTexture>,
+    pass: Option<WebGLOutputPass>,
+    adjustment: OutputAdjustment,
+    used: bool,
+}
+
+impl WebGLAntialiasing {
+    pub unsafe fn new(
+        ctx: &WebGL,
+        mode: AaMode,
+        adjustment: OutputAdjustment,
+        capabilities: &Capabilities,
+    ) -> Result<Self> {
+        if !Self::is_required(mode, adjustment) {
+            bail!("[WebGL] AaMode::None does not need offscreen framebuffer.");
+        }
+
+        let samples = match mode {
+            AaMode::None => 0,
+            AaMode::Msaa(n) => (u32::from(n)).min(capabilities.max_samples) as i32,
+            AaMode::Fxaa => 0,
+        };
+
+        let pass = if mode == AaMode::Fxaa {
+            Some(WebGLOutputPass::new(ctx, FXAA_FS)?)
+        } else if !adjustment.is_identity() {
+            Some(WebGLOutputPass::new(ctx, COPY_FS)?)
+        } else {
+            None
+        };
+
+        let fbo = ctx.create_framebuffer().unwrap();
+        let resolve_fbo = if samples > 0 && pass.is_some() {
+            Some(ctx.create_framebuffer().unwrap())
+        } else {
+            None
+        };
+
+        info!(
+            "[WebGL] Enables antialiasing {:?} with {} samples, and {:?}.",
+            mode, samples, adjustment
+        );
+
+        Ok(WebGLAntialiasing {
+            mode,
+            samples,
+            dimensions: Vector2::new(0, 0),
+            fbo,
+            color: None,
+            depth_stencil: None,
+            resolve_fbo,
+            resolve_color: None,
+            pass,
+            adjustment,
+            used: false,
+        })
+    }
+
+    /// Returns true if the offscreen framebuffer is required to resolve with `mode` and
+    /// `adjustment`.
+    #[inline]
+    pub fn is_required(mode: AaMode, adjustment: OutputAdjustment) -> bool {
+        mode != AaMode::None || !adjustment.is_identity()
+    }
+
+    #[inline]
+    pub fn mode(&self) -> AaMode {
+        self.mode
+    }
+
+    /// Sets the adjustment of output, returns false if the offscreen framebuffer should be
+    /// recreated for it.
+    pub fn set_adjustment(&mut self, adjustment: OutputAdjustment) -> bool {
+        // The FXAA pass applies the adjustment anyway, while the other modes need a copy
+        // pass only if the adjustment is not the identity.
+        if self.mode != AaMode::Fxaa && self.pass.is_some() == adjustment.is_identity() {
+            return false;
+        }
+
+        self.adjustment = adjustment;
+        true
+    }
+
+    /// Returns true if there are something rendered into the offscreen framebuffer since
+    /// last resolving.
+    #[inline]
+    pub fn is_pending(&self) -> bool {
+        self.used
+    }
+
+    #[inline]
+    pub fn dimensions(&self) -> Vector2<u32> {
+        self.dimensions
+    }
+
+    /// Gets the offscreen framebuffer with `dimensions`, the attachments will be reallocated
+    /// if the dimensions changes.
+    pub unsafe fn framebuffer(
+        &mut self,
+        ctx: &WebGL,
+        dimensions: Vector2<u32>,
+    ) -> Result<&WebGlFramebuffer> {
+        if self.dimensions != dimensions {
+            self.free_attachments(ctx);
+            self.dimensions = dimensions;
+
+            let (w, h) = (dimensions.x as i32, dimensions.y as i32);
+
+            if let Some(ref fbo) = self.resolve_fbo {
+                ctx.bind_framebuffer(WebGL::FRAMEBUFFER, Some(fbo));
+                self.resolve_color = Some(Self::create_color_texture(ctx, w, h));
+
+                let status = ctx.check_framebuffer_status(WebGL::FRAMEBUFFER);
+                if status != WebGL::FRAMEBUFFER_COMPLETE {
+                    bail!(
+                        "[WebGL] The resolve framebuffer of {:?} is incomplete.",
+                        self.mode
+                    );
+                }
+            }
+
+            ctx.bind_framebuffer(WebGL::FRAMEBUFFER, Some(&self.fbo));
+
+            if self.samples == 0 {
+                let id = Self::create_color_texture(ctx, w, h);
+                self.color = Some(WebGLColorTarget::T(id));
+            } else {
+                let id = ctx.create_renderbuffer().unwrap();
+                ctx.bind_renderbuffer(WebGL::RENDERBUFFER, Some(&id));
+                ctx.renderbuffer_storage_multisample(
+                    WebGL::RENDERBUFFER,
+                    self.samples,
+                    WebGL::RGBA8,
+                    w,
+                    h,
+                );
+
+                ctx.framebuffer_renderbuffer(
+                    WebGL::FRAMEBUFFER,
+                    WebGL::COLOR_ATTACHMENT0,
+                    WebGL::RENDERBUFFER,
+                    Some(&id),
+                );
+
+                self.color = Some(WebGLColorTarget::R(id));
+            }
+
+            let id = ctx.create_renderbuffer().unwrap();
+            ctx.bind_renderbuffer(WebGL::RENDERBUFFER, Some(&id));
+            ctx.renderbuffer_storage_multisample(
+                WebGL::RENDERBUFFER,
+                self.samples,
+                WebGL::DEPTH24_STENCIL8,
+                w,
+                h,
+            );
+
+            ctx.framebuffer_renderbuffer(
+                WebGL::FRAMEBUFFER,
+                WebGL::DEPTH_STENCIL_ATTACHMENT,
+                WebGL::RENDERBUFFER,
+                Some(&id),
+            );
+
+            self.depth_stencil = Some(id);
+
+            if ctx.check_framebuffer_status(WebGL::FRAMEBUFFER) != WebGL::FRAMEBUFFER_COMPLETE {
+                bail!(
+                    "[WebGL] The offscreen framebuffer of {:?} is incomplete.",
+                    self.mode
+                );
+            }
+
+            check(ctx)?;
+        }
+
+        self.used = true;
+        Ok(&self.fbo)
+    }
+
+    /// Resolves the offscreen framebuffer into the window framebuffer.
+    ///
+    /// Notes that the binded framebuffer, program, vertex array and the texture of unit 0
+    /// will be changed, and the fixed-function states are expected to be reset by the
+    /// caller before the full-screen pass.
+    pub unsafe fn resolve(&mut self, ctx: &WebGL) -> Result<()> {
+        self.used = false;
+        let (w, h) = (self.dimensions.x as i32, self.dimensions.y as i32);
+
+        let mut texture = match self.color {
+            Some(WebGLColorTarget::T(ref id)) => Some(id),
+            _ => None,
+        };
+
+        if self.samples > 0 {
+            ctx.bind_framebuffer(WebGL::READ_FRAMEBUFFER, Some(&self.fbo));
+            ctx.bind_framebuffer(WebGL::DRAW_FRAMEBUFFER, self.resolve_fbo.as_ref());
+            ctx.blit_framebuffer(
+                0,
+                0,
+                w,
+                h,
+                0,
+                0,
+                w,
+                h,
+                WebGL::COLOR_BUFFER_BIT,
+                WebGL::NEAREST,
+            );
+
+            texture = self.resolve_color.as_ref();
+        }
+
+        ctx.bind_framebuffer(WebGL::FRAMEBUFFER, None);
+
+        if let Some(ref pass) = self.pass {
+            pass.draw(ctx, texture, self.dimensions, self.adjustment);
+        }
+
+        check(ctx)
+    }
+
+    /// Deletes the WebGL objects.
+    pub unsafe fn delete(&mut self, ctx: &WebGL) -> Result<()> {
+        self.free_attachments(ctx);
+        ctx.delete_framebuffer(Some(&self.fbo));
+        ctx.delete_framebuffer(self.resolve_fbo.as_ref());
+
+        if let Some(ref pass) = self.pass {
+            pass.delete(ctx);
+        }
+
+        check(ctx)
+    }
+
+    /// Creates a color texture and attaches it to the binded framebuffer.
+    unsafe fn create_color_texture(ctx: &WebGL, w: i32, h: i32) -> WebGlTexture {
+        // Keeps the texture binding untouched, which is cached by the visitor.
+        let binding = ctx
+            .get_parameter(WebGL::TEXTURE_BINDING_2D)
+            .ok()
+            .and_then(|v| v.dyn_into::<WebGlTexture>().ok());
+
+        let id = ctx.create_texture().unwrap();
+        ctx.bind_texture(WebGL::TEXTURE_2D, Some(&id));
+        let clamp = WebGL::CLAMP_TO_EDGE as i32;
+        let linear = WebGL::LINEAR as i32;
+        ctx.tex_parameteri(WebGL::TEXTURE_2D, WebGL::TEXTURE_WRAP_S, clamp);
+        ctx.tex_parameteri(WebGL::TEXTURE_2D, WebGL::TEXTURE_WRAP_T, clamp);
+        ctx.tex_parameteri(WebGL::TEXTURE_2D, WebGL::TEXTURE_MIN_FILTER, linear);
+        ctx.tex_parameteri(WebGL::TEXTURE_2D, WebGL::TEXTURE_MAG_FILTER, linear);
+        ctx.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            WebGL::TEXTURE_2D,
+            0,
+            WebGL::RGBA8 as i32,
+            w,
+            h,
+            0,
+            WebGL::RGBA,
+            WebGL::UNSIGNED_BYTE,
+            None,
+        )
+        .unwrap();
+
+        ctx.framebuffer_texture_2d(
+            WebGL::FRAMEBUFFER,
+            WebGL::COLOR_ATTACHMENT0,
+            WebGL::TEXTURE_2D,
+            Some(&id),
+            0,
+        );
+
+        ctx.bind_texture(WebGL::TEXTURE_2D, binding.as_ref());
+        id
+    }
+
+    unsafe fn free_attachments(&mut self, ctx: &WebGL) {
+        match self.color.take() {
+            Some(WebGLColorTarget::T(id)) => ctx.delete_texture(Some(&id)),
+            Some(WebGLColorTarget::R(id)) => ctx.delete_renderbuffer(Some(&id)),
+            None => {}
+        }
+
+        if let Some(id) = self.depth_stencil.take() {
+            ctx.delete_renderbuffer(Some(&id));
+        }
+
+        if let Some(id) = self.resolve_color.take() {
+            ctx.delete_texture(Some(&id));
+        }
+    }
+}
+
+impl WebGLOutputPass {
+    /// Compiles the pass with fragment shader `fs`, which could use the `output_adjust`.
+    pub(super) unsafe fn new(ctx: &WebGL, fs: &str) -> Result<Self> {
+        let header = "#version 100\nprecision mediump float;\n";
+        let vs = format!("{}{}", header, FULLSCREEN_VS);
+        let fs = format!("{}{}{}", header, OUTPUT_FS, fs);
+        let vs = WebGLVisitor::compile(ctx, WebGL::VERTEX_SHADER, &vs)?;
+        let fs = WebGLVisitor::compile(ctx, WebGL::FRAGMENT_SHADER, &fs)?;
+        let program = WebGLVisitor::link(ctx, &[vs.clone(), fs.clone()]);
+
+        ctx.delete_shader(Some(&vs));
+        ctx.delete_shader(Some(&fs));
+        let program = program?;
+
+        let texture = ctx.get_uniform_location(&program, "u_Texture");
+        let texel_size = ctx.get_uniform_location(&program, "u_TexelSize");
+        let output = ctx.get_uniform_location(&program, "u_Output");
+        let position = ctx.get_attrib_location(&program, "a_Position");
+        if position < 0 {
+            bail!("[WebGL] Failed to locate the attribute of output pass.");
+        }
+
+        // A triangle that covers the whole viewport.
+        let verts: [f32; 6] = [-1.0, -1.0, 3.0, -1.0, -1.0, 3.0];
+        let bytes = ::std::slice::from_raw_parts_mut(
+            verts.as_ptr() as *mut u8,
+            ::std::mem::size_of_val(&verts),
+        );
+
+        let vao = ctx.create_vertex_array().unwrap();
+        ctx.bind_vertex_array(Some(&vao));
+
+        let vbo = ctx.create_buffer().unwrap();
+        ctx.bind_buffer(WebGL::ARRAY_BUFFER, Some(&vbo));
+        ctx.buffer_data_with_u8_array(WebGL::ARRAY_BUFFER, bytes, WebGL::STATIC_DRAW);
+        ctx.enable_vertex_attrib_array(position as u32);
+        ctx.vertex_attrib_pointer_with_i32(position as u32, 2, WebGL::FLOAT, false, 0, 0);
+
+        ctx.bind_vertex_array(None);
+        check(ctx)?;
+
+        Ok(WebGLOutputPass {
+            program,
+            vao,
+            vbo,
+            texture,
+            texel_size,
+            output,
+        })
+    }
+    /// Draws `texture` of `dimensions` into the binded framebuffer. The program, vertex
+    /// array and the texture of unit 0 will be changed.
+    pub(super) unsafe fn draw(
+        &self,
+        ctx: &WebGL,
+        texture: Option<&WebGlTexture>,
+        dimensions: Vector2<u32>,
+        adjustment: OutputAdjustment,
+    ) {
+        let (w, h) = (dimensions.x.max(1) as f32, dimensions.y.max(1) as f32);
+        let v = adjustment;
+
+        ctx.use_program(Some(&self.program));
+        ctx.active_texture(WebGL::TEXTURE0);
+        ctx.bind_texture(WebGL::TEXTURE_2D, texture);
+        ctx.uniform1i(self.texture.as_ref(), 0);
+        ctx.uniform2f(self.texel_size.as_ref(), 1.0 / w, 1.0 / h);
+        ctx.uniform3f(
+            self.output.as_ref(),
+            1.0 / v.gamma,
+            v.brightness,
+            v.contrast,
+        );
+        ctx.bind_vertex_array(Some(&self.vao));
+        ctx.draw_arrays(WebGL::TRIANGLES, 0, 3);
+    }
+
+    pub(super) unsafe fn delete(&self, ctx: &WebGL) {
+        ctx.delete_program(Some(&self.program));
+        ctx.delete_vertex_array(Some(&self.vao));
+        ctx.delete_buffer(Some(&self.vbo));
+    }
+}
@@ -1,6 +1,6 @@
 use web_sys::WebGl2RenderingContext as WebGL;
 
-use crate::video::assets::texture::TextureFormat;
+use crate::video::assets::texture::{FormatUsage, RenderTextureFormat, TextureFormat};
 
 /// Represents the capabilities of the context.
 ///
@@ -9,12 +9,60 @@ use crate::video::assets::texture::TextureFormat;
 pub struct Capabilities {
     /// The list of OpenGL extensions support by this implementation.
     pub extensions: Extensions,
+
+    /// Maximum number of samples of multisampled renderbuffers.
+    pub max_samples: u32,
+
+    /// The minimum required alignment of the offsets of `bindBufferRange` for
+    /// `UNIFORM_BUFFER`.
+    pub uniform_buffer_offset_alignment: u32,
+
+    /// Maximum width and height of 2D textures.
+    pub max_texture_size: u32,
+
+    /// Maximum number of color attachment bind points.
+    pub max_color_attachments: u32,
+
+    /// Maximum number of generic vertex attributes.
+    pub max_vertex_attributes: u32,
+
+    /// Maximum number of textures that can be bound to a program.
+    pub max_combined_texture_image_units: u32,
 }
 
 impl Capabilities {
     pub unsafe fn new(ctx: &WebGL) -> Result<Capabilities, failure::Error> {
+        let max_samples = ctx
+            .get_parameter(WebGL::MAX_SAMPLES)
+            .ok()
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0) as u32;
+
+        let uniform_buffer_offset_alignment = ctx
+            .get_parameter(WebGL::UNIFORM_BUFFER_OFFSET_ALIGNMENT)
+            .ok()
+            .and_then(|v| v.as_f64())
+            .unwrap_or(256.0) as u32;
+
+        // The fallbacks are the minimum values that required by WebGL 2.0.
+        let parameter = |name, min: f64| {
+            ctx.get_parameter(name)
+                .ok()
+                .and_then(|v| v.as_f64())
+                .unwrap_or(min) as u32
+        };
+
         Ok(Capabilities {
             extensions: Extensions::parse(ctx)?,
+            max_samples,
+            uniform_buffer_offset_alignment: uniform_buffer_offset_alignment.max(1),
+            max_texture_size: parameter(WebGL::MAX_TEXTURE_SIZE, 2048.0),
+            max_color_attachments: parameter(WebGL::MAX_COLOR_ATTACHMENTS, 4.0),
+            max_vertex_attributes: parameter(WebGL::MAX_VERTEX_ATTRIBS, 16.0),
+            max_combined_texture_image_units: parameter(
+                WebGL::MAX_COMBINED_TEXTURE_IMAGE_UNITS,
+                32.0,
+            ),
         })
     }
 
@@ -33,6 +81,18 @@ impl Capabilities {
             _ => true,
         }
     }
+
+    pub fn is_format_supported(&self, format: RenderTextureFormat, usage: FormatUsage) -> bool {
+        // WebGL2 could NOT query the internal format, so we follow the requirements of
+        // specification here.
+        match usage {
+            FormatUsage::Render if format == RenderTextureFormat::RGBA16F => {
+                self.extensions.ext_color_buffer_float
+            }
+            FormatUsage::Texture | FormatUsage::Render => format != RenderTextureFormat::Depth32,
+            FormatUsage::Filter => format.is_color() && !format.is_integer(),
+        }
+    }
 }
 
 macro_rules! extensions {
@@ -72,4 +132,5 @@ extensions! {
     "WEBGL_compressed_texture_s3tc" => webgl_compressed_texture_s3tc,
     "WEBGL_compressed_texture_pvrtc" => webgl_compressed_texture_pvrtc,
     "WEBGL_compressed_texture_etc" => webgl_compressed_texture_etc,
+    "EXT_color_buffer_float" => ext_color_buffer_float,
 }
@@ -1,3 +1,5 @@
+pub mod antialiasing;
 pub mod capabilities;
 pub mod types;
+pub mod uniform_ring;
 pub mod visitor;
@@ -1,6 +1,20 @@
 use web_sys::WebGl2RenderingContext as WebGL;
 
 use super::super::super::assets::prelude::*;
+use super::super::utils::FramebufferStatus;
+
+pub fn framebuffer_status(status: u32) -> FramebufferStatus {
+    match status {
+        WebGL::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => FramebufferStatus::IncompleteAttachment,
+        WebGL::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => {
+            FramebufferStatus::IncompleteMissingAttachment
+        }
+        WebGL::FRAMEBUFFER_INCOMPLETE_DIMENSIONS => FramebufferStatus::IncompleteDimensions,
+        WebGL::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => FramebufferStatus::IncompleteMultisample,
+        WebGL::FRAMEBUFFER_UNSUPPORTED => FramebufferStatus::Unsupported,
+        v => FramebufferStatus::Unknown(v),
+    }
+}
 
 impl From<MeshHint> for u32 {
     fn from(hint: MeshHint) -> Self {
@@ -83,6 +97,14 @@ impl From<BlendFactor> for u32 {
             BlendFactor::OneMinusValue(BlendValue::SourceAlpha) => WebGL::ONE_MINUS_SRC_ALPHA,
             BlendFactor::OneMinusValue(BlendValue::DestinationColor) => WebGL::ONE_MINUS_DST_COLOR,
             BlendFactor::OneMinusValue(BlendValue::DestinationAlpha) => WebGL::ONE_MINUS_DST_ALPHA,
+            BlendFactor::Value(BlendValue::ConstantColor) => WebGL::CONSTANT_COLOR,
+            BlendFactor::Value(BlendValue::ConstantAlpha) => WebGL::CONSTANT_ALPHA,
+            BlendFactor::OneMinusValue(BlendValue::ConstantColor) => {
+                WebGL::ONE_MINUS_CONSTANT_COLOR
+            }
+            BlendFactor::OneMinusValue(BlendValue::ConstantAlpha) => {
+                WebGL::ONE_MINUS_CONSTANT_ALPHA
+            }
         }
     }
 }
@@ -93,12 +115,25 @@ impl From<TextureWrap> for u32 {
             TextureWrap::Repeat => WebGL::REPEAT,
             TextureWrap::Mirror => WebGL::MIRRORED_REPEAT,
             TextureWrap::Clamp => WebGL::CLAMP_TO_EDGE,
-            // WebGL does NOT support MIRROR_CLAMP_TO_EDGE
-            TextureWrap::MirrorClamp => WebGL::CLAMP_TO_EDGE,
+            // WebGL does NOT support MIRROR_CLAMP_TO_EDGE and CLAMP_TO_BORDER
+            TextureWrap::MirrorClamp | TextureWrap::Border => WebGL::CLAMP_TO_EDGE,
         }
     }
 }
 
+/// Gets the filtering function of `filter`, which also picks the mipmap levels with `mip`
+/// when minifying.
+pub fn filter(filter: TextureFilter, mip: Option<TextureFilter>) -> u32 {
+    match (filter, mip) {
+        (TextureFilter::Nearest, None) => WebGL::NEAREST,
+        (TextureFilter::Linear, None) => WebGL::LINEAR,
+        (TextureFilter::Nearest, Some(TextureFilter::Nearest)) => WebGL::NEAREST_MIPMAP_NEAREST,
+        (TextureFilter::Nearest, Some(TextureFilter::Linear)) => WebGL::NEAREST_MIPMAP_LINEAR,
+        (TextureFilter::Linear, Some(TextureFilter::Nearest)) => WebGL::LINEAR_MIPMAP_NEAREST,
+        (TextureFilter::Linear, Some(TextureFilter::Linear)) => WebGL::LINEAR_MIPMAP_LINEAR,
+    }
+}
+
 impl From<TextureFormat> for (u32, u32, u32) {
     fn from(format: TextureFormat) -> Self {
         // FIXME
@@ -148,6 +183,9 @@ impl From<RenderTextureFormat> for (u32, u32, u32) {
             RenderTextureFormat::RGB8 => (WebGL::RGB, WebGL::RGB, WebGL::UNSIGNED_BYTE),
             RenderTextureFormat::RGBA4 => (WebGL::RGBA, WebGL::RGBA, WebGL::UNSIGNED_SHORT_4_4_4_4),
             RenderTextureFormat::RGBA8 => (WebGL::RGBA, WebGL::RGBA, WebGL::UNSIGNED_BYTE),
+            RenderTextureFormat::RGBA16F => (WebGL::RGBA16F, WebGL::RGBA, WebGL::HALF_FLOAT),
+            RenderTextureFormat::R32UI => (WebGL::R32UI, WebGL::RED_INTEGER, WebGL::UNSIGNED_INT),
+            RenderTextureFormat::R32I => (WebGL::R32I, WebGL::RED_INTEGER, WebGL::INT),
             RenderTextureFormat::Depth16 => {
                 (WebGL::DEPTH_COMPONENT, WebGL::DEPTH_COMPONENT, WebGL::FLOAT)
             }
@@ -0,0 +1,85 @@
+//! A ring-buffered uniform buffer, which holds the per-object uniform blocks of draws in
+//! sub-ranges of one buffer object.
+
+use web_sys::WebGl2RenderingContext as WebGL;
+use web_sys::WebGlBuffer;
+
+use crate::errors::*;
+
+use super::visitor::check;
+
+/// The initial capacity in bytes of the ring.
+const MIN_CAPACITY: usize = 16 * 1024;
+
+pub struct WebGLUniformRing {
+    id: Option<WebGlBuffer>,
+    capacity: usize,
+    cursor: usize,
+    used: usize,
+}
+
+impl WebGLUniformRing {
+    pub fn new() -> Self {
+        WebGLUniformRing {
+            id: None,
+            capacity: 0,
+            cursor: 0,
+            used: 0,
+        }
+    }
+
+    /// Writes `bytes` at the next offset that aligned with `alignment`, and reserves `size`
+    /// bytes for it. Returns the buffer object and the offset of this write.
+    ///
+    /// The storage is orphaned instead of being overwritten when the ring wraps around, and
+    /// it grows to fit the usage of a whole frame.
+    pub unsafe fn push(
+        &mut self,
+        ctx: &WebGL,
+        bytes: &[u8],
+        size: usize,
+        alignment: usize,
+    ) -> Result<(&WebGlBuffer, usize)> {
+        let size = size.max(bytes.len());
+        let mut offset = align(self.cursor, alignment);
+
+        if offset + size > self.capacity {
+            if self.id.is_none() {
+                self.id = Some(ctx.create_buffer().unwrap());
+            }
+
+            let capacity = (self.used + size).next_power_of_two().max(MIN_CAPACITY);
+            self.capacity = self.capacity.max(capacity);
+
+            ctx.bind_buffer(WebGL::UNIFORM_BUFFER, self.id.as_ref());
+            let capacity = self.capacity as i32;
+            ctx.buffer_data_with_i32(WebGL::UNIFORM_BUFFER, capacity, WebGL::STREAM_DRAW);
+            offset = 0;
+        } else {
+            ctx.bind_buffer(WebGL::UNIFORM_BUFFER, self.id.as_ref());
+        }
+
+        if !bytes.is_empty() {
+            let mv = ::std::slice::from_raw_parts_mut(bytes.as_ptr() as *mut u8, bytes.len());
+            ctx.buffer_sub_data_with_i32_and_u8_array(WebGL::UNIFORM_BUFFER, offset as i32, mv);
+        }
+
+        check(ctx)?;
+
+        self.used += offset + size - self.cursor.min(offset);
+        self.cursor = offset + size;
+        Ok((self.id.as_ref().unwrap(), offset))
+    }
+
+    /// Resets the statistics of usage at the begining of frame.
+    #[inline]
+    pub fn advance(&mut self) {
+        self.used = 0;
+    }
+}
+
+#[inline]
+fn align(offset: usize, alignment: usize) -> usize {
+    let alignment = alignment.max(1);
+    (offset + alignment - 1) / alignment * alignment
+}
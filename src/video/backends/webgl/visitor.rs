@@ -1,9 +1,10 @@
 use std::cell::RefCell;
+use std::time::Duration;
 
 use smallvec::SmallVec;
 use web_sys::{
     self, HtmlCanvasElement, WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlRenderbuffer,
-    WebGlShader, WebGlTexture, WebGlUniformLocation, WebGlVertexArrayObject,
+    WebGlSampler, WebGlShader, WebGlTexture, WebGlUniformLocation, WebGlVertexArrayObject,
 };
 
 use wasm_bindgen::JsCast;
@@ -14,10 +15,20 @@ use crate::math::prelude::*;
 use crate::utils::hash::{FastHashMap, FastHashSet};
 use crate::utils::hash_value::HashValue;
 use crate::video::assets::prelude::*;
+use crate::video::{MAX_UNIFORM_TEXTURE_SLOTS, PER_OBJECT_BLOCK, PER_OBJECT_BLOCK_BINDING};
 
-use super::super::utils::DataVec;
-use super::super::{UniformVar, Visitor};
+use super::super::frame::{DrawCommand, FrameStats, MemoryBarrier};
+use super::super::utils::{
+    incomplete_framebuffer, mesh_elements, texture_units, validate_attachments, ClearBuffer,
+    ClearCache, ClearStates, DataVec, SurfaceAttachment,
+};
+use super::super::{DeviceLimits, UniformVar, Visitor};
+use super::antialiasing::{WebGLAntialiasing, WebGLOutputPass};
 use super::capabilities::Capabilities;
+use super::types;
+use super::uniform_ring::WebGLUniformRing;
+
+const BLIT_FS: &str = include_str!("../shaders/blit.fs");
 
 #[derive(Debug, Clone)]
 struct GLSurfaceData {
@@ -25,6 +36,7 @@ struct GLSurfaceData {
     id: Option<WebGlFramebuffer>,
     dims: Option<Vector2<u32>>,
     params: SurfaceParams,
+    color_format: Option<RenderTextureFormat>,
 }
 
 #[derive(Debug, Clone)]
@@ -34,6 +46,10 @@ pub struct GLShaderData {
     params: ShaderParams,
     uniforms: RefCell<FastHashMap<HashValue<str>, WebGlUniformLocation>>,
     attributes: RefCell<FastHashMap<HashValue<str>, i32>>,
+    /// The data size of the per-object uniform block, if there is one.
+    per_object_block: Option<usize>,
+    /// The indices and data sizes of the uniform blocks that binded with uniform buffers.
+    uniform_blocks: FastHashMap<HashValue<str>, (u32, usize)>,
 }
 
 impl GLShaderData {
@@ -105,27 +121,48 @@ struct GLRenderTextureData {
 enum Sampler {
     RenderTexture(RenderTextureHandle),
     Texture(TextureHandle),
+    Cube(TextureHandle),
 }
 
 #[derive(Debug, Clone)]
 struct GLMeshData {
     handle: MeshHandle,
     vbo: WebGlBuffer,
-    ibo: WebGlBuffer,
+    ibo: Option<WebGlBuffer>,
     params: MeshParams,
 }
 
+#[derive(Debug, Clone)]
+struct GLInstanceBufferData {
+    handle: InstanceBufferHandle,
+    vbo: WebGlBuffer,
+    params: InstanceBufferParams,
+}
+
+type VAOKey = (ShaderHandle, MeshHandle, Option<InstanceBufferHandle>);
+
+#[derive(Debug, Clone)]
+struct GLUniformBufferData {
+    id: WebGlBuffer,
+    params: UniformBufferParams,
+}
+
 struct WebGLState {
     render_state: RenderState,
     scissor: SurfaceScissor,
     view: SurfaceViewport,
     cleared_surfaces: FastHashSet<SurfaceHandle>,
-    vaos: FastHashMap<(ShaderHandle, MeshHandle), WebGlVertexArrayObject>,
+    clears: ClearCache,
+    vaos: FastHashMap<VAOKey, WebGlVertexArrayObject>,
     binded_surface: Option<SurfaceHandle>,
+    binded_framebuffer: Option<Option<WebGlFramebuffer>>,
     binded_shader: Option<ShaderHandle>,
     binded_texture_index: usize,
     binded_textures: SmallVec<[Option<Sampler>; 8]>,
-    binded_vao: Option<(ShaderHandle, MeshHandle)>,
+    binded_vao: Option<VAOKey>,
+    unpack_alignment: i32,
+    cull_override: Option<CullFace>,
+    sampler: Option<WebGlSampler>,
 }
 
 pub struct WebGLVisitor {
@@ -138,6 +175,16 @@ pub struct WebGLVisitor {
     meshes: DataVec<GLMeshData>,
     textures: DataVec<GLTextureData>,
     render_textures: DataVec<GLRenderTextureData>,
+    instance_buffers: DataVec<GLInstanceBufferData>,
+    uniform_buffers: DataVec<GLUniformBufferData>,
+    per_object_ring: WebGLUniformRing,
+    samplers: FastHashMap<SurfaceSampler, WebGlSampler>,
+    antialiasing: Option<WebGLAntialiasing>,
+    blit_pass: Option<WebGLOutputPass>,
+    readbacks: Vec<(VideoHandle, Vec<u8>)>,
+    output: OutputAdjustment,
+    fallback_texture: Option<TextureHandle>,
+    stats: FrameStats,
 }
 
 impl WebGLVisitor {
@@ -165,18 +212,26 @@ impl WebGLVisitor {
                 size: Vector2::new(0, 0),
             },
             cleared_surfaces: FastHashSet::default(),
+            clears: ClearCache::default(),
             binded_surface: None,
+            binded_framebuffer: None,
             binded_shader: None,
             binded_texture_index: 0,
             binded_textures: SmallVec::new(),
             vaos: FastHashMap::default(),
             binded_vao: None,
+            unpack_alignment: 0,
+            cull_override: None,
+            sampler: None,
         };
 
         Self::reset_render_state(&ctx, &mut state)?;
 
+        let capabilities = Capabilities::new(&ctx)?;
+        info!("WebGLVisitor {:#?}", capabilities);
+
         Ok(WebGLVisitor {
-            capabilities: Capabilities::new(&ctx)?,
+            capabilities,
             ctx: ctx,
             state: state,
             surfaces: DataVec::new(),
@@ -184,14 +239,32 @@ impl WebGLVisitor {
             textures: DataVec::new(),
             render_textures: DataVec::new(),
             meshes: DataVec::new(),
+            instance_buffers: DataVec::new(),
+            uniform_buffers: DataVec::new(),
+            per_object_ring: WebGLUniformRing::new(),
+            samplers: FastHashMap::default(),
+            antialiasing: None,
+            blit_pass: None,
+            readbacks: Vec::new(),
+            output: OutputAdjustment::default(),
+            fallback_texture: None,
+            stats: FrameStats::default(),
         })
     }
 }
 
 impl Visitor for WebGLVisitor {
     unsafe fn advance(&mut self) -> Result<()> {
+        if self.ctx.is_context_lost() {
+            warn!("[WebGL] The rendering context is lost.");
+        }
+
         self.state.cleared_surfaces.clear();
+        self.state.clears.reset();
         self.state.binded_surface = None;
+        self.state.binded_framebuffer = None;
+        self.per_object_ring.advance();
+        self.stats = FrameStats::default();
         Ok(())
     }
 
@@ -200,89 +273,89 @@ impl Visitor for WebGLVisitor {
         handle: SurfaceHandle,
         params: SurfaceParams,
     ) -> Result<()> {
+        if params.viewports[0].is_some() {
+            bail!("Viewport arrays is not supported in WebGL.");
+        }
+
+        if params.layered {
+            bail!("Layered attachment is not supported in WebGL.");
+        }
+
+        if params.srgb_write {
+            bail!("sRGB write control is not supported in WebGL.");
+        }
+
+        if let Some(v) = params.sampler_override {
+            if !self.samplers.contains_key(&v) {
+                let sampler = Self::create_sampler(&self.ctx, v)?;
+                self.samplers.insert(v, sampler);
+            }
+        }
+
         let mut data = GLSurfaceData {
             handle: handle,
             id: None,
             dims: None,
             params: params,
+            color_format: None,
         };
 
         if params.colors[0].is_some() || params.depth_stencil.is_some() {
-            let id = self.ctx.create_framebuffer().unwrap();
-            self.ctx.bind_framebuffer(WebGL::FRAMEBUFFER, Some(&id));
-            self.state.binded_surface = None;
-
-            let mut dimensions = None;
-            for (i, attachment) in params.colors.iter().enumerate() {
-                if let Some(v) = *attachment {
+            let mut attachments = Vec::new();
+            let depth_stencil = (SurfaceAttachment::DepthStencil, params.depth_stencil);
+            let points = params
+                .colors
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (SurfaceAttachment::Color(i), *v))
+                .chain(Some(depth_stencil));
+
+            for (point, v) in points {
+                if let Some(v) = v {
                     let rt = self
                         .render_textures
                         .get(v)
                         .ok_or_else(|| format_err!("RenderTexture handle {:?} is invalid.", v))?;
 
-                    if !rt.params.format.is_color() {
-                        bail!("Incompitable(mismatch format) attachments of SurfaceObject.");
-                    }
-
-                    if dimensions.is_some() && dimensions != Some(rt.params.dimensions) {
-                        bail!("Incompitable(mismatch dimensons) attachments of SurfaceObject.");
-                    }
-
-                    dimensions = Some(rt.params.dimensions);
-                    Self::bind_surface_render_texture(&self.ctx, &rt, i)?;
+                    attachments.push((point, v, rt.params));
                 }
             }
 
-            if let Some(v) = params.depth_stencil {
-                let rt = self
-                    .render_textures
-                    .get(v)
-                    .ok_or_else(|| format_err!("RenderTexture handle {:?} is invalid.", v))?;
-
-                if rt.params.format.is_color() {
-                    bail!("Incompitable(mismatch format) attachments of SurfaceObject.");
-                }
-
-                if dimensions.is_some() && dimensions != Some(rt.params.dimensions) {
-                    bail!("Incompitable(mismatch dimensions) attachments of SurfaceObject.");
-                }
-
-                dimensions = Some(rt.params.dimensions);
-                Self::bind_surface_render_texture(&self.ctx, &rt, 0)?;
+            if let Some(status) = validate_attachments(&attachments) {
+                return Err(incomplete_framebuffer(handle, status, &attachments));
             }
 
-            let status = self.ctx.check_framebuffer_status(WebGL::FRAMEBUFFER);
-            if status != WebGL::FRAMEBUFFER_COMPLETE {
-                self.ctx.bind_framebuffer(WebGL::FRAMEBUFFER, None);
-                self.state.binded_surface = None;
+            let id = self.ctx.create_framebuffer().unwrap();
+            self.ctx.bind_framebuffer(WebGL::FRAMEBUFFER, Some(&id));
+            self.state.binded_surface = None;
+            self.state.binded_framebuffer = None;
 
-                match status {
-                    WebGL::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => {
-                        bail!("[GL] Surface is incomplete. Not all framebuffer attachment points \
-                        are framebuffer attachment complete. This means that at least one attachment point with a \
-                        renderbuffer or texture attached has its attached object no longer in existence or has an \
-                        attached image with a width or height of zero, or the color attachment point has a non-color-renderable \
-                        image attached, or the depth attachment point has a non-depth-renderable image attached, or \
-                        the stencil attachment point has a non-stencil-renderable image attached.");
-                    }
+            let attached = attachments.iter().try_for_each(|&(point, v, _)| {
+                let index = match point {
+                    SurfaceAttachment::Color(i) => i,
+                    SurfaceAttachment::DepthStencil => 0,
+                };
 
-                    WebGL::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => {
-                        bail!("[GL] Surface is incomplete. No images are attached to the framebuffer.");
-                    }
+                let rt = self.render_textures.get(v).unwrap();
+                Self::bind_surface_render_texture(&self.ctx, WebGL::FRAMEBUFFER, &rt, index)
+            });
 
-                    WebGL::FRAMEBUFFER_UNSUPPORTED => {
-                        bail!("[GL] Surface is incomplete. The combination of internal formats \
-                        of the attached images violates an implementation-dependent set of restrictions.");
-                    }
+            let status = attached.map(|_| self.ctx.check_framebuffer_status(WebGL::FRAMEBUFFER));
+            if status.as_ref().ok() != Some(&WebGL::FRAMEBUFFER_COMPLETE) {
+                self.ctx.bind_framebuffer(WebGL::FRAMEBUFFER, None);
+                self.ctx.delete_framebuffer(Some(&id));
 
-                    _ => {
-                        bail!("[GL] Surface is incomplete.");
-                    }
-                }
+                let status = types::framebuffer_status(status?);
+                return Err(incomplete_framebuffer(handle, status, &attachments));
             }
 
+            data.color_format = attachments
+                .iter()
+                .find(|v| v.0 == SurfaceAttachment::Color(0))
+                .map(|v| v.2.format);
+
             data.id = Some(id);
-            data.dims = dimensions;
+            data.dims = attachments.first().map(|v| v.2.dimensions);
         };
 
         self.surfaces.create(handle, data);
@@ -297,6 +370,7 @@ impl Visitor for WebGLVisitor {
 
         if self.state.binded_surface == Some(handle) {
             self.state.binded_surface = None;
+            self.state.binded_framebuffer = None;
         }
 
         if let Some(v) = surface.id {
@@ -314,9 +388,40 @@ impl Visitor for WebGLVisitor {
         vs: &str,
         fs: &str,
     ) -> Result<()> {
+        if params.state.logic_op.is_some() {
+            bail!("Logical operation is not supported in WebGL.");
+        }
+
+        if params.state.sample_mask != !0 {
+            bail!("Sample mask is not supported in WebGL.");
+        }
+
+        if params.state.provoking_vertex != ProvokingVertex::Last {
+            bail!("Provoking vertex is fixed to the last one in WebGL.");
+        }
+
+        if params.state.polygon_mode != PolygonMode::Fill {
+            bail!("Polygon mode is fixed to fill in WebGL.");
+        }
+
         let vs = Self::compile(&self.ctx, WebGL::VERTEX_SHADER, vs)?;
         let fs = Self::compile(&self.ctx, WebGL::FRAGMENT_SHADER, fs)?;
-        let id = Self::link(&self.ctx, &[vs, fs])?;
+        let id = Self::link_with_locations(&self.ctx, &[vs, fs], &params.attribute_locations)?;
+        let per_object_block = match Self::per_object_block(&self.ctx, &id) {
+            Ok(v) => v,
+            Err(err) => {
+                self.ctx.delete_program(Some(&id));
+                return Err(err);
+            }
+        };
+
+        let uniform_blocks = match Self::uniform_blocks(&self.ctx, &id, &params) {
+            Ok(v) => v,
+            Err(err) => {
+                self.ctx.delete_program(Some(&id));
+                return Err(err);
+            }
+        };
 
         let shader = GLShaderData {
             handle: handle,
@@ -324,6 +429,8 @@ impl Visitor for WebGLVisitor {
             params: params,
             uniforms: RefCell::new(FastHashMap::default()),
             attributes: RefCell::new(FastHashMap::default()),
+            per_object_block,
+            uniform_blocks,
         };
 
         for (name, _, _) in shader.params.attributes.iter() {
@@ -334,7 +441,11 @@ impl Visitor for WebGLVisitor {
             }
         }
 
-        for &(ref name, _) in shader.params.uniforms.iter() {
+        for &(ref name, tp) in shader.params.uniforms.iter() {
+            if tp == UniformVariableType::UniformBuffer {
+                continue;
+            }
+
             if let Err(err) = shader.uniform_location(&self.ctx, name) {
                 self.ctx.delete_program(Some(&shader.id));
                 bail!(err);
@@ -354,7 +465,7 @@ impl Visitor for WebGLVisitor {
         // Removes deprecated `VertexArrayObject`s.
         {
             let ctx = &self.ctx;
-            self.state.vaos.retain(|&(h, _), vao| {
+            self.state.vaos.retain(|&(h, _, _), vao| {
                 if h == shader.handle {
                     ctx.delete_vertex_array(Some(&vao));
                     false
@@ -379,6 +490,11 @@ impl Visitor for WebGLVisitor {
         data: Option<TextureData>,
     ) -> Result<()> {
         if !self.capabilities.support_texture_format(params.format) {
+            warn!(
+                "[WebGL] Texture format {:?} of {:?} is not supported.",
+                params.format, handle
+            );
+
             bail!(
                 "The GL Context does not support the texture format {:?}.",
                 params.format
@@ -388,7 +504,7 @@ impl Visitor for WebGLVisitor {
         let id = self.ctx.create_texture().unwrap();
         let mut allocated = false;
 
-        if let Some(mut data) = data {
+        if let Some(data) = data {
             let len = data.bytes.len();
             if len > 0 {
                 Self::bind_texture(
@@ -399,46 +515,13 @@ impl Visitor for WebGLVisitor {
                     Some(&id),
                 )?;
 
-                Self::bind_texture_params(&self.ctx, params.wrap, params.filter, len as u32)?;
-
-                let (internal_format, format, pixel_type) = params.format.into();
-                let mut dims = (params.dimensions.x as i32, params.dimensions.y as i32);
-
-                if params.format.compressed() {
-                    for (i, v) in data.bytes.drain(..).enumerate() {
-                        let mv = ::std::slice::from_raw_parts_mut(v.as_ptr() as *mut u8, v.len());
-                        self.ctx.compressed_tex_image_2d_with_u8_array(
-                            WebGL::TEXTURE_2D,
-                            i as i32,
-                            internal_format,
-                            dims.0,
-                            dims.1,
-                            0,
-                            mv,
-                        );
-
-                        dims.0 = (dims.0 / 2).max(1);
-                        dims.1 = (dims.1 / 2).max(1);
-                    }
-                } else {
-                    for (i, v) in data.bytes.drain(..).enumerate() {
-                        let mv = ::std::slice::from_raw_parts_mut(v.as_ptr() as *mut u8, v.len());
-                        self.ctx
-                            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
-                                WebGL::TEXTURE_2D,
-                                i as i32,
-                                internal_format as i32,
-                                dims.0,
-                                dims.1,
-                                0,
-                                format,
-                                pixel_type,
-                                Some(mv),
-                            ).unwrap();
-
-                        dims.0 = (dims.0 / 2).max(1);
-                        dims.1 = (dims.1 / 2).max(1);
-                    }
+                let wrap = (params.wrap_s, params.wrap_t);
+                let filter = (params.min_filter, params.mag_filter, params.mip_filter);
+                let levels = params.mip_levels(len as u32);
+                Self::bind_texture_params(&self.ctx, WebGL::TEXTURE_2D, wrap, filter, levels)?;
+                self.upload_texture(WebGL::TEXTURE_2D, params, &data)?;
+                if params.mipmap {
+                    self.ctx.generate_mipmap(WebGL::TEXTURE_2D);
                 }
 
                 allocated = true;
@@ -460,10 +543,88 @@ impl Visitor for WebGLVisitor {
         Ok(())
     }
 
+    unsafe fn create_cube_texture(
+        &mut self,
+        handle: TextureHandle,
+        params: TextureParams,
+        data: Option<CubeTextureData>,
+    ) -> Result<()> {
+        if !self.capabilities.support_texture_format(params.format) {
+            bail!(
+                "The GL Context does not support the texture format {:?}.",
+                params.format
+            );
+        }
+
+        let id = self.ctx.create_texture().unwrap();
+        Self::bind_texture(
+            &self.ctx,
+            &mut self.state,
+            Some(Sampler::Cube(handle)),
+            0,
+            Some(&id),
+        )?;
+
+        let len = data.as_ref().map(|v| v.faces[0].bytes.len()).unwrap_or(0);
+        let wrap = (params.wrap_s, params.wrap_t);
+        let filter = (params.min_filter, params.mag_filter, params.mip_filter);
+        let levels = params.mip_levels(len as u32);
+        Self::bind_texture_params(&self.ctx, WebGL::TEXTURE_CUBE_MAP, wrap, filter, levels)?;
+
+        // The storage of faces are always allocated, since the cube maps could not be
+        // updated partially.
+        if let Some(data) = data.filter(|_| len > 0) {
+            for (i, face) in data.faces.iter().enumerate() {
+                let target = WebGL::TEXTURE_CUBE_MAP_POSITIVE_X + i as u32;
+                self.upload_texture(target, params, face)?;
+            }
+
+            if params.mipmap {
+                self.ctx.generate_mipmap(WebGL::TEXTURE_CUBE_MAP);
+            }
+        } else {
+            let (internal_format, format, pixel_type) = params.format.into();
+            for i in 0..6 {
+                self.ctx
+                    .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                        WebGL::TEXTURE_CUBE_MAP_POSITIVE_X + i,
+                        0,
+                        internal_format as i32,
+                        params.dimensions.x as i32,
+                        params.dimensions.y as i32,
+                        0,
+                        format,
+                        pixel_type,
+                        None,
+                    )
+                    .unwrap();
+            }
+        }
+
+        check(&self.ctx)?;
+
+        self.textures.create(
+            handle,
+            GLTextureData {
+                handle: handle,
+                id: id,
+                params: params,
+                allocated: RefCell::new(true),
+            },
+        );
+
+        Ok(())
+    }
+
+    unsafe fn import_texture(&mut self, _: TextureHandle, _: TextureParams, _: u32) -> Result<()> {
+        bail!("Importing native texture objects is not supported in WebGL.");
+    }
+
     unsafe fn update_texture(
         &mut self,
         handle: TextureHandle,
         area: Aabb2<u32>,
+        row_length: u32,
         data: &[u8],
     ) -> Result<()> {
         let texture = self
@@ -479,13 +640,21 @@ impl Visitor for WebGLVisitor {
             bail!("Trying to update compressed texture.");
         }
 
-        if data.len() > area.volume() as usize
-            || area.min.x >= texture.params.dimensions.x
-            || area.min.y >= texture.params.dimensions.y
-        {
+        if texture.params.kind == TextureKind::Cube {
+            bail!("Trying to update cube texture.");
+        }
+
+        if area.min.x >= texture.params.dimensions.x || area.min.y >= texture.params.dimensions.y {
             bail!("Trying to update texture data out of bounds.");
         }
 
+        let dims = area.dim();
+        let pitch = texture.params.format.size(Vector2::new(row_length, 1)) as usize;
+        let last = texture.params.format.size(Vector2::new(dims.x, 1)) as usize;
+        if data.len() < pitch * (dims.y.max(1) - 1) as usize + last {
+            bail!("Trying to update texture with insufficient data.");
+        }
+
         let (internal_format, format, pixel_type) = texture.params.format.into();
 
         Self::bind_texture(
@@ -497,7 +666,11 @@ impl Visitor for WebGLVisitor {
         )?;
 
         if !*texture.allocated.borrow() {
-            Self::bind_texture_params(&self.ctx, texture.params.wrap, texture.params.filter, 1)?;
+            let params = texture.params;
+            let wrap = (params.wrap_s, params.wrap_t);
+            let filter = (params.min_filter, params.mag_filter, params.mip_filter);
+            let levels = params.mip_levels(1);
+            Self::bind_texture_params(&self.ctx, WebGL::TEXTURE_2D, wrap, filter, levels)?;
 
             self.ctx
                 .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
@@ -515,6 +688,14 @@ impl Visitor for WebGLVisitor {
             *texture.allocated.borrow_mut() = true;
         }
 
+        let alignment = texture.params.format.unpack_alignment(row_length);
+        Self::set_unpack_alignment(&self.ctx, &mut self.state, alignment)?;
+
+        if row_length != dims.x {
+            self.ctx
+                .pixel_storei(WebGL::UNPACK_ROW_LENGTH, row_length as i32);
+        }
+
         let mv = ::std::slice::from_raw_parts_mut(data.as_ptr() as *mut u8, data.len());
         self.ctx
             .tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_u8_array(
@@ -522,13 +703,22 @@ impl Visitor for WebGLVisitor {
                 0,
                 area.min.x as i32,
                 area.min.y as i32,
-                area.dim().x as i32,
-                area.dim().y as i32,
+                dims.x as i32,
+                dims.y as i32,
                 format,
                 pixel_type,
                 Some(mv),
             ).unwrap();
 
+        if row_length != dims.x {
+            self.ctx.pixel_storei(WebGL::UNPACK_ROW_LENGTH, 0);
+        }
+
+        // Regenerates the whole chain, since the mipmaps could not be generated partially.
+        if texture.params.mipmap {
+            self.ctx.generate_mipmap(WebGL::TEXTURE_2D);
+        }
+
         check(&self.ctx)
     }
 
@@ -539,7 +729,7 @@ impl Visitor for WebGLVisitor {
             .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
 
         for v in self.state.binded_textures.iter_mut() {
-            if *v == Some(Sampler::Texture(handle)) {
+            if *v == Some(Sampler::Texture(handle)) || *v == Some(Sampler::Cube(handle)) {
                 *v = None;
             }
         }
@@ -563,7 +753,9 @@ impl Visitor for WebGLVisitor {
                 0,
                 Some(&id),
             )?;
-            Self::bind_texture_params(&self.ctx, params.wrap, params.filter, 1)?;
+            let wrap = (params.wrap, params.wrap);
+            let filter = (params.filter, params.filter, None);
+            Self::bind_texture_params(&self.ctx, WebGL::TEXTURE_2D, wrap, filter, (0, 0))?;
 
             let (internal_format, format, pixel_type) = params.format.into();
             self.ctx
@@ -579,6 +771,24 @@ impl Visitor for WebGLVisitor {
                     None,
                 ).unwrap();
 
+            if let Some(cmp) = params.compare {
+                if !params.format.is_color() {
+                    let func: u32 = cmp.into();
+                    self.ctx.tex_parameteri(
+                        WebGL::TEXTURE_2D,
+                        WebGL::TEXTURE_COMPARE_MODE,
+                        WebGL::COMPARE_REF_TO_TEXTURE as i32,
+                    );
+                    self.ctx.tex_parameteri(
+                        WebGL::TEXTURE_2D,
+                        WebGL::TEXTURE_COMPARE_FUNC,
+                        func as i32,
+                    );
+                } else {
+                    warn!("[WebGL] Ignores compare mode of color {:?}.", handle);
+                }
+            }
+
             GLRenderTexture::T(id)
         } else {
             let id = self.ctx.create_renderbuffer().unwrap();
@@ -609,6 +819,62 @@ impl Visitor for WebGLVisitor {
         Ok(())
     }
 
+    unsafe fn resize_render_texture(
+        &mut self,
+        handle: RenderTextureHandle,
+        dimensions: Vector2<u32>,
+    ) -> Result<()> {
+        let rt = self
+            .render_textures
+            .get_mut(handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        rt.params.dimensions = dimensions;
+        let (internal_format, format, pixel_type) = rt.params.format.into();
+
+        // Respecifies the storage of the same object, so it stays attached to framebuffers.
+        match rt.id {
+            GLRenderTexture::T(ref id) => {
+                let sampler = Some(Sampler::RenderTexture(handle));
+                Self::bind_texture(&self.ctx, &mut self.state, sampler, 0, Some(id))?;
+                self.ctx
+                    .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                        WebGL::TEXTURE_2D,
+                        0,
+                        internal_format as i32,
+                        dimensions.x as i32,
+                        dimensions.y as i32,
+                        0,
+                        format,
+                        pixel_type,
+                        None,
+                    ).unwrap();
+            }
+            GLRenderTexture::R(ref id) => {
+                self.ctx.bind_renderbuffer(WebGL::RENDERBUFFER, Some(id));
+                self.ctx.renderbuffer_storage(
+                    WebGL::RENDERBUFFER,
+                    internal_format,
+                    dimensions.x as i32,
+                    dimensions.y as i32,
+                );
+            }
+        }
+
+        check(&self.ctx)?;
+
+        for surface in self.surfaces.buf.iter_mut().filter_map(|v| v.as_mut()) {
+            if surface.params.is_attached(handle) {
+                surface.dims = Some(dimensions);
+                if self.state.binded_surface == Some(surface.handle) {
+                    self.state.binded_surface = None;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     unsafe fn delete_render_texture(&mut self, handle: RenderTextureHandle) -> Result<()> {
         let rt = self
             .render_textures
@@ -647,13 +913,18 @@ impl Visitor for WebGLVisitor {
             data.as_ref().map(|v| v.vptr.as_ref()),
         )?;
 
-        let ibo = Self::create_buffer(
-            &self.ctx,
-            WebGL::ELEMENT_ARRAY_BUFFER,
-            params.hint,
-            params.index_buffer_len(),
-            data.as_ref().map(|v| v.iptr.as_ref()),
-        )?;
+        // Non-indexed meshes have no index buffer at all.
+        let ibo = if params.is_indexed() {
+            Some(Self::create_buffer(
+                &self.ctx,
+                WebGL::ELEMENT_ARRAY_BUFFER,
+                params.hint,
+                params.index_buffer_len(),
+                data.as_ref().map(|v| v.iptr.as_ref()),
+            )?)
+        } else {
+            None
+        };
 
         self.meshes.create(
             handle,
@@ -701,13 +972,12 @@ impl Visitor for WebGLVisitor {
             bail!("Trying to update immutable buffer");
         }
 
-        Self::update_buffer(
-            &self.ctx,
-            WebGL::ELEMENT_ARRAY_BUFFER,
-            &mesh.ibo,
-            offset,
-            data,
-        )
+        let ibo = match mesh.ibo {
+            Some(ref ibo) => ibo,
+            None => bail!("Trying to update index buffer of non-indexed mesh."),
+        };
+
+        Self::update_buffer(&self.ctx, WebGL::ELEMENT_ARRAY_BUFFER, ibo, offset, data)
     }
 
     unsafe fn delete_mesh(&mut self, handle: MeshHandle) -> Result<()> {
@@ -719,7 +989,7 @@ impl Visitor for WebGLVisitor {
         // Removes deprecated `VertexArrayObject`s.
         {
             let ctx = &self.ctx;
-            self.state.vaos.retain(|&(_, h), vao| {
+            self.state.vaos.retain(|&(_, h, _), vao| {
                 if h == mesh.handle {
                     ctx.delete_vertex_array(Some(&vao));
                     false
@@ -730,105 +1000,410 @@ impl Visitor for WebGLVisitor {
         }
 
         self.ctx.delete_buffer(Some(&mesh.vbo));
-        self.ctx.delete_buffer(Some(&mesh.ibo));
+        self.ctx.delete_buffer(mesh.ibo.as_ref());
         check(&self.ctx)
     }
 
-    unsafe fn bind(&mut self, handle: SurfaceHandle, dimensions: Vector2<u32>) -> Result<()> {
-        if self.state.binded_surface == Some(handle) {
-            return Ok(());
-        }
+    unsafe fn create_instance_buffer(
+        &mut self,
+        handle: InstanceBufferHandle,
+        params: InstanceBufferParams,
+        data: Option<&[u8]>,
+    ) -> Result<()> {
+        let vbo = Self::create_buffer(
+            &self.ctx,
+            WebGL::ARRAY_BUFFER,
+            params.hint,
+            params.buffer_len(),
+            data,
+        )?;
 
-        let surface = self
-            .surfaces
-            .get(handle)
-            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+        self.instance_buffers.create(
+            handle,
+            GLInstanceBufferData {
+                handle,
+                vbo,
+                params,
+            },
+        );
 
-        // Bind frame buffer.
-        let id = surface.id.as_ref();
-        let dimensions = surface.dims.unwrap_or(dimensions);
-        self.ctx.bind_framebuffer(WebGL::FRAMEBUFFER, id);
+        Ok(())
+    }
 
-        // Reset the viewport and scissor box.
-        let vp = SurfaceViewport {
-            position: Vector2::new(0, 0),
-            size: dimensions,
-        };
+    unsafe fn update_instance_buffer(
+        &mut self,
+        handle: InstanceBufferHandle,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<()> {
+        let instances = self
+            .instance_buffers
+            .get(handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
 
-        Self::set_viewport(&self.ctx, &mut self.state, vp)?;
-        Self::set_scissor(&self.ctx, &mut self.state, SurfaceScissor::Disable)?;
+        if instances.params.hint == MeshHint::Immutable {
+            bail!("Trying to update immutable buffer");
+        }
 
-        if !self.state.cleared_surfaces.contains(&handle) {
-            // Sets depth write enable to make sure that we can clear depth buffer properly.
-            if surface.params.clear_depth.is_some() {
-                self.state.binded_shader = None;
-                Self::set_depth_test(&self.ctx, &mut self.state, true, Comparison::Always)?;
-            }
+        Self::update_buffer(&self.ctx, WebGL::ARRAY_BUFFER, &instances.vbo, offset, data)
+    }
 
-            // Clears frame buffer.
-            Self::clear(
-                &self.ctx,
-                surface.params.clear_color,
-                surface.params.clear_depth,
-                surface.params.clear_stencil,
-            )?;
+    unsafe fn delete_instance_buffer(&mut self, handle: InstanceBufferHandle) -> Result<()> {
+        let instances = self
+            .instance_buffers
+            .free(handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
 
-            self.state.cleared_surfaces.insert(handle);
+        // Removes deprecated `VertexArrayObject`s.
+        {
+            let ctx = &self.ctx;
+            self.state.vaos.retain(|&(_, _, h), vao| {
+                if h == Some(instances.handle) {
+                    ctx.delete_vertex_array(Some(&vao));
+                    false
+                } else {
+                    true
+                }
+            });
         }
 
-        self.state.binded_surface = Some(handle);
-        Ok(())
+        self.ctx.delete_buffer(Some(&instances.vbo));
+        check(&self.ctx)
     }
 
-    unsafe fn draw(
+    unsafe fn create_shader_storage_buffer(
         &mut self,
-        shader: ShaderHandle,
-        mesh: MeshHandle,
-        mesh_index: MeshIndex,
-        uniforms: &[UniformVar],
-    ) -> Result<u32> {
-        // Bind program and associated uniforms and textures.
-        let shader = self
-            .shaders
-            .get(shader)
-            .ok_or_else(|| format_err!("{:?} is invalid.", shader))?;
+        _: ShaderStorageBufferHandle,
+        _: ShaderStorageBufferParams,
+        _: Option<&[u8]>,
+    ) -> Result<()> {
+        bail!("Shader storage buffer objects are not supported in WebGL.");
+    }
 
-        Self::bind_shader(&self.ctx, &mut self.state, &shader)?;
+    unsafe fn update_shader_storage_buffer(
+        &mut self,
+        _: ShaderStorageBufferHandle,
+        _: usize,
+        _: &[u8],
+    ) -> Result<()> {
+        bail!("Shader storage buffer objects are not supported in WebGL.");
+    }
 
-        let mut index = 0usize;
-        for &(field, variable) in uniforms {
-            if let Some(tp) = shader.params.uniforms.variable_type(field) {
-                if tp != variable.variable_type() {
-                    let name = shader.params.uniforms.variable_name(field).unwrap();
-                    bail!(
-                        "The uniform {} needs a {:?} instead of {:?}.",
-                        name,
-                        tp,
-                        variable.variable_type(),
-                    );
-                }
+    unsafe fn delete_shader_storage_buffer(&mut self, _: ShaderStorageBufferHandle) -> Result<()> {
+        bail!("Shader storage buffer objects are not supported in WebGL.");
+    }
 
-                let location = shader.hash_uniform_location(field).unwrap();
-                match variable {
-                    UniformVariable::Texture(handle) => {
-                        let v = UniformVariable::I32(index as i32);
-                        Self::bind_uniform_variable(&self.ctx, &location, &v)?;
+    unsafe fn read_shader_storage_buffer(&mut self, _: ShaderStorageBufferHandle) -> Result<()> {
+        bail!("Shader storage buffer objects are not supported in WebGL.");
+    }
 
-                        if let Some(texture) = self.textures.get(handle) {
-                            Self::bind_texture(
-                                &self.ctx,
-                                &mut self.state,
-                                Some(Sampler::Texture(handle)),
-                                index,
+    unsafe fn create_uniform_buffer(
+        &mut self,
+        handle: UniformBufferHandle,
+        params: UniformBufferParams,
+        data: Option<&[u8]>,
+    ) -> Result<()> {
+        let id = Self::create_buffer(
+            &self.ctx,
+            WebGL::UNIFORM_BUFFER,
+            params.hint,
+            params.size,
+            data,
+        )?;
+
+        self.uniform_buffers
+            .create(handle, GLUniformBufferData { id, params });
+
+        Ok(())
+    }
+
+    unsafe fn update_uniform_buffer(
+        &mut self,
+        handle: UniformBufferHandle,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<()> {
+        let buffer = self
+            .uniform_buffers
+            .get(handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        if buffer.params.hint == MeshHint::Immutable {
+            bail!("Trying to update immutable buffer");
+        }
+
+        Self::update_buffer(&self.ctx, WebGL::UNIFORM_BUFFER, &buffer.id, offset, data)
+    }
+
+    unsafe fn delete_uniform_buffer(&mut self, handle: UniformBufferHandle) -> Result<()> {
+        let buffer = self
+            .uniform_buffers
+            .free(handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        self.ctx.delete_buffer(Some(&buffer.id));
+        check(&self.ctx)
+    }
+
+    unsafe fn read_render_texture(
+        &mut self,
+        handle: RenderTextureHandle,
+        area: Aabb2<u32>,
+    ) -> Result<()> {
+        let rt = self
+            .render_textures
+            .get(handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        let format = rt.params.format;
+        if !format.is_color() {
+            bail!("{:?} of {:?} could not be read back.", format, handle);
+        }
+
+        // Reads through a temporary framebuffer, so it works even if the render texture
+        // is attached to the binded surface.
+        let ctx = &self.ctx;
+        let fbo = ctx.create_framebuffer().unwrap();
+        self.state.binded_surface = None;
+        self.state.binded_framebuffer = None;
+
+        ctx.bind_framebuffer(WebGL::READ_FRAMEBUFFER, Some(&fbo));
+        Self::bind_surface_render_texture(ctx, WebGL::READ_FRAMEBUFFER, rt, 0)?;
+
+        let dimensions = area.dim();
+        let mut bytes = vec![0u8; format.size(dimensions) as usize];
+        let (_, format, pixel_type) = format.into();
+
+        ctx.pixel_storei(WebGL::PACK_ALIGNMENT, 1);
+        let result = ctx.read_pixels_with_opt_u8_array(
+            area.min.x as i32,
+            area.min.y as i32,
+            dimensions.x as i32,
+            dimensions.y as i32,
+            format,
+            pixel_type,
+            Some(&mut bytes),
+        );
+
+        ctx.bind_framebuffer(WebGL::FRAMEBUFFER, None);
+        ctx.delete_framebuffer(Some(&fbo));
+        check(ctx)?;
+
+        if result.is_err() {
+            bail!("[WebGL] Failed to read back the pixels of {:?}.", handle);
+        }
+
+        self.readbacks.push((handle.into(), bytes));
+        Ok(())
+    }
+
+    fn take_readbacks(&mut self) -> Vec<(VideoHandle, Vec<u8>)> {
+        ::std::mem::replace(&mut self.readbacks, Vec::new())
+    }
+
+    // The timer queries of WebGL are behind the `EXT_disjoint_timer_query_webgl2`, which
+    // is disabled by most of browsers. So the timers are just ignored.
+    unsafe fn begin_gpu_timer(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+
+    unsafe fn end_gpu_timer(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn take_gpu_timers(&mut self) -> Vec<(String, Duration)> {
+        Vec::new()
+    }
+
+    unsafe fn bind(&mut self, handle: SurfaceHandle, dimensions: Vector2<u32>) -> Result<()> {
+        if self.state.binded_surface == Some(handle) {
+            return Ok(());
+        }
+
+        let surface = self
+            .surfaces
+            .get(handle)
+            .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+        // Bind frame buffer, the window framebuffer will be redirected into the offscreen
+        // framebuffer if antialiasing is enabled.
+        let id = match (surface.id.as_ref(), self.antialiasing.as_mut()) {
+            (Some(id), _) => Some(id),
+            (None, Some(aa)) => Some(aa.framebuffer(&self.ctx, dimensions)?),
+            (None, None) => None,
+        };
+
+        let dimensions = surface.dims.unwrap_or(dimensions);
+        if self.state.binded_framebuffer.as_ref().map(|v| v.as_ref()) != Some(id) {
+            self.ctx.bind_framebuffer(WebGL::FRAMEBUFFER, id);
+            self.state.binded_framebuffer = Some(id.cloned());
+            self.stats.framebuffer_binds += 1;
+        }
+
+        // Reset the viewport and scissor box.
+        let vp = SurfaceViewport {
+            position: Vector2::new(0, 0),
+            size: dimensions,
+        };
+
+        Self::set_viewport(&self.ctx, &mut self.state, vp)?;
+        Self::set_scissor(&self.ctx, &mut self.state, SurfaceScissor::Disable)?;
+        self.state.cull_override = surface.params.cull_override;
+
+        let sampler = surface
+            .params
+            .sampler_override
+            .and_then(|v| self.samplers.get(&v));
+        Self::set_sampler(&self.ctx, &mut self.state, sampler)?;
+
+        self.state.clears.bind(&surface.params);
+
+        if !self.state.cleared_surfaces.contains(&handle) {
+            // Skips the clears of attachments which hold the same values already, e.g. the
+            // surfaces sharing the window framebuffer. The integer color attachment could
+            // only be cleared with `clearBuffer[u]iv`.
+            let params = &surface.params;
+            let clears = &mut self.state.clears;
+            let (color, integer) = match surface.color_format {
+                Some(format) if format.is_integer() => {
+                    let v = clears.filter(ClearBuffer::Color, params.clear_integer);
+                    (None, v.map(|v| (format, v)))
+                }
+                _ => (clears.filter(ClearBuffer::Color, params.clear_color), None),
+            };
+
+            let depth = clears.filter(ClearBuffer::Depth, params.clear_depth);
+            let stencil = clears.filter(ClearBuffer::Stencil, params.clear_stencil);
+
+            // The clear operation ignores the viewport, but respects the scissor box and
+            // write masks. So we make sure that the whole attachments will be cleared, and
+            // the masks of shader would be re-applied by the next draw.
+            let last = ClearStates::new(self.state.scissor, &self.state.render_state);
+            let states = last.clear(color.is_some() || integer.is_some(), depth.is_some());
+            let (write, test) = (states.depth_write, states.depth_test);
+            Self::set_scissor(&self.ctx, &mut self.state, states.scissor)?;
+            Self::set_color_write(&self.ctx, &mut self.state, states.color_write)?;
+            Self::set_depth_test(&self.ctx, &mut self.state, write, test)?;
+
+            if last.is_overwritten_by(&states) {
+                self.state.binded_shader = None;
+            }
+
+            if let Some((format, v)) = integer {
+                Self::clear_integer(&self.ctx, format, v)?;
+            }
+
+            Self::clear(&self.ctx, color, depth, stencil)?;
+
+            if color.is_some() || integer.is_some() || depth.is_some() || stencil.is_some() {
+                self.stats.clears += 1;
+            } else if params.clear_color.is_some()
+                || params.clear_integer.is_some()
+                || params.clear_depth.is_some()
+                || params.clear_stencil.is_some()
+            {
+                self.stats.skipped_clears += 1;
+            }
+
+            self.state.cleared_surfaces.insert(handle);
+        }
+
+        self.state.binded_surface = Some(handle);
+        Ok(())
+    }
+
+    unsafe fn draw(
+        &mut self,
+        dc: DrawCommand,
+        uniforms: &[UniformVar],
+        per_object: Option<&[u8]>,
+    ) -> Result<u32> {
+        // Bind program and associated uniforms and textures.
+        let shader = self
+            .shaders
+            .get(dc.shader)
+            .ok_or_else(|| format_err!("{:?} is invalid.", dc.shader))?;
+
+        Self::bind_shader(&self.ctx, &mut self.state, &shader)?;
+        let face = self.state.cull_override;
+        let face = face.unwrap_or(shader.params.state.cull_face);
+        Self::set_cull_face(&self.ctx, &mut self.state, face)?;
+        self.state.clears.touch();
+
+        let units = texture_units(uniforms)?;
+        for (i, &(field, variable)) in uniforms.iter().enumerate() {
+            if let Some(tp) = shader.params.uniforms.variable_type(field) {
+                if !tp.accepts(variable.variable_type()) {
+                    let name = shader.params.uniforms.variable_name(field).unwrap();
+                    bail!(
+                        "The uniform {} needs a {:?} instead of {:?}.",
+                        name,
+                        tp,
+                        variable.variable_type(),
+                    );
+                }
+
+                // The uniform blocks are binded by their indices instead of locations.
+                if let UniformVariable::UniformBuffer(binding, handle) = variable {
+                    let (index, size) = shader.uniform_blocks[&field];
+                    let buffer = self
+                        .uniform_buffers
+                        .get(handle)
+                        .ok_or_else(|| format_err!("{:?} is invalid.", handle))?;
+
+                    if buffer.params.size < size {
+                        let name = shader.params.uniforms.variable_name(field).unwrap();
+                        bail!(
+                            "The uniform block {} needs {} bytes, but {:?} only has {}.",
+                            name,
+                            size,
+                            handle,
+                            buffer.params.size
+                        );
+                    }
+
+                    let binding = u32::from(binding);
+                    self.ctx.uniform_block_binding(&shader.id, index, binding);
+                    self.ctx
+                        .bind_buffer_base(WebGL::UNIFORM_BUFFER, binding, Some(&buffer.id));
+                    check(&self.ctx)?;
+                    continue;
+                }
+
+                let location = shader.hash_uniform_location(field).unwrap();
+                match variable {
+                    UniformVariable::Texture(handle) | UniformVariable::TextureUnit(_, handle) => {
+                        let index = units[i];
+                        let v = UniformVariable::I32(index as i32);
+                        Self::bind_uniform_variable(&self.ctx, &location, &v)?;
+
+                        // Substitutes the fallback texture if the resource is not ready,
+                        // e.g. still loading or failed to load.
+                        let handle = match self.fallback_texture {
+                            Some(fallback) if self.textures.get(handle).is_none() => fallback,
+                            _ => handle,
+                        };
+
+                        if let Some(texture) = self.textures.get(handle) {
+                            let sampler = match texture.params.kind {
+                                TextureKind::Texture2D => Sampler::Texture(handle),
+                                TextureKind::Cube => Sampler::Cube(handle),
+                            };
+
+                            Self::bind_texture(
+                                &self.ctx,
+                                &mut self.state,
+                                Some(sampler),
+                                index,
                                 Some(&texture.id),
                             )?;
                         } else {
                             Self::bind_texture(&self.ctx, &mut self.state, None, index, None)?;
                         }
-
-                        index += 1;
                     }
-                    UniformVariable::RenderTexture(handle) => {
+                    UniformVariable::RenderTexture(handle)
+                    | UniformVariable::RenderTextureUnit(_, handle) => {
+                        let index = units[i];
                         let v = UniformVariable::I32(index as i32);
                         Self::bind_uniform_variable(&self.ctx, &location, &v)?;
 
@@ -850,8 +1425,12 @@ impl Visitor for WebGLVisitor {
                         } else {
                             Self::bind_texture(&self.ctx, &mut self.state, None, index, None)?;
                         }
-
-                        index += 1;
+                    }
+                    UniformVariable::Image(_, _) => {
+                        bail!("Image load/store is not supported in WebGL.");
+                    }
+                    UniformVariable::StorageBuffer(_, _) => {
+                        bail!("Shader storage buffer objects are not supported in WebGL.");
                     }
                     _ => {
                         Self::bind_uniform_variable(&self.ctx, &location, &variable)?;
@@ -862,46 +1441,99 @@ impl Visitor for WebGLVisitor {
             }
         }
 
-        if let Some(mesh) = self.meshes.get(mesh) {
-            // Bind vertex buffer and vertex array object.
-            Self::bind_mesh(&self.ctx, &mut self.state, &shader, &mesh)?;
+        if let Some(bytes) = per_object {
+            let size = shader.per_object_block.ok_or_else(|| {
+                format_err!(
+                    "Uniform block {} is undefined in shader sources.",
+                    PER_OBJECT_BLOCK
+                )
+            })?;
+
+            let alignment = self.capabilities.uniform_buffer_offset_alignment as usize;
+            let (id, offset) = self
+                .per_object_ring
+                .push(&self.ctx, bytes, size, alignment)?;
+
+            self.ctx.bind_buffer_range_with_i32_and_i32(
+                WebGL::UNIFORM_BUFFER,
+                PER_OBJECT_BLOCK_BINDING,
+                Some(id),
+                offset as i32,
+                size.max(bytes.len()) as i32,
+            );
 
-            let (from, len) = match mesh_index {
-                MeshIndex::Ptr(from, len) => {
-                    if (from + len) > mesh.params.num_idxes {
-                        bail!("MeshIndex is out of bounds");
-                    }
+            check(&self.ctx)?;
+        }
 
-                    ((from * mesh.params.index_format.stride()), len)
-                }
-                MeshIndex::SubMesh(index) => {
-                    let num = mesh.params.sub_mesh_offsets.len();
-                    let from = mesh
-                        .params
-                        .sub_mesh_offsets
-                        .get(index)
-                        .ok_or_else(|| format_err!("MeshIndex is out of bounds"))?;
-
-                    let to = if index == (num - 1) {
-                        mesh.params.num_idxes
-                    } else {
-                        mesh.params.sub_mesh_offsets[index + 1]
-                    };
-
-                    ((from * mesh.params.index_format.stride()), (to - from))
-                }
-                MeshIndex::All => (0, mesh.params.num_idxes),
+        if let Some(mesh) = self.meshes.get(dc.mesh) {
+            let instances = match dc.instances {
+                Some(handle) => Some(
+                    self.instance_buffers
+                        .get(handle)
+                        .ok_or_else(|| format_err!("{:?} is invalid.", handle))?,
+                ),
+                None => None,
             };
 
-            self.ctx.draw_elements_with_i32(
-                mesh.params.primitive.into(),
-                len as i32,
-                mesh.params.index_format.into(),
-                from as i32,
-            );
+            if let (Some(num), Some(instances)) = (dc.num_instances, instances) {
+                if num as usize > instances.params.num_instances {
+                    bail!(
+                        "Drawing {} instances with {:?} that only has {}.",
+                        num,
+                        instances.handle,
+                        instances.params.num_instances
+                    );
+                }
+            }
+
+            // Bind vertex buffer and vertex array object.
+            Self::bind_mesh(&self.ctx, &mut self.state, &shader, &mesh, instances)?;
+
+            let (from, len) = mesh_elements(&mesh.params, dc.mesh_index)?;
+
+            // Applies the scissor and viewport of this draw, and restores the ones of surface
+            // afterwards.
+            let last_scissor = self.state.scissor;
+            if let Some(v) = dc.scissor {
+                Self::set_scissor(&self.ctx, &mut self.state, v)?;
+            }
+
+            let last_view = self.state.view;
+            if let Some(v) = dc.viewport {
+                Self::set_viewport(&self.ctx, &mut self.state, v)?;
+            }
+
+            let primitive = mesh.params.primitive.into();
+            if mesh.params.is_indexed() {
+                let offset = (from * mesh.params.index_format.stride()) as i32;
+                let format = mesh.params.index_format.into();
+                if let Some(num) = dc.num_instances {
+                    self.ctx.draw_elements_instanced_with_i32(
+                        primitive, len as i32, format, offset, num as i32,
+                    );
+                } else {
+                    self.ctx
+                        .draw_elements_with_i32(primitive, len as i32, format, offset);
+                }
+            } else if let Some(num) = dc.num_instances {
+                self.ctx
+                    .draw_arrays_instanced(primitive, from as i32, len as i32, num as i32);
+            } else {
+                self.ctx.draw_arrays(primitive, from as i32, len as i32);
+            }
 
             check(&self.ctx)?;
-            Ok(mesh.params.primitive.assemble(len as u32))
+
+            if dc.scissor.is_some() {
+                Self::set_scissor(&self.ctx, &mut self.state, last_scissor)?;
+            }
+
+            if dc.viewport.is_some() {
+                Self::set_viewport(&self.ctx, &mut self.state, last_view)?;
+            }
+
+            let num = dc.num_instances.unwrap_or(1);
+            Ok(mesh.params.primitive.assemble(len as u32) * num)
         } else {
             Ok(0)
         }
@@ -915,22 +1547,250 @@ impl Visitor for WebGLVisitor {
         Self::set_viewport(&self.ctx, &mut self.state, vp)
     }
 
+    unsafe fn finish(&mut self) -> Result<()> {
+        self.ctx.finish();
+        check(&self.ctx)
+    }
+
+    unsafe fn flush_gpu(&mut self) -> Result<()> {
+        self.ctx.flush();
+        check(&self.ctx)
+    }
+
+    unsafe fn memory_barrier(&mut self, _: MemoryBarrier) -> Result<()> {
+        // WebGL has no incoherent memory writes, the accesses are always ordered.
+        Ok(())
+    }
+
+    unsafe fn blit(
+        &mut self,
+        src: RenderTextureHandle,
+        dst: RenderTextureHandle,
+        filter: TextureFilter,
+    ) -> Result<()> {
+        let src = self
+            .render_textures
+            .get(src)
+            .ok_or_else(|| format_err!("{:?} is invalid.", src))?;
+
+        let handle = dst;
+        let dst = self
+            .render_textures
+            .get(dst)
+            .ok_or_else(|| format_err!("{:?} is invalid.", dst))?;
+
+        src.params.format.validate_blit(dst.params.format, filter)?;
+
+        // Both the blit and its fallback pass overwrite the cleared values of `dst`.
+        self.state.clears.forget(handle);
+
+        // WebGL 2.0 could not blit between fixed-point and floating-point color formats.
+        let float = |v: RenderTextureFormat| v == RenderTextureFormat::RGBA16F;
+        if float(src.params.format) != float(dst.params.format) {
+            let (src, dst) = (src.clone(), dst.clone());
+            return self.blit_with_pass(&src, &dst, filter);
+        }
+
+        let mask = match src.params.format {
+            RenderTextureFormat::Depth16
+            | RenderTextureFormat::Depth24
+            | RenderTextureFormat::Depth32 => WebGL::DEPTH_BUFFER_BIT,
+            RenderTextureFormat::Depth24Stencil8 => {
+                WebGL::DEPTH_BUFFER_BIT | WebGL::STENCIL_BUFFER_BIT
+            }
+            _ => WebGL::COLOR_BUFFER_BIT,
+        };
+
+        let filter = match filter {
+            TextureFilter::Nearest => WebGL::NEAREST,
+            TextureFilter::Linear => WebGL::LINEAR,
+        };
+
+        let ctx = &self.ctx;
+        let read = ctx.create_framebuffer().unwrap();
+        let draw = ctx.create_framebuffer().unwrap();
+        self.state.binded_surface = None;
+        self.state.binded_framebuffer = None;
+
+        ctx.bind_framebuffer(WebGL::READ_FRAMEBUFFER, Some(&read));
+        Self::bind_surface_render_texture(ctx, WebGL::READ_FRAMEBUFFER, src, 0)?;
+        ctx.bind_framebuffer(WebGL::DRAW_FRAMEBUFFER, Some(&draw));
+        Self::bind_surface_render_texture(ctx, WebGL::DRAW_FRAMEBUFFER, dst, 0)?;
+
+        // The blits are clipped by the scissor test.
+        Self::set_scissor(ctx, &mut self.state, SurfaceScissor::Disable)?;
+
+        ctx.blit_framebuffer(
+            0,
+            0,
+            src.params.dimensions.x as i32,
+            src.params.dimensions.y as i32,
+            0,
+            0,
+            dst.params.dimensions.x as i32,
+            dst.params.dimensions.y as i32,
+            mask,
+            filter,
+        );
+
+        ctx.bind_framebuffer(WebGL::FRAMEBUFFER, None);
+        ctx.delete_framebuffer(Some(&read));
+        ctx.delete_framebuffer(Some(&draw));
+        check(ctx)
+    }
+
+    unsafe fn invalidate_state_cache(&mut self) -> Result<()> {
+        Self::reset_render_state(&self.ctx, &mut self.state)?;
+        self.state.clears.reset();
+
+        let vp = self.state.view;
+        self.ctx.viewport(
+            vp.position.x as i32,
+            vp.position.y as i32,
+            vp.size.x as i32,
+            vp.size.y as i32,
+        );
+
+        self.state.binded_surface = None;
+        self.state.binded_framebuffer = None;
+        self.state.binded_shader = None;
+        self.state.binded_vao = None;
+        self.state.binded_texture_index = 0;
+        self.state.binded_textures.clear();
+        self.ctx.active_texture(WebGL::TEXTURE0);
+        check(&self.ctx)
+    }
+
+    unsafe fn set_fallback_texture(&mut self, handle: Option<TextureHandle>) -> Result<()> {
+        self.fallback_texture = handle;
+        Ok(())
+    }
+
     unsafe fn flush(&mut self) -> Result<()> {
+        if let Some(aa) = self.antialiasing.as_mut() {
+            if aa.is_pending() {
+                let vp = SurfaceViewport {
+                    position: Vector2::new(0, 0),
+                    size: aa.dimensions(),
+                };
+
+                Self::reset_render_state(&self.ctx, &mut self.state)?;
+                Self::set_viewport(&self.ctx, &mut self.state, vp)?;
+                aa.resolve(&self.ctx)?;
+
+                self.state.binded_surface = None;
+                self.state.binded_framebuffer = None;
+                self.state.binded_shader = None;
+                self.state.binded_vao = None;
+                self.state.binded_texture_index = 0;
+                if let Some(v) = self.state.binded_textures.get_mut(0) {
+                    *v = None;
+                }
+            }
+        }
+
         self.ctx.finish();
         Ok(())
     }
+
+    unsafe fn set_antialiasing(&mut self, mode: AaMode) -> Result<()> {
+        let current = self.antialiasing.as_ref().map(|v| v.mode());
+        if current.unwrap_or(AaMode::None) == mode {
+            return Ok(());
+        }
+
+        self.update_offscreen(mode)
+    }
+
+    unsafe fn set_output_adjustment(&mut self, adjustment: OutputAdjustment) -> Result<()> {
+        self.output = adjustment;
+
+        let mode = match self.antialiasing.as_mut() {
+            Some(aa) => {
+                if aa.set_adjustment(adjustment) {
+                    return Ok(());
+                }
+
+                aa.mode()
+            }
+            None if adjustment.is_identity() => return Ok(()),
+            None => AaMode::None,
+        };
+
+        self.update_offscreen(mode)
+    }
+
+    unsafe fn set_max_frame_latency(&mut self, _: u32) -> Result<()> {
+        // WebGL could not block on fences, the browser paces the frames by itself.
+        Ok(())
+    }
+
+    fn is_format_supported(&self, format: RenderTextureFormat, usage: FormatUsage) -> bool {
+        self.capabilities.is_format_supported(format, usage)
+    }
+
+    fn is_texture_format_supported(&self, format: TextureFormat) -> bool {
+        self.capabilities.support_texture_format(format)
+    }
+
+    fn limits(&self) -> DeviceLimits {
+        let caps = &self.capabilities;
+        DeviceLimits {
+            max_texture_size: caps.max_texture_size,
+            max_color_attachments: caps.max_color_attachments,
+            max_vertex_attributes: caps.max_vertex_attributes,
+            max_combined_texture_units: caps.max_combined_texture_image_units,
+            max_samples: caps.max_samples,
+        }
+    }
+
+    fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    fn native_texture(&self, _: TextureHandle) -> Option<u32> {
+        // The texture objects of WebGL are opaque JavaScript objects.
+        None
+    }
+
+    unsafe fn capture(&mut self, dimensions: Vector2<u32>) -> Result<Image> {
+        let mut pixels = vec![0u8; (dimensions.x * dimensions.y * 4) as usize];
+
+        self.ctx.bind_framebuffer(WebGL::FRAMEBUFFER, None);
+        self.state.binded_surface = None;
+        self.state.binded_framebuffer = None;
+
+        self.ctx.pixel_storei(WebGL::PACK_ALIGNMENT, 1);
+        self.ctx
+            .read_pixels_with_opt_u8_array(
+                0,
+                0,
+                dimensions.x as i32,
+                dimensions.y as i32,
+                WebGL::RGBA,
+                WebGL::UNSIGNED_BYTE,
+                Some(&mut pixels),
+            ).unwrap();
+
+        check(&self.ctx)?;
+        Ok(Image { dimensions, pixels })
+    }
 }
 
 impl WebGLVisitor {
     unsafe fn bind_surface_render_texture(
         ctx: &WebGL,
+        target: u32,
         rt: &GLRenderTextureData,
         index: usize,
     ) -> Result<()> {
         let location = match rt.params.format {
-            RenderTextureFormat::RGB8 | RenderTextureFormat::RGBA4 | RenderTextureFormat::RGBA8 => {
-                WebGL::COLOR_ATTACHMENT0 + index as u32
-            }
+            RenderTextureFormat::RGB8
+            | RenderTextureFormat::RGBA4
+            | RenderTextureFormat::RGBA8
+            | RenderTextureFormat::RGBA16F
+            | RenderTextureFormat::R32UI
+            | RenderTextureFormat::R32I => WebGL::COLOR_ATTACHMENT0 + index as u32,
             RenderTextureFormat::Depth16
             | RenderTextureFormat::Depth24
             | RenderTextureFormat::Depth32 => WebGL::DEPTH_ATTACHMENT,
@@ -938,19 +1798,12 @@ impl WebGLVisitor {
         };
 
         match rt.id {
-            GLRenderTexture::T(ref v) => ctx.framebuffer_texture_2d(
-                WebGL::FRAMEBUFFER,
-                location,
-                WebGL::TEXTURE_2D,
-                Some(v),
-                0,
-            ),
-            GLRenderTexture::R(ref v) => ctx.framebuffer_renderbuffer(
-                WebGL::FRAMEBUFFER,
-                location,
-                WebGL::RENDERBUFFER,
-                Some(v),
-            ),
+            GLRenderTexture::T(ref v) => {
+                ctx.framebuffer_texture_2d(target, location, WebGL::TEXTURE_2D, Some(v), 0)
+            }
+            GLRenderTexture::R(ref v) => {
+                ctx.framebuffer_renderbuffer(target, location, WebGL::RENDERBUFFER, Some(v))
+            }
         }
 
         check(&ctx)
@@ -958,7 +1811,7 @@ impl WebGLVisitor {
 }
 
 impl WebGLVisitor {
-    unsafe fn compile(ctx: &WebGL, tp: u32, src: &str) -> Result<WebGlShader> {
+    pub(super) unsafe fn compile(ctx: &WebGL, tp: u32, src: &str) -> Result<WebGlShader> {
         let shader = ctx
             .create_shader(tp)
             .ok_or_else(|| "Unable to create shader object".into())
@@ -982,7 +1835,19 @@ impl WebGLVisitor {
         }
     }
 
-    unsafe fn link<'a, T>(ctx: &WebGL, shaders: T) -> Result<WebGlProgram>
+    #[inline]
+    pub(super) unsafe fn link<'a, T>(ctx: &WebGL, shaders: T) -> Result<WebGlProgram>
+    where
+        T: IntoIterator<Item = &'a WebGlShader>,
+    {
+        Self::link_with_locations(ctx, shaders, &AttributeLocations::default())
+    }
+
+    unsafe fn link_with_locations<'a, T>(
+        ctx: &WebGL,
+        shaders: T,
+        locations: &AttributeLocations,
+    ) -> Result<WebGlProgram>
     where
         T: IntoIterator<Item = &'a WebGlShader>,
     {
@@ -994,6 +1859,13 @@ impl WebGLVisitor {
         for shader in shaders {
             ctx.attach_shader(&program, shader)
         }
+
+        // The explicit locations only take effects after linking.
+        for (name, location) in locations.iter() {
+            let name: &'static str = name.into();
+            ctx.bind_attrib_location(&program, u32::from(location), name);
+        }
+
         ctx.link_program(&program);
 
         if ctx
@@ -1024,12 +1896,14 @@ impl WebGLVisitor {
         check(ctx)?;
 
         let rs = &shader.params.state;
-        Self::set_cull_face(ctx, state, rs.cull_face)?;
+        Self::set_cull_face(ctx, state, state.cull_override.unwrap_or(rs.cull_face))?;
         Self::set_front_face_order(ctx, state, rs.front_face_order)?;
         Self::set_depth_test(ctx, state, rs.depth_write, rs.depth_test)?;
         Self::set_depth_write_offset(ctx, state, rs.depth_write_offset)?;
         Self::set_color_blend(ctx, state, rs.color_blend)?;
+        Self::set_blend_color(ctx, state, rs.blend_color)?;
         Self::set_color_write(ctx, state, rs.color_write)?;
+        Self::set_sample_coverage(ctx, state, rs.sample_coverage)?;
 
         state.binded_shader = Some(shader.handle);
         Ok(())
@@ -1040,10 +1914,11 @@ impl WebGLVisitor {
         state: &mut WebGLState,
         shader: &GLShaderData,
         mesh: &GLMeshData,
+        instances: Option<&GLInstanceBufferData>,
     ) -> Result<()> {
         assert!(state.binded_shader == Some(shader.handle));
 
-        let k = (shader.handle, mesh.handle);
+        let k = (shader.handle, mesh.handle, instances.map(|v| v.handle));
         if state.binded_vao != Some(k) {
             if let Some(vao) = state.vaos.get(&k).cloned() {
                 ctx.bind_vertex_array(Some(&vao));
@@ -1077,13 +1952,41 @@ impl WebGLVisitor {
                             stride as i32,
                             offset as i32,
                         );
-                    } else {
-                        if required {
+                    } else if let Some((instances, element)) =
+                        instances.and_then(|v| v.params.layout.element(name).map(|e| (v, e)))
+                    {
+                        // Sources the attribute from instance buffer, which advances once per
+                        // instance.
+                        if element.size < size {
                             bail!(
-                                "Can't find attribute {:?} description in vertex buffer.",
-                                name
+                                "Instance buffer has incompatible attribute `{:?}` [{:?} - {:?}].",
+                                name,
+                                element.size,
+                                size
                             );
                         }
+
+                        let offset = instances.params.layout.offset(name).unwrap();
+                        let stride = instances.params.layout.stride();
+
+                        let location = shader.attribute_location(ctx, name.into())?;
+                        ctx.bind_buffer(WebGL::ARRAY_BUFFER, Some(&instances.vbo));
+                        ctx.enable_vertex_attrib_array(location as u32);
+                        ctx.vertex_attrib_pointer_with_i32(
+                            location as u32,
+                            element.size as i32,
+                            element.format.into(),
+                            element.normalized,
+                            stride as i32,
+                            offset as i32,
+                        );
+                        ctx.vertex_attrib_divisor(location as u32, 1);
+                        ctx.bind_buffer(WebGL::ARRAY_BUFFER, Some(&mesh.vbo));
+                    } else if required {
+                        bail!(
+                            "Can't find attribute {:?} description in vertex buffer.",
+                            name
+                        );
                     }
                 }
 
@@ -1094,7 +1997,7 @@ impl WebGLVisitor {
             state.binded_vao = Some(k);
         }
 
-        ctx.bind_buffer(WebGL::ELEMENT_ARRAY_BUFFER, Some(&mesh.ibo));
+        ctx.bind_buffer(WebGL::ELEMENT_ARRAY_BUFFER, mesh.ibo.as_ref());
         Ok(())
     }
 
@@ -1106,6 +2009,11 @@ impl WebGLVisitor {
         match *variable {
             UniformVariable::Texture(_) => unreachable!(),
             UniformVariable::RenderTexture(_) => unreachable!(),
+            UniformVariable::TextureUnit(_, _) => unreachable!(),
+            UniformVariable::RenderTextureUnit(_, _) => unreachable!(),
+            UniformVariable::Image(_, _) => unreachable!(),
+            UniformVariable::StorageBuffer(_, _) => unreachable!(),
+            UniformVariable::UniformBuffer(_, _) => unreachable!(),
             UniformVariable::I32(v) => ctx.uniform1i(Some(&location), v),
             UniformVariable::F32(v) => ctx.uniform1f(Some(&location), v),
             UniformVariable::Vector2f(v) => ctx.uniform2f(Some(&location), v[0], v[1]),
@@ -1147,15 +2055,24 @@ impl WebGLVisitor {
 
         ctx.disable(WebGL::BLEND);
         rs.color_blend = None;
+        ctx.blend_color(0.0, 0.0, 0.0, 0.0);
+        rs.blend_color = Color::transparent();
+        rs.logic_op = None;
 
         ctx.color_mask(true, true, true, true);
         rs.color_write = (true, true, true, true);
 
+        ctx.disable(WebGL::SAMPLE_COVERAGE);
+        rs.sample_coverage = None;
+
         ctx.disable(WebGL::SCISSOR_TEST);
         state.scissor = SurfaceScissor::Disable;
 
         ctx.pixel_storei(WebGL::UNPACK_ALIGNMENT, 1);
+        state.unpack_alignment = 1;
         ctx.bind_framebuffer(WebGL::FRAMEBUFFER, None);
+        state.binded_framebuffer = None;
+        Self::set_sampler(ctx, state, None)?;
 
         check(&ctx)
     }
@@ -1292,6 +2209,23 @@ impl WebGLVisitor {
         Ok(())
     }
 
+    /// Specify the constant color used by constant blend factors.
+    unsafe fn set_blend_color(
+        ctx: &WebGL,
+        state: &mut WebGLState,
+        color: Color<f32>,
+    ) -> Result<()> {
+        let state = &mut state.render_state;
+
+        if state.blend_color != color {
+            ctx.blend_color(color.r, color.g, color.b, color.a);
+            state.blend_color = color;
+            check(&ctx)?;
+        }
+
+        Ok(())
+    }
+
     /// Enable or disable writing color elements into the color buffer.
     unsafe fn set_color_write(
         ctx: &WebGL,
@@ -1309,6 +2243,51 @@ impl WebGLVisitor {
         Ok(())
     }
 
+    /// Specify the temporary coverage value of multisampled rasterization.
+    unsafe fn set_sample_coverage(
+        ctx: &WebGL,
+        state: &mut WebGLState,
+        coverage: Option<(f32, bool)>,
+    ) -> Result<()> {
+        let state = &mut state.render_state;
+
+        if state.sample_coverage != coverage {
+            if let Some((value, invert)) = coverage {
+                if state.sample_coverage == None {
+                    ctx.enable(WebGL::SAMPLE_COVERAGE);
+                }
+
+                ctx.sample_coverage(value, invert);
+            } else if state.sample_coverage != None {
+                ctx.disable(WebGL::SAMPLE_COVERAGE);
+            }
+
+            state.sample_coverage = coverage;
+            check(&ctx)?;
+        }
+
+        Ok(())
+    }
+
+    /// Binds the sampler object to all the texture units that could be used by draws, it
+    /// overrides the sampling parameters of textures until being unbinded with `None`.
+    unsafe fn set_sampler(
+        ctx: &WebGL,
+        state: &mut WebGLState,
+        sampler: Option<&WebGlSampler>,
+    ) -> Result<()> {
+        if state.sampler.as_ref() != sampler {
+            for unit in 0..MAX_UNIFORM_TEXTURE_SLOTS {
+                ctx.bind_sampler(unit as u32, sampler);
+            }
+
+            state.sampler = sampler.cloned();
+            check(&ctx)?;
+        }
+
+        Ok(())
+    }
+
     /// Set the scissor box relative to the top-lef corner of th window, in pixels.
     unsafe fn set_scissor(
         ctx: &WebGL,
@@ -1387,6 +2366,37 @@ impl WebGLVisitor {
             Ok(())
         }
     }
+
+    unsafe fn clear_integer(
+        ctx: &WebGL,
+        format: RenderTextureFormat,
+        value: SurfaceIntegerClear,
+    ) -> Result<()> {
+        if format == RenderTextureFormat::R32I {
+            ctx.clear_bufferiv_with_i32_array(WebGL::COLOR, 0, &value.to_i32());
+        } else {
+            ctx.clear_bufferuiv_with_u32_array(WebGL::COLOR, 0, &value.to_u32());
+        }
+
+        check(&ctx)
+    }
+
+    /// Recreates the offscreen framebuffer that the window framebuffer is redirected into.
+    unsafe fn update_offscreen(&mut self, mode: AaMode) -> Result<()> {
+        if let Some(mut aa) = self.antialiasing.take() {
+            aa.delete(&self.ctx)?;
+        }
+
+        if WebGLAntialiasing::is_required(mode, self.output) {
+            let aa = WebGLAntialiasing::new(&self.ctx, mode, self.output, &self.capabilities)?;
+            self.antialiasing = Some(aa);
+        }
+
+        self.state.clears.reset();
+        self.state.binded_surface = None;
+        self.state.binded_framebuffer = None;
+        Ok(())
+    }
 }
 
 impl WebGLVisitor {
@@ -1408,63 +2418,181 @@ impl WebGLVisitor {
 
         if state.binded_textures[index] != sampler {
             state.binded_textures[index] = sampler;
-            ctx.bind_texture(WebGL::TEXTURE_2D, id);
+            let target = match sampler {
+                Some(Sampler::Cube(_)) => WebGL::TEXTURE_CUBE_MAP,
+                _ => WebGL::TEXTURE_2D,
+            };
+
+            ctx.bind_texture(target, id);
         }
 
         check(ctx)
     }
 
-    unsafe fn bind_texture_params(
+    /// Specify the alignment requirements for the start of each pixel row in memory.
+    unsafe fn set_unpack_alignment(
         ctx: &WebGL,
-        wrap: TextureWrap,
+        state: &mut WebGLState,
+        alignment: u32,
+    ) -> Result<()> {
+        if state.unpack_alignment != alignment as i32 {
+            ctx.pixel_storei(WebGL::UNPACK_ALIGNMENT, alignment as i32);
+            state.unpack_alignment = alignment as i32;
+            check(ctx)?;
+        }
+
+        Ok(())
+    }
+
+    unsafe fn create_sampler(ctx: &WebGL, sampler: SurfaceSampler) -> Result<WebGlSampler> {
+        let id = ctx
+            .create_sampler()
+            .ok_or_else(|| format_err!("Failed to create sampler object."))?;
+
+        let wrap: u32 = sampler.wrap.into();
+        let filter = match sampler.filter {
+            TextureFilter::Nearest => WebGL::NEAREST,
+            TextureFilter::Linear => WebGL::LINEAR,
+        };
+
+        ctx.sampler_parameteri(&id, WebGL::TEXTURE_WRAP_S, wrap as i32);
+        ctx.sampler_parameteri(&id, WebGL::TEXTURE_WRAP_T, wrap as i32);
+        ctx.sampler_parameteri(&id, WebGL::TEXTURE_MIN_FILTER, filter as i32);
+        ctx.sampler_parameteri(&id, WebGL::TEXTURE_MAG_FILTER, filter as i32);
+        check(&ctx)?;
+
+        Ok(id)
+    }
+
+    /// Copies the color of `src` into `dst` with a full-screen pass, which is the fallback
+    /// of blits that `blitFramebuffer` could not convert the formats of.
+    unsafe fn blit_with_pass(
+        &mut self,
+        src: &GLRenderTextureData,
+        dst: &GLRenderTextureData,
         filter: TextureFilter,
-        levels: u32,
     ) -> Result<()> {
-        let wrap: u32 = wrap.into();
-        let wrap = wrap as i32;
+        let texture = match src.id {
+            GLRenderTexture::T(ref v) => v,
+            GLRenderTexture::R(_) => bail!(
+                "[WebGL] {:?} could not be blitted into {:?}, unless the source could be \
+                 sampled.",
+                src.params.format,
+                dst.params.format
+            ),
+        };
 
-        ctx.tex_parameteri(WebGL::TEXTURE_2D, WebGL::TEXTURE_WRAP_S, wrap);
-        ctx.tex_parameteri(WebGL::TEXTURE_2D, WebGL::TEXTURE_WRAP_T, wrap);
+        if self.blit_pass.is_none() {
+            self.blit_pass = Some(WebGLOutputPass::new(&self.ctx, BLIT_FS)?);
+        }
 
-        match filter {
-            TextureFilter::Nearest => {
-                let min_filter = if levels > 1 {
-                    WebGL::NEAREST_MIPMAP_NEAREST
-                } else {
-                    WebGL::NEAREST
-                } as i32;
+        let ctx = &self.ctx;
+        Self::reset_render_state(ctx, &mut self.state)?;
+        self.state.binded_surface = None;
 
-                ctx.tex_parameteri(WebGL::TEXTURE_2D, WebGL::TEXTURE_MIN_FILTER, min_filter);
+        let fbo = ctx.create_framebuffer().unwrap();
+        ctx.bind_framebuffer(WebGL::FRAMEBUFFER, Some(&fbo));
+        Self::bind_surface_render_texture(ctx, WebGL::FRAMEBUFFER, dst, 0)?;
 
-                ctx.tex_parameteri(
-                    WebGL::TEXTURE_2D,
-                    WebGL::TEXTURE_MAG_FILTER,
-                    WebGL::NEAREST as i32,
-                );
-            }
-            TextureFilter::Linear => {
-                let min_filter = if levels > 1 {
-                    WebGL::LINEAR_MIPMAP_LINEAR
-                } else {
-                    WebGL::LINEAR
-                } as i32;
+        let vp = SurfaceViewport {
+            position: Vector2::new(0, 0),
+            size: dst.params.dimensions,
+        };
 
-                ctx.tex_parameteri(WebGL::TEXTURE_2D, WebGL::TEXTURE_MIN_FILTER, min_filter);
-                ctx.tex_parameteri(
-                    WebGL::TEXTURE_2D,
-                    WebGL::TEXTURE_MAG_FILTER,
-                    WebGL::LINEAR as i32,
+        Self::set_viewport(ctx, &mut self.state, vp)?;
+
+        // Samples the source with the filter of blit temporarily.
+        ctx.active_texture(WebGL::TEXTURE0);
+        ctx.bind_texture(WebGL::TEXTURE_2D, Some(texture));
+        let wrap = (src.params.wrap, src.params.wrap);
+        Self::bind_texture_params(ctx, WebGL::TEXTURE_2D, wrap, (filter, filter, None), (0, 0))?;
+
+        let pass = self.blit_pass.as_ref().unwrap();
+        let adjustment = OutputAdjustment::default();
+        pass.draw(ctx, Some(texture), src.params.dimensions, adjustment);
+        let filter = (src.params.filter, src.params.filter, None);
+        Self::bind_texture_params(ctx, WebGL::TEXTURE_2D, wrap, filter, (0, 0))?;
+
+        self.state.binded_shader = None;
+        self.state.binded_vao = None;
+        self.state.binded_texture_index = 0;
+        if let Some(v) = self.state.binded_textures.get_mut(0) {
+            *v = None;
+        }
+
+        ctx.bind_framebuffer(WebGL::FRAMEBUFFER, None);
+        ctx.delete_framebuffer(Some(&fbo));
+        check(ctx)
+    }
+
+    /// Uploads the mipmap levels in `data` into `target`, which is either `TEXTURE_2D` or
+    /// one of the faces of cube map.
+    unsafe fn upload_texture(
+        &mut self,
+        target: u32,
+        params: TextureParams,
+        data: &TextureData,
+    ) -> Result<()> {
+        let (internal_format, format, pixel_type) = params.format.into();
+        let mut dims = (params.dimensions.x as i32, params.dimensions.y as i32);
+
+        for (i, v) in data.bytes.iter().enumerate() {
+            let mv = ::std::slice::from_raw_parts_mut(v.as_ptr() as *mut u8, v.len());
+            if params.format.compressed() {
+                self.ctx.compressed_tex_image_2d_with_u8_array(
+                    target,
+                    i as i32,
+                    internal_format,
+                    dims.0,
+                    dims.1,
+                    0,
+                    mv,
                 );
+            } else {
+                let alignment = params.format.unpack_alignment(dims.0 as u32);
+                Self::set_unpack_alignment(&self.ctx, &mut self.state, alignment)?;
+
+                self.ctx
+                    .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                        target,
+                        i as i32,
+                        internal_format as i32,
+                        dims.0,
+                        dims.1,
+                        0,
+                        format,
+                        pixel_type,
+                        Some(mv),
+                    )
+                    .unwrap();
             }
+
+            dims.0 = (dims.0 / 2).max(1);
+            dims.1 = (dims.1 / 2).max(1);
         }
 
-        if levels > 1 {
-            ctx.tex_parameteri(WebGL::TEXTURE_2D, WebGL::TEXTURE_BASE_LEVEL, 0);
-            ctx.tex_parameteri(
-                WebGL::TEXTURE_2D,
-                WebGL::TEXTURE_MAX_LEVEL,
-                (levels - 1) as i32,
-            );
+        Ok(())
+    }
+
+    unsafe fn bind_texture_params(
+        ctx: &WebGL,
+        target: u32,
+        (wrap_s, wrap_t): (TextureWrap, TextureWrap),
+        (min, mag, mip): (TextureFilter, TextureFilter, Option<TextureFilter>),
+        (base, max): (u32, u32),
+    ) -> Result<()> {
+        let (wrap_s, wrap_t): (u32, u32) = (wrap_s.into(), wrap_t.into());
+        ctx.tex_parameteri(target, WebGL::TEXTURE_WRAP_S, wrap_s as i32);
+        ctx.tex_parameteri(target, WebGL::TEXTURE_WRAP_T, wrap_t as i32);
+
+        let min_filter = types::filter(min, mip.filter(|_| max > base));
+        let mag_filter = types::filter(mag, None);
+        ctx.tex_parameteri(target, WebGL::TEXTURE_MIN_FILTER, min_filter as i32);
+        ctx.tex_parameteri(target, WebGL::TEXTURE_MAG_FILTER, mag_filter as i32);
+
+        if max > 0 {
+            ctx.tex_parameteri(target, WebGL::TEXTURE_BASE_LEVEL, base as i32);
+            ctx.tex_parameteri(target, WebGL::TEXTURE_MAX_LEVEL, max as i32);
         }
 
         check(&ctx)
@@ -1472,6 +2600,62 @@ impl WebGLVisitor {
 }
 
 impl WebGLVisitor {
+    /// Assigns the per-object uniform block of program to `PER_OBJECT_BLOCK_BINDING`, and
+    /// returns the data size of it.
+    unsafe fn per_object_block(ctx: &WebGL, program: &WebGlProgram) -> Result<Option<usize>> {
+        let index = ctx.get_uniform_block_index(program, PER_OBJECT_BLOCK);
+        if index == WebGL::INVALID_INDEX {
+            return Ok(None);
+        }
+
+        ctx.uniform_block_binding(program, index, PER_OBJECT_BLOCK_BINDING);
+
+        let size = ctx
+            .get_active_uniform_block_parameter(program, index, WebGL::UNIFORM_BLOCK_DATA_SIZE)
+            .ok()
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0);
+
+        check(ctx)?;
+        Ok(Some(size as usize))
+    }
+
+    /// Gets the indices and data sizes of the uniform blocks declared in `params`.
+    unsafe fn uniform_blocks(
+        ctx: &WebGL,
+        program: &WebGlProgram,
+        params: &ShaderParams,
+    ) -> Result<FastHashMap<HashValue<str>, (u32, usize)>> {
+        let mut blocks = FastHashMap::default();
+        for &(ref name, tp) in params.uniforms.iter() {
+            if tp != UniformVariableType::UniformBuffer {
+                continue;
+            }
+
+            if name == PER_OBJECT_BLOCK {
+                bail!("Uniform block {} is reserved for per-object data.", name);
+            }
+
+            let index = ctx.get_uniform_block_index(program, name);
+            if index == WebGL::INVALID_INDEX {
+                bail!("Uniform({:?}) is undefined in shader sources.", name);
+            }
+
+            let size = ctx
+                .get_active_uniform_block_parameter(program, index, WebGL::UNIFORM_BLOCK_DATA_SIZE)
+                .ok()
+                .and_then(|v| v.as_f64())
+                .unwrap_or(0.0);
+
+            check(ctx)?;
+
+            let hash: HashValue<str> = name.as_str().into();
+            blocks.insert(hash, (index, size as usize));
+        }
+
+        Ok(blocks)
+    }
+
     unsafe fn create_buffer(
         ctx: &WebGL,
         target: u32,
@@ -1510,7 +2694,7 @@ impl WebGLVisitor {
     }
 }
 
-unsafe fn check(ctx: &WebGL) -> Result<()> {
+pub(super) unsafe fn check(ctx: &WebGL) -> Result<()> {
     match ctx.get_error() {
         WebGL::NO_ERROR => Ok(()),
 
@@ -2,15 +2,20 @@ use crate::math::prelude::Aabb2;
 use crate::utils::prelude::{DataBuffer, HashValue};
 
 use super::assets::prelude::*;
-use super::backends::frame::Command;
+use super::backends::frame::{Command, DrawCommand};
 use super::errors::*;
-use super::MAX_UNIFORM_VARIABLES;
+use super::{
+    MAX_IMAGE_UNITS, MAX_PER_OBJECT_BLOCK_SIZE, MAX_STORAGE_BUFFER_BINDINGS,
+    MAX_UNIFORM_BUFFER_BINDINGS, MAX_UNIFORM_TEXTURE_SLOTS, MAX_UNIFORM_VARIABLES,
+    PER_OBJECT_BLOCK_BINDING,
+};
 
 /// The command buffer of video system.
 #[derive(Default)]
 pub struct CommandBuffer {
     cmds: Vec<Command>,
     bufs: DataBuffer,
+    scissors: Vec<SurfaceScissor>,
 }
 
 impl CommandBuffer {
@@ -20,27 +25,56 @@ impl CommandBuffer {
         CommandBuffer {
             cmds: Vec::with_capacity(32),
             bufs: DataBuffer::with_capacity(512),
+            scissors: Vec::new(),
         }
     }
 
     /// Draws ur mesh.
     #[inline]
     pub fn draw(&mut self, dc: Draw) {
-        let len = dc.uniforms_len;
-        let ptr = self.bufs.extend_from_slice(&dc.uniforms[0..len]);
-        let cmd = Command::Draw(dc.shader, dc.mesh, dc.mesh_index, ptr);
+        let cmd = dc.command(&mut self.bufs);
         self.cmds.push(cmd);
     }
 
     /// Updates the scissor test of surface.
     ///
     /// The test is initially disabled. While the test is enabled, only pixels that lie within
-    /// the scissor box can be modified by drawing commands.
+    /// the scissor box can be modified by drawing commands. Notes that this bypasses the
+    /// stack of `push_scissor`, use `pop_scissor` to restore the previous entry instead.
     #[inline]
     pub fn update_scissor(&mut self, scissor: SurfaceScissor) {
         self.cmds.push(Command::UpdateScissor(scissor));
     }
 
+    /// Pushes a scissor box onto the stack, which is intersected with the one on the top of
+    /// stack, so the nested clipping regions (e.g. of UI panels) compose instead of
+    /// replacing each other. The intersection is applied to the following draws until it's
+    /// popped.
+    pub fn push_scissor(&mut self, scissor: SurfaceScissor) {
+        let scissor = scissor.intersect(self.scissor());
+        self.scissors.push(scissor);
+        self.cmds.push(Command::UpdateScissor(scissor));
+    }
+
+    /// Pops the scissor box on the top of stack, and restores the previous entry. The
+    /// scissor test is disabled only if the stack becomes empty.
+    pub fn pop_scissor(&mut self) {
+        if self.scissors.pop().is_some() {
+            let scissor = self.scissor();
+            self.cmds.push(Command::UpdateScissor(scissor));
+        }
+    }
+
+    /// Gets the scissor box on the top of stack, or `SurfaceScissor::Disable` if the stack
+    /// is empty.
+    #[inline]
+    pub fn scissor(&self) -> SurfaceScissor {
+        self.scissors
+            .last()
+            .cloned()
+            .unwrap_or(SurfaceScissor::Disable)
+    }
+
     /// Updates the viewport of surface.
     #[inline]
     pub fn update_viewport(&mut self, viewport: SurfaceViewport) {
@@ -50,9 +84,23 @@ impl CommandBuffer {
     /// Update a contiguous subregion of an existing two-dimensional texture object.
     #[inline]
     pub fn update_texture(&mut self, id: TextureHandle, area: Aabb2<u32>, bytes: &[u8]) {
+        self.update_texture_with_stride(id, area, area.dim().x, bytes);
+    }
+
+    /// Update a subregion of an existing two-dimensional texture object with pixels that
+    /// are `stride` pixels apart between rows, e.g. a sub-rectangle of a larger image.
+    #[inline]
+    pub fn update_texture_with_stride(
+        &mut self,
+        id: TextureHandle,
+        area: Aabb2<u32>,
+        stride: u32,
+        bytes: &[u8],
+    ) {
         let bufs = &mut self.bufs;
         let ptr = bufs.extend_from_slice(bytes);
-        self.cmds.push(Command::UpdateTexture(id, area, ptr));
+        let cmd = Command::UpdateTexture(id, area, stride, ptr);
+        self.cmds.push(cmd);
     }
 
     /// Update a subset of dynamic vertex buffer. Use `offset` specifies the offset
@@ -75,27 +123,45 @@ impl CommandBuffer {
         self.cmds.push(Command::UpdateIndexBuffer(id, offset, ptr));
     }
 
+    /// Update a subset of dynamic instance buffer. Use `offset` specifies the offset
+    /// into the buffer object's data store where data replacement will begin, measured
+    /// in bytes.
+    #[inline]
+    pub fn update_instance_buffer(
+        &mut self,
+        id: InstanceBufferHandle,
+        offset: usize,
+        bytes: &[u8],
+    ) {
+        let bufs = &mut self.bufs;
+        let ptr = bufs.extend_from_slice(bytes);
+        let cmd = Command::UpdateInstanceBuffer(id, offset, ptr);
+        self.cmds.push(cmd);
+    }
+
     /// Clears the batch, and submits all the commands into video device. Its guaranteed that
     /// all the commands in this batch will be executed one by one in order.
     ///
     /// Notes that this method has no effect on the allocated capacity of the underlying storage.
     pub fn submit(&mut self, surface: SurfaceHandle) -> Result<()> {
+        profile_scope!("Submit");
         let doubele_frame = unsafe { super::frames() };
         let mut frame = doubele_frame.write();
         frame.cmds.push(Command::Bind(surface));
 
         for v in self.cmds.drain(..) {
             match v {
-                Command::Draw(shader, mesh, mesh_index, ptr) => {
+                Command::Draw(dc, ptr) => {
                     let vars = self.bufs.as_slice(ptr);
                     let ptr = frame.bufs.extend_from_slice(vars);
-                    let cmd = Command::Draw(shader, mesh, mesh_index, ptr);
+                    let cmd = Command::Draw(dc, ptr);
                     frame.cmds.push(cmd);
                 }
 
-                Command::UpdateTexture(id, area, ptr) => {
+                Command::UpdateTexture(id, area, stride, ptr) => {
                     let ptr = frame.bufs.extend_from_slice(self.bufs.as_slice(ptr));
-                    frame.cmds.push(Command::UpdateTexture(id, area, ptr));
+                    let cmd = Command::UpdateTexture(id, area, stride, ptr);
+                    frame.cmds.push(cmd);
                 }
 
                 Command::UpdateVertexBuffer(id, offset, ptr) => {
@@ -109,11 +175,18 @@ impl CommandBuffer {
                     frame.cmds.push(Command::UpdateIndexBuffer(id, offset, ptr));
                 }
 
+                Command::UpdateInstanceBuffer(id, offset, ptr) => {
+                    let ptr = frame.bufs.extend_from_slice(self.bufs.as_slice(ptr));
+                    let cmd = Command::UpdateInstanceBuffer(id, offset, ptr);
+                    frame.cmds.push(cmd);
+                }
+
                 other => frame.cmds.push(other),
             }
         }
 
         self.bufs.clear();
+        self.scissors.clear();
         Ok(())
     }
 }
@@ -142,9 +215,7 @@ impl<T: Ord + Copy> DrawCommandBuffer<T> {
     /// Draws ur mesh.
     #[inline]
     pub fn draw(&mut self, order: T, dc: Draw) {
-        let len = dc.uniforms_len;
-        let ptr = self.bufs.extend_from_slice(&dc.uniforms[0..len]);
-        let cmd = Command::Draw(dc.shader, dc.mesh, dc.mesh_index, ptr);
+        let cmd = dc.command(&mut self.bufs);
         self.cmds.push((order, cmd));
     }
 
@@ -153,16 +224,22 @@ impl<T: Ord + Copy> DrawCommandBuffer<T> {
     ///
     /// Notes that this method has no effect on the allocated capacity of the underlying storage.
     pub fn submit(&mut self, surface: SurfaceHandle) -> Result<()> {
+        profile_scope!("Submit");
         let doubele_frame = unsafe { super::frames() };
         let mut frame = doubele_frame.write();
         frame.cmds.push(Command::Bind(surface));
 
         self.cmds.as_mut_slice().sort_by_key(|v| v.0);
         for v in self.cmds.drain(..) {
-            if let (_, Command::Draw(shader, mesh, mesh_index, ptr)) = v {
+            if let (_, Command::Draw(mut dc, ptr)) = v {
+                if let Some(v) = dc.per_object {
+                    let bytes = self.bufs.as_slice(v);
+                    dc.per_object = Some(frame.bufs.extend_from_slice(bytes));
+                }
+
                 let vars = self.bufs.as_slice(ptr);
                 let ptr = frame.bufs.extend_from_slice(vars);
-                let cmd = Command::Draw(shader, mesh, mesh_index, ptr);
+                let cmd = Command::Draw(dc, ptr);
                 frame.cmds.push(cmd);
             }
         }
@@ -177,10 +254,23 @@ impl<T: Ord + Copy> DrawCommandBuffer<T> {
 pub struct Draw {
     pub(crate) uniforms: [(HashValue<str>, UniformVariable); MAX_UNIFORM_VARIABLES],
     pub(crate) uniforms_len: usize,
+    pub(crate) per_object: [u8; MAX_PER_OBJECT_BLOCK_SIZE],
+    pub(crate) per_object_len: usize,
 
     pub shader: ShaderHandle,
     pub mesh: MeshHandle,
     pub mesh_index: MeshIndex,
+    /// The optional scissor test of this draw. The scissor of surface will be restored
+    /// after this draw.
+    pub scissor: Option<SurfaceScissor>,
+    /// The optional viewport of this draw. The viewport of surface will be restored after
+    /// this draw.
+    pub viewport: Option<SurfaceViewport>,
+    /// The optional per-instance vertex buffer of this draw.
+    pub instances: Option<InstanceBufferHandle>,
+    /// The number of instances to draw with a single instanced draw call, e.g.
+    /// `glDrawElementsInstanced`. It's a ordinary draw call if its `None`.
+    pub num_instances: Option<u32>,
 }
 
 impl Draw {
@@ -192,10 +282,161 @@ impl Draw {
             mesh,
             uniforms: [nil; MAX_UNIFORM_VARIABLES],
             uniforms_len: 0,
+            per_object: [0; MAX_PER_OBJECT_BLOCK_SIZE],
+            per_object_len: 0,
             mesh_index: MeshIndex::All,
+            scissor: None,
+            viewport: None,
+            instances: None,
+            num_instances: None,
         }
     }
 
+    /// Sets the instance buffer of this draw. The attributes of shader which could NOT be
+    /// found in the vertex layout of mesh, will be sourced from the instance buffer.
+    #[inline]
+    pub fn set_instance_buffer(&mut self, instances: InstanceBufferHandle) {
+        self.instances = Some(instances);
+    }
+
+    /// Draws `num` instances of the mesh with a single instanced draw call. The instance
+    /// buffer of this draw, if there is one, should hold at least `num` instances.
+    ///
+    /// Notes that this requires OpenGL 3.1 or OpenGL ES 3.0, the draw fails if its not
+    /// supported.
+    #[inline]
+    pub fn set_num_instances(&mut self, num: u32) {
+        self.num_instances = Some(num);
+    }
+
+    /// Draws the sub-mesh named `name` only, which is declared in `MeshParams::sub_meshes`.
+    pub fn set_sub_mesh(&mut self, name: &str) -> Result<()> {
+        let params = super::mesh(self.mesh)
+            .ok_or_else(|| Error::HandleInvalid(format!("{:?}", self.mesh)))?;
+
+        self.mesh_index = params
+            .sub_mesh(name)
+            .ok_or_else(|| Error::MeshInvalid(format!("sub-mesh {} is undefined.", name)))?;
+        Ok(())
+    }
+
+    /// Sets the scissor test that applies to this draw only. Since the scissor travels with
+    /// the draw, it could be sorted with `DrawCommandBuffer` freely.
+    #[inline]
+    pub fn set_scissor(&mut self, scissor: SurfaceScissor) {
+        self.scissor = Some(scissor);
+    }
+
+    /// Sets the viewport that applies to this draw only, e.g. to draw a picture-in-picture
+    /// without splitting the draws into another surface.
+    #[inline]
+    pub fn set_viewport(&mut self, viewport: SurfaceViewport) {
+        self.viewport = Some(viewport);
+    }
+
+    /// Binds the named sampler field with texture at a explicit texture unit. Textures binded
+    /// with `set_uniform_variable` will take the rest of free units in order.
+    pub fn bind_texture<F>(&mut self, unit: usize, field: F, texture: TextureHandle)
+    where
+        F: Into<HashValue<str>>,
+    {
+        assert!(unit < MAX_UNIFORM_TEXTURE_SLOTS);
+        self.set_uniform_variable(field, UniformVariable::TextureUnit(unit as u8, texture));
+    }
+
+    /// Binds the named sampler field with render texture at a explicit texture unit. The
+    /// render texture must not be an attachment of the surface this draw is submitted to.
+    pub fn bind_render_texture<F>(&mut self, unit: usize, field: F, texture: RenderTextureHandle)
+    where
+        F: Into<HashValue<str>>,
+    {
+        assert!(unit < MAX_UNIFORM_TEXTURE_SLOTS);
+        let v = UniformVariable::RenderTextureUnit(unit as u8, texture);
+        self.set_uniform_variable(field, v);
+    }
+
+    /// Binds the `level` of render texture as image at image unit, which could be accessed
+    /// with `imageLoad`/`imageStore` in shaders. The texels are interpreted as `format`.
+    ///
+    /// Notes that this requires OpenGL 4.2 or OpenGL ES 3.1, the draw fails if its not
+    /// supported. The image uniforms of GLSL ES must declare their units with the layout
+    /// qualifier `binding` explicitly, and the writes should be synchronized with
+    /// `video::memory_barrier` before being read by following draws.
+    pub fn bind_image<F>(
+        &mut self,
+        unit: usize,
+        field: F,
+        texture: RenderTextureHandle,
+        access: ImageAccess,
+        format: RenderTextureFormat,
+        level: u8,
+    ) where
+        F: Into<HashValue<str>>,
+    {
+        assert!(unit < MAX_IMAGE_UNITS);
+        let image = ImageBinding {
+            texture,
+            access,
+            format,
+            level,
+        };
+
+        self.set_uniform_variable(field, UniformVariable::Image(unit as u8, image));
+    }
+
+    /// Binds the shader storage buffer to the indexed `binding` point, the `field` is the
+    /// name of storage block in shader.
+    ///
+    /// Notes that this requires OpenGL 4.3 or OpenGL ES 3.1, the draw fails if its not
+    /// supported. The storage blocks of GLSL ES must declare their binding points with the
+    /// layout qualifier `binding` explicitly.
+    pub fn bind_storage_buffer<F>(
+        &mut self,
+        binding: usize,
+        field: F,
+        buffer: ShaderStorageBufferHandle,
+    ) where
+        F: Into<HashValue<str>>,
+    {
+        assert!(binding < MAX_STORAGE_BUFFER_BINDINGS);
+        let v = UniformVariable::StorageBuffer(binding as u8, buffer);
+        self.set_uniform_variable(field, v);
+    }
+
+    /// Binds the uniform buffer to the indexed `binding` point, the `field` is the name of
+    /// uniform block in shader. The buffer could be shared by many draws, e.g. a block of
+    /// camera matrices that updated once per frame.
+    ///
+    /// The binding point `PER_OBJECT_BLOCK_BINDING` is reserved for the per-object uniform
+    /// block, and the draw fails if the buffer is smaller than the uniform block.
+    pub fn bind_uniform_buffer<F>(&mut self, binding: usize, field: F, buffer: UniformBufferHandle)
+    where
+        F: Into<HashValue<str>>,
+    {
+        assert!(binding != PER_OBJECT_BLOCK_BINDING as usize);
+        assert!(binding < MAX_UNIFORM_BUFFER_BINDINGS);
+        let v = UniformVariable::UniformBuffer(binding as u8, buffer);
+        self.set_uniform_variable(field, v);
+    }
+
+    /// Sets the data of the per-object uniform block, which should be declared as
+    /// `PerObject` with the `std140` layout in shader, e.g. the model matrix of object.
+    ///
+    /// Instead of creating an uniform buffer for every object, the blocks of all draws are
+    /// written into a ring-buffered uniform buffer with the offset alignment of device, and
+    /// binded as sub-ranges of it at `PER_OBJECT_BLOCK_BINDING`.
+    pub fn set_per_object<T: Copy>(&mut self, block: &T) {
+        let len = std::mem::size_of::<T>();
+        assert!(len <= MAX_PER_OBJECT_BLOCK_SIZE);
+
+        unsafe {
+            let src = block as *const T as *const u8;
+            std::ptr::copy_nonoverlapping(src, self.per_object.as_mut_ptr(), len);
+        }
+
+        self.per_object_len = len;
+    }
+
     /// Binds the named field with `UniformVariable`.
     pub fn set_uniform_variable<F, V>(&mut self, field: F, variable: V)
     where
@@ -217,4 +458,48 @@ impl Draw {
         self.uniforms[self.uniforms_len] = (field, variable);
         self.uniforms_len += 1;
     }
+
+    pub(crate) fn command(&self, bufs: &mut DataBuffer) -> Command {
+        let per_object = if self.per_object_len > 0 {
+            let bytes = &self.per_object[0..self.per_object_len];
+            Some(bufs.extend_from_slice(bytes))
+        } else {
+            None
+        };
+
+        let dc = DrawCommand {
+            shader: self.shader,
+            mesh: self.mesh,
+            mesh_index: self.mesh_index,
+            scissor: self.scissor,
+            viewport: self.viewport,
+            instances: self.instances,
+            num_instances: self.num_instances,
+            per_object,
+        };
+
+        let ptr = bufs.extend_from_slice(&self.uniforms[0..self.uniforms_len]);
+        Command::Draw(dc, ptr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn num_instances() {
+        let mut bufs = DataBuffer::new();
+        let mut dc = Draw::new(ShaderHandle::default(), MeshHandle::default());
+        match dc.command(&mut bufs) {
+            Command::Draw(v, _) => assert_eq!(v.num_instances, None),
+            _ => unreachable!(),
+        }
+
+        dc.set_num_instances(16);
+        match dc.command(&mut bufs) {
+            Command::Draw(v, _) => assert_eq!(v.num_instances, Some(16)),
+            _ => unreachable!(),
+        }
+    }
 }
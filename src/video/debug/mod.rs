@@ -0,0 +1,199 @@
+//! Immediate-mode drawing of debug primitives.
+//!
+//! The wide and smooth lines of `glLineWidth` are deprecated in core profiles, and most of
+//! drivers only support 1-pixel lines. So the `DebugDraw` expands every segment into a quad
+//! in the vertex shader instead, and fades its edges out in the fragment shader, which gives
+//! consistent anti-aliased lines of any width across drivers.
+//!
+//! ```rust,ignore
+//! let mut debug = DebugDraw::new()?;
+//!
+//! // Appends primitives during the frame.
+//! debug.line_aa([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], 2.0, Color::red());
+//!
+//! // Draws all the primitives into surface, and clears them.
+//! debug.draw(surface, view_proj, dimensions)?;
+//! ```
+
+pub mod prelude {
+    pub use super::DebugDraw;
+}
+
+use crate::errors::*;
+use crate::math::prelude::{Color, Matrix4, Vector2, Vector3};
+
+use super::assets::prelude::*;
+use super::command::{CommandBuffer, Draw};
+use super::dynamic_mesh::DynamicMesh;
+
+const LINE_AA_VS: &str = include_str!("shaders/line_aa.vs");
+const LINE_AA_FS: &str = include_str!("shaders/line_aa.fs");
+
+/// The maximum number of segments in one draw, which keeps the indices in `IndexFormat::U16`.
+const MAX_SEGMENTS_PER_DRAW: usize = 0x4000;
+
+/// A batch of debug primitives, which are drawn and cleared all at once.
+pub struct DebugDraw {
+    shader: ShaderHandle,
+    mesh: DynamicMesh,
+    verts: Vec<u8>,
+    cmds: CommandBuffer,
+}
+
+impl DebugDraw {
+    /// Creates a new and empty `DebugDraw`.
+    pub fn new() -> Result<Self> {
+        let mut params = MeshParams::default();
+        params.num_verts = 256;
+        params.num_idxes = 384;
+        params.layout = VertexLayout::build()
+            .with(Attribute::Position, VertexFormat::Float, 3, false)
+            .with(Attribute::Normal, VertexFormat::Float, 3, false)
+            .with(Attribute::Texcoord0, VertexFormat::Float, 4, false)
+            .with(Attribute::Color0, VertexFormat::UByte, 4, true)
+            .finish();
+
+        let mesh = DynamicMesh::new(params)?;
+
+        let mut params = ShaderParams::default();
+        params.attributes = AttributeLayout::build()
+            .with(Attribute::Position, 3)
+            .with(Attribute::Normal, 3)
+            .with(Attribute::Texcoord0, 4)
+            .with(Attribute::Color0, 4)
+            .finish();
+        params.uniforms = UniformVariableLayout::build()
+            .with("u_ViewProj", UniformVariableType::Matrix4f)
+            .with("u_Viewport", UniformVariableType::Vector2f)
+            .finish();
+        params.state.depth_test = Comparison::LessOrEqual;
+        params.state.color_blend = Some((
+            Equation::Add,
+            BlendFactor::Value(BlendValue::SourceAlpha),
+            BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+        ));
+
+        let header = "#version 100\nprecision mediump float;\n";
+        let vs = format!("{}{}", header, LINE_AA_VS);
+        let fs = format!("{}{}", header, LINE_AA_FS);
+        let shader = crate::video::create_shader(params, vs, fs)?;
+
+        Ok(DebugDraw {
+            shader,
+            mesh,
+            verts: Vec::new(),
+            cmds: CommandBuffer::new(),
+        })
+    }
+
+    /// Appends an anti-aliased line segment from `from` to `to`. The `width` is measured
+    /// in pixels, lines that are thinner than one pixel are drawn as one pixel with less
+    /// opacity.
+    pub fn line_aa<T1, T2>(&mut self, from: T1, to: T2, width: f32, color: Color<f32>)
+    where
+        T1: Into<Vector3<f32>>,
+        T2: Into<Vector3<f32>>,
+    {
+        let (from, to) = (from.into(), to.into());
+
+        let mut color = color;
+        color.a *= width.max(0.0).min(1.0);
+        let color: [u8; 4] = color.into();
+        let half_width = width.max(1.0) * 0.5;
+
+        for &(t, side) in &[(0.0, -1.0), (0.0, 1.0), (1.0, 1.0), (1.0, -1.0)] {
+            let attributes = [
+                from.x, from.y, from.z, to.x, to.y, to.z, t, side, half_width, 0.0,
+            ];
+
+            for v in &attributes {
+                self.verts.extend_from_slice(&v.to_ne_bytes());
+            }
+
+            self.verts.extend_from_slice(&color);
+        }
+    }
+
+    /// Gets the number of line segments that have not been drawn yet.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.verts.len() / Self::segment_size()
+    }
+
+    /// Returns true if there are no primitives.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.verts.is_empty()
+    }
+
+    /// Removes all the primitives without drawing them.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.verts.clear();
+    }
+
+    /// Draws all the primitives into `surface` and clears them. The `dimensions` should be
+    /// the size of viewport in pixels, which is used to measure the widths of lines.
+    pub fn draw(
+        &mut self,
+        surface: SurfaceHandle,
+        view_proj: Matrix4<f32>,
+        dimensions: Vector2<u32>,
+    ) -> Result<()> {
+        let verts = std::mem::replace(&mut self.verts, Vec::new());
+        let result = self.draw_segments(&verts, surface, view_proj, dimensions);
+
+        self.verts = verts;
+        self.verts.clear();
+        result
+    }
+
+    fn draw_segments(
+        &mut self,
+        verts: &[u8],
+        surface: SurfaceHandle,
+        view_proj: Matrix4<f32>,
+        dimensions: Vector2<u32>,
+    ) -> Result<()> {
+        self.mesh.reset();
+        if verts.is_empty() {
+            return Ok(());
+        }
+
+        let viewport = [dimensions.x.max(1) as f32, dimensions.y.max(1) as f32];
+        for chunk in verts.chunks(MAX_SEGMENTS_PER_DRAW * Self::segment_size()) {
+            let num = chunk.len() / Self::segment_size();
+            let mut idxes = Vec::with_capacity(num * 6);
+            for i in 0..num as u16 {
+                let v = i * 4;
+                idxes.extend_from_slice(&[v, v + 1, v + 2, v, v + 2, v + 3]);
+            }
+
+            self.mesh.push_vertices(chunk)?;
+            let range = self.mesh.push_indices(&idxes)?;
+
+            let mut dc = Draw::new(self.shader, range.mesh);
+            dc.mesh_index = range.index;
+            dc.set_uniform_variable("u_ViewProj", view_proj);
+            dc.set_uniform_variable("u_Viewport", viewport);
+            self.cmds.draw(dc);
+        }
+
+        self.cmds.submit(surface)?;
+        Ok(())
+    }
+
+    #[inline]
+    fn segment_size() -> usize {
+        // 4 vertices of (from, to, texcoord, color).
+        4 * (10 * 4 + 4)
+    }
+}
+
+impl Drop for DebugDraw {
+    fn drop(&mut self) {
+        if crate::video::valid() {
+            crate::video::delete_shader(self.shader);
+        }
+    }
+}
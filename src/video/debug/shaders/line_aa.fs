@@ -0,0 +1,8 @@
+varying vec4 v_Color;
+varying float v_Edge;
+varying float v_HalfWidth;
+
+void main() {
+    float coverage = clamp(v_HalfWidth + 0.5 - abs(v_Edge), 0.0, 1.0);
+    gl_FragColor = vec4(v_Color.rgb, v_Color.a * coverage);
+}
@@ -0,0 +1,35 @@
+attribute vec3 Position;
+attribute vec3 Normal;
+attribute vec4 Texcoord0;
+attribute vec4 Color0;
+
+uniform mat4 u_ViewProj;
+uniform vec2 u_Viewport;
+
+varying vec4 v_Color;
+varying float v_Edge;
+varying float v_HalfWidth;
+
+// Expands the segment from `Position` to `Normal` into a quad in screen space. The
+// `Texcoord0` holds (endpoint, side, half width in pixels).
+void main() {
+    vec4 a = u_ViewProj * vec4(Position, 1.0);
+    vec4 b = u_ViewProj * vec4(Normal, 1.0);
+
+    vec2 dir = (b.xy / b.w - a.xy / a.w) * u_Viewport;
+    float len = length(dir);
+    dir = len > 0.0001 ? dir / len : vec2(1.0, 0.0);
+    vec2 normal = vec2(-dir.y, dir.x);
+
+    // Leaves one more pixel on every side for the falloff.
+    float extent = Texcoord0.z + 1.0;
+    vec2 offset = normal * Texcoord0.y * extent + dir * (Texcoord0.x * 2.0 - 1.0) * extent;
+
+    vec4 p = mix(a, b, Texcoord0.x);
+    p.xy += offset / u_Viewport * 2.0 * p.w;
+
+    v_Color = Color0;
+    v_Edge = Texcoord0.y * extent;
+    v_HalfWidth = Texcoord0.z;
+    gl_Position = p;
+}
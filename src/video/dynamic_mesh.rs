@@ -0,0 +1,215 @@
+//! Streaming ring-buffer of vertices and indices for dynamic geometry.
+//!
+//! The `DynamicMesh` manages a `MeshHint::Stream` mesh object and hands out ranges of it
+//! per frame, so systems like UI, particles or debug drawing don't have to reimplement the
+//! buffer growth by themselves.
+//!
+//! ```rust,ignore
+//! // At the begining of every frame.
+//! dm.reset();
+//!
+//! // Appends geometries, and draws them with the returned ranges.
+//! dm.push_vertices(Vertex::encode(&verts))?;
+//! let range = dm.push_indices(&idxes)?;
+//!
+//! let mut dc = Draw::new(shader, range.mesh);
+//! dc.mesh_index = range.index;
+//! ```
+
+use crate::errors::*;
+
+use super::assets::prelude::*;
+
+/// The range of geometries that have been pushed into `DynamicMesh`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DynamicMeshRange {
+    /// The underlying mesh object that holds the geometries.
+    pub mesh: MeshHandle,
+    /// The range of indices.
+    pub index: MeshIndex,
+}
+
+/// A growable ring-buffer of vertices and indices.
+///
+/// All the writes into the buffer are recorded into the frame in order, so ranges that handed
+/// out in the same frame never overlap with each other, and writes of the next frame will
+/// always be executed after the draws of current frame. When the capacity is exhausted, a new
+/// mesh object with power-of-two capacity will be created, while the old one is kept alive
+/// until the next `reset`.
+pub struct DynamicMesh {
+    params: MeshParams,
+    mesh: MeshHandle,
+    retired: Vec<MeshHandle>,
+    verts: usize,
+    idxes: usize,
+    base: usize,
+    last: Vec<u8>,
+}
+
+impl DynamicMesh {
+    /// Creates a new `DynamicMesh` with the initial capacity specified by `params.num_verts`
+    /// and `params.num_idxes`. The usage hint will always be `MeshHint::Stream`.
+    pub fn new(mut params: MeshParams) -> Result<Self> {
+        if params.layout.stride() == 0 {
+            bail!("[DynamicMesh] vertex layout is empty.");
+        }
+
+        params.hint = MeshHint::Stream;
+        params.num_verts = params.num_verts.max(1);
+        params.num_idxes = params.num_idxes.max(1);
+        params.sub_mesh_offsets.clear();
+
+        let mesh = crate::video::create_mesh(params.clone(), None::<MeshData>)?;
+
+        Ok(DynamicMesh {
+            params,
+            mesh,
+            retired: Vec::new(),
+            verts: 0,
+            idxes: 0,
+            base: 0,
+            last: Vec::new(),
+        })
+    }
+
+    /// Gets the handle of current mesh object.
+    #[inline]
+    pub fn mesh(&self) -> MeshHandle {
+        self.mesh
+    }
+
+    /// Gets the capacity of vertices and indices.
+    #[inline]
+    pub fn capacity(&self) -> (usize, usize) {
+        (self.params.num_verts, self.params.num_idxes)
+    }
+
+    /// Rewinds the write cursors and releases the retired mesh objects. This should be called
+    /// once at the begining of every frame, before any pushes.
+    pub fn reset(&mut self) {
+        for v in self.retired.drain(..) {
+            crate::video::delete_mesh(v);
+        }
+
+        self.verts = 0;
+        self.idxes = 0;
+        self.base = 0;
+        self.last.clear();
+    }
+
+    /// Appends vertices into the buffer, and returns the base vertex of them. The following
+    /// `push_indices` will be rebased with this base vertex.
+    pub fn push_vertices(&mut self, bytes: &[u8]) -> Result<usize> {
+        let stride = self.params.layout.stride() as usize;
+        if bytes.len() % stride != 0 {
+            bail!(
+                "[DynamicMesh] vertex data length {} is not a multiple of the stride {}.",
+                bytes.len(),
+                stride
+            );
+        }
+
+        let num = bytes.len() / stride;
+        if self.verts + num > self.params.num_verts {
+            let verts = (self.verts + num).next_power_of_two();
+            let idxes = self.params.num_idxes;
+            self.grow(verts, idxes)?;
+        }
+
+        crate::video::update_vertex_buffer(self.mesh, self.verts * stride, bytes)?;
+
+        self.base = self.verts;
+        self.verts += num;
+        self.last.clear();
+        self.last.extend_from_slice(bytes);
+        Ok(self.base)
+    }
+
+    /// Appends indices that are relative to the vertices of last `push_vertices`, and returns
+    /// the range that could be used to draw them.
+    pub fn push_indices<T>(&mut self, idxes: &[T]) -> Result<DynamicMeshRange>
+    where
+        T: Copy + Into<u32>,
+    {
+        let num = idxes.len();
+        let num_verts = self.verts - self.base;
+        for &v in idxes {
+            if v.into() as usize >= num_verts {
+                bail!(
+                    "[DynamicMesh] index {} is out of range of {} vertices.",
+                    v.into(),
+                    num_verts
+                );
+            }
+        }
+
+        if self.idxes + num > self.params.num_idxes {
+            let verts = self.params.num_verts;
+            let idxes = (self.idxes + num).next_power_of_two();
+            self.grow(verts, idxes)?;
+
+            // The vertices of last push should be visible in the new mesh object.
+            if !self.last.is_empty() {
+                let bytes = std::mem::replace(&mut self.last, Vec::new());
+                crate::video::update_vertex_buffer(self.mesh, 0, &bytes)?;
+                self.verts = num_verts;
+                self.last = bytes;
+            }
+        }
+
+        let mut bytes = Vec::with_capacity(num * self.params.index_format.stride());
+        for &v in idxes {
+            let v = v.into() as usize + self.base;
+            match self.params.index_format {
+                IndexFormat::U16 => {
+                    if v > u16::max_value() as usize {
+                        bail!("[DynamicMesh] index {} overflows IndexFormat::U16.", v);
+                    }
+
+                    bytes.extend_from_slice(&(v as u16).to_ne_bytes());
+                }
+                IndexFormat::U32 => bytes.extend_from_slice(&(v as u32).to_ne_bytes()),
+            }
+        }
+
+        let offset = self.idxes * self.params.index_format.stride();
+        crate::video::update_index_buffer(self.mesh, offset, &bytes)?;
+
+        let range = DynamicMeshRange {
+            mesh: self.mesh,
+            index: MeshIndex::Ptr(self.idxes, num),
+        };
+
+        self.idxes += num;
+        Ok(range)
+    }
+
+    fn grow(&mut self, verts: usize, idxes: usize) -> Result<()> {
+        info!(
+            "[DynamicMesh] grows from ({}, {}) to ({}, {}).",
+            self.params.num_verts, self.params.num_idxes, verts, idxes
+        );
+
+        let mut params = self.params.clone();
+        params.num_verts = verts;
+        params.num_idxes = idxes;
+
+        let mesh = crate::video::create_mesh(params.clone(), None::<MeshData>)?;
+        self.retired.push(self.mesh);
+        self.mesh = mesh;
+        self.params = params;
+        self.verts = 0;
+        self.idxes = 0;
+        self.base = 0;
+        Ok(())
+    }
+}
+
+impl Drop for DynamicMesh {
+    fn drop(&mut self) {
+        if crate::video::valid() {
+            self.reset();
+            crate::video::delete_mesh(self.mesh);
+        }
+    }
+}
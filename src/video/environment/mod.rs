@@ -0,0 +1,244 @@
+//! Prefiltering of environment maps for image-based lighting.
+//!
+//! The `EnvironmentPrefilter` convolves an equirectangular environment map into a chain of
+//! specular levels with increasing roughness (importance-sampled GGX), and a diffuse
+//! irradiance map. All the convolutions are done with fragment passes on the GPU.
+//!
+//! Notes that both the source and the results are equirectangular instead of cube maps.
+//! Surfaces could only render into the 2D render textures with a single level, but not the
+//! faces or mip levels of cube textures. So every specular level is a separate render
+//! texture, which should be selected (or blended) by roughness in shaders instead of
+//! sampling with a level of detail.
+//!
+//! ```rust,ignore
+//! let mut prefilter = EnvironmentPrefilter::new()?;
+//! let env = prefilter.prefilter(sky, EnvironmentPrefilterParams::default())?;
+//!
+//! // Samples `env.specular[n]` with the roughness `n / (levels - 1)`, and `env.irradiance`
+//! // with the normal, both of them are equirectangular.
+//! ```
+
+pub mod prelude {
+    pub use super::{EnvironmentPrefilter, EnvironmentPrefilterParams, PrefilteredEnvironment};
+}
+
+use crate::errors::*;
+use crate::math::prelude::Vector2;
+use crate::utils::hash::FastHashMap;
+
+use super::assets::prelude::*;
+use super::command::{CommandBuffer, Draw};
+use super::post_process::{create_fullscreen_mesh, FULLSCREEN_VS};
+
+const PREFILTER_FS: &str = include_str!("shaders/prefilter.fs");
+
+/// The parameters of environment map prefiltering.
+#[derive(Debug, Copy, Clone)]
+pub struct EnvironmentPrefilterParams {
+    /// The format of the prefiltered render textures.
+    pub format: RenderTextureFormat,
+    /// The dimensions of the first specular level, whose roughness is 0.
+    pub dimensions: Vector2<u32>,
+    /// The number of specular levels, each of them has half the dimensions of previous one.
+    pub levels: u32,
+    /// The dimensions of the irradiance map.
+    pub irradiance_dimensions: Vector2<u32>,
+    /// The number of samples of every pixel.
+    pub samples: u32,
+}
+
+impl Default for EnvironmentPrefilterParams {
+    fn default() -> Self {
+        EnvironmentPrefilterParams {
+            format: RenderTextureFormat::RGBA16F,
+            dimensions: Vector2::new(512, 256),
+            levels: 5,
+            irradiance_dimensions: Vector2::new(64, 32),
+            samples: 64,
+        }
+    }
+}
+
+/// The prefiltered render textures of environment map. It's the responsibility of user to
+/// delete them with `PrefilteredEnvironment::delete`.
+#[derive(Debug, Clone)]
+pub struct PrefilteredEnvironment {
+    /// The specular levels, the roughness of level `n` is `n / (levels - 1)`.
+    pub specular: Vec<RenderTextureHandle>,
+    /// The diffuse irradiance map, which has been divided by PI already.
+    pub irradiance: RenderTextureHandle,
+}
+
+impl PrefilteredEnvironment {
+    /// Deletes all the render textures.
+    pub fn delete(self) {
+        for v in self.specular {
+            crate::video::delete_render_texture(v);
+        }
+
+        crate::video::delete_render_texture(self.irradiance);
+    }
+}
+
+/// Convolves equirectangular environment maps.
+pub struct EnvironmentPrefilter {
+    mesh: MeshHandle,
+    shaders: FastHashMap<(bool, u32), ShaderHandle>,
+    cmds: CommandBuffer,
+}
+
+impl EnvironmentPrefilter {
+    /// Creates a new `EnvironmentPrefilter`.
+    pub fn new() -> Result<Self> {
+        Ok(EnvironmentPrefilter {
+            mesh: create_fullscreen_mesh()?,
+            shaders: FastHashMap::default(),
+            cmds: CommandBuffer::new(),
+        })
+    }
+
+    /// Prefilters the equirectangular environment map `src`. The commands are submitted into
+    /// the current frame, so the render textures will be available after it is dispatched.
+    pub fn prefilter(
+        &mut self,
+        src: TextureHandle,
+        params: EnvironmentPrefilterParams,
+    ) -> Result<PrefilteredEnvironment> {
+        if params.levels == 0 || params.samples == 0 {
+            bail!("[EnvironmentPrefilter] the number of levels and samples should be positive.");
+        }
+
+        if !crate::video::is_format_supported(params.format, FormatUsage::Render) {
+            bail!(
+                "[EnvironmentPrefilter] {:?} is not renderable.",
+                params.format
+            );
+        }
+
+        let mut textures = Vec::new();
+        let result = self.draw(&mut textures, src, params);
+
+        if let Err(err) = result {
+            for v in textures {
+                crate::video::delete_render_texture(v);
+            }
+
+            return Err(err);
+        }
+
+        let irradiance = textures.pop().unwrap();
+        Ok(PrefilteredEnvironment {
+            specular: textures,
+            irradiance,
+        })
+    }
+
+    fn draw(
+        &mut self,
+        textures: &mut Vec<RenderTextureHandle>,
+        src: TextureHandle,
+        params: EnvironmentPrefilterParams,
+    ) -> Result<()> {
+        let mut dimensions = params.dimensions;
+        for i in 0..params.levels {
+            let roughness = if params.levels > 1 {
+                i as f32 / (params.levels - 1) as f32
+            } else {
+                0.0
+            };
+
+            let shader = self.shader(false, params.samples)?;
+            let mut dc = Draw::new(shader, self.mesh);
+            dc.set_uniform_variable("u_Texture", src);
+            dc.set_uniform_variable("u_Roughness", roughness);
+            textures.push(self.submit(dc, params.format, dimensions)?);
+
+            dimensions = Vector2::new((dimensions.x / 2).max(1), (dimensions.y / 2).max(1));
+        }
+
+        let shader = self.shader(true, params.samples)?;
+        let mut dc = Draw::new(shader, self.mesh);
+        dc.set_uniform_variable("u_Texture", src);
+        textures.push(self.submit(dc, params.format, params.irradiance_dimensions)?);
+        Ok(())
+    }
+
+    fn submit(
+        &mut self,
+        dc: Draw,
+        format: RenderTextureFormat,
+        dimensions: Vector2<u32>,
+    ) -> Result<RenderTextureHandle> {
+        let mut params = RenderTextureParams::default();
+        params.format = format;
+        params.dimensions = dimensions;
+        let texture = crate::video::create_render_texture(params)?;
+
+        let mut params = SurfaceParams::default();
+        params.set_attachments(&[texture], None)?;
+        params.set_clear(None, None, None);
+
+        let surface = match crate::video::create_surface(params) {
+            Ok(surface) => surface,
+            Err(err) => {
+                crate::video::delete_render_texture(texture);
+                return Err(err.into());
+            }
+        };
+
+        // The surface could be deleted right after submitting, since the commands are
+        // executed in order.
+        self.cmds.draw(dc);
+        let result = self.cmds.submit(surface);
+        crate::video::delete_surface(surface);
+
+        if let Err(err) = result {
+            crate::video::delete_render_texture(texture);
+            return Err(err.into());
+        }
+
+        Ok(texture)
+    }
+
+    fn shader(&mut self, irradiance: bool, samples: u32) -> Result<ShaderHandle> {
+        if let Some(&shader) = self.shaders.get(&(irradiance, samples)) {
+            return Ok(shader);
+        }
+
+        let mut uniforms =
+            UniformVariableLayout::build().with("u_Texture", UniformVariableType::Texture);
+
+        let defines = if irradiance {
+            format!("#define IRRADIANCE\n#define SAMPLE_COUNT {}\n", samples)
+        } else {
+            uniforms = uniforms.with("u_Roughness", UniformVariableType::F32);
+            format!("#define SAMPLE_COUNT {}\n", samples)
+        };
+
+        let mut params = ShaderParams::default();
+        params.attributes = AttributeLayout::build()
+            .with(Attribute::Position, 2)
+            .finish();
+        params.uniforms = uniforms.finish();
+
+        let header = "#version 100\nprecision highp float;\n";
+        let vs = format!("{}{}", header, FULLSCREEN_VS);
+        let fs = format!("{}{}{}", header, defines, PREFILTER_FS);
+        let shader = crate::video::create_shader(params, vs, fs)?;
+
+        self.shaders.insert((irradiance, samples), shader);
+        Ok(shader)
+    }
+}
+
+impl Drop for EnvironmentPrefilter {
+    fn drop(&mut self) {
+        if crate::video::valid() {
+            for (_, v) in self.shaders.drain() {
+                crate::video::delete_shader(v);
+            }
+
+            crate::video::delete_mesh(self.mesh);
+        }
+    }
+}
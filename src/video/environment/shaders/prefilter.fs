@@ -0,0 +1,78 @@
+varying vec2 v_Texcoord;
+
+uniform sampler2D u_Texture;
+#ifndef IRRADIANCE
+uniform float u_Roughness;
+#endif
+
+const float PI = 3.14159265359;
+
+// Converts between directions and texcoords of equirectangular maps.
+vec3 direction(vec2 uv) {
+    float phi = (uv.x * 2.0 - 1.0) * PI;
+    float theta = (uv.y - 0.5) * PI;
+    return vec3(cos(theta) * sin(phi), sin(theta), -cos(theta) * cos(phi));
+}
+
+vec2 texcoord(vec3 d) {
+    return vec2(atan(d.x, -d.z) / (2.0 * PI) + 0.5, asin(clamp(d.y, -1.0, 1.0)) / PI + 0.5);
+}
+
+// The van der Corput sequence without bitwise operations, which are not available in GLSL ES 1.0.
+float radical_inverse(float i) {
+    float result = 0.0;
+    float f = 0.5;
+    for (int k = 0; k < 16; k++) {
+        if (i < 1.0) {
+            break;
+        }
+
+        result += f * mod(i, 2.0);
+        i = floor(i / 2.0);
+        f *= 0.5;
+    }
+
+    return result;
+}
+
+vec3 tangent_to_world(vec3 v, vec3 n) {
+    vec3 up = abs(n.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tx = normalize(cross(up, n));
+    vec3 ty = cross(n, tx);
+    return normalize(tx * v.x + ty * v.y + n * v.z);
+}
+
+void main() {
+    vec3 n = direction(v_Texcoord);
+    vec3 color = vec3(0.0);
+    float weight = 0.0;
+
+    for (int i = 0; i < SAMPLE_COUNT; i++) {
+        vec2 xi = vec2(float(i) / float(SAMPLE_COUNT), radical_inverse(float(i)));
+        float phi = 2.0 * PI * xi.x;
+
+#ifdef IRRADIANCE
+        // Cosine-weighted hemisphere sampling, the pdf cancels out the cosine term.
+        float cos_theta = sqrt(1.0 - xi.y);
+        float sin_theta = sqrt(xi.y);
+        vec3 l = tangent_to_world(vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), n);
+        color += texture2D(u_Texture, texcoord(l)).rgb;
+        weight += 1.0;
+#else
+        // Importance sampling of GGX distribution, with the assumption that n = v.
+        float a = u_Roughness * u_Roughness;
+        float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+        float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+        vec3 h = tangent_to_world(vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), n);
+        vec3 l = normalize(2.0 * dot(n, h) * h - n);
+
+        float ndl = dot(n, l);
+        if (ndl > 0.0) {
+            color += texture2D(u_Texture, texcoord(l)).rgb * ndl;
+            weight += ndl;
+        }
+#endif
+    }
+
+    gl_FragColor = vec4(color / max(weight, 0.0001), 1.0);
+}
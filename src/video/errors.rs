@@ -12,6 +12,16 @@ pub enum Error {
     SampleRenderBuffer,
     #[fail(display = "Failed to create surface, errors:\n{}\n", _0)]
     SurfaceInvalid(String),
+    #[fail(display = "Failed to create mesh, errors:\n{}\n", _0)]
+    MeshInvalid(String),
+    #[fail(display = "Failed to create texture, errors:\n{}\n", _0)]
+    TextureInvalid(String),
+    #[fail(display = "Failed to create buffer, errors:\n{}\n", _0)]
+    BufferInvalid(String),
+    #[fail(display = "Failed to blit, errors:\n{}\n", _0)]
+    BlitInvalid(String),
+    #[fail(display = "Failed to read back, errors:\n{}\n", _0)]
+    ReadbackInvalid(String),
     #[fail(display = "Attribute({}) is undefined.", _0)]
     AttributeUndefined(String),
 }
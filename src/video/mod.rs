@@ -162,7 +162,12 @@
 //!
 //! #### Compressed Texture Format
 //!
-//! _TODO_: Cube texture.
+//! #### Cube Texture
+//!
+//! The cube maps are created with the data of six faces in the order of `+X`, `-X`, `+Y`,
+//! `-Y`, `+Z` and `-Z`, and sampled with `samplerCube` in shaders. The uniforms of them are
+//! declared as `UniformVariableType::Texture` like the 2D ones.
+//!
 //! _TODO_: 3D texture.
 //!
 //! ### Mesh Object
@@ -189,32 +194,68 @@
 pub const MAX_VERTEX_ATTRIBUTES: usize = 12;
 /// Maximum number of attachments in framebuffer.
 pub const MAX_FRAMEBUFFER_ATTACHMENTS: usize = 8;
+/// Maximum number of viewports in surface for layered rendering.
+pub const MAX_VIEWPORTS: usize = 16;
 /// Maximum number of uniform variables in shader.
 pub const MAX_UNIFORM_VARIABLES: usize = 32;
 /// Maximum number of textures in shader.
 pub const MAX_UNIFORM_TEXTURE_SLOTS: usize = 8;
+/// Maximum number of image units that could be binded for load/store in shader.
+pub const MAX_IMAGE_UNITS: usize = 8;
+/// Maximum number of shader storage buffer binding points in shader.
+pub const MAX_STORAGE_BUFFER_BINDINGS: usize = 8;
+/// Maximum number of uniform buffer binding points in shader, including the one that
+/// reserved for the per-object uniform block.
+pub const MAX_UNIFORM_BUFFER_BINDINGS: usize = 8;
+/// Maximum size in bytes of the per-object uniform block of a draw.
+pub const MAX_PER_OBJECT_BLOCK_SIZE: usize = 256;
+/// The name of the uniform block that receives the per-object data of draws.
+pub const PER_OBJECT_BLOCK: &str = "PerObject";
+/// The uniform buffer binding point of the per-object uniform block.
+pub const PER_OBJECT_BLOCK_BINDING: u32 = 0;
 
 #[macro_use]
 pub mod assets;
 pub mod command;
+pub mod debug;
+pub mod dynamic_mesh;
+pub mod environment;
 pub mod errors;
+pub mod post_process;
 
+mod render_thread;
 mod system;
 
 mod backends;
 
+pub use self::backends::frame::{
+    DispatchBudget, FrameLog, FrameShrinkPolicy, FrameStats, MemoryBarrier,
+};
+pub use self::backends::DeviceLimits;
+
 pub mod prelude {
     pub use super::assets::prelude::*;
+    pub use super::backends::frame::{
+        DispatchBudget, FrameLog, FrameShrinkPolicy, FrameStats, MemoryBarrier,
+    };
+    pub use super::backends::DeviceLimits;
     pub use super::command::{CommandBuffer, Draw, DrawCommandBuffer};
+    pub use super::debug::prelude::*;
+    pub use super::dynamic_mesh::{DynamicMesh, DynamicMeshRange};
+    pub use super::environment::prelude::*;
+    pub use super::post_process::prelude::*;
 }
 
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
 use crate::math::prelude::Aabb2;
 use crate::prelude::CrResult;
+use crate::res::request::Request;
 use crate::res::utils::prelude::ResourceState;
-use crate::utils::double_buf::DoubleBuf;
+use crate::utils::hash::FastHashMap;
+use crate::utils::triple_buf::TripleBuf;
 
 use self::assets::prelude::*;
 use self::backends::frame::Frame;
@@ -249,10 +290,153 @@ pub(crate) unsafe fn discard() {
     CTX = std::ptr::null();
 }
 
-pub(crate) unsafe fn frames() -> Arc<DoubleBuf<Frame>> {
+/// Checks if the video system is enabled.
+#[inline]
+pub fn valid() -> bool {
+    unsafe { !CTX.is_null() }
+}
+
+pub(crate) unsafe fn frames() -> Arc<TripleBuf<Frame>> {
     ctx().frames()
 }
 
+/// Swaps the double-buffered frame and dispatches the commands immediately on the
+/// current thread. The OpenGL context must be current on the calling thread.
+#[inline]
+pub fn flush() -> CrResult<()> {
+    ctx().flush()
+}
+
+/// Dispatches the commands incrementally until the `budget` is exhausted, and continues
+/// where it left off at next call. Returns true if the frame is finished.
+#[inline]
+pub fn flush_budget(budget: DispatchBudget) -> CrResult<bool> {
+    ctx().flush_budget(budget)
+}
+
+/// Enables or disables the dispatching of frames on a dedicated render thread.
+#[inline]
+pub fn set_threaded(threaded: bool) -> CrResult<()> {
+    ctx().set_threaded(threaded)
+}
+
+/// Returns true if the frames are dispatched on a dedicated render thread.
+#[inline]
+pub fn is_threaded() -> bool {
+    ctx().is_threaded()
+}
+
+/// Enables or disables the triple buffering of frames with threaded rendering.
+#[inline]
+pub fn set_triple_buffering(enabled: bool) -> CrResult<()> {
+    ctx().set_triple_buffering(enabled)
+}
+
+/// Returns true if the frames are triple buffered.
+#[inline]
+pub fn is_triple_buffering() -> bool {
+    ctx().is_triple_buffering()
+}
+
+/// Runs the lifecycle of `n` frames immediately in headless mode, and captures the
+/// framebuffer after each frame has been dispatched.
+#[inline]
+pub fn run_capture_frames(n: usize) -> CrResult<Vec<Image>> {
+    ctx().run_capture_frames(n)
+}
+
+/// Blocks the dispatching until all the commands submitted before this point have been
+/// completed by GPU. This stalls the CPU, use it sparingly.
+#[inline]
+pub fn finish() {
+    ctx().finish()
+}
+
+/// Forces the commands submitted before this point to be issued to GPU without waiting.
+#[inline]
+pub fn flush_gpu() {
+    ctx().flush_gpu()
+}
+
+/// Inserts memory barriers, which orders the memory accesses of `barriers` after the
+/// incoherent writes of shaders before this point.
+#[inline]
+pub fn memory_barrier(barriers: MemoryBarrier) {
+    ctx().memory_barrier(barriers)
+}
+
+/// Tells the video system that its cached OpenGL states are stale after native OpenGL calls.
+#[inline]
+pub fn invalidate_state_cache() {
+    ctx().invalidate_state_cache()
+}
+
+/// Sets the human-readable `name` of a resource.
+#[inline]
+pub fn set_name<T, N>(handle: T, name: N)
+where
+    T: Into<VideoHandle>,
+    N: Into<String>,
+{
+    ctx().set_name(handle, name)
+}
+
+/// Gets the name of a resource, if it has been named with `set_name`.
+#[inline]
+pub fn name_of<T: Into<VideoHandle>>(handle: T) -> Option<String> {
+    ctx().name_of(handle)
+}
+
+/// Gets the statistics of the last dispatched frame.
+#[inline]
+pub fn frame_stats() -> FrameStats {
+    ctx().frame_stats()
+}
+
+/// Sets the policy of shrinking the buffers of frames.
+#[inline]
+pub fn set_frame_shrink_policy(policy: FrameShrinkPolicy) {
+    ctx().set_frame_shrink_policy(policy);
+}
+
+/// Starts the GPU timer `name`, which measures the time GPU spent on the commands submitted
+/// after it until `end_gpu_timer`.
+#[inline]
+pub fn begin_gpu_timer<T: Into<String>>(name: T) {
+    ctx().begin_gpu_timer(name);
+}
+
+/// Stops the running GPU timer.
+#[inline]
+pub fn end_gpu_timer() {
+    ctx().end_gpu_timer();
+}
+
+/// Gets the most recent completed measurements of GPU timers.
+#[inline]
+pub fn gpu_timers() -> FastHashMap<String, Duration> {
+    ctx().gpu_timers()
+}
+
+/// Sets the maximum bytes of resource data that are uploaded per frame, the creations of
+/// textures and meshes beyond it are deferred to subsequent frames.
+#[inline]
+pub fn set_upload_budget(budget: Option<usize>) {
+    ctx().set_upload_budget(budget);
+}
+
+/// Records the commands that have been submitted into current frame so far.
+#[inline]
+pub fn record() -> FrameLog {
+    ctx().record()
+}
+
+/// Appends the commands of recorded `log` into current frame.
+#[inline]
+pub fn replay(log: &FrameLog) {
+    ctx().replay(log)
+}
+
 /// Creates an surface with `SurfaceParams`.
 #[inline]
 pub fn create_surface(params: SurfaceParams) -> Result<SurfaceHandle> {
@@ -277,6 +461,50 @@ pub fn delete_surface(handle: SurfaceHandle) {
     ctx().delete_surface(handle)
 }
 
+/// Sets the antialiasing mode of surfaces that render into the window framebuffer.
+#[inline]
+pub fn set_antialiasing(mode: AaMode) -> CrResult<()> {
+    ctx().set_antialiasing(mode)
+}
+
+/// Gets the antialiasing mode of surfaces that render into the window framebuffer.
+#[inline]
+pub fn antialiasing() -> AaMode {
+    ctx().antialiasing()
+}
+
+/// Sets the adjustment of colors that presented to the window.
+#[inline]
+pub fn set_output_adjustment(adjustment: OutputAdjustment) -> CrResult<()> {
+    ctx().set_output_adjustment(adjustment)
+}
+
+/// Sets the gamma of colors that presented to the window, the brightness and contrast are
+/// kept untouched.
+#[inline]
+pub fn set_output_gamma(gamma: f32) -> CrResult<()> {
+    ctx().set_output_gamma(gamma)
+}
+
+/// Gets the adjustment of colors that presented to the window.
+#[inline]
+pub fn output_adjustment() -> OutputAdjustment {
+    ctx().output_adjustment()
+}
+
+/// Sets the maximum number of frames that the GPU could lag behind the CPU, which trades
+/// the throughput for lower input latency as it gets smaller.
+#[inline]
+pub fn set_max_frame_latency(frames: u32) {
+    ctx().set_max_frame_latency(frames)
+}
+
+/// Gets the maximum number of frames that the GPU could lag behind the CPU.
+#[inline]
+pub fn max_frame_latency() -> u32 {
+    ctx().max_frame_latency()
+}
+
 /// Create a shader with initial shaders and render state. It encapusulates all the
 /// informations we need to configurate graphics pipeline before real drawing.
 #[inline]
@@ -311,6 +539,12 @@ where
     ctx().create_mesh(params, data)
 }
 
+/// Creates a batch of mesh objects at once.
+#[inline]
+pub fn create_meshes(items: Vec<(MeshParams, Option<MeshData>)>) -> CrResult<Vec<MeshHandle>> {
+    ctx().create_meshes(items)
+}
+
 /// Creates a mesh object from file asynchronously.
 #[inline]
 pub fn create_mesh_from<T: AsRef<str>>(url: T) -> CrResult<MeshHandle> {
@@ -323,6 +557,13 @@ pub fn create_mesh_from_uuid(uuid: Uuid) -> CrResult<MeshHandle> {
     ctx().create_mesh_from_uuid(uuid)
 }
 
+/// Creates a mesh object from the encoded `bytes` in memory asynchronously. The bytes are
+/// decoded with `format` on the loading threads.
+#[inline]
+pub fn create_mesh_from_bytes(bytes: &[u8], format: MeshFormat) -> CrResult<MeshHandle> {
+    ctx().create_mesh_from_bytes(bytes, format)
+}
+
 /// Gets the `MeshParams` if available.
 #[inline]
 pub fn mesh(handle: MeshHandle) -> Option<MeshParams> {
@@ -335,6 +576,13 @@ pub fn mesh_state(handle: MeshHandle) -> ResourceState {
     ctx().mesh_state(handle)
 }
 
+/// Gets the UUID of the mesh which is created from file. The handle could be recreated
+/// with `create_mesh_from_uuid` in the next run.
+#[inline]
+pub fn mesh_uuid(handle: MeshHandle) -> Option<Uuid> {
+    ctx().mesh_uuid(handle)
+}
+
 /// Update a subset of dynamic vertex buffer. Use `offset` specifies the offset
 /// into the buffer object's data store where data replacement will begin, measured
 /// in bytes.
@@ -351,6 +599,13 @@ pub fn update_index_buffer(handle: MeshHandle, offset: usize, data: &[u8]) -> Cr
     ctx().update_index_buffer(handle, offset, data)
 }
 
+/// Sets whether the winding order of triangles should be made consistent when meshes are
+/// imported from files.
+#[inline]
+pub fn set_fix_mesh_winding(enabled: bool) {
+    ctx().set_fix_mesh_winding(enabled)
+}
+
 /// Delete mesh object.
 #[inline]
 pub fn delete_mesh(handle: MeshHandle) {
@@ -367,6 +622,37 @@ where
     ctx().create_texture(params, data)
 }
 
+/// Creates a batch of texture objects at once.
+#[inline]
+pub fn create_textures(
+    items: Vec<(TextureParams, Option<TextureData>)>,
+) -> CrResult<Vec<TextureHandle>> {
+    ctx().create_textures(items)
+}
+
+/// Creates a cube map with the data of six faces, which is sampled with `samplerCube` in
+/// shaders.
+#[inline]
+pub fn create_cube_texture<T>(params: TextureParams, data: T) -> CrResult<TextureHandle>
+where
+    T: Into<Option<CubeTextureData>>,
+{
+    ctx().create_cube_texture(params, data)
+}
+
+/// Wraps the native OpenGL texture object `id` into a `TextureHandle`, which will not be
+/// deleted along with the handle.
+#[inline]
+pub fn import_texture(id: u32, params: TextureParams) -> CrResult<TextureHandle> {
+    ctx().import_texture(id, params)
+}
+
+/// Gets the name of native OpenGL texture object of `handle`, if it has been created.
+#[inline]
+pub fn native_texture(handle: TextureHandle) -> Option<u32> {
+    ctx().native_texture(handle)
+}
+
 /// Creates a texture object from file asynchronously.
 #[inline]
 pub fn create_texture_from<T: AsRef<str>>(url: T) -> CrResult<TextureHandle> {
@@ -379,24 +665,83 @@ pub fn create_texture_from_uuid(uuid: Uuid) -> CrResult<TextureHandle> {
     ctx().create_texture_from_uuid(uuid)
 }
 
+/// Creates a texture object from the encoded `bytes` in memory asynchronously. The bytes are
+/// decoded with `format` on the loading threads.
+#[inline]
+pub fn create_texture_from_bytes(bytes: &[u8], format: ImageFormat) -> CrResult<TextureHandle> {
+    ctx().create_texture_from_bytes(bytes, format)
+}
+
 /// Get the resource state of specified texture.
 #[inline]
 pub fn texture_state(handle: TextureHandle) -> ResourceState {
     ctx().texture_state(handle)
 }
 
+/// Gets the UUID of the texture which is created from file. The handle could be recreated
+/// with `create_texture_from_uuid` in the next run.
+#[inline]
+pub fn texture_uuid(handle: TextureHandle) -> Option<Uuid> {
+    ctx().texture_uuid(handle)
+}
+
+/// Sets the texture which is sampled instead of the textures that are not ready.
+#[inline]
+pub fn set_fallback_texture<T>(handle: T)
+where
+    T: Into<Option<TextureHandle>>,
+{
+    ctx().set_fallback_texture(handle)
+}
+
+/// Gets the texture which is sampled instead of the textures that are not ready.
+#[inline]
+pub fn fallback_texture() -> Option<TextureHandle> {
+    ctx().fallback_texture()
+}
+
 /// Update a contiguous subregion of an existing two-dimensional texture object.
 #[inline]
 pub fn update_texture(handle: TextureHandle, area: Aabb2<u32>, data: &[u8]) -> CrResult<()> {
     ctx().update_texture(handle, area, data)
 }
 
+/// Update a subregion of an existing two-dimensional texture object with pixels whose
+/// rows are `stride` pixels apart.
+#[inline]
+pub fn update_texture_with_stride(
+    handle: TextureHandle,
+    area: Aabb2<u32>,
+    stride: u32,
+    data: &[u8],
+) -> CrResult<()> {
+    ctx().update_texture_with_stride(handle, area, stride, data)
+}
+
 /// Delete the texture object.
 #[inline]
 pub fn delete_texture(handle: TextureHandle) {
     ctx().delete_texture(handle);
 }
 
+/// Checks if the render texture `format` could be used for `usage` on this device.
+#[inline]
+pub fn is_format_supported(format: RenderTextureFormat, usage: FormatUsage) -> bool {
+    ctx().is_format_supported(format, usage)
+}
+
+/// Checks if textures could be created with `format` on this device.
+#[inline]
+pub fn is_texture_format_supported(format: TextureFormat) -> bool {
+    ctx().is_texture_format_supported(format)
+}
+
+/// Gets the limits of this device, e.g. the maximum size of textures.
+#[inline]
+pub fn limits() -> DeviceLimits {
+    ctx().limits()
+}
+
 /// Create render texture object, which could be attached with a framebuffer.
 #[inline]
 pub fn create_render_texture(params: RenderTextureParams) -> Result<RenderTextureHandle> {
@@ -421,6 +766,145 @@ pub fn delete_render_texture(handle: RenderTextureHandle) {
     ctx().delete_render_texture(handle)
 }
 
+/// Copies the whole render texture `src` into `dst`, scaling with `filter` if their
+/// dimensions differ. The formats should be compatible, the rules are listed in
+/// `RenderTextureFormat::validate_blit`.
+///
+/// The blits between fixed-point and floating-point color formats fall back to a
+/// full-screen pass on OpenGL ES and WebGL, which requires `src` to be a sampler.
+#[inline]
+pub fn blit(
+    src: RenderTextureHandle,
+    dst: RenderTextureHandle,
+    filter: TextureFilter,
+) -> Result<()> {
+    ctx().blit(src, dst, filter)
+}
+
+/// Requests to read back the pixels of `area` in render texture after the commands
+/// before it have been dispatched. The returned `Request` would be resolved with the
+/// pixels once the frame has been dispatched.
+#[inline]
+pub fn read_render_texture(handle: RenderTextureHandle, area: Aabb2<u32>) -> Result<Request> {
+    ctx().read_render_texture(handle, area)
+}
+
+/// Create per-instance vertex buffer object, which could be used with `Draw`s to
+/// render a number of instances of mesh.
+#[inline]
+pub fn create_instance_buffer(
+    params: InstanceBufferParams,
+    data: Option<&[u8]>,
+) -> Result<InstanceBufferHandle> {
+    ctx().create_instance_buffer(params, data)
+}
+
+/// Gets the `InstanceBufferParams` if available.
+#[inline]
+pub fn instance_buffer(handle: InstanceBufferHandle) -> Option<InstanceBufferParams> {
+    ctx().instance_buffer(handle)
+}
+
+/// Update a subset of dynamic instance buffer. Use `offset` specifies the offset
+/// into the buffer object's data store where data replacement will begin, measured
+/// in bytes.
+#[inline]
+pub fn update_instance_buffer(
+    handle: InstanceBufferHandle,
+    offset: usize,
+    data: &[u8],
+) -> CrResult<()> {
+    ctx().update_instance_buffer(handle, offset, data)
+}
+
+/// Delete the instance buffer object.
+#[inline]
+pub fn delete_instance_buffer(handle: InstanceBufferHandle) {
+    ctx().delete_instance_buffer(handle)
+}
+
+/// Create shader storage buffer object, which could be read and written by shaders
+/// after being binded to `Draw`s with `bind_storage_buffer`.
+#[inline]
+pub fn create_shader_storage_buffer(
+    params: ShaderStorageBufferParams,
+    data: Option<&[u8]>,
+) -> Result<ShaderStorageBufferHandle> {
+    ctx().create_shader_storage_buffer(params, data)
+}
+
+/// Gets the `ShaderStorageBufferParams` if available.
+#[inline]
+pub fn shader_storage_buffer(
+    handle: ShaderStorageBufferHandle,
+) -> Option<ShaderStorageBufferParams> {
+    ctx().shader_storage_buffer(handle)
+}
+
+/// Update a subset of dynamic shader storage buffer. Use `offset` specifies the
+/// offset into the buffer object's data store where data replacement will begin,
+/// measured in bytes.
+#[inline]
+pub fn update_shader_storage_buffer(
+    handle: ShaderStorageBufferHandle,
+    offset: usize,
+    data: &[u8],
+) -> CrResult<()> {
+    ctx().update_shader_storage_buffer(handle, offset, data)
+}
+
+/// Requests to read back the contents of shader storage buffer after the commands
+/// before it have been dispatched.
+#[inline]
+pub fn read_shader_storage_buffer(handle: ShaderStorageBufferHandle) -> CrResult<()> {
+    ctx().read_shader_storage_buffer(handle)
+}
+
+/// Takes the latest contents of shader storage buffer that have been read back.
+#[inline]
+pub fn take_shader_storage_buffer_data(handle: ShaderStorageBufferHandle) -> Option<Vec<u8>> {
+    ctx().take_shader_storage_buffer_data(handle)
+}
+
+/// Delete the shader storage buffer object.
+#[inline]
+pub fn delete_shader_storage_buffer(handle: ShaderStorageBufferHandle) {
+    ctx().delete_shader_storage_buffer(handle)
+}
+
+/// Create uniform buffer object, which holds the uniform blocks shared by draws after
+/// being binded to `Draw`s with `bind_uniform_buffer`.
+#[inline]
+pub fn create_uniform_buffer(
+    params: UniformBufferParams,
+    data: Option<&[u8]>,
+) -> Result<UniformBufferHandle> {
+    ctx().create_uniform_buffer(params, data)
+}
+
+/// Gets the `UniformBufferParams` if available.
+#[inline]
+pub fn uniform_buffer(handle: UniformBufferHandle) -> Option<UniformBufferParams> {
+    ctx().uniform_buffer(handle)
+}
+
+/// Update a subset of dynamic uniform buffer. Use `offset` specifies the offset into the
+/// buffer object's data store where data replacement will begin, measured in bytes.
+#[inline]
+pub fn update_uniform_buffer(
+    handle: UniformBufferHandle,
+    offset: usize,
+    data: &[u8],
+) -> CrResult<()> {
+    ctx().update_uniform_buffer(handle, offset, data)
+}
+
+/// Delete the uniform buffer object.
+#[inline]
+pub fn delete_uniform_buffer(handle: UniformBufferHandle) {
+    ctx().delete_uniform_buffer(handle)
+}
+
 mod ins {
     use super::system::VideoSystem;
 
@@ -0,0 +1,428 @@
+//! Chains of full-screen post-processing effects.
+//!
+//! The `PostProcess` takes the render texture that the scene has been rendered into, runs
+//! all the effects in order through transient render textures of `RenderTexturePool`, and
+//! outputs the result of last effect into the destination surface.
+//!
+//! ```rust,ignore
+//! let mut pp = PostProcess::new()?;
+//! pp.bloom(1.0, 0.5).tonemap(1.0, TonemapOperator::Aces);
+//!
+//! // Renders the scene into `hdr`, which is a `RenderTextureFormat::RGBA16F` render
+//! // texture, and then blooms and tonemaps it into the window framebuffer.
+//! pp.draw(hdr, surface)?;
+//! ```
+
+pub mod pixel_perfect;
+pub mod pool;
+
+pub mod prelude {
+    pub use super::pixel_perfect::PixelPerfect;
+    pub use super::pool::RenderTexturePool;
+    pub use super::{PostProcess, TonemapOperator};
+}
+
+use crate::errors::*;
+use crate::math::prelude::Vector2;
+use crate::utils::hash::FastHashMap;
+
+use super::assets::prelude::*;
+use super::command::{CommandBuffer, Draw};
+
+use self::pool::RenderTexturePool;
+
+/// The vertex shader of full-screen passes, which outputs `v_Texcoord` in [0, 1].
+pub(crate) const FULLSCREEN_VS: &str = include_str!("shaders/fullscreen.vs");
+const TONEMAP_FS: &str = include_str!("shaders/tonemap.fs");
+const BLOOM_DOWNSAMPLE_FS: &str = include_str!("shaders/bloom_downsample.fs");
+const BLOOM_BLUR_FS: &str = include_str!("shaders/bloom_blur.fs");
+const BLOOM_UPSAMPLE_FS: &str = include_str!("shaders/bloom_upsample.fs");
+const BLOOM_COMPOSITE_FS: &str = include_str!("shaders/bloom_composite.fs");
+
+/// The default number of downsample iterations of bloom.
+pub const DEFAULT_BLOOM_ITERATIONS: u32 = 5;
+
+/// The operators that map HDR colors into the LDR range [0, 1].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TonemapOperator {
+    /// The simple `x / (1 + x)` operator.
+    Reinhard,
+    /// The curve fitting of ACES filmic tonemapping, which has more contrast and saturation.
+    Aces,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Effect {
+    Tonemap {
+        exposure: f32,
+        operator: TonemapOperator,
+    },
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        iterations: u32,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum EffectShader {
+    Tonemap(TonemapOperator),
+    BloomPrefilter,
+    BloomDownsample,
+    BloomBlur,
+    BloomUpsample,
+    BloomComposite,
+}
+
+/// A chain of full-screen post-processing effects.
+pub struct PostProcess {
+    mesh: MeshHandle,
+    shaders: FastHashMap<EffectShader, ShaderHandle>,
+    effects: Vec<Effect>,
+    pool: RenderTexturePool,
+    cmds: CommandBuffer,
+}
+
+impl PostProcess {
+    /// Creates a new `PostProcess` without any effects.
+    pub fn new() -> Result<Self> {
+        Ok(PostProcess {
+            mesh: create_fullscreen_mesh()?,
+            shaders: FastHashMap::default(),
+            effects: Vec::new(),
+            pool: RenderTexturePool::new(),
+            cmds: CommandBuffer::new(),
+        })
+    }
+
+    /// Appends a tonemapping effect, which scales the HDR colors with `exposure` and maps
+    /// them into the LDR range with `operator`.
+    ///
+    /// Notes that the output is still in linear space.
+    pub fn tonemap(&mut self, exposure: f32, operator: TonemapOperator) -> &mut Self {
+        self.effects.push(Effect::Tonemap { exposure, operator });
+        self
+    }
+
+    /// Appends a bloom effect with `DEFAULT_BLOOM_ITERATIONS` iterations. Colors that are
+    /// brighter than `threshold` will be blurred and added back with `intensity`.
+    #[inline]
+    pub fn bloom(&mut self, threshold: f32, intensity: f32) -> &mut Self {
+        self.bloom_with_iterations(threshold, intensity, DEFAULT_BLOOM_ITERATIONS)
+    }
+
+    /// Appends a bloom effect, the bright-pass is downsampled and blurred progressively for
+    /// `iterations` times, and then upsampled additively back into the half resolution. More
+    /// iterations produce wider glows.
+    pub fn bloom_with_iterations(
+        &mut self,
+        threshold: f32,
+        intensity: f32,
+        iterations: u32,
+    ) -> &mut Self {
+        self.effects.push(Effect::Bloom {
+            threshold,
+            intensity,
+            iterations: iterations.max(1),
+        });
+
+        self
+    }
+
+    /// Removes all the effects.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+
+    /// Gets the pool of transient render textures that used by the effects.
+    #[inline]
+    pub fn pool(&mut self) -> &mut RenderTexturePool {
+        &mut self.pool
+    }
+
+    /// Runs all the effects on `src` in order, and outputs the result into `dst`. The
+    /// intermediate render textures share the same format and dimensions with `src`.
+    pub fn draw(&mut self, src: RenderTextureHandle, dst: SurfaceHandle) -> Result<()> {
+        let params = match crate::video::render_texture(src) {
+            Some(params) => params,
+            None => bail!("[PostProcess] {:?} is invalid.", src),
+        };
+
+        if self.effects.is_empty() {
+            bail!("[PostProcess] there is no effect to draw.");
+        }
+
+        let mut input = src;
+        let mut acquired = None;
+        let last = self.effects.len() - 1;
+
+        for i in 0..self.effects.len() {
+            let (output, surface) = if i == last {
+                (None, dst)
+            } else {
+                let (texture, surface) = self.pool.acquire(params.format, params.dimensions)?;
+                (Some(texture), surface)
+            };
+
+            let effect = self.effects[i];
+            self.draw_effect(effect, input, params.format, params.dimensions, surface)?;
+
+            if let Some(texture) = acquired.take() {
+                self.pool.release(texture);
+            }
+
+            if let Some(texture) = output {
+                input = texture;
+                acquired = Some(texture);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw_effect(
+        &mut self,
+        effect: Effect,
+        input: RenderTextureHandle,
+        format: RenderTextureFormat,
+        dimensions: Vector2<u32>,
+        surface: SurfaceHandle,
+    ) -> Result<()> {
+        match effect {
+            Effect::Tonemap { exposure, operator } => {
+                let mut dc = self.draw_call(EffectShader::Tonemap(operator))?;
+                dc.set_uniform_variable("u_Texture", input);
+                dc.set_uniform_variable("u_Exposure", exposure);
+                self.submit(dc, surface)
+            }
+
+            Effect::Bloom {
+                threshold,
+                intensity,
+                iterations,
+            } => {
+                let mut levels = Vec::new();
+                let result = self.draw_bloom(
+                    &mut levels,
+                    (input, format, dimensions),
+                    surface,
+                    (threshold, intensity, iterations),
+                );
+
+                for (texture, _, _) in levels {
+                    self.pool.release(texture);
+                }
+
+                result
+            }
+        }
+    }
+
+    fn draw_bloom(
+        &mut self,
+        levels: &mut Vec<(RenderTextureHandle, SurfaceHandle, Vector2<u32>)>,
+        (input, format, dimensions): (RenderTextureHandle, RenderTextureFormat, Vector2<u32>),
+        surface: SurfaceHandle,
+        (threshold, intensity, iterations): (f32, f32, u32),
+    ) -> Result<()> {
+        // Extracts the bright part into half resolution, and downsamples it progressively.
+        let (mut src, mut src_dimensions) = (input, dimensions);
+        for i in 0..iterations {
+            let dimensions =
+                Vector2::new((src_dimensions.x / 2).max(1), (src_dimensions.y / 2).max(1));
+            if i > 0 && dimensions == src_dimensions {
+                break;
+            }
+
+            let (texture, dst) = self.pool.acquire(format, dimensions)?;
+            levels.push((texture, dst, dimensions));
+
+            let mut dc = if i == 0 {
+                let mut dc = self.draw_call(EffectShader::BloomPrefilter)?;
+                dc.set_uniform_variable("u_Threshold", threshold);
+                dc
+            } else {
+                self.draw_call(EffectShader::BloomDownsample)?
+            };
+
+            dc.set_uniform_variable("u_Texture", src);
+            dc.set_uniform_variable("u_TexelSize", texel_size(src_dimensions));
+            self.submit(dc, dst)?;
+
+            src = texture;
+            src_dimensions = dimensions;
+        }
+
+        // Blurs every levels with separable gaussian kernel.
+        for &(texture, dst, dimensions) in levels.iter() {
+            let (tmp, tmp_surface) = self.pool.acquire(format, dimensions)?;
+            let size = texel_size(dimensions);
+
+            let mut dc = self.draw_call(EffectShader::BloomBlur)?;
+            dc.set_uniform_variable("u_Texture", texture);
+            dc.set_uniform_variable("u_Direction", [size[0], 0.0]);
+            let result = self.submit(dc, tmp_surface);
+
+            let result = result.and_then(|_| {
+                let mut dc = self.draw_call(EffectShader::BloomBlur)?;
+                dc.set_uniform_variable("u_Texture", tmp);
+                dc.set_uniform_variable("u_Direction", [0.0, size[1]]);
+                self.submit(dc, dst)
+            });
+
+            self.pool.release(tmp);
+            result?;
+        }
+
+        // Upsamples and accumulates the levels from the smallest one.
+        for i in (1..levels.len()).rev() {
+            let mut dc = self.draw_call(EffectShader::BloomUpsample)?;
+            dc.set_uniform_variable("u_Texture", levels[i].0);
+            dc.set_uniform_variable("u_TexelSize", texel_size(levels[i].2));
+            self.submit(dc, levels[i - 1].1)?;
+        }
+
+        let mut dc = self.draw_call(EffectShader::BloomComposite)?;
+        dc.set_uniform_variable("u_Texture", input);
+        dc.set_uniform_variable("u_Bloom", levels[0].0);
+        dc.set_uniform_variable("u_Intensity", intensity);
+        self.submit(dc, surface)
+    }
+
+    #[inline]
+    fn draw_call(&mut self, key: EffectShader) -> Result<Draw> {
+        let shader = self.shader(key)?;
+        Ok(Draw::new(shader, self.mesh))
+    }
+
+    #[inline]
+    fn submit(&mut self, dc: Draw, surface: SurfaceHandle) -> Result<()> {
+        self.cmds.draw(dc);
+        self.cmds.submit(surface)?;
+        Ok(())
+    }
+
+    fn shader(&mut self, key: EffectShader) -> Result<ShaderHandle> {
+        if let Some(&shader) = self.shaders.get(&key) {
+            return Ok(shader);
+        }
+
+        let mut state = RenderState::default();
+        let (defines, fs, uniforms) = match key {
+            EffectShader::Tonemap(operator) => {
+                let defines = match operator {
+                    TonemapOperator::Reinhard => "#define TONEMAP_REINHARD\n",
+                    TonemapOperator::Aces => "#define TONEMAP_ACES\n",
+                };
+
+                let uniforms = UniformVariableLayout::build()
+                    .with("u_Texture", UniformVariableType::RenderTexture)
+                    .with("u_Exposure", UniformVariableType::F32)
+                    .finish();
+
+                (defines, TONEMAP_FS, uniforms)
+            }
+
+            EffectShader::BloomPrefilter | EffectShader::BloomDownsample => {
+                let mut uniforms = UniformVariableLayout::build()
+                    .with("u_Texture", UniformVariableType::RenderTexture)
+                    .with("u_TexelSize", UniformVariableType::Vector2f);
+
+                let defines = if key == EffectShader::BloomPrefilter {
+                    uniforms = uniforms.with("u_Threshold", UniformVariableType::F32);
+                    "#define BLOOM_PREFILTER\n"
+                } else {
+                    ""
+                };
+
+                (defines, BLOOM_DOWNSAMPLE_FS, uniforms.finish())
+            }
+
+            EffectShader::BloomBlur => {
+                let uniforms = UniformVariableLayout::build()
+                    .with("u_Texture", UniformVariableType::RenderTexture)
+                    .with("u_Direction", UniformVariableType::Vector2f)
+                    .finish();
+
+                ("", BLOOM_BLUR_FS, uniforms)
+            }
+
+            EffectShader::BloomUpsample => {
+                let uniforms = UniformVariableLayout::build()
+                    .with("u_Texture", UniformVariableType::RenderTexture)
+                    .with("u_TexelSize", UniformVariableType::Vector2f)
+                    .finish();
+
+                state.color_blend = Some((Equation::Add, BlendFactor::One, BlendFactor::One));
+                ("", BLOOM_UPSAMPLE_FS, uniforms)
+            }
+
+            EffectShader::BloomComposite => {
+                let uniforms = UniformVariableLayout::build()
+                    .with("u_Texture", UniformVariableType::RenderTexture)
+                    .with("u_Bloom", UniformVariableType::RenderTexture)
+                    .with("u_Intensity", UniformVariableType::F32)
+                    .finish();
+
+                ("", BLOOM_COMPOSITE_FS, uniforms)
+            }
+        };
+
+        let mut params = ShaderParams::default();
+        params.attributes = AttributeLayout::build()
+            .with(Attribute::Position, 2)
+            .finish();
+        params.uniforms = uniforms;
+        params.state = state;
+
+        let header = "#version 100\nprecision mediump float;\n";
+        let vs = format!("{}{}", header, FULLSCREEN_VS);
+        let fs = format!("{}{}{}", header, defines, fs);
+        let shader = crate::video::create_shader(params, vs, fs)?;
+
+        self.shaders.insert(key, shader);
+        Ok(shader)
+    }
+}
+
+/// Creates a non-indexed triangle that covers the whole viewport, with a 2-components
+/// `Position` attribute.
+pub(crate) fn create_fullscreen_mesh() -> Result<MeshHandle> {
+    let verts: [f32; 6] = [-1.0, -1.0, 3.0, -1.0, -1.0, 3.0];
+
+    let mut params = MeshParams::default();
+    params.num_verts = 3;
+    params.layout = VertexLayout::build()
+        .with(Attribute::Position, VertexFormat::Float, 2, false)
+        .finish();
+
+    let mut vptr = Vec::with_capacity(std::mem::size_of_val(&verts));
+    for v in &verts {
+        vptr.extend_from_slice(&v.to_ne_bytes());
+    }
+
+    let data = MeshData {
+        vptr: vptr.into_boxed_slice(),
+        iptr: Box::new([]),
+    };
+
+    crate::video::create_mesh(params, data)
+}
+
+#[inline]
+fn texel_size(dimensions: Vector2<u32>) -> [f32; 2] {
+    [1.0 / dimensions.x as f32, 1.0 / dimensions.y as f32]
+}
+
+impl Drop for PostProcess {
+    fn drop(&mut self) {
+        if crate::video::valid() {
+            for (_, v) in self.shaders.drain() {
+                crate::video::delete_shader(v);
+            }
+
+            crate::video::delete_mesh(self.mesh);
+        }
+    }
+}
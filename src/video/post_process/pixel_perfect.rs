@@ -0,0 +1,259 @@
+use crate::errors::*;
+use crate::math::prelude::{Color, Vector2};
+
+use super::super::assets::prelude::*;
+use super::super::command::{CommandBuffer, Draw};
+use super::super::system::dimensions_pixels;
+use super::{create_fullscreen_mesh, FULLSCREEN_VS};
+
+const BLIT_FS: &str = include_str!("shaders/blit.fs");
+
+/// Renders the scene into a fixed low-resolution render texture, and upscales it into the
+/// destination surface with the largest integer scale and nearest filtering, which keeps
+/// the pixel-art crisp and square.
+///
+/// The pixel centers are at the half-integer coordinates of render texture, so a sprite
+/// maps into pixels 1:1 only if its edges land on the integer coordinates exactly. It's
+/// the case when the sprites are placed on the pixel grid of world, and the camera is
+/// positioned with `PixelPerfect::snap_camera`, which takes care of the half-pixel offset
+/// of odd resolutions. Otherwise every edge lands on the centers of pixels, and the
+/// sprites are blurred or seamed depending on the rasterization and filtering.
+///
+/// ```rust,ignore
+/// let mut pp = PixelPerfect::new(Vector2::new(320, 180), Color::black())?;
+///
+/// // Draws the sprites into `pp.surface()` with an orthographic camera of
+/// // `pp.ortho_size(16.0)`, whose position is snapped by `pp.snap_camera`.
+/// pp.draw(surface)?;
+/// ```
+pub struct PixelPerfect {
+    resolution: Vector2<u32>,
+    letterbox: bool,
+    color: RenderTextureHandle,
+    depth: RenderTextureHandle,
+    surface: SurfaceHandle,
+    mesh: MeshHandle,
+    shader: ShaderHandle,
+    cmds: CommandBuffer,
+}
+
+impl PixelPerfect {
+    /// Creates a new `PixelPerfect` with a fixed `resolution` in pixels. The render texture
+    /// is cleared with `clear` at the beginning of every frame.
+    pub fn new<T>(resolution: Vector2<u32>, clear: T) -> Result<Self>
+    where
+        T: Into<Option<Color<f32>>>,
+    {
+        if resolution.x == 0 || resolution.y == 0 {
+            bail!("[PixelPerfect] the resolution should be positive.");
+        }
+
+        let mut params = ShaderParams::default();
+        params.attributes = AttributeLayout::build()
+            .with(Attribute::Position, 2)
+            .finish();
+        params.uniforms = UniformVariableLayout::build()
+            .with("u_Texture", UniformVariableType::RenderTexture)
+            .finish();
+
+        let header = "#version 100\nprecision mediump float;\n";
+        let vs = format!("{}{}", header, FULLSCREEN_VS);
+        let fs = format!("{}{}", header, BLIT_FS);
+        let shader = crate::video::create_shader(params, vs, fs)?;
+
+        let mut params = RenderTextureParams::default();
+        params.format = RenderTextureFormat::RGBA8;
+        params.filter = TextureFilter::Nearest;
+        params.dimensions = resolution;
+        let color = crate::video::create_render_texture(params)?;
+
+        params.format = RenderTextureFormat::Depth16;
+        params.sampler = false;
+        let depth = crate::video::create_render_texture(params)?;
+
+        let mut params = SurfaceParams::default();
+        params.set_attachments(&[color], depth)?;
+        params.set_clear(clear, 1.0, None);
+        let surface = crate::video::create_surface(params)?;
+
+        Ok(PixelPerfect {
+            resolution,
+            letterbox: true,
+            color,
+            depth,
+            surface,
+            mesh: create_fullscreen_mesh()?,
+            shader,
+            cmds: CommandBuffer::new(),
+        })
+    }
+
+    /// Gets the fixed resolution in pixels.
+    #[inline]
+    pub fn resolution(&self) -> Vector2<u32> {
+        self.resolution
+    }
+
+    /// Gets the surface that the scene should be rendered into.
+    #[inline]
+    pub fn surface(&self) -> SurfaceHandle {
+        self.surface
+    }
+
+    /// Gets the low-resolution render texture.
+    #[inline]
+    pub fn texture(&self) -> RenderTextureHandle {
+        self.color
+    }
+
+    /// Sets whether the upscaled image should fit into the destination with bars, which are
+    /// filled by the clear color of destination surface. Otherwise it covers the whole
+    /// destination, and the exceeding pixels are cropped. It's enabled by default.
+    #[inline]
+    pub fn set_letterbox(&mut self, letterbox: bool) {
+        self.letterbox = letterbox;
+    }
+
+    /// Gets the integer scale of upscaling into the destination with `dimensions`.
+    #[inline]
+    pub fn scale(&self, dimensions: Vector2<u32>) -> u32 {
+        integer_scale(self.resolution, dimensions, self.letterbox)
+    }
+
+    /// Gets the viewport of upscaled image, which is centered in the destination with
+    /// `dimensions`.
+    pub fn viewport(&self, dimensions: Vector2<u32>) -> SurfaceViewport {
+        let size = self.resolution * self.scale(dimensions);
+        let position = Vector2::new(
+            (dimensions.x as i32 - size.x as i32) / 2,
+            (dimensions.y as i32 - size.y as i32) / 2,
+        );
+
+        SurfaceViewport { position, size }
+    }
+
+    /// Gets the width and height of orthographic projection, which maps one world unit into
+    /// `pixels_per_unit` pixels of the render texture.
+    #[inline]
+    pub fn ortho_size(&self, pixels_per_unit: f32) -> Vector2<f32> {
+        Vector2::new(
+            self.resolution.x as f32 / pixels_per_unit,
+            self.resolution.y as f32 / pixels_per_unit,
+        )
+    }
+
+    /// Snaps the position of camera into the pixel grid, so the sprites always land on the
+    /// whole pixels and never shimmer while moving.
+    #[inline]
+    pub fn snap(position: Vector2<f32>, pixels_per_unit: f32) -> Vector2<f32> {
+        Vector2::new(
+            (position.x * pixels_per_unit).round() / pixels_per_unit,
+            (position.y * pixels_per_unit).round() / pixels_per_unit,
+        )
+    }
+
+    /// Snaps the position of camera into the pixel grid like `PixelPerfect::snap`, and
+    /// shifts it by a half pixel on the axes that have odd resolution. The orthographic
+    /// camera is centered in the render texture, so its edges are a half pixel away from
+    /// the pixel grid of world in that case.
+    #[inline]
+    pub fn snap_camera(&self, position: Vector2<f32>, pixels_per_unit: f32) -> Vector2<f32> {
+        snap_camera(self.resolution, position, pixels_per_unit)
+    }
+
+    /// Upscales the render texture into `dst`, whose dimensions are the window's if it
+    /// does not have any attachment.
+    pub fn draw(&mut self, dst: SurfaceHandle) -> Result<()> {
+        let dimensions = match crate::video::surface(dst) {
+            Some(params) => match params.colors[0] {
+                Some(texture) => match crate::video::render_texture(texture) {
+                    Some(v) => v.dimensions,
+                    None => bail!("[PixelPerfect] {:?} is invalid.", texture),
+                },
+                None => dimensions_pixels(),
+            },
+            None => bail!("[PixelPerfect] {:?} is invalid.", dst),
+        };
+
+        let mut dc = Draw::new(self.shader, self.mesh);
+        dc.set_uniform_variable("u_Texture", self.color);
+
+        self.cmds.update_viewport(self.viewport(dimensions));
+        self.cmds.draw(dc);
+        self.cmds.submit(dst)?;
+        Ok(())
+    }
+}
+
+impl Drop for PixelPerfect {
+    fn drop(&mut self) {
+        if crate::video::valid() {
+            crate::video::delete_surface(self.surface);
+            crate::video::delete_render_texture(self.color);
+            crate::video::delete_render_texture(self.depth);
+            crate::video::delete_shader(self.shader);
+            crate::video::delete_mesh(self.mesh);
+        }
+    }
+}
+
+/// Gets the largest integer scale that fits into `dimensions` if `letterbox` is enabled,
+/// otherwise the smallest one that covers it.
+fn integer_scale(resolution: Vector2<u32>, dimensions: Vector2<u32>, letterbox: bool) -> u32 {
+    let scale = if letterbox {
+        (dimensions.x / resolution.x).min(dimensions.y / resolution.y)
+    } else {
+        let x = (dimensions.x + resolution.x - 1) / resolution.x;
+        let y = (dimensions.y + resolution.y - 1) / resolution.y;
+        x.max(y)
+    };
+
+    scale.max(1)
+}
+
+/// Snaps the position of camera with the render texture of `resolution` into the pixel
+/// grid of world, which is shifted by a half pixel on the axes that have odd resolution.
+fn snap_camera(resolution: Vector2<u32>, position: Vector2<f32>, ppu: f32) -> Vector2<f32> {
+    let offset = half_pixel_offset(resolution);
+    PixelPerfect::snap(position, ppu) + offset / ppu
+}
+
+/// Gets the offset in pixels that moves the center of render texture onto the pixel grid.
+fn half_pixel_offset(resolution: Vector2<u32>) -> Vector2<f32> {
+    Vector2::new(
+        (resolution.x % 2) as f32 * 0.5,
+        (resolution.y % 2) as f32 * 0.5,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pixel_center() {
+        let ppu = 16.0;
+        for &r in &[Vector2::new(320, 180), Vector2::new(321, 181)] {
+            let camera = snap_camera(r, Vector2::new(3.3, -1.7), ppu);
+
+            // Maps the edges of sprite at the pixel grid into the render texture, which
+            // should land on the integer coordinates exactly.
+            for &edge in &[-2.0, 0.0, 1.5, 4.0625] {
+                let x = (edge - camera.x) * ppu + r.x as f32 * 0.5;
+                let y = (edge - camera.y) * ppu + r.y as f32 * 0.5;
+                assert_eq!(x.fract(), 0.0);
+                assert_eq!(y.fract(), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn scale() {
+        let r = Vector2::new(320, 180);
+        assert_eq!(integer_scale(r, Vector2::new(1920, 1080), true), 6);
+        assert_eq!(integer_scale(r, Vector2::new(1280, 1024), true), 4);
+        assert_eq!(integer_scale(r, Vector2::new(100, 100), true), 1);
+        assert_eq!(integer_scale(r, Vector2::new(1920, 1080), false), 6);
+        assert_eq!(integer_scale(r, Vector2::new(1280, 1024), false), 6);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::errors::*;
+use crate::math::prelude::Vector2;
+
+use super::super::assets::prelude::*;
+
+struct RenderTextureEntry {
+    format: RenderTextureFormat,
+    dimensions: Vector2<u32>,
+    texture: RenderTextureHandle,
+    surface: SurfaceHandle,
+    used: bool,
+}
+
+/// A pool of transient color render textures, and the surfaces that render into them.
+///
+/// Since all the commands are executed in the order they were submitted, a render texture
+/// could be released as soon as the passes that sample it have been submitted, and reused by
+/// the following passes of the same frame.
+#[derive(Default)]
+pub struct RenderTexturePool {
+    entries: Vec<RenderTextureEntry>,
+}
+
+impl RenderTexturePool {
+    /// Creates a new and empty `RenderTexturePool`.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Acquires a free render texture with `format` and `dimensions`, a new one will be
+    /// created if there is no such one in the pool. The returned surface takes the render
+    /// texture as its only color attachment, and clears nothing when binded.
+    pub fn acquire(
+        &mut self,
+        format: RenderTextureFormat,
+        dimensions: Vector2<u32>,
+    ) -> Result<(RenderTextureHandle, SurfaceHandle)> {
+        if !format.is_color() {
+            bail!("[RenderTexturePool] {:?} is not a color format.", format);
+        }
+
+        for v in &mut self.entries {
+            if !v.used && v.format == format && v.dimensions == dimensions {
+                v.used = true;
+                return Ok((v.texture, v.surface));
+            }
+        }
+
+        let mut params = RenderTextureParams::default();
+        params.format = format;
+        params.dimensions = dimensions;
+        let texture = crate::video::create_render_texture(params)?;
+
+        let mut params = SurfaceParams::default();
+        params.set_attachments(&[texture], None)?;
+        params.set_clear(None, None, None);
+        let surface = match crate::video::create_surface(params) {
+            Ok(surface) => surface,
+            Err(err) => {
+                crate::video::delete_render_texture(texture);
+                return Err(err.into());
+            }
+        };
+
+        self.entries.push(RenderTextureEntry {
+            format,
+            dimensions,
+            texture,
+            surface,
+            used: true,
+        });
+
+        Ok((texture, surface))
+    }
+
+    /// Releases the render texture back into the pool.
+    pub fn release(&mut self, texture: RenderTextureHandle) {
+        for v in &mut self.entries {
+            if v.texture == texture {
+                v.used = false;
+            }
+        }
+    }
+
+    /// Deletes all the render textures that are not in use.
+    pub fn purge(&mut self) {
+        for v in &self.entries {
+            if !v.used {
+                crate::video::delete_surface(v.surface);
+                crate::video::delete_render_texture(v.texture);
+            }
+        }
+
+        self.entries.retain(|v| v.used);
+    }
+}
+
+impl Drop for RenderTexturePool {
+    fn drop(&mut self) {
+        if crate::video::valid() {
+            for v in self.entries.drain(..) {
+                crate::video::delete_surface(v.surface);
+                crate::video::delete_render_texture(v.texture);
+            }
+        }
+    }
+}
@@ -0,0 +1,26 @@
+varying vec2 v_Texcoord;
+
+uniform sampler2D u_Texture;
+uniform vec2 u_TexelSize;
+
+#ifdef BLOOM_PREFILTER
+uniform float u_Threshold;
+#endif
+
+void main() {
+    // Averages 4x4 texels of source with 4 bilinear fetches.
+    vec4 d = u_TexelSize.xyxy * vec4(-1.0, -1.0, 1.0, 1.0);
+    vec3 color = texture2D(u_Texture, v_Texcoord + d.xy).rgb;
+    color += texture2D(u_Texture, v_Texcoord + d.zy).rgb;
+    color += texture2D(u_Texture, v_Texcoord + d.xw).rgb;
+    color += texture2D(u_Texture, v_Texcoord + d.zw).rgb;
+    color *= 0.25;
+
+#ifdef BLOOM_PREFILTER
+    // Keeps the part of color that is brighter than threshold.
+    float brightness = max(color.r, max(color.g, color.b));
+    color *= max(brightness - u_Threshold, 0.0) / max(brightness, 0.0001);
+#endif
+
+    gl_FragColor = vec4(color, 1.0);
+}
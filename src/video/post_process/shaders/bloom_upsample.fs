@@ -0,0 +1,19 @@
+varying vec2 v_Texcoord;
+
+uniform sampler2D u_Texture;
+uniform vec2 u_TexelSize;
+
+void main() {
+    // The 3x3 tent filter.
+    vec4 d = u_TexelSize.xyxy * vec4(1.0, 1.0, -1.0, 0.0);
+    vec3 color = texture2D(u_Texture, v_Texcoord - d.xy).rgb;
+    color += texture2D(u_Texture, v_Texcoord - d.wy).rgb * 2.0;
+    color += texture2D(u_Texture, v_Texcoord - d.zy).rgb;
+    color += texture2D(u_Texture, v_Texcoord + d.zw).rgb * 2.0;
+    color += texture2D(u_Texture, v_Texcoord).rgb * 4.0;
+    color += texture2D(u_Texture, v_Texcoord + d.xw).rgb * 2.0;
+    color += texture2D(u_Texture, v_Texcoord + d.zy).rgb;
+    color += texture2D(u_Texture, v_Texcoord + d.wy).rgb * 2.0;
+    color += texture2D(u_Texture, v_Texcoord + d.xy).rgb;
+    gl_FragColor = vec4(color * 0.0625, 1.0);
+}
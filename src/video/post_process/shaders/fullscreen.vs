@@ -0,0 +1,7 @@
+attribute vec2 Position;
+varying vec2 v_Texcoord;
+
+void main() {
+    v_Texcoord = Position * 0.5 + 0.5;
+    gl_Position = vec4(Position, 0.0, 1.0);
+}
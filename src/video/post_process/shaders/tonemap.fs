@@ -0,0 +1,25 @@
+varying vec2 v_Texcoord;
+
+uniform sampler2D u_Texture;
+uniform float u_Exposure;
+
+#ifdef TONEMAP_ACES
+// The curve fitting of ACES filmic tonemapping by Krzysztof Narkowicz.
+vec3 tonemap(vec3 x) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+#else
+vec3 tonemap(vec3 x) {
+    return x / (x + vec3(1.0));
+}
+#endif
+
+void main() {
+    vec3 hdr = texture2D(u_Texture, v_Texcoord).rgb * u_Exposure;
+    gl_FragColor = vec4(tonemap(hdr), 1.0);
+}
@@ -0,0 +1,117 @@
+//! A dedicated thread that dispatches the frames into the OpenGL context.
+
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+use crate::errors::*;
+use crate::math::prelude::Vector2;
+
+pub(crate) struct RenderThread {
+    kick: Sender<Option<Vector2<u32>>>,
+    done: Receiver<Result<()>>,
+    pending: bool,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl RenderThread {
+    /// Spawns the render thread, which makes the OpenGL context current on itself before
+    /// running `dispatch` every time a frame is kicked off.
+    pub fn spawn<F>(mut dispatch: F) -> Result<Self>
+    where
+        F: FnMut(Vector2<u32>) -> Result<()> + Send + 'static,
+    {
+        let (kick, kick_rx) = mpsc::channel::<Option<Vector2<u32>>>();
+        let (done_tx, done) = mpsc::channel();
+
+        let handle = thread::Builder::new()
+            .name("Render".to_string())
+            .spawn(move || {
+                let result = crate::window::make_current();
+                let failed = result.is_err();
+                if done_tx.send(result).is_err() || failed {
+                    return;
+                }
+
+                while let Ok(Some(dimensions)) = kick_rx.recv() {
+                    let result = dispatch(dimensions).and_then(|_| crate::window::swap_buffers());
+                    if done_tx.send(result).is_err() {
+                        return;
+                    }
+                }
+            })?;
+
+        let mut rt = RenderThread {
+            kick,
+            done,
+            pending: true,
+            handle: Some(handle),
+        };
+
+        rt.wait()?;
+        Ok(rt)
+    }
+
+    /// Blocks until the frame in flight has been dispatched, the back buffer of frames
+    /// could be swapped safely after that.
+    pub fn wait(&mut self) -> Result<()> {
+        if self.pending {
+            self.pending = false;
+            match self.done.recv() {
+                Ok(result) => result?,
+                Err(_) => bail!("[RenderThread] The render thread has been terminated."),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if there is no frame in flight, without blocking.
+    pub fn is_idle(&mut self) -> Result<bool> {
+        if self.pending {
+            match self.done.try_recv() {
+                Ok(result) => {
+                    self.pending = false;
+                    result?;
+                }
+                Err(TryRecvError::Empty) => return Ok(false),
+                Err(TryRecvError::Disconnected) => {
+                    bail!("[RenderThread] The render thread has been terminated.")
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Kicks off the dispatch of back buffer of frames.
+    pub fn kick(&mut self, dimensions: Vector2<u32>) -> Result<()> {
+        self.wait()?;
+
+        if self.kick.send(Some(dimensions)).is_err() {
+            bail!("[RenderThread] The render thread has been terminated.");
+        }
+
+        self.pending = true;
+        Ok(())
+    }
+
+    /// Waits for the frame in flight, and terminates the render thread.
+    pub fn stop(mut self) -> Result<()> {
+        let result = self.wait();
+        self.terminate();
+        result
+    }
+
+    fn terminate(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = self.kick.send(None);
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for RenderThread {
+    fn drop(&mut self) {
+        self.terminate();
+    }
+}
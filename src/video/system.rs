@@ -1,18 +1,26 @@
-use std::sync::{Arc, RwLock};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 use uuid::Uuid;
 
 use crate::application::prelude::{LifecycleListener, LifecycleListenerHandle};
 use crate::math::prelude::{Aabb2, Vector2};
 use crate::prelude::CrResult;
+use crate::res::request::{Request, Response};
 use crate::res::utils::prelude::{ResourcePool, ResourceState};
-use crate::utils::prelude::{DoubleBuf, ObjectPool};
+use crate::sched::prelude::LockLatch;
+use crate::utils::hash::FastHashMap;
+use crate::utils::prelude::{ObjectPool, TripleBuf};
 
 use super::assets::mesh_loader::MeshLoader;
 use super::assets::prelude::*;
-use super::assets::texture_loader::TextureLoader;
+use super::assets::texture_loader::{TextureLoader, TextureSource};
 use super::backends::frame::*;
-use super::backends::{self, Visitor};
+use super::backends::upload::UploadQueue;
+use super::backends::{self, DeviceLimits, Visitor};
 use super::errors::*;
+use super::render_thread::RenderThread;
 
 /// The centralized management of video sub-system.
 pub struct VideoSystem {
@@ -21,17 +29,50 @@ pub struct VideoSystem {
 }
 
 struct VideoState {
-    frames: Arc<DoubleBuf<Frame>>,
+    frames: Arc<TripleBuf<Frame>>,
     surfaces: RwLock<ObjectPool<SurfaceHandle, SurfaceParams>>,
     shaders: RwLock<ObjectPool<ShaderHandle, ShaderParams>>,
     meshes: RwLock<ResourcePool<MeshHandle, MeshLoader>>,
     textures: RwLock<ResourcePool<TextureHandle, TextureLoader>>,
     render_textures: RwLock<ObjectPool<RenderTextureHandle, RenderTextureParams>>,
+    instance_buffers: RwLock<ObjectPool<InstanceBufferHandle, InstanceBufferParams>>,
+    storage_buffers: RwLock<ObjectPool<ShaderStorageBufferHandle, ShaderStorageBufferParams>>,
+    uniform_buffers: RwLock<ObjectPool<UniformBufferHandle, UniformBufferParams>>,
+    readbacks: Mutex<FastHashMap<VideoHandle, Vec<u8>>>,
+    pending_reads: Mutex<FastHashMap<RenderTextureHandle, VecDeque<PendingRead>>>,
+    frame_index: AtomicUsize,
+    gpu_timers: Mutex<FastHashMap<String, Duration>>,
+    names: RwLock<FastHashMap<VideoHandle, String>>,
+    deletions: Mutex<Vec<VideoHandle>>,
+    visitor: Mutex<Box<dyn Visitor>>,
+    limits: DeviceLimits,
+    uploads: Mutex<UploadQueue>,
+    last_dimensions: Mutex<Vector2<u32>>,
+    screen_dimensions: Mutex<Vector2<u32>>,
+    captures: Mutex<Option<Vec<Image>>>,
+    antialiasing: Mutex<AaMode>,
+    output: Mutex<OutputAdjustment>,
+    max_frame_latency: Mutex<u32>,
+    render_thread: Mutex<Option<RenderThread>>,
+    triple_buffering: AtomicBool,
+    fallback_texture: Mutex<Option<TextureHandle>>,
+    stats: Mutex<FrameStats>,
+    shrink_policy: Mutex<FrameShrinkPolicy>,
 }
 
+/// The latch of a read of render texture, along with the index of frame it was requested in.
+type PendingRead = (usize, Arc<LockLatch<Response>>);
+
+// The visitor is only touched by the render thread while the threaded rendering is enabled,
+// and the `TripleBuf` swap is synchronized with the dispatch of back buffer.
+struct SharedState(Arc<VideoState>);
+
+unsafe impl Send for SharedState {}
+
 impl VideoState {
-    fn new() -> Self {
-        let frames = Arc::new(DoubleBuf::new(
+    fn new(visitor: Box<dyn Visitor>, dimensions: Vector2<u32>) -> Self {
+        let frames = Arc::new(TripleBuf::new(
+            Frame::with_capacity(64 * 1024),
             Frame::with_capacity(64 * 1024),
             Frame::with_capacity(64 * 1024),
         ));
@@ -42,90 +83,507 @@ impl VideoState {
             meshes: RwLock::new(ResourcePool::new(MeshLoader::new(frames.clone()))),
             textures: RwLock::new(ResourcePool::new(TextureLoader::new(frames.clone()))),
             render_textures: RwLock::new(ObjectPool::new()),
+            instance_buffers: RwLock::new(ObjectPool::new()),
+            storage_buffers: RwLock::new(ObjectPool::new()),
+            uniform_buffers: RwLock::new(ObjectPool::new()),
+            readbacks: Mutex::new(FastHashMap::default()),
+            pending_reads: Mutex::new(FastHashMap::default()),
+            frame_index: AtomicUsize::new(0),
+            gpu_timers: Mutex::new(FastHashMap::default()),
+            names: RwLock::new(FastHashMap::default()),
+            deletions: Mutex::new(Vec::new()),
+            limits: visitor.limits(),
+            visitor: Mutex::new(visitor),
+            uploads: Mutex::new(UploadQueue::new()),
+            last_dimensions: Mutex::new(dimensions),
+            screen_dimensions: Mutex::new(dimensions),
+            captures: Mutex::new(None),
+            antialiasing: Mutex::new(AaMode::None),
+            output: Mutex::new(OutputAdjustment::default()),
+            max_frame_latency: Mutex::new(0),
+            render_thread: Mutex::new(None),
+            triple_buffering: AtomicBool::new(false),
+            fallback_texture: Mutex::new(None),
+            stats: Mutex::new(FrameStats::default()),
+            shrink_policy: Mutex::new(FrameShrinkPolicy::default()),
             frames,
         }
     }
+
+    fn advance(&self) -> CrResult<()> {
+        // Swap internal commands frame.
+        self.frames.swap();
+        self.advance_resources()
+    }
+
+    /// Publishes the frame as the freshest one without waiting for the render thread, the
+    /// stale frame which has not been picked up yet is dropped except its resource commands.
+    fn publish(&self) -> CrResult<()> {
+        let mut merged = false;
+        self.frames.publish_with(|stale, fresh| {
+            stale.merge_stale(fresh);
+            merged = true;
+        });
+
+        // The reads of render textures in the stale frame have been dropped.
+        if merged {
+            let index = self.frame_index.load(Ordering::Relaxed).wrapping_sub(1);
+            for reads in self.pending_reads.lock().unwrap().values_mut() {
+                reads.retain(|&(v, ref latch)| {
+                    if v == index {
+                        latch.set(Err(format_err!("The frame has been dropped.")));
+                    }
+
+                    v != index
+                });
+            }
+        }
+
+        self.advance_resources()
+    }
+
+    fn advance_resources(&self) -> CrResult<()> {
+        self.frame_index.fetch_add(1, Ordering::Relaxed);
+
+        let policy = *self.shrink_policy.lock().unwrap();
+        self.frames.write().clear(policy);
+        self.flush_deletions();
+        self.meshes.write().unwrap().advance()?;
+        self.textures.write().unwrap().advance()?;
+        self.resize_render_textures(dimensions_pixels());
+        Ok(())
+    }
+
+    /// Deletes the resources that were requested to be deleted during the last frame. The
+    /// handles are kept alive until then, so the commands that still refer to them in the
+    /// last frame (e.g. draws submitted after the deletion) are always valid, and their
+    /// slots could not be reused by new resources before the GPU objects are deleted.
+    fn flush_deletions(&self) {
+        let deletions = std::mem::replace(&mut *self.deletions.lock().unwrap(), Vec::new());
+        for handle in deletions {
+            let cmd = match handle {
+                VideoHandle::Surface(handle) => self
+                    .surfaces
+                    .write()
+                    .unwrap()
+                    .free(handle)
+                    .map(|_| Command::DeleteSurface(handle)),
+                VideoHandle::Shader(handle) => self
+                    .shaders
+                    .write()
+                    .unwrap()
+                    .free(handle)
+                    .map(|_| Command::DeleteShader(handle)),
+                VideoHandle::RenderTexture(handle) => {
+                    self.render_textures.write().unwrap().free(handle).map(|_| {
+                        let reads = self.pending_reads.lock().unwrap().remove(&handle);
+                        for (_, latch) in reads.into_iter().flatten() {
+                            latch.set(Err(format_err!("{:?} has been deleted.", handle)));
+                        }
+
+                        Command::DeleteRenderTexture(handle)
+                    })
+                }
+                VideoHandle::InstanceBuffer(handle) => self
+                    .instance_buffers
+                    .write()
+                    .unwrap()
+                    .free(handle)
+                    .map(|_| Command::DeleteInstanceBuffer(handle)),
+                VideoHandle::ShaderStorageBuffer(handle) => {
+                    self.storage_buffers.write().unwrap().free(handle).map(|_| {
+                        self.readbacks.lock().unwrap().remove(&handle.into());
+                        Command::DeleteShaderStorageBuffer(handle)
+                    })
+                }
+                VideoHandle::UniformBuffer(handle) => self
+                    .uniform_buffers
+                    .write()
+                    .unwrap()
+                    .free(handle)
+                    .map(|_| Command::DeleteUniformBuffer(handle)),
+                // The meshes and textures are reference counted, their loaders push the
+                // deletion commands once they are disposed.
+                VideoHandle::Mesh(handle) => {
+                    let mut meshes = self.meshes.write().unwrap();
+                    meshes.delete(handle);
+                    if meshes.contains(handle) {
+                        continue;
+                    }
+
+                    None
+                }
+                VideoHandle::Texture(handle) => {
+                    let mut textures = self.textures.write().unwrap();
+                    textures.delete(handle);
+                    if textures.contains(handle) {
+                        continue;
+                    }
+
+                    None
+                }
+            };
+
+            if let Some(cmd) = cmd {
+                self.frames.write().cmds.push(cmd);
+            }
+
+            self.names.write().unwrap().remove(&handle);
+        }
+    }
+
+    /// Reallocates the screen-relative render textures if the window has been resized. It's
+    /// called at the begining of frame, so the draws of this frame use the new dimensions.
+    fn resize_render_textures(&self, dimensions: Vector2<u32>) {
+        let mut screen_dimensions = self.screen_dimensions.lock().unwrap();
+        if dimensions == *screen_dimensions {
+            return;
+        }
+
+        *screen_dimensions = dimensions;
+
+        let mut frame = self.frames.write();
+        for (handle, params) in self.render_textures.write().unwrap().iter_mut() {
+            let v = params.dimensions_of(dimensions);
+            if v != params.dimensions {
+                params.dimensions = v;
+                frame.cmds.push(Command::ResizeRenderTexture(handle, v));
+            }
+        }
+    }
+
+    fn dispatch(&self, dimensions: Vector2<u32>) -> CrResult<()> {
+        self.resize(dimensions);
+        self.dispatch_frame(dimensions)
+    }
+
+    fn dispatch_frame(&self, dimensions: Vector2<u32>) -> CrResult<()> {
+        profile_scope!("Dispatch");
+        let mut visitor = self.visitor.lock().unwrap();
+        let mut uploads = self.uploads.lock().unwrap();
+        let mut frame = self.frames.write_back_buf();
+        frame.dispatch(visitor.as_mut(), &mut uploads, dimensions)?;
+        self.update_stats(visitor.as_ref(), &uploads, &frame);
+        self.collect_readbacks(visitor.as_mut());
+        self.collect_gpu_timers(visitor.as_mut());
+
+        if let Some(ref mut captures) = *self.captures.lock().unwrap() {
+            let image = unsafe { visitor.capture(dimensions)? };
+            captures.push(image);
+        }
+
+        Ok(())
+    }
+
+    fn dispatch_budget(&self, dimensions: Vector2<u32>, budget: DispatchBudget) -> CrResult<bool> {
+        self.resize(dimensions);
+
+        profile_scope!("Dispatch");
+        let mut visitor = self.visitor.lock().unwrap();
+        let mut uploads = self.uploads.lock().unwrap();
+        let mut frame = self.frames.write_back_buf();
+        let finished = frame.dispatch_budget(visitor.as_mut(), &mut uploads, dimensions, budget)?;
+
+        if finished {
+            self.update_stats(visitor.as_ref(), &uploads, &frame);
+            self.collect_readbacks(visitor.as_mut());
+            self.collect_gpu_timers(visitor.as_mut());
+        }
+
+        Ok(finished)
+    }
+
+    fn update_stats(&self, visitor: &dyn Visitor, uploads: &UploadQueue, frame: &Frame) {
+        let mut stats = frame.stats(visitor.stats());
+        stats.deferred_uploads = uploads.len() as u32;
+        *self.stats.lock().unwrap() = stats;
+    }
+
+    fn collect_readbacks(&self, visitor: &mut dyn Visitor) {
+        let mut readbacks = self.readbacks.lock().unwrap();
+        let mut pending_reads = self.pending_reads.lock().unwrap();
+        for (handle, bytes) in visitor.take_readbacks() {
+            // The resource might have been deleted before its contents arrive.
+            match handle {
+                VideoHandle::RenderTexture(v) => {
+                    // The reads of render texture are completed in the order of requests.
+                    let read = pending_reads.get_mut(&v).and_then(|v| v.pop_front());
+                    if let Some((_, latch)) = read {
+                        latch.set(Ok(bytes.into_boxed_slice()));
+                    }
+                }
+                VideoHandle::ShaderStorageBuffer(v) => {
+                    if self.storage_buffers.read().unwrap().contains(v) {
+                        readbacks.insert(handle, bytes);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn collect_gpu_timers(&self, visitor: &mut dyn Visitor) {
+        let mut gpu_timers = self.gpu_timers.lock().unwrap();
+        for (name, elapsed) in visitor.take_gpu_timers() {
+            #[cfg(feature = "profiler")]
+            crate::utils::profiler::gpu_timer(&name, elapsed);
+
+            gpu_timers.insert(name, elapsed);
+        }
+    }
+
+    fn resize(&self, dimensions: Vector2<u32>) {
+        let mut last_dimensions = self.last_dimensions.lock().unwrap();
+
+        // Resize the window, which would recreate the underlying framebuffer.
+        if dimensions != *last_dimensions {
+            info!(
+                "[VideoSystem] Resizes surface from {:?} to {:?}.",
+                *last_dimensions, dimensions
+            );
+
+            *last_dimensions = dimensions;
+            crate::window::resize(dimensions);
+        }
+    }
 }
 
 struct Lifecycle {
-    last_dimensions: Vector2<u32>,
-    visitor: Box<dyn Visitor>,
     state: Arc<VideoState>,
 }
 
 impl LifecycleListener for Lifecycle {
     fn on_pre_update(&mut self) -> CrResult<()> {
-        // Swap internal commands frame.
-        self.state.frames.swap();
-        self.state.frames.write().clear();
-        self.state.meshes.write().unwrap().advance()?;
-        self.state.textures.write().unwrap().advance()?;
-        Ok(())
+        // Waits for the render thread before swapping the frames, since the back buffer
+        // might be still in use.
+        if let Some(ref mut rt) = *self.state.render_thread.lock().unwrap() {
+            if self.state.triple_buffering.load(Ordering::Relaxed) {
+                return self.state.publish();
+            }
+
+            rt.wait()?;
+        }
+
+        self.state.advance()
     }
 
     fn on_post_update(&mut self) -> CrResult<()> {
         let dimensions = dimensions_pixels();
+        if let Some(ref mut rt) = *self.state.render_thread.lock().unwrap() {
+            // With triple buffering, the render thread picks up the freshest frame once
+            // it has finished the previous one.
+            if self.state.triple_buffering.load(Ordering::Relaxed) && !rt.is_idle()? {
+                return Ok(());
+            }
 
-        // Resize the window, which would recreate the underlying framebuffer.
-        if dimensions != self.last_dimensions {
-            self.last_dimensions = dimensions;
-            crate::window::resize(dimensions);
+            self.state.resize(dimensions);
+            return rt.kick(dimensions);
         }
 
-        self.state
-            .frames
-            .write_back_buf()
-            .dispatch(self.visitor.as_mut(), self.last_dimensions)?;
-
-        Ok(())
+        self.state.dispatch(dimensions)
     }
 }
 
 impl Drop for VideoSystem {
     fn drop(&mut self) {
         crate::application::detach(self.lis);
+
+        if let Some(rt) = self.state.render_thread.lock().unwrap().take() {
+            if let Err(err) = rt.stop() {
+                warn!("[VideoSystem] Failed to stop render thread. {}", err);
+            }
+        }
     }
 }
 
 impl VideoSystem {
     /// Create a new `VideoSystem`.
     pub fn new() -> CrResult<Self> {
-        let state = Arc::new(VideoState::new());
         let visitor = backends::new()?;
+        let state = Arc::new(VideoState::new(visitor, dimensions_pixels()));
 
-        Ok(VideoSystem {
+        let video = VideoSystem {
             state: state.clone(),
-            lis: crate::application::attach(Lifecycle {
-                state,
-                visitor,
-                last_dimensions: dimensions_pixels(),
-            }),
-        })
+            lis: crate::application::attach(Lifecycle { state }),
+        };
+
+        let fallback = video.create_checkerboard_texture()?;
+        video.set_fallback_texture(fallback);
+        Ok(video)
     }
 
     /// Create a headless `VideoSystem`.
     pub fn headless() -> Self {
-        let state = Arc::new(VideoState::new());
         let visitor = backends::new_headless();
+        let state = Arc::new(VideoState::new(visitor, Vector2::new(0, 0)));
 
-        VideoSystem {
+        let video = VideoSystem {
             state: state.clone(),
-            lis: crate::application::attach(Lifecycle {
-                state,
-                visitor,
-                last_dimensions: Vector2::new(0, 0),
-            }),
+            lis: crate::application::attach(Lifecycle { state }),
+        };
+
+        match video.create_checkerboard_texture() {
+            Ok(fallback) => video.set_fallback_texture(fallback),
+            Err(err) => warn!("[VideoSystem] Failed to create fallback texture. {}", err),
         }
+
+        video
     }
 
-    pub(crate) fn frames(&self) -> Arc<DoubleBuf<Frame>> {
+    pub(crate) fn frames(&self) -> Arc<TripleBuf<Frame>> {
         self.state.frames.clone()
     }
+
+    /// Swaps the double-buffered frame and dispatches the commands immediately on the
+    /// current thread, without waiting for the `on_post_update` of application lifecycle.
+    ///
+    /// This is useful when driving crayon with your own main loop. Notes that the OpenGL
+    /// context must be current on the calling thread.
+    pub fn flush(&self) -> CrResult<()> {
+        if self.is_threaded() {
+            bail!("[VideoSystem] Flushes frame while the threaded rendering is enabled.");
+        }
+
+        if !crate::window::is_current() {
+            bail!("[VideoSystem] Flushes frame while the context is not current.");
+        }
+
+        self.state.advance()?;
+        self.state.dispatch(dimensions_pixels())
+    }
+
+    /// Dispatches the commands incrementally until the `budget` is exhausted, and
+    /// continues where it left off at next call. A new frame will be swapped in only
+    /// after the previous one has been dispatched completely. Returns true if the
+    /// frame is finished.
+    ///
+    /// This trades latency for responsiveness with very heavy frames, and should be
+    /// used with your own main loop instead of the application lifecycle.
+    pub fn flush_budget(&self, budget: DispatchBudget) -> CrResult<bool> {
+        if self.is_threaded() {
+            bail!("[VideoSystem] Flushes frame while the threaded rendering is enabled.");
+        }
+
+        if !crate::window::is_current() {
+            bail!("[VideoSystem] Flushes frame while the context is not current.");
+        }
+
+        if !self.state.frames.read_back_buf().is_dispatching() {
+            self.state.advance()?;
+        }
+
+        self.state.dispatch_budget(dimensions_pixels(), budget)
+    }
+
+    /// Enables or disables the dispatching of frames on a dedicated render thread. The main
+    /// thread only produces the commands then, and the OpenGL context will be made current
+    /// on the render thread, which also swaps the buffers of window.
+    ///
+    /// The frame `N` is dispatched while the main thread is producing frame `N + 1`, and the
+    /// swap of double-buffered frames blocks until the dispatch of previous frame finished,
+    /// unless the triple buffering is enabled with `set_triple_buffering`.
+    ///
+    /// # Platform-specific
+    ///
+    /// This is not supported in headless mode and browsers. Some platforms refuse to make
+    /// the context current on the render thread while it's still current on the main thread.
+    pub fn set_threaded(&self, threaded: bool) -> CrResult<()> {
+        if cfg!(target_arch = "wasm32") || crate::application::headless() {
+            bail!("[VideoSystem] Threaded rendering is not supported on this platform.");
+        }
+
+        let mut render_thread = self.state.render_thread.lock().unwrap();
+        if render_thread.is_some() == threaded {
+            return Ok(());
+        }
+
+        if threaded {
+            let state = SharedState(self.state.clone());
+            let rt = RenderThread::spawn(move |dimensions| {
+                state.0.frames.consume();
+                state.0.dispatch_frame(dimensions)
+            })?;
+
+            *render_thread = Some(rt);
+            crate::window::set_auto_swap(false);
+            info!("[VideoSystem] Enables threaded rendering.");
+        } else {
+            let rt = render_thread.take().unwrap();
+            let result = rt.stop();
+
+            crate::window::set_auto_swap(true);
+            crate::window::make_current()?;
+            result?;
+
+            // Dispatches the published frame that has not been picked up yet.
+            if self.state.frames.consume() {
+                self.state.dispatch_frame(dimensions_pixels())?;
+            }
+
+            info!("[VideoSystem] Disables threaded rendering.");
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the frames are dispatched on a dedicated render thread.
+    #[inline]
+    pub fn is_threaded(&self) -> bool {
+        self.state.render_thread.lock().unwrap().is_some()
+    }
+
+    /// Enables or disables the triple buffering of frames (mailbox-style presentation),
+    /// which only takes effect with threaded rendering, and should be configured before
+    /// enabling it.
+    ///
+    /// The main thread never waits for the render thread then. Once the render thread has
+    /// finished a frame, it picks up the freshest complete one, and the stale frames are
+    /// dropped, except their resource commands (e.g. the creations and updates). This
+    /// reduces the input latency if the rendering is slower than the simulation.
+    pub fn set_triple_buffering(&self, enabled: bool) -> CrResult<()> {
+        if self.is_threaded() {
+            bail!("[VideoSystem] Configures triple buffering while threaded rendering is enabled.");
+        }
+
+        self.state
+            .triple_buffering
+            .store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns true if the frames are triple buffered.
+    #[inline]
+    pub fn is_triple_buffering(&self) -> bool {
+        self.state.triple_buffering.load(Ordering::Relaxed)
+    }
+
+    /// Runs the lifecycle of `n` frames immediately in headless mode, and captures the
+    /// framebuffer after each frame has been dispatched. The images could be compared
+    /// with golden ones for rendering regression tests.
+    ///
+    /// This should never be called inside the callbacks of `LifecycleListener`.
+    pub fn run_capture_frames(&self, n: usize) -> CrResult<Vec<Image>> {
+        if !crate::application::headless() {
+            bail!("[VideoSystem] Frames could only be captured in headless mode.");
+        }
+
+        *self.state.captures.lock().unwrap() = Some(Vec::with_capacity(n));
+
+        let result = (0..n).try_for_each(|_| crate::application::run_oneshot());
+        let captures = self.state.captures.lock().unwrap().take();
+
+        result?;
+        Ok(captures.unwrap_or_default())
+    }
 }
 
 impl VideoSystem {
     /// Creates an surface with `SurfaceParams`.
+    ///
+    /// All the attachments must share the same dimensions, otherwise an error that lists
+    /// the sizes of attachments is returned instead of an incomplete framebuffer.
     pub fn create_surface(&self, params: SurfaceParams) -> Result<SurfaceHandle> {
+        self.validate_attachments(&params)?;
         let handle = self.state.surfaces.write().unwrap().create(params);
 
         {
@@ -136,6 +594,34 @@ impl VideoSystem {
         Ok(handle)
     }
 
+    fn validate_attachments(&self, params: &SurfaceParams) -> Result<()> {
+        let render_textures = self.state.render_textures.read().unwrap();
+
+        let mut attachments = Vec::new();
+        for &v in params.colors.iter().chain(Some(&params.depth_stencil)) {
+            if let Some(handle) = v {
+                let rt = render_textures
+                    .get(handle)
+                    .ok_or_else(|| Error::HandleInvalid(format!("{:?}", handle)))?;
+                attachments.push((handle, rt.dimensions));
+            }
+        }
+
+        if attachments.windows(2).all(|v| v[0].1 == v[1].1) {
+            return Ok(());
+        }
+
+        let list: Vec<_> = attachments
+            .iter()
+            .map(|(handle, v)| format!("{:?} is {}x{}", handle, v.x, v.y))
+            .collect();
+
+        Err(Error::SurfaceInvalid(format!(
+            "The attachments have mismatched dimensions: {}.",
+            list.join(", ")
+        )))
+    }
+
     /// Gets the `SurfaceParams` if available.
     pub fn surface(&self, handle: SurfaceHandle) -> Option<SurfaceParams> {
         self.state.surfaces.read().unwrap().get(handle).cloned()
@@ -153,11 +639,211 @@ impl VideoSystem {
 
     /// Deletes surface object.
     pub fn delete_surface(&self, handle: SurfaceHandle) {
-        if self.state.surfaces.write().unwrap().free(handle).is_some() {
-            let cmd = Command::DeleteSurface(handle);
+        self.delete(handle);
+    }
+
+    /// Sets the antialiasing mode of surfaces that render into the window framebuffer.
+    ///
+    /// Notes that the window framebuffer itself should not be multisampled (checkout
+    /// `WindowParams::multisample`) when using `AaMode::Msaa`, since the multisampled
+    /// attachments could only be resolved into a single-sampled framebuffer.
+    pub fn set_antialiasing(&self, mode: AaMode) -> CrResult<()> {
+        if let AaMode::Msaa(n) = mode {
+            if n < 2 || !n.is_power_of_two() {
+                bail!("The samples of AaMode::Msaa should be a power of two greater than 1.");
+            }
+        }
+
+        let mut antialiasing = self.state.antialiasing.lock().unwrap();
+        if *antialiasing != mode {
+            *antialiasing = mode;
+            let cmd = Command::UpdateAntialiasing(mode);
+            self.state.frames.write().cmds.push(cmd);
+        }
+
+        Ok(())
+    }
+
+    /// Gets the antialiasing mode of surfaces that render into the window framebuffer.
+    #[inline]
+    pub fn antialiasing(&self) -> AaMode {
+        *self.state.antialiasing.lock().unwrap()
+    }
+
+    /// Sets the adjustment of colors that presented to the window, which is applied by a
+    /// full-screen pass before presenting.
+    pub fn set_output_adjustment(&self, adjustment: OutputAdjustment) -> CrResult<()> {
+        if !(adjustment.gamma.is_finite() && adjustment.gamma > 0.0) {
+            bail!("The gamma of output should be a finite number greater than 0.");
+        }
+
+        if !(adjustment.brightness.is_finite() && adjustment.contrast.is_finite()) {
+            bail!("The brightness and contrast of output should be finite numbers.");
+        }
+
+        let mut output = self.state.output.lock().unwrap();
+        if *output != adjustment {
+            *output = adjustment;
+            let cmd = Command::UpdateOutputAdjustment(adjustment);
+            self.state.frames.write().cmds.push(cmd);
+        }
+
+        Ok(())
+    }
+
+    /// Sets the gamma of colors that presented to the window, the brightness and contrast
+    /// are kept untouched.
+    pub fn set_output_gamma(&self, gamma: f32) -> CrResult<()> {
+        let mut adjustment = self.output_adjustment();
+        adjustment.gamma = gamma;
+        self.set_output_adjustment(adjustment)
+    }
+
+    /// Gets the adjustment of colors that presented to the window.
+    #[inline]
+    pub fn output_adjustment(&self) -> OutputAdjustment {
+        *self.state.output.lock().unwrap()
+    }
+
+    /// Sets the maximum number of frames that the GPU could lag behind the CPU. The end of
+    /// frame blocks until the GPU has completed the frame `frames` before the current one,
+    /// which trades the throughput for lower input latency as it gets smaller.
+    ///
+    /// It's 0 by default, which waits for the completion of every frame. Notes that the
+    /// GL context without fence syncs always behaves as 0, and it's ignored on WebGL.
+    pub fn set_max_frame_latency(&self, frames: u32) {
+        let mut latency = self.state.max_frame_latency.lock().unwrap();
+        if *latency != frames {
+            *latency = frames;
+            let cmd = Command::UpdateMaxFrameLatency(frames);
             self.state.frames.write().cmds.push(cmd);
         }
     }
+
+    /// Gets the maximum number of frames that the GPU could lag behind the CPU.
+    #[inline]
+    pub fn max_frame_latency(&self) -> u32 {
+        *self.state.max_frame_latency.lock().unwrap()
+    }
+
+    /// Blocks the dispatching until all the commands submitted before this point have been
+    /// completed by GPU, e.g. before a CPU readback or measuring GPU time.
+    ///
+    /// Notes that this stalls the CPU and drains the whole GPU pipeline, so it should be
+    /// used sparingly.
+    pub fn finish(&self) {
+        self.state.frames.write().cmds.push(Command::Finish);
+    }
+
+    /// Forces the commands submitted before this point to be issued to GPU when they are
+    /// dispatched, without waiting for their completion.
+    pub fn flush_gpu(&self) {
+        self.state.frames.write().cmds.push(Command::FlushGpu);
+    }
+
+    /// Inserts memory barriers, which makes sure that the memory accesses of `barriers`
+    /// after this point could observe the incoherent writes of shaders before it, e.g. the
+    /// draws that read the buffers written by shader storage blocks.
+    pub fn memory_barrier(&self, barriers: MemoryBarrier) {
+        let cmd = Command::MemoryBarrier(barriers);
+        self.state.frames.write().cmds.push(cmd);
+    }
+
+    /// Tells the video system that the OpenGL states it has cached are stale, since the
+    /// context has been modified by native OpenGL calls outside crayon (e.g. interop with
+    /// other libraries). All the states will be applied again by the following commands.
+    ///
+    /// Notes that this should be called after the native calls and before submitting any
+    /// command that depends on the states.
+    pub fn invalidate_state_cache(&self) {
+        let cmd = Command::InvalidateStateCache;
+        self.state.frames.write().cmds.push(cmd);
+    }
+
+    /// Sets the human-readable `name` of a resource, which makes the handles in logs and
+    /// error messages identifiable. The name is dropped along with the resource.
+    pub fn set_name<T, N>(&self, handle: T, name: N)
+    where
+        T: Into<VideoHandle>,
+        N: Into<String>,
+    {
+        self.state
+            .names
+            .write()
+            .unwrap()
+            .insert(handle.into(), name.into());
+    }
+
+    /// Gets the name of a resource, if it has been named with `set_name`.
+    pub fn name_of<T: Into<VideoHandle>>(&self, handle: T) -> Option<String> {
+        self.state
+            .names
+            .read()
+            .unwrap()
+            .get(&handle.into())
+            .cloned()
+    }
+
+    /// Requests to delete the resource at the begining of next frame, after all the
+    /// commands of current frame which might still refer to it.
+    fn delete<T: Into<VideoHandle>>(&self, handle: T) {
+        self.state.deletions.lock().unwrap().push(handle.into());
+    }
+
+    /// Gets the statistics of the last dispatched frame.
+    pub fn frame_stats(&self) -> FrameStats {
+        *self.state.stats.lock().unwrap()
+    }
+
+    /// Starts the GPU timer `name`, which measures the time GPU spent on the commands
+    /// submitted after it until `end_gpu_timer`, e.g. the draws of a surface.
+    ///
+    /// Notes that the timers could NOT be nested. And it requires OpenGL 3.3 or
+    /// `GL_ARB_timer_query`, the timers are ignored if its not supported.
+    pub fn begin_gpu_timer<T: Into<String>>(&self, name: T) {
+        let cmd = Command::BeginGpuTimer(name.into());
+        self.state.frames.write().cmds.push(cmd);
+    }
+
+    /// Stops the running GPU timer.
+    pub fn end_gpu_timer(&self) {
+        self.state.frames.write().cmds.push(Command::EndGpuTimer);
+    }
+
+    /// Gets the most recent completed measurements of GPU timers. The results of GPU
+    /// lag a frame or two behind, so the timers of current frame are not included.
+    pub fn gpu_timers(&self) -> FastHashMap<String, Duration> {
+        self.state.gpu_timers.lock().unwrap().clone()
+    }
+
+    /// Sets the policy of shrinking the buffers of frames, which balances the cost of
+    /// reallocations against the memory pinned by occasional huge frames.
+    pub fn set_frame_shrink_policy(&self, policy: FrameShrinkPolicy) {
+        *self.state.shrink_policy.lock().unwrap() = policy;
+    }
+
+    /// Sets the maximum bytes of resource data that are uploaded per frame, the creations
+    /// of textures and meshes beyond it are deferred to subsequent frames. Uploads are never
+    /// deferred if it's `None`, which is the default.
+    ///
+    /// The deferred resources are skipped by draws until they have been uploaded, and
+    /// at least one upload is processed every frame, so a large resource could exceed the
+    /// budget but is never starved.
+    pub fn set_upload_budget(&self, budget: Option<usize>) {
+        self.state.uploads.lock().unwrap().set_budget(budget);
+    }
+
+    /// Records the commands that have been submitted into current frame so far, which
+    /// could be serialized and replayed later with `VideoSystem::replay`.
+    pub fn record(&self) -> FrameLog {
+        self.state.frames.write().record()
+    }
+
+    /// Appends the commands of `log` into current frame, they will be dispatched along
+    /// with the others at the end of this frame.
+    pub fn replay(&self, log: &FrameLog) {
+        self.state.frames.write().replay(log);
+    }
 }
 
 impl VideoSystem {
@@ -200,10 +886,7 @@ impl VideoSystem {
     /// Delete shader state object.
     #[inline]
     pub fn delete_shader(&self, handle: ShaderHandle) {
-        if self.state.shaders.write().unwrap().free(handle).is_some() {
-            let cmd = Command::DeleteShader(handle);
-            self.state.frames.write().cmds.push(cmd);
-        }
+        self.delete(handle);
     }
 }
 
@@ -218,6 +901,17 @@ impl VideoSystem {
         meshes.create((params, data.into()))
     }
 
+    /// Creates a batch of mesh objects at once. The lock of meshes is acquired only once,
+    /// and all the creations are pushed as a single command. None of them will be created
+    /// if any one is invalid.
+    pub fn create_meshes(
+        &self,
+        items: Vec<(MeshParams, Option<MeshData>)>,
+    ) -> CrResult<Vec<MeshHandle>> {
+        let mut meshes = self.state.meshes.write().unwrap();
+        meshes.create_batch(items)
+    }
+
     /// Creates a mesh object from file asynchronously.
     #[inline]
     pub fn create_mesh_from<T: AsRef<str>>(&self, url: T) -> CrResult<MeshHandle> {
@@ -232,6 +926,15 @@ impl VideoSystem {
         meshes.create_from_uuid(uuid)
     }
 
+    /// Creates a mesh object from the encoded `bytes` in memory asynchronously. The bytes
+    /// are decoded with `format` on the loading threads.
+    pub fn create_mesh_from_bytes(&self, bytes: &[u8], format: MeshFormat) -> CrResult<MeshHandle> {
+        let mut meshes = self.state.meshes.write().unwrap();
+        let loader = meshes.loader().clone();
+        let bytes = bytes.to_vec();
+        meshes.create_async(move |handle| loader.decode(handle, &bytes, format))
+    }
+
     /// Gets the `MeshParams` if available.
     #[inline]
     pub fn mesh(&self, handle: MeshHandle) -> Option<MeshParams> {
@@ -244,6 +947,12 @@ impl VideoSystem {
         self.state.meshes.read().unwrap().state(handle)
     }
 
+    /// Gets the UUID of the mesh which is created from file, it's stable across runs
+    /// and could be stored in persistent data instead of the handle.
+    pub fn mesh_uuid(&self, handle: MeshHandle) -> Option<Uuid> {
+        self.state.meshes.read().unwrap().uuid(handle)
+    }
+
     /// Update a subset of dynamic vertex buffer. Use `offset` specifies the offset
     /// into the buffer object's data store where data replacement will begin, measured
     /// in bytes.
@@ -267,7 +976,8 @@ impl VideoSystem {
 
     /// Update a subset of dynamic index buffer. Use `offset` specifies the offset
     /// into the buffer object's data store where data replacement will begin, measured
-    /// in bytes.
+    /// in bytes. Both the `offset` and `data` should be aligned with the `IndexFormat`
+    /// of mesh.
     pub fn update_index_buffer(
         &self,
         handle: MeshHandle,
@@ -276,6 +986,18 @@ impl VideoSystem {
     ) -> CrResult<()> {
         let meshes = self.state.meshes.read().unwrap();
         if meshes.contains(handle) {
+            // The parameters are unknown until the mesh from file has been loaded.
+            if let Some(params) = meshes.resource(handle) {
+                let stride = params.index_format.stride();
+                if offset % stride != 0 || data.len() % stride != 0 {
+                    bail!(
+                        "The update of {:?} is not aligned with {:?}.",
+                        handle,
+                        params.index_format
+                    );
+                }
+            }
+
             let mut frame = self.state.frames.write();
             let ptr = frame.bufs.extend_from_slice(data);
             let cmd = Command::UpdateIndexBuffer(handle, offset, ptr);
@@ -286,10 +1008,21 @@ impl VideoSystem {
         }
     }
 
+    /// Sets whether the winding order of triangles should be made consistent when meshes
+    /// are imported from files, which fixes the meshes that are partially inside-out. It
+    /// only applies to the indexed triangles, and is disabled by default.
+    ///
+    /// The in-memory meshes could be fixed with `mesh_process::fix_winding` before
+    /// `create_mesh`.
+    pub fn set_fix_mesh_winding(&self, enabled: bool) {
+        let meshes = self.state.meshes.read().unwrap();
+        meshes.loader().set_fix_winding(enabled);
+    }
+
     /// Delete mesh object.
     #[inline]
     pub fn delete_mesh(&self, handle: MeshHandle) {
-        self.state.meshes.write().unwrap().delete(handle);
+        self.delete(handle);
     }
 }
 
@@ -300,8 +1033,64 @@ impl VideoSystem {
     where
         T: Into<Option<TextureData>>,
     {
+        self.validate_texture_size(params.dimensions)?;
+        let mut textures = self.state.textures.write().unwrap();
+        textures.create((params, TextureSource::Data(data.into())))
+    }
+
+    /// Creates a batch of texture objects at once, which is useful for asset-heavy
+    /// startups. The lock of textures is acquired only once, and all the creations are
+    /// pushed as a single command. None of them will be created if any one is invalid.
+    pub fn create_textures(
+        &self,
+        items: Vec<(TextureParams, Option<TextureData>)>,
+    ) -> CrResult<Vec<TextureHandle>> {
+        let items = items
+            .into_iter()
+            .map(|(params, data)| {
+                self.validate_texture_size(params.dimensions)?;
+                Ok((params, TextureSource::Data(data)))
+            })
+            .collect::<Result<_>>()?;
+
+        let mut textures = self.state.textures.write().unwrap();
+        textures.create_batch(items)
+    }
+
+    /// Creates a cube map with the data of six faces, e.g. for skyboxes and reflection
+    /// probes. The faces should be squares of `params.dimensions`, and all of them should
+    /// have the same number of mipmap levels in the format of `params.format`.
+    ///
+    /// The cube map is sampled with `samplerCube` in shaders, which is declared as
+    /// `UniformVariableType::Texture` in the layout of uniforms. Notes that it could not be
+    /// updated with `update_texture`.
+    pub fn create_cube_texture<T>(&self, params: TextureParams, data: T) -> CrResult<TextureHandle>
+    where
+        T: Into<Option<CubeTextureData>>,
+    {
+        self.validate_texture_size(params.dimensions)?;
+
+        let mut params = params;
+        params.kind = TextureKind::Cube;
+
         let mut textures = self.state.textures.write().unwrap();
-        textures.create((params, data.into()))
+        textures.create((params, TextureSource::Cube(data.into())))
+    }
+
+    /// Wraps the native OpenGL texture object `id`, which is created outside crayon (e.g.
+    /// by a video decoder), into a `TextureHandle`. The `params` should describe the
+    /// texture object, and its storage must have been allocated already.
+    ///
+    /// Notes that the texture object will NOT be deleted by `delete_texture`, it's the
+    /// responsibility of user to keep it alive until the handle is deleted. It's not
+    /// supported on the web.
+    pub fn import_texture(&self, id: u32, params: TextureParams) -> CrResult<TextureHandle> {
+        if id == 0 {
+            bail!("0 is not a name of texture object.");
+        }
+
+        let mut textures = self.state.textures.write().unwrap();
+        textures.create((params, TextureSource::Native(id)))
     }
 
     /// Creates a texture object from file asynchronously.
@@ -316,24 +1105,72 @@ impl VideoSystem {
         textures.create_from_uuid(uuid)
     }
 
+    /// Creates a texture object from the encoded `bytes` in memory asynchronously, e.g. the
+    /// bytes downloaded over network or embedded with `include_bytes!`. The bytes are
+    /// decoded with `format` on the loading threads.
+    pub fn create_texture_from_bytes(
+        &self,
+        bytes: &[u8],
+        format: ImageFormat,
+    ) -> CrResult<TextureHandle> {
+        let mut textures = self.state.textures.write().unwrap();
+        let loader = textures.loader().clone();
+        let bytes = bytes.to_vec();
+        textures.create_async(move |handle| loader.decode(handle, &bytes, format))
+    }
+
     /// Get the resource state of specified texture.
     #[inline]
     pub fn texture_state(&self, handle: TextureHandle) -> ResourceState {
         self.state.textures.read().unwrap().state(handle)
     }
 
+    /// Gets the UUID of the texture which is created from file, it's stable across runs
+    /// and could be stored in persistent data instead of the handle.
+    pub fn texture_uuid(&self, handle: TextureHandle) -> Option<Uuid> {
+        self.state.textures.read().unwrap().uuid(handle)
+    }
+
     /// Update a contiguous subregion of an existing two-dimensional texture object.
+    #[inline]
     pub fn update_texture(
         &self,
         handle: TextureHandle,
         area: Aabb2<u32>,
         data: &[u8],
     ) -> CrResult<()> {
+        self.update_texture_with_stride(handle, area, area.dim().x, data)
+    }
+
+    /// Update a subregion of an existing two-dimensional texture object with pixels whose
+    /// rows are `stride` pixels apart. The `data` should starts at the first pixel of `area`,
+    /// so a sub-rectangle of a larger image could be uploaded without packing it first.
+    pub fn update_texture_with_stride(
+        &self,
+        handle: TextureHandle,
+        area: Aabb2<u32>,
+        stride: u32,
+        data: &[u8],
+    ) -> CrResult<()> {
+        if stride < area.dim().x {
+            bail!(
+                "The stride {} is less than the width {} of updating area.",
+                stride,
+                area.dim().x
+            );
+        }
+
         let textures = self.state.textures.read().unwrap();
+        if let Some(params) = textures.resource(handle) {
+            if params.kind == TextureKind::Cube {
+                bail!("{:?} is a cube map, which could not be updated.", handle);
+            }
+        }
+
         if textures.contains(handle) {
             let mut frame = self.state.frames.write();
             let ptr = frame.bufs.extend_from_slice(data);
-            let cmd = Command::UpdateTexture(handle, area, ptr);
+            let cmd = Command::UpdateTexture(handle, area, stride, ptr);
             frame.cmds.push(cmd);
             Ok(())
         } else {
@@ -341,18 +1178,115 @@ impl VideoSystem {
         }
     }
 
+    /// Gets the name of native OpenGL texture object of `handle`, which could be handed to
+    /// other OpenGL codes sharing the same context. Returns `None` if the texture has not
+    /// been created by the dispatching of frames yet, or on the web.
+    ///
+    /// Notes that the name is only valid while the handle is alive, and it should only be
+    /// used on the thread that the OpenGL context is current on.
+    pub fn native_texture(&self, handle: TextureHandle) -> Option<u32> {
+        let visitor = self.state.visitor.lock().unwrap();
+        visitor.native_texture(handle)
+    }
+
+    /// Sets the texture which is sampled instead of the textures that are not ready, e.g.
+    /// still loading or failed to load. It's a magenta checkerboard provided by engine by
+    /// default, and the unready textures are sampled as black if it's `None`.
+    pub fn set_fallback_texture<T>(&self, handle: T)
+    where
+        T: Into<Option<TextureHandle>>,
+    {
+        let handle = handle.into();
+        *self.state.fallback_texture.lock().unwrap() = handle;
+
+        let cmd = Command::SetFallbackTexture(handle);
+        self.state.frames.write().cmds.push(cmd);
+    }
+
+    /// Gets the texture which is sampled instead of the textures that are not ready.
+    pub fn fallback_texture(&self) -> Option<TextureHandle> {
+        *self.state.fallback_texture.lock().unwrap()
+    }
+
+    fn create_checkerboard_texture(&self) -> CrResult<TextureHandle> {
+        const MAGENTA: [u8; 4] = [255, 0, 255, 255];
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+        let mut params = TextureParams::default();
+        params.set_wrap(TextureWrap::Repeat);
+        params.set_filter(TextureFilter::Nearest);
+        params.dimensions = Vector2::new(8, 8);
+
+        let mut bytes = Vec::with_capacity(8 * 8 * 4);
+        for y in 0..8 {
+            for x in 0..8 {
+                let texel = if (x / 4 + y / 4) % 2 == 0 {
+                    MAGENTA
+                } else {
+                    BLACK
+                };
+
+                bytes.extend_from_slice(&texel);
+            }
+        }
+
+        let data = TextureData {
+            bytes: vec![bytes.into_boxed_slice()],
+        };
+
+        let handle = self.create_texture(params, data)?;
+        self.set_name(handle, "Fallback");
+        Ok(handle)
+    }
+
     /// Delete the texture object.
     pub fn delete_texture(&self, handle: TextureHandle) {
-        self.state.textures.write().unwrap().delete(handle);
+        self.delete(handle);
     }
 }
 
 impl VideoSystem {
+    /// Checks if the render texture `format` could be used for `usage` on this device. This
+    /// helps to avoid creating broken render targets on limited hardwares.
+    pub fn is_format_supported(&self, format: RenderTextureFormat, usage: FormatUsage) -> bool {
+        let visitor = self.state.visitor.lock().unwrap();
+        visitor.is_format_supported(format, usage)
+    }
+
+    /// Checks if textures could be created with `format` on this device, the compressed
+    /// formats are only available with specific extensions.
+    pub fn is_texture_format_supported(&self, format: TextureFormat) -> bool {
+        let visitor = self.state.visitor.lock().unwrap();
+        visitor.is_texture_format_supported(format)
+    }
+
+    /// Gets the limits of this device, e.g. the maximum size of textures.
+    #[inline]
+    pub fn limits(&self) -> DeviceLimits {
+        self.state.limits
+    }
+
+    fn validate_texture_size(&self, dimensions: Vector2<u32>) -> Result<()> {
+        let max = self.state.limits.max_texture_size;
+        if dimensions.x > max || dimensions.y > max {
+            return Err(Error::TextureInvalid(format!(
+                "the dimensions {:?} exceed the maximum texture size {} of this device.",
+                dimensions, max
+            )));
+        }
+
+        Ok(())
+    }
+
     /// Create render texture object, which could be attached with a framebuffer.
     pub fn create_render_texture(
         &self,
         params: RenderTextureParams,
     ) -> Result<RenderTextureHandle> {
+        let mut params = params;
+        params.dimensions = params.dimensions_of(*self.state.screen_dimensions.lock().unwrap());
+        self.validate_texture_size(params.dimensions)?;
+
         let handle = self.state.render_textures.write().unwrap().create(params);
 
         {
@@ -385,21 +1319,303 @@ impl VideoSystem {
 
     /// Delete the render texture object.
     pub fn delete_render_texture(&self, handle: RenderTextureHandle) {
-        if self
+        self.delete(handle);
+    }
+
+    /// Copies the whole render texture `src` into `dst`, scaling with `filter` if their
+    /// dimensions differ. The formats should be compatible, the rules are listed in
+    /// `RenderTextureFormat::validate_blit`.
+    ///
+    /// The blits between fixed-point and floating-point color formats fall back to a
+    /// full-screen pass on OpenGL ES and WebGL, which requires `src` to be a sampler.
+    pub fn blit(
+        &self,
+        src: RenderTextureHandle,
+        dst: RenderTextureHandle,
+        filter: TextureFilter,
+    ) -> Result<()> {
+        if src == dst {
+            let err = format!("{:?} could not be blitted into itself.", src);
+            return Err(Error::BlitInvalid(err));
+        }
+
+        let (from, to) = {
+            let render_textures = self.state.render_textures.read().unwrap();
+            let from = render_textures
+                .get(src)
+                .ok_or_else(|| Error::HandleInvalid(format!("{:?}", src)))?;
+            let to = render_textures
+                .get(dst)
+                .ok_or_else(|| Error::HandleInvalid(format!("{:?}", dst)))?;
+            (from.format, to.format)
+        };
+
+        from.validate_blit(to, filter)?;
+        let cmd = Command::Blit(src, dst, filter);
+        self.state.frames.write().cmds.push(cmd);
+        Ok(())
+    }
+
+    /// Requests to read back the pixels of `area` in render texture after the commands
+    /// before it have been dispatched, e.g. the draws into the surfaces it attached to.
+    /// This method returns a `Request` immediately, which would be resolved with the
+    /// pixels once the frame has been dispatched, or with an error if the render texture
+    /// is deleted before that.
+    ///
+    /// The pixels are tightly packed rows from bottom to top, and sized with the format of
+    /// render texture as `RenderTextureFormat::size` (e.g. 3 bytes per pixel of `RGB8`).
+    /// Only the color formats could be read back.
+    pub fn read_render_texture(
+        &self,
+        handle: RenderTextureHandle,
+        area: Aabb2<u32>,
+    ) -> Result<Request> {
+        let params = *self
             .state
             .render_textures
-            .write()
+            .read()
+            .unwrap()
+            .get(handle)
+            .ok_or_else(|| Error::HandleInvalid(format!("{:?}", handle)))?;
+
+        if !params.format.is_color() {
+            return Err(Error::ReadbackInvalid(format!(
+                "{:?} of {:?} could not be read back.",
+                params.format, handle
+            )));
+        }
+
+        let dimensions = params.dimensions;
+        if area.max.x > dimensions.x || area.max.y > dimensions.y {
+            return Err(Error::OutOfBounds);
+        }
+
+        let latch = Request::latch();
+
+        // Queues the latch along with the command, so the reads are completed in order.
+        let mut frame = self.state.frames.write();
+        frame.cmds.push(Command::ReadRenderTexture(handle, area));
+
+        let index = self.state.frame_index.load(Ordering::Relaxed);
+        self.state
+            .pending_reads
+            .lock()
+            .unwrap()
+            .entry(handle)
+            .or_insert_with(VecDeque::new)
+            .push_back((index, latch.clone()));
+
+        Ok(Request::new(latch))
+    }
+}
+
+impl VideoSystem {
+    /// Create per-instance vertex buffer object, which could be used with `Draw`s to
+    /// render a number of instances of mesh.
+    pub fn create_instance_buffer(
+        &self,
+        params: InstanceBufferParams,
+        data: Option<&[u8]>,
+    ) -> Result<InstanceBufferHandle> {
+        params.validate(data)?;
+        let handle = self.state.instance_buffers.write().unwrap().create(params);
+
+        {
+            let data = data.map(|v| v.to_vec().into_boxed_slice());
+            let cmd = Command::CreateInstanceBuffer(Box::new((handle, params, data)));
+            self.state.frames.write().cmds.push(cmd);
+        }
+
+        Ok(handle)
+    }
+
+    /// Gets the `InstanceBufferParams` if available.
+    pub fn instance_buffer(&self, handle: InstanceBufferHandle) -> Option<InstanceBufferParams> {
+        self.state
+            .instance_buffers
+            .read()
+            .unwrap()
+            .get(handle)
+            .cloned()
+    }
+
+    /// Update a subset of dynamic instance buffer. Use `offset` specifies the offset
+    /// into the buffer object's data store where data replacement will begin, measured
+    /// in bytes.
+    pub fn update_instance_buffer(
+        &self,
+        handle: InstanceBufferHandle,
+        offset: usize,
+        data: &[u8],
+    ) -> CrResult<()> {
+        let instance_buffers = self.state.instance_buffers.read().unwrap();
+        if instance_buffers.contains(handle) {
+            let mut frame = self.state.frames.write();
+            let ptr = frame.bufs.extend_from_slice(data);
+            let cmd = Command::UpdateInstanceBuffer(handle, offset, ptr);
+            frame.cmds.push(cmd);
+            Ok(())
+        } else {
+            bail!("{:?} is invalid.", handle);
+        }
+    }
+
+    /// Delete the instance buffer object.
+    pub fn delete_instance_buffer(&self, handle: InstanceBufferHandle) {
+        self.delete(handle);
+    }
+}
+
+impl VideoSystem {
+    /// Create shader storage buffer object, which could be read and written by shaders
+    /// after being binded to `Draw`s with `bind_storage_buffer`.
+    pub fn create_shader_storage_buffer(
+        &self,
+        params: ShaderStorageBufferParams,
+        data: Option<&[u8]>,
+    ) -> Result<ShaderStorageBufferHandle> {
+        params.validate(data)?;
+        let handle = self.state.storage_buffers.write().unwrap().create(params);
+
+        {
+            let data = data.map(|v| v.to_vec().into_boxed_slice());
+            let cmd = Command::CreateShaderStorageBuffer(Box::new((handle, params, data)));
+            self.state.frames.write().cmds.push(cmd);
+        }
+
+        Ok(handle)
+    }
+
+    /// Gets the `ShaderStorageBufferParams` if available.
+    pub fn shader_storage_buffer(
+        &self,
+        handle: ShaderStorageBufferHandle,
+    ) -> Option<ShaderStorageBufferParams> {
+        self.state
+            .storage_buffers
+            .read()
             .unwrap()
-            .free(handle)
-            .is_some()
+            .get(handle)
+            .cloned()
+    }
+
+    /// Update a subset of dynamic shader storage buffer. Use `offset` specifies the
+    /// offset into the buffer object's data store where data replacement will begin,
+    /// measured in bytes.
+    pub fn update_shader_storage_buffer(
+        &self,
+        handle: ShaderStorageBufferHandle,
+        offset: usize,
+        data: &[u8],
+    ) -> CrResult<()> {
+        let storage_buffers = self.state.storage_buffers.read().unwrap();
+        if let Some(params) = storage_buffers.get(handle) {
+            if offset + data.len() > params.size {
+                return Err(Error::OutOfBounds.into());
+            }
+
+            let mut frame = self.state.frames.write();
+            let ptr = frame.bufs.extend_from_slice(data);
+            let cmd = Command::UpdateShaderStorageBuffer(handle, offset, ptr);
+            frame.cmds.push(cmd);
+            Ok(())
+        } else {
+            bail!("{:?} is invalid.", handle);
+        }
+    }
+
+    /// Requests to read back the contents of shader storage buffer after the commands
+    /// before it have been dispatched. The contents could be taken with
+    /// `take_shader_storage_buffer_data` once the frame has been dispatched.
+    ///
+    /// Notes that the reading stalls the pipeline until GPU finishes the writes into this
+    /// buffer, it should be used sparingly.
+    pub fn read_shader_storage_buffer(&self, handle: ShaderStorageBufferHandle) -> CrResult<()> {
+        if self.state.storage_buffers.read().unwrap().contains(handle) {
+            let cmd = Command::ReadShaderStorageBuffer(handle);
+            self.state.frames.write().cmds.push(cmd);
+            Ok(())
+        } else {
+            bail!("{:?} is invalid.", handle);
+        }
+    }
+
+    /// Takes the latest contents of shader storage buffer that have been read back.
+    /// Returns `None` if nothing has arrived since last taking.
+    pub fn take_shader_storage_buffer_data(
+        &self,
+        handle: ShaderStorageBufferHandle,
+    ) -> Option<Vec<u8>> {
+        self.state.readbacks.lock().unwrap().remove(&handle.into())
+    }
+
+    /// Delete the shader storage buffer object.
+    pub fn delete_shader_storage_buffer(&self, handle: ShaderStorageBufferHandle) {
+        self.delete(handle);
+    }
+}
+
+impl VideoSystem {
+    /// Create uniform buffer object, which holds the uniform blocks shared by draws after
+    /// being binded to `Draw`s with `bind_uniform_buffer`.
+    pub fn create_uniform_buffer(
+        &self,
+        params: UniformBufferParams,
+        data: Option<&[u8]>,
+    ) -> Result<UniformBufferHandle> {
+        params.validate(data)?;
+        let handle = self.state.uniform_buffers.write().unwrap().create(params);
+
         {
-            let cmd = Command::DeleteRenderTexture(handle);
+            let data = data.map(|v| v.to_vec().into_boxed_slice());
+            let cmd = Command::CreateUniformBuffer(Box::new((handle, params, data)));
             self.state.frames.write().cmds.push(cmd);
         }
+
+        Ok(handle)
+    }
+
+    /// Gets the `UniformBufferParams` if available.
+    pub fn uniform_buffer(&self, handle: UniformBufferHandle) -> Option<UniformBufferParams> {
+        self.state
+            .uniform_buffers
+            .read()
+            .unwrap()
+            .get(handle)
+            .cloned()
+    }
+
+    /// Update a subset of dynamic uniform buffer. Use `offset` specifies the offset into
+    /// the buffer object's data store where data replacement will begin, measured in bytes.
+    pub fn update_uniform_buffer(
+        &self,
+        handle: UniformBufferHandle,
+        offset: usize,
+        data: &[u8],
+    ) -> CrResult<()> {
+        let uniform_buffers = self.state.uniform_buffers.read().unwrap();
+        if let Some(params) = uniform_buffers.get(handle) {
+            if offset + data.len() > params.size {
+                return Err(Error::OutOfBounds.into());
+            }
+
+            let mut frame = self.state.frames.write();
+            let ptr = frame.bufs.extend_from_slice(data);
+            let cmd = Command::UpdateUniformBuffer(handle, offset, ptr);
+            frame.cmds.push(cmd);
+            Ok(())
+        } else {
+            bail!("{:?} is invalid.", handle);
+        }
+    }
+
+    /// Delete the uniform buffer object.
+    pub fn delete_uniform_buffer(&self, handle: UniformBufferHandle) {
+        self.delete(handle);
     }
 }
 
-fn dimensions_pixels() -> Vector2<u32> {
+pub(crate) fn dimensions_pixels() -> Vector2<u32> {
     let dimensions = crate::window::dimensions();
     let dpr = crate::window::device_pixel_ratio();
     Vector2::new(
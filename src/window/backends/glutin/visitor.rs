@@ -1,3 +1,5 @@
+use std::sync::Mutex;
+
 use gl;
 use glutin;
 use glutin::GlContext;
@@ -5,14 +7,15 @@ use glutin::GlContext;
 use crate::errors::*;
 use crate::math::prelude::Vector2;
 
-use super::super::super::events::Event;
-use super::super::super::WindowParams;
-use super::super::Visitor;
+use super::super::super::events::{coalesce, Event};
+use super::super::super::{ContextApi, ContextProfile, WindowParams};
+use super::super::{Visitor, Waker};
 use super::types;
 
 pub struct GlutinVisitor {
     window: glutin::GlWindow,
     events_loop: glutin::EventsLoop,
+    coalesce_events: bool,
 }
 
 impl GlutinVisitor {
@@ -25,17 +28,46 @@ impl GlutinVisitor {
             ))
             .with_multitouch();
 
-        let context = glutin::ContextBuilder::new()
+        let request = match params.api {
+            ContextApi::Latest => glutin::GlRequest::Latest,
+            ContextApi::GL(major, minor) => {
+                glutin::GlRequest::Specific(glutin::Api::OpenGl, (major, minor))
+            }
+            ContextApi::ES(major, minor) => {
+                glutin::GlRequest::Specific(glutin::Api::OpenGlEs, (major, minor))
+            }
+        };
+
+        let profile = match params.profile {
+            ContextProfile::Core => glutin::GlProfile::Core,
+            ContextProfile::Compatibility => glutin::GlProfile::Compatibility,
+        };
+
+        let mut context = glutin::ContextBuilder::new()
             .with_multisampling(params.multisample as u16)
-            .with_gl_profile(glutin::GlProfile::Core)
-            .with_gl(glutin::GlRequest::Latest)
+            .with_gl_profile(profile)
+            .with_gl(request)
             .with_vsync(params.vsync);
 
+        if params.no_error {
+            context = context
+                .with_gl_robustness(glutin::Robustness::NoError)
+                .with_gl_debug_flag(false);
+        }
+
         let events_loop = glutin::EventsLoop::new();
-        let window = glutin::GlWindow::new(builder, context, &events_loop).unwrap();
+        let window = glutin::GlWindow::new(builder, context, &events_loop).map_err(|err| {
+            format_err!(
+                "[Window] Failed to create {:?} {:?} context: {}",
+                request,
+                profile,
+                err
+            )
+        })?;
         let mut visitor = GlutinVisitor {
             window,
             events_loop,
+            coalesce_events: params.coalesce_events,
         };
 
         let size = visitor.dimensions();
@@ -84,6 +116,7 @@ impl Visitor for GlutinVisitor {
 
     #[inline]
     fn resize(&self, dimensions: Vector2<u32>) {
+        info!("[Glutin] Resizes window to {:?}.", dimensions);
         let size = glutin::dpi::PhysicalSize::new(f64::from(dimensions.x), f64::from(dimensions.y));
         self.window.resize(size)
     }
@@ -91,13 +124,36 @@ impl Visitor for GlutinVisitor {
     #[inline]
     fn poll_events(&mut self, events: &mut Vec<Event>) {
         let dims = self.dimensions();
+        let coalesce_events = self.coalesce_events;
         self.events_loop.poll_events(|v| {
             if let Some(e) = types::from_event(v, dims) {
-                events.push(e);
+                push(events, e, coalesce_events);
             }
         });
     }
 
+    fn wait_events(&mut self, events: &mut Vec<Event>) {
+        let dims = self.dimensions();
+        let coalesce_events = self.coalesce_events;
+        self.events_loop.run_forever(|v| {
+            if let Some(e) = types::from_event(v, dims) {
+                push(events, e, coalesce_events);
+            }
+
+            glutin::ControlFlow::Break
+        });
+
+        // Drains the rest of events that arrived along with the first one.
+        self.poll_events(events);
+    }
+
+    fn waker(&self) -> Waker {
+        let proxy = Mutex::new(self.events_loop.create_proxy());
+        Box::new(move || {
+            let _ = proxy.lock().unwrap().wakeup();
+        })
+    }
+
     #[inline]
     fn is_current(&self) -> bool {
         self.window.is_current()
@@ -116,4 +172,77 @@ impl Visitor for GlutinVisitor {
         self.window.swap_buffers()?;
         Ok(())
     }
+
+    fn set_vsync(&self, enabled: bool) -> Result<()> {
+        if !self.window.is_current() {
+            bail!("[Window] The vsync could only be changed when the context is current.");
+        }
+
+        let interval = if enabled { 1 } else { 0 };
+        if unsafe { swap_interval(&self.window, interval) } {
+            Ok(())
+        } else {
+            bail!("[Window] Failed to change the swap interval of GL context.");
+        }
+    }
+}
+
+/// Sets the swap interval of the current context, returns false if it's not supported.
+#[cfg(target_os = "windows")]
+unsafe fn swap_interval(window: &glutin::GlWindow, interval: i32) -> bool {
+    let func = window.get_proc_address("wglSwapIntervalEXT");
+    if func.is_null() {
+        return false;
+    }
+
+    let func: extern "system" fn(i32) -> i32 = ::std::mem::transmute(func);
+    func(interval) != 0
+}
+
+/// Sets the swap interval of the current context, returns false if it's not supported.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "openbsd"
+))]
+unsafe fn swap_interval(window: &glutin::GlWindow, interval: i32) -> bool {
+    use glutin::os::unix::WindowExt;
+    use std::os::raw::{c_int, c_ulong, c_void};
+
+    // Only the GLX contexts of X11 are supported, but not the EGL ones of Wayland.
+    let (display, drawable) = match (window.get_xlib_display(), window.get_xlib_window()) {
+        (Some(display), Some(drawable)) => (display, drawable),
+        _ => return false,
+    };
+
+    let func = window.get_proc_address("glXSwapIntervalEXT");
+    if func.is_null() {
+        return false;
+    }
+
+    let func: extern "C" fn(*mut c_void, c_ulong, c_int) = ::std::mem::transmute(func);
+    func(display, drawable, interval);
+    true
+}
+
+/// Sets the swap interval of the current context, returns false if it's not supported.
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "openbsd"
+)))]
+unsafe fn swap_interval(_: &glutin::GlWindow, _: i32) -> bool {
+    false
+}
+
+#[inline]
+fn push(events: &mut Vec<Event>, e: Event, coalesce_events: bool) {
+    if coalesce_events {
+        coalesce(events, e);
+    } else {
+        events.push(e);
+    }
 }
@@ -2,7 +2,7 @@ use crate::errors::*;
 use crate::math::prelude::Vector2;
 
 use super::super::events::Event;
-use super::Visitor;
+use super::{Visitor, Waker};
 
 pub struct HeadlessVisitor {}
 
@@ -34,6 +34,14 @@ impl Visitor for HeadlessVisitor {
     #[inline]
     fn poll_events(&mut self, _: &mut Vec<Event>) {}
 
+    #[inline]
+    fn wait_events(&mut self, _: &mut Vec<Event>) {}
+
+    #[inline]
+    fn waker(&self) -> Waker {
+        Box::new(|| {})
+    }
+
     #[inline]
     fn is_current(&self) -> bool {
         true
@@ -48,4 +56,9 @@ impl Visitor for HeadlessVisitor {
     fn swap_buffers(&self) -> Result<()> {
         Ok(())
     }
+
+    #[inline]
+    fn set_vsync(&self, _: bool) -> Result<()> {
+        Ok(())
+    }
 }
@@ -5,6 +5,9 @@ use crate::math::prelude::Vector2;
 
 use super::events::Event;
 
+/// Wakes up the events loop that is blocked in `Visitor::wait_events`, from any thread.
+pub type Waker = Box<dyn Fn() + Send + Sync>;
+
 pub trait Visitor {
     fn show(&self);
     fn hide(&self);
@@ -13,9 +16,15 @@ pub trait Visitor {
     fn device_pixel_ratio(&self) -> f32;
     fn resize(&self, dimensions: Vector2<u32>);
     fn poll_events(&mut self, events: &mut Vec<Event>);
+    /// Blocks until at least one event arrives, or the `Waker` of this visitor is called.
+    fn wait_events(&mut self, events: &mut Vec<Event>);
+    fn waker(&self) -> Waker;
     fn is_current(&self) -> bool;
     fn make_current(&self) -> Result<()>;
     fn swap_buffers(&self) -> Result<()>;
+    /// Sets whether the swaps of buffers are synchronized with the vertical refresh of
+    /// screen. The context should be current on the calling thread.
+    fn set_vsync(&self, enabled: bool) -> Result<()>;
 }
 
 pub fn new_headless() -> Box<Visitor> {
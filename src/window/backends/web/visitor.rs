@@ -12,6 +12,7 @@ use crate::window::prelude::{Event, WindowEvent, WindowParams};
 use crate::math::prelude::Vector2;
 use crate::errors::*;
 
+use super::super::Waker;
 use super::{types, Visitor};
 
 #[allow(dead_code)]
@@ -273,6 +274,18 @@ impl Visitor for WebVisitor {
         v.extend(events.drain(..));
     }
 
+    // The browser drives the frames with `requestAnimationFrame`, which could never be
+    // blocked, so the events are just polled here.
+    #[inline]
+    fn wait_events(&mut self, v: &mut Vec<Event>) {
+        self.poll_events(v);
+    }
+
+    #[inline]
+    fn waker(&self) -> Waker {
+        Box::new(|| {})
+    }
+
     #[inline]
     fn is_current(&self) -> bool {
         self.document.has_focus().unwrap_or(false)
@@ -288,4 +301,13 @@ impl Visitor for WebVisitor {
     fn swap_buffers(&self) -> Result<()> {
         Ok(())
     }
+
+    #[inline]
+    fn set_vsync(&self, enabled: bool) -> Result<()> {
+        if !enabled {
+            warn!("You can not disable vsync in browser.");
+        }
+
+        Ok(())
+    }
 }
@@ -27,3 +27,69 @@ pub enum Event {
     Window(WindowEvent),
     InputDevice(InputEvent),
 }
+
+/// Pushes `event` into `events`, or replaces the last one with it if both of them are
+/// `MouseMoved` or `Resized`. The positions of cursor are absolute, so the latest one
+/// still accumulates all the motions in between.
+pub(crate) fn coalesce(events: &mut Vec<Event>, event: Event) {
+    let replace = match (events.last(), event) {
+        (
+            Some(Event::InputDevice(InputEvent::MouseMoved { .. })),
+            Event::InputDevice(InputEvent::MouseMoved { .. }),
+        ) => true,
+        (
+            Some(Event::Window(WindowEvent::Resized(_, _))),
+            Event::Window(WindowEvent::Resized(_, _)),
+        ) => true,
+        _ => false,
+    };
+
+    if replace {
+        *events.last_mut().unwrap() = event;
+    } else {
+        events.push(event);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::mouse::MouseButton;
+
+    fn moved(x: f32) -> Event {
+        Event::InputDevice(InputEvent::MouseMoved { position: (x, 0.0) })
+    }
+
+    #[test]
+    fn coalesce_consecutive() {
+        let mut events = Vec::new();
+        coalesce(&mut events, moved(1.0));
+        coalesce(&mut events, moved(2.0));
+        coalesce(&mut events, Event::Window(WindowEvent::Resized(1, 1)));
+        coalesce(&mut events, Event::Window(WindowEvent::Resized(2, 2)));
+
+        let button = MouseButton::Left;
+        coalesce(
+            &mut events,
+            Event::InputDevice(InputEvent::MousePressed { button }),
+        );
+        coalesce(&mut events, moved(3.0));
+        coalesce(&mut events, moved(4.0));
+
+        assert_eq!(events.len(), 4);
+        match events[0] {
+            Event::InputDevice(InputEvent::MouseMoved { position }) => assert_eq!(position.0, 2.0),
+            _ => unreachable!(),
+        }
+
+        match events[1] {
+            Event::Window(WindowEvent::Resized(w, h)) => assert_eq!((w, h), (2, 2)),
+            _ => unreachable!(),
+        }
+
+        match events[3] {
+            Event::InputDevice(InputEvent::MouseMoved { position }) => assert_eq!(position.0, 4.0),
+            _ => unreachable!(),
+        }
+    }
+}
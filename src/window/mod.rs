@@ -4,7 +4,7 @@ pub mod events;
 pub mod prelude {
     pub use super::events::{Event, WindowEvent};
     pub use super::system::{EventListener, EventListenerHandle};
-    pub use super::WindowParams;
+    pub use super::{ContextApi, ContextProfile, WindowParams};
 }
 
 mod backends;
@@ -27,6 +27,42 @@ pub struct WindowParams {
     pub multisample: u16,
     /// Specifies whether should we have vsync.
     pub vsync: bool,
+    /// Sets the API and version of the OpenGL context to request. It's ignored on the web.
+    pub api: ContextApi,
+    /// Sets the profile of the OpenGL context to request. It's ignored on the web, OpenGL ES
+    /// and the versions of OpenGL prior to 3.2.
+    pub profile: ContextProfile,
+    /// Requests a context without error reporting (`GL_KHR_no_error`), which skips the
+    /// validations of driver for better performance. It falls back to a regular context if
+    /// it's not supported, and is ignored on the web.
+    ///
+    /// The debug flag of context is incompatible with it, so it's always disabled in that
+    /// case, and the errors will not be checked by the video system either.
+    pub no_error: bool,
+    /// Coalesces the consecutive cursor motions and resizes into the latest one when
+    /// polling events, which reduces the events of high polling rate mouses a lot. It's
+    /// disabled by default, so every sample is delivered. It's ignored on the web.
+    pub coalesce_events: bool,
+}
+
+/// The API and version of the OpenGL context.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ContextApi {
+    /// The latest version of the best API of the platform, which is OpenGL on desktop.
+    Latest,
+    /// A specific version of OpenGL.
+    GL(u8, u8),
+    /// A specific version of OpenGL ES.
+    ES(u8, u8),
+}
+
+/// The profile of the OpenGL context.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ContextProfile {
+    /// Includes only the future-compatible functions and definitions.
+    Core,
+    /// Includes all the immediate mode functions and definitions.
+    Compatibility,
 }
 
 impl Default for WindowParams {
@@ -36,6 +72,10 @@ impl Default for WindowParams {
             size: Vector2::new(640, 320),
             multisample: 2,
             vsync: false,
+            api: ContextApi::Latest,
+            profile: ContextProfile::Core,
+            no_error: false,
+            coalesce_events: false,
         }
     }
 }
@@ -62,6 +102,30 @@ pub(crate) fn resize(dimensions: Vector2<u32>) {
     ctx().resize(dimensions);
 }
 
+/// Swaps the buffers of the GL context.
+#[inline]
+pub(crate) fn swap_buffers() -> Result<()> {
+    ctx().swap_buffers()
+}
+
+/// Sets whether the buffers should be swapped at the end of every frame.
+#[inline]
+pub(crate) fn set_auto_swap(auto_swap: bool) {
+    ctx().set_auto_swap(auto_swap);
+}
+
+/// Sets whether the events should be waited instead of being polled every frame.
+#[inline]
+pub(crate) fn set_wait_events(wait_events: bool) {
+    ctx().set_wait_events(wait_events);
+}
+
+/// Requests the next frame without waiting for events.
+#[inline]
+pub(crate) fn request_redraw() {
+    ctx().request_redraw();
+}
+
 /// Discard the window system.
 pub(crate) unsafe fn discard() {
     if CTX.is_null() {
@@ -114,6 +178,13 @@ pub fn is_current() -> bool {
     ctx().is_current()
 }
 
+/// Sets whether the swaps of buffers are synchronized with the vertical refresh of screen.
+/// The context should be current on the calling thread.
+#[inline]
+pub fn set_vsync(enabled: bool) -> Result<()> {
+    ctx().set_vsync(enabled)
+}
+
 /// Returns the position of the lower-left hand corner of the window relative to the lower-left
 /// hand corner of the desktop. Note that the lower-left hand corner of the desktop is not
 /// necessarily the same as the screen. If the user uses a desktop with multiple monitors,
@@ -143,6 +214,27 @@ pub fn device_pixel_ratio() -> f32 {
     ctx().device_pixel_ratio()
 }
 
+/// Overrides the device pixel ratio reported by the platform, or restores it with `None`.
+/// The framebuffer of window is sized with the overridden value in the following frames,
+/// which makes it possible to test the HiDPI code paths on a standard display, or scale the
+/// UI for accessibility.
+///
+/// # Panics
+///
+/// Panics if `dpr` is not a finite positive number.
+#[inline]
+pub fn set_device_pixel_ratio_override(dpr: Option<f32>) {
+    if let Some(v) = dpr {
+        assert!(
+            v.is_finite() && v > 0.0,
+            "The device pixel ratio {} is invalid.",
+            v
+        );
+    }
+
+    ctx().set_device_pixel_ratio_override(dpr);
+}
+
 mod ins {
     use super::system::WindowSystem;
 
@@ -1,3 +1,4 @@
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 
 use crate::application::prelude::{LifecycleListener, LifecycleListenerHandle};
@@ -5,7 +6,7 @@ use crate::errors::*;
 use crate::math::prelude::Vector2;
 use crate::utils::object_pool::ObjectPool;
 
-use super::backends::{self, Visitor};
+use super::backends::{self, Visitor, Waker};
 use super::events::Event;
 use super::WindowParams;
 
@@ -26,6 +27,11 @@ struct WindowState {
     events: Mutex<Vec<Event>>,
     last_frame_listeners: Mutex<Vec<Arc<Mutex<dyn EventListener>>>>,
     listeners: Mutex<ObjectPool<EventListenerHandle, Arc<Mutex<dyn EventListener>>>>,
+    auto_swap: AtomicBool,
+    wait_events: AtomicBool,
+    redraw: AtomicBool,
+    waker: Waker,
+    dpr_override: Mutex<Option<f32>>,
 }
 
 impl LifecycleListener for Arc<WindowState> {
@@ -34,8 +40,16 @@ impl LifecycleListener for Arc<WindowState> {
         let mut events = self.events.lock().unwrap();
         events.clear();
 
-        let mut visitor = self.visitor.write().unwrap();
-        visitor.poll_events(&mut events);
+        {
+            profile_scope!("PollEvents");
+            let mut visitor = self.visitor.write().unwrap();
+            let redraw = self.redraw.swap(false, Ordering::AcqRel);
+            if self.wait_events.load(Ordering::Acquire) && !redraw {
+                visitor.wait_events(&mut events);
+            } else {
+                visitor.poll_events(&mut events);
+            }
+        }
 
         let mut last_frame_listeners = self.last_frame_listeners.lock().unwrap();
 
@@ -61,7 +75,11 @@ impl LifecycleListener for Arc<WindowState> {
         // **Warning**: if you enabled vsync, this function will block until the next time the screen
         // is refreshed. However drivers can choose to override your vsync settings, which means that
         // you can't know in advance whether swap_buffers will block or not.
-        self.visitor.read().unwrap().swap_buffers()?;
+        if self.auto_swap.load(Ordering::Acquire) {
+            profile_scope!("SwapBuffers");
+            self.visitor.read().unwrap().swap_buffers()?;
+        }
+
         Ok(())
     }
 }
@@ -75,28 +93,25 @@ impl Drop for WindowSystem {
 impl WindowSystem {
     /// Creates a new `WindowSystem` and initalize OpenGL context.
     pub fn from(params: WindowParams) -> Result<Self> {
-        let state = Arc::new(WindowState {
-            last_frame_listeners: Mutex::new(Vec::new()),
-            listeners: Mutex::new(ObjectPool::new()),
-            events: Mutex::new(Vec::new()),
-            visitor: RwLock::new(backends::new(params)?),
-        });
-
-        let window = WindowSystem {
-            state: state.clone(),
-            lis: crate::application::attach(state),
-        };
-
-        Ok(window)
+        Ok(Self::with(backends::new(params)?))
     }
 
     /// Creates a new `Window` with headless context.
     pub fn headless() -> Self {
+        Self::with(backends::new_headless())
+    }
+
+    fn with(visitor: Box<dyn Visitor>) -> Self {
         let state = Arc::new(WindowState {
             last_frame_listeners: Mutex::new(Vec::new()),
             listeners: Mutex::new(ObjectPool::new()),
             events: Mutex::new(Vec::new()),
-            visitor: RwLock::new(backends::new_headless()),
+            waker: visitor.waker(),
+            visitor: RwLock::new(visitor),
+            auto_swap: AtomicBool::new(true),
+            wait_events: AtomicBool::new(false),
+            redraw: AtomicBool::new(false),
+            dpr_override: Mutex::new(None),
         });
 
         WindowSystem {
@@ -172,14 +187,73 @@ impl WindowSystem {
 
     /// Returns the ratio between the backing framebuffer resolution and the window size in
     /// screen pixels. This is typically one for a normal display and two for a retina display.
+    ///
+    /// The value of platform is replaced if it has been overridden with
+    /// `set_device_pixel_ratio_override`.
     #[inline]
     pub fn device_pixel_ratio(&self) -> f32 {
+        if let Some(dpr) = *self.state.dpr_override.lock().unwrap() {
+            return dpr;
+        }
+
         self.state.visitor.read().unwrap().device_pixel_ratio()
     }
 
+    /// Overrides the device pixel ratio of platform, or restores it with `None`.
+    #[inline]
+    pub fn set_device_pixel_ratio_override(&self, dpr: Option<f32>) {
+        *self.state.dpr_override.lock().unwrap() = dpr;
+    }
+
     /// Resize the GL context.
     #[inline]
     pub fn resize(&self, dimensions: Vector2<u32>) {
         self.state.visitor.read().unwrap().resize(dimensions);
     }
+
+    /// Swaps the buffers of the GL context, which should be current on the calling thread.
+    #[inline]
+    pub fn swap_buffers(&self) -> Result<()> {
+        self.state.visitor.read().unwrap().swap_buffers()
+    }
+
+    /// Sets whether the swaps of buffers are synchronized with the vertical refresh of
+    /// screen, e.g. disables it for uncapped benchmarking. It could be toggled at runtime
+    /// without recreating the window.
+    ///
+    /// Notes that the context should be current on the calling thread, and the drivers
+    /// might override this setting.
+    ///
+    /// # Platform-specific
+    ///
+    /// Only supported with WGL and GLX contexts, it always fails on other desktop
+    /// platforms and is ignored in browser.
+    #[inline]
+    pub fn set_vsync(&self, enabled: bool) -> Result<()> {
+        self.state.visitor.read().unwrap().set_vsync(enabled)
+    }
+
+    /// Sets whether the buffers should be swapped at the end of every frame on the main
+    /// thread. It's disabled when the buffers are swapped by the render thread instead.
+    #[inline]
+    pub fn set_auto_swap(&self, auto_swap: bool) {
+        self.state.auto_swap.store(auto_swap, Ordering::Release);
+    }
+
+    /// Sets whether the events should be waited instead of being polled at the begining
+    /// of every frame, which blocks the frames until there are events or the redraw is
+    /// requested.
+    #[inline]
+    pub fn set_wait_events(&self, wait_events: bool) {
+        self.state.wait_events.store(wait_events, Ordering::Release);
+        self.request_redraw();
+    }
+
+    /// Requests the next frame without waiting for events. It could be called from any
+    /// thread.
+    #[inline]
+    pub fn request_redraw(&self) {
+        self.state.redraw.store(true, Ordering::Release);
+        (self.state.waker)();
+    }
 }
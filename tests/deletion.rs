@@ -0,0 +1,39 @@
+extern crate crayon;
+
+use crayon::res::utils::prelude::ResourceState;
+use crayon::video;
+use crayon::video::prelude::*;
+
+// The frames are advanced with `run_capture_frames`, which runs the headless application
+// for the given number of frames.
+#[test]
+fn deferred_deletion() {
+    crayon::application::oneshot().unwrap();
+
+    let surface = video::create_surface(SurfaceParams::default()).unwrap();
+    let shader = video::create_shader(ShaderParams::default(), "vs".into(), "fs".into()).unwrap();
+
+    let mut params = MeshParams::default();
+    params.num_verts = 3;
+    params.num_idxes = 3;
+    let mesh = video::create_mesh(params, None).unwrap();
+    video::run_capture_frames(1).unwrap();
+    assert_eq!(video::mesh_state(mesh), ResourceState::Ok);
+
+    // The mesh is still alive until the end of frame, so the draw submitted after its
+    // deletion is valid.
+    video::delete_mesh(mesh);
+    assert_eq!(video::mesh_state(mesh), ResourceState::Ok);
+
+    let mut cmds = CommandBuffer::new();
+    cmds.draw(Draw::new(shader, mesh));
+    cmds.submit(surface).unwrap();
+    video::run_capture_frames(1).unwrap();
+    assert_eq!(video::mesh_state(mesh), ResourceState::NotReady);
+    assert_eq!(video::frame_stats().draws, 1);
+
+    // The GPU object is deleted by the next frame.
+    video::run_capture_frames(1).unwrap();
+    assert_eq!(video::mesh_state(mesh), ResourceState::NotReady);
+    assert_eq!(video::frame_stats().draws, 0);
+}
@@ -0,0 +1,20 @@
+extern crate crayon;
+
+use crayon::video;
+use crayon::window;
+
+#[test]
+fn capture_frames() {
+    crayon::application::oneshot().unwrap();
+
+    // The headless backend has no pixel storage, so only the shape of captures is checked.
+    let images = video::run_capture_frames(2).unwrap();
+    assert_eq!(images.len(), 2);
+
+    let dimensions = window::dimensions();
+    let len = (dimensions.x * dimensions.y * 4) as usize;
+    for image in &images {
+        assert_eq!(image.dimensions, dimensions);
+        assert_eq!(image.pixels.len(), len);
+    }
+}